@@ -0,0 +1,91 @@
+//! Benchmarks for `SearchItems` lookups in the attribute index of a large keyring.
+//!
+//! Run with `cargo bench --bench attribute_index`.
+use std::collections;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+
+// The server is a binary crate, so the index module is included directly.
+#[allow(dead_code)]
+#[path = "../src/index.rs"]
+mod index;
+
+const ITEMS: usize = 100_000;
+const SERVICES: usize = 1_000;
+
+/// Build an index of `ITEMS` items spread over a few collections.
+///
+/// Every item has a unique user, and shares its service with `ITEMS / SERVICES` other items.
+fn build_index() -> index::AttributeIndex {
+    let collection_ids: Vec<uuid::Uuid> = (0..4).map(|_| uuid::Uuid::new_v4()).collect();
+    let mut attribute_index = index::AttributeIndex::new();
+
+    for i in 0..ITEMS {
+        let item_path = zvariant::ObjectPath::try_from(format!(
+            "/org/freedesktop/secrets/collection/benchmark/item{i}"
+        ))
+        .unwrap();
+        let attributes = collections::HashMap::from([
+            ("xdg:schema".to_owned(), "org.example.Password".to_owned()),
+            ("service".to_owned(), format!("service-{}", i % SERVICES)),
+            ("user".to_owned(), format!("user-{i}")),
+        ]);
+
+        attribute_index.insert(
+            item_path.into(),
+            collection_ids[i % collection_ids.len()],
+            attributes,
+        );
+    }
+
+    attribute_index
+}
+
+fn query(pairs: &[(&str, &str)]) -> collections::HashMap<String, String> {
+    pairs
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+fn search_items(c: &mut Criterion) {
+    let attribute_index = build_index();
+
+    let mut group = c.benchmark_group("search_items_100k");
+
+    let single_item = query(&[
+        ("xdg:schema", "org.example.Password"),
+        ("service", "service-42"),
+        ("user", "user-50042"),
+    ]);
+    group.bench_function("single_match", |b| {
+        b.iter(|| {
+            let found = attribute_index.search(&single_item);
+            assert_eq!(found.len(), 1);
+        })
+    });
+
+    let one_service = query(&[("service", "service-42")]);
+    group.bench_function("one_service", |b| {
+        b.iter(|| {
+            let found = attribute_index.search(&one_service);
+            assert_eq!(found.len(), ITEMS / SERVICES);
+        })
+    });
+
+    let no_match = query(&[
+        ("xdg:schema", "org.example.Password"),
+        ("service", "missing-service"),
+    ]);
+    group.bench_function("no_match", |b| {
+        b.iter(|| {
+            let found = attribute_index.search(&no_match);
+            assert!(found.is_empty());
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, search_items);
+criterion_main!(benches);
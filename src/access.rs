@@ -0,0 +1,493 @@
+//! Per-application access control to items.
+//!
+//! Callers are identified by the process behind their D-Bus connection: the bus
+//! reports its PID and UID through `GetConnectionCredentials`, and the executable of
+//! the process is read from `/proc/<pid>/exe`. Each item records the executable of the
+//! application that created it, and other applications are only given its secret as
+//! decided by the configured `Policy`.
+//!
+//! Applications run by an interpreter are identified by the interpreter, so they
+//! can't be told apart from each other.
+use std::collections;
+use std::fmt;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path;
+use std::sync;
+
+use crate::error;
+use crate::object::item;
+use crate::object::prompt;
+use crate::object::DbusObject;
+
+/// What to do when an application accesses an item.
+#[derive(Clone, Copy, Debug, Default, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Decision {
+    Allow,
+    Deny,
+    /// Ask the user to confirm through the prompter. Without one, items of unknown
+    /// applications are allowed, and those of other applications denied.
+    #[default]
+    Prompt,
+}
+
+/// Limits the items an application can access to those with the given attributes.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize)]
+pub struct Rule {
+    /// Executable of the application, either its full path or its file name.
+    pub application: String,
+    #[serde(default)]
+    pub attributes: collections::HashMap<String, String>,
+}
+
+impl Rule {
+    fn applies_to(&self, application: &Application) -> bool {
+        application.executable.as_ref().is_some_and(|executable| {
+            executable.as_os_str() == self.application.as_str()
+                || executable
+                    .file_name()
+                    .is_some_and(|name| name == self.application.as_str())
+        })
+    }
+
+    fn matches(&self, attributes: &collections::HashMap<String, String>) -> bool {
+        self.attributes
+            .iter()
+            .all(|(key, value)| attributes.get(key) == Some(value))
+    }
+}
+
+/// Access policy, read from the `access` table of the configuration:
+///
+/// ```toml
+/// [access]
+/// default = "prompt"
+///
+/// [[access.rules]]
+/// application = "firefox"
+/// attributes = { origin = "firefox" }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, serde::Deserialize)]
+pub struct Policy {
+    /// Decision for applications accessing items created by another application, or by an
+    /// unknown one, like imported items and those stored before creators were recorded.
+    #[serde(default)]
+    pub default: Decision,
+    /// Applications with a rule can only see, and access, the items that match it.
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+}
+
+impl Policy {
+    /// Whether `application` can see an item with `attributes`, for example in search results.
+    pub fn is_visible(
+        &self,
+        application: &Application,
+        attributes: &collections::HashMap<String, String>,
+    ) -> bool {
+        self.rules
+            .iter()
+            .filter(|rule| rule.applies_to(application))
+            .all(|rule| rule.matches(attributes))
+    }
+
+    /// Decide whether `application` can access an item with `attributes` created by `creator`.
+    ///
+    /// Items without a `creator` are decided by `default`, like those of other applications.
+    pub fn decide(
+        &self,
+        application: &Application,
+        creator: Option<&str>,
+        attributes: &collections::HashMap<String, String>,
+    ) -> Decision {
+        let mut rules = self
+            .rules
+            .iter()
+            .filter(|rule| rule.applies_to(application))
+            .peekable();
+        if rules.peek().is_some() {
+            return if rules.all(|rule| rule.matches(attributes)) {
+                Decision::Allow
+            } else {
+                Decision::Deny
+            };
+        }
+
+        match creator {
+            Some(creator)
+                if application.executable.as_deref() == Some(path::Path::new(creator)) =>
+            {
+                Decision::Allow
+            }
+            _ => self.default,
+        }
+    }
+}
+
+/// The process behind a D-Bus connection.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Application {
+    pub pid: u32,
+    pub uid: u32,
+    /// `None` if it can't be read, for example because the process already exited.
+    pub executable: Option<path::PathBuf>,
+}
+
+impl Application {
+    /// Identify the application behind the `sender` connection.
+    pub async fn resolve(
+        connection: &zbus::Connection,
+        sender: &zbus_names::UniqueName<'_>,
+    ) -> Result<Self, error::Error> {
+        let credentials = zbus::fdo::DBusProxy::new(connection)
+            .await?
+            .get_connection_credentials(zbus_names::BusName::Unique(sender.to_owned()))
+            .await
+            .map_err(zbus::Error::from)?;
+
+        let (Some(pid), Some(uid)) = (credentials.process_id(), credentials.unix_user_id()) else {
+            return Err(error::Error::AccessDenied(format!(
+                "Cannot identify the process of '{sender}'"
+            )));
+        };
+        let executable = fs::read_link(format!("/proc/{pid}/exe")).ok();
+
+        Ok(Self {
+            pid,
+            uid,
+            executable,
+        })
+    }
+
+    /// Executable path recorded on the items the application creates.
+    pub fn executable_str(&self) -> Option<String> {
+        self.executable
+            .as_ref()
+            .map(|executable| executable.to_string_lossy().into_owned())
+    }
+}
+
+impl fmt::Display for Application {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.executable {
+            Some(executable) => write!(f, "{} ({})", executable.display(), self.pid),
+            None => write!(f, "process {}", self.pid),
+        }
+    }
+}
+
+/// An application's access to an item, waiting for the user to confirm it.
+#[derive(Clone, Debug)]
+pub struct Confirmation {
+    pub application: Application,
+    executable: path::PathBuf,
+    item_id: uuid::Uuid,
+    item_label: String,
+    pub item_path: zvariant::OwnedObjectPath,
+}
+
+/// Enforces a `Policy` on the callers of the server.
+#[derive(Debug)]
+pub struct AccessControl {
+    /// Applications behind the connections that called the server, until they disconnect.
+    callers: sync::Mutex<collections::HashMap<zbus_names::OwnedUniqueName, Application>>,
+    connection: zbus::Connection,
+    /// Items that the user allowed an application to access, until the server exits.
+    granted: sync::Mutex<collections::HashSet<(path::PathBuf, uuid::Uuid)>>,
+    policy: Policy,
+    prompter: Option<prompt::Prompter>,
+    /// User running the server, as callers run by other users are never given access.
+    uid: Option<u32>,
+}
+
+impl AccessControl {
+    pub fn new(
+        connection: zbus::Connection,
+        policy: Policy,
+        prompter: Option<prompt::Prompter>,
+    ) -> Self {
+        Self {
+            callers: sync::Mutex::new(collections::HashMap::new()),
+            connection,
+            granted: sync::Mutex::new(collections::HashSet::new()),
+            policy,
+            prompter,
+            uid: fs::metadata("/proc/self").map(|meta| meta.uid()).ok(),
+        }
+    }
+
+    /// Identify the application that sent the message with `header`.
+    pub async fn caller(
+        &self,
+        header: &zbus::message::Header<'_>,
+    ) -> Result<Application, error::Error> {
+        let sender = header.sender().ok_or_else(|| {
+            error::Error::AccessDenied("Cannot identify a message without a sender".to_owned())
+        })?;
+
+        if let Some(application) = self
+            .callers
+            .lock()
+            .expect("lock is not poisoned")
+            .get(&zbus_names::OwnedUniqueName::from(sender.to_owned()))
+        {
+            return Ok(application.clone());
+        }
+
+        let application = Application::resolve(&self.connection, sender).await?;
+        self.callers
+            .lock()
+            .expect("lock is not poisoned")
+            .insert(sender.to_owned().into(), application.clone());
+        Ok(application)
+    }
+
+    /// Forget the application behind `sender`, once it disconnected.
+    pub fn forget(&self, sender: &zbus_names::UniqueName<'_>) {
+        self.callers
+            .lock()
+            .expect("lock is not poisoned")
+            .remove(&zbus_names::OwnedUniqueName::from(sender.to_owned()));
+    }
+
+    /// Whether `application` can see an item with `attributes`, for example in search results.
+    pub fn is_visible(
+        &self,
+        application: &Application,
+        attributes: &collections::HashMap<String, String>,
+    ) -> bool {
+        self.is_same_user(application) && self.policy.is_visible(application, attributes)
+    }
+
+    /// Whether `application` can access `item` without asking the user.
+    pub fn is_allowed(&self, application: &Application, item: &item::Item) -> bool {
+        self.decide(application, item) == Decision::Allow
+    }
+
+    /// Fail with `AccessDenied` unless `application` can access `item`.
+    ///
+    /// Items that the user must confirm access to fail with `IsLocked` instead, as they are
+    /// unlocked for the application by confirming through the prompt of `Service.Unlock`.
+    pub fn ensure_allowed(
+        &self,
+        application: &Application,
+        item: &item::Item,
+    ) -> Result<(), error::Error> {
+        let item_path = item.get_object_path().to_string();
+        if self.confirmation(application, item).is_some() {
+            return Err(error::Error::IsLocked(item_path));
+        }
+        if self.is_allowed(application, item) {
+            return Ok(());
+        }
+
+        log::warn!("Denied {application} access to '{item_path}'");
+        Err(error::Error::AccessDenied(item_path))
+    }
+
+    /// What the user must confirm before `application` can access `item`, if anything.
+    ///
+    /// Nothing is asked if there is no prompter, or the application can't be identified,
+    /// and access is then decided as `Decision::Prompt` describes.
+    pub fn confirmation(
+        &self,
+        application: &Application,
+        item: &item::Item,
+    ) -> Option<Confirmation> {
+        let executable = application.executable.clone()?;
+        if self.prompter.is_none() || self.decide(application, item) != Decision::Prompt {
+            return None;
+        }
+
+        Some(Confirmation {
+            application: application.clone(),
+            executable,
+            item_id: item.id,
+            item_label: item.label.clone(),
+            item_path: item.get_object_path(),
+        })
+    }
+
+    /// Ask the user to allow the application of `confirmation` to access its item, and
+    /// remember it until the server exits if they do.
+    ///
+    /// Meant to be called from a `PromptAction`, which passes the client's `window_id`.
+    pub async fn confirm(
+        &self,
+        confirmation: &Confirmation,
+        window_id: &str,
+    ) -> Result<bool, error::Error> {
+        let Some(prompter) = &self.prompter else {
+            return Ok(false);
+        };
+
+        let message = format!(
+            "Allow '{}' to access the secret '{}'?",
+            confirmation.executable.display(),
+            confirmation.item_label
+        );
+        let application = &confirmation.application;
+        let item_path = &confirmation.item_path;
+        if !prompter.confirm(&message, window_id).await? {
+            log::warn!("Denied {application} access to '{item_path}'");
+            return Ok(false);
+        }
+
+        log::info!("Allowed {application} to access '{item_path}'");
+        self.granted
+            .lock()
+            .expect("lock is not poisoned")
+            .insert((confirmation.executable.clone(), confirmation.item_id));
+        Ok(true)
+    }
+
+    fn decide(&self, application: &Application, item: &item::Item) -> Decision {
+        if !self.is_same_user(application) {
+            return Decision::Deny;
+        }
+
+        match self
+            .policy
+            .decide(application, item.application.as_deref(), &item.attributes)
+        {
+            // Otherwise, no application could ever access imported items, nor those stored
+            // before creators were recorded, as there is no one to ask.
+            Decision::Prompt if self.prompter.is_none() && item.application.is_none() => {
+                Decision::Allow
+            }
+            Decision::Prompt
+                if application.executable.as_ref().is_some_and(|executable| {
+                    self.granted
+                        .lock()
+                        .expect("lock is not poisoned")
+                        .contains(&(executable.clone(), item.id))
+                }) =>
+            {
+                Decision::Allow
+            }
+            decision => decision,
+        }
+    }
+
+    fn is_same_user(&self, application: &Application) -> bool {
+        self.uid == Some(application.uid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn application(executable: &str) -> Application {
+        Application {
+            pid: 1,
+            uid: 1000,
+            executable: Some(path::PathBuf::from(executable)),
+        }
+    }
+
+    #[test]
+    fn test_applications_can_access_their_own_items() {
+        let policy = Policy {
+            default: Decision::Deny,
+            rules: Vec::new(),
+        };
+        let firefox = application("/usr/bin/firefox");
+        let attributes = collections::HashMap::new();
+
+        assert_eq!(
+            policy.decide(&firefox, Some("/usr/bin/firefox"), &attributes),
+            Decision::Allow
+        );
+        assert_eq!(
+            policy.decide(&firefox, Some("/usr/bin/chromium"), &attributes),
+            Decision::Deny
+        );
+        // Items created before applications were recorded.
+        assert_eq!(policy.decide(&firefox, None, &attributes), Decision::Deny);
+        assert_eq!(
+            Policy::default().decide(&firefox, None, &attributes),
+            Decision::Prompt
+        );
+        assert_eq!(
+            Policy::default().decide(&firefox, Some("/usr/bin/chromium"), &attributes),
+            Decision::Prompt
+        );
+    }
+
+    #[test]
+    fn test_rules_limit_applications_to_matching_items() {
+        let policy = Policy {
+            default: Decision::Deny,
+            rules: vec![Rule {
+                application: "firefox".to_owned(),
+                attributes: collections::HashMap::from([(
+                    "origin".to_owned(),
+                    "firefox".to_owned(),
+                )]),
+            }],
+        };
+        let firefox = application("/usr/bin/firefox");
+        let chromium = application("/usr/bin/chromium");
+        let matching = collections::HashMap::from([
+            ("origin".to_owned(), "firefox".to_owned()),
+            ("user".to_owned(), "me".to_owned()),
+        ]);
+        let other = collections::HashMap::from([("origin".to_owned(), "chromium".to_owned())]);
+
+        // Even an item created by another application, if it matches.
+        assert_eq!(
+            policy.decide(&firefox, Some("/usr/bin/chromium"), &matching),
+            Decision::Allow
+        );
+        // But not one it created itself, if it doesn't.
+        assert_eq!(
+            policy.decide(&firefox, Some("/usr/bin/firefox"), &other),
+            Decision::Deny
+        );
+        assert!(policy.is_visible(&firefox, &matching));
+        assert!(!policy.is_visible(&firefox, &other));
+
+        // Applications without a rule are not limited.
+        assert!(policy.is_visible(&chromium, &matching));
+        assert_eq!(
+            policy.decide(&chromium, Some("/usr/bin/chromium"), &other),
+            Decision::Allow
+        );
+    }
+
+    #[test]
+    fn test_policy_from_config() {
+        let settings = config::Config::builder()
+            .add_source(config::File::from_str(
+                r#"
+                [access]
+                default = "deny"
+
+                [[access.rules]]
+                application = "/usr/bin/firefox"
+                attributes = { origin = "firefox" }
+                "#,
+                config::FileFormat::Toml,
+            ))
+            .build()
+            .unwrap();
+
+        let policy: Policy = settings.get("access").unwrap();
+
+        assert_eq!(
+            policy,
+            Policy {
+                default: Decision::Deny,
+                rules: vec![Rule {
+                    application: "/usr/bin/firefox".to_owned(),
+                    attributes: collections::HashMap::from([(
+                        "origin".to_owned(),
+                        "firefox".to_owned(),
+                    )]),
+                }],
+            }
+        );
+    }
+}
@@ -1,8 +1,10 @@
 use std::fmt;
+use std::io;
 use zbus::DBusError;
 
 #[derive(Debug)]
 pub enum Error {
+    AccessDenied(String),
     AlgorithmUnsupported(String),
     ItemExists(String),
     ItemIsDeleted(String),
@@ -10,10 +12,20 @@ pub enum Error {
     CollectionIsDeleted(String),
     Config(config::ConfigError),
     IsLocked(String),
-    InvalidArgs(String, String),
+    /// An argument of a method call was invalid, for a reason other than its signature.
+    InvalidArgs {
+        method: &'static str,
+        argument: &'static str,
+        reason: String,
+    },
+    InvalidSecret(String),
+    Io(io::Error),
+    Json(serde_json::Error),
     NoSession(String),
     NoSuchObject(String),
     SessionIsClosed,
+    Sqlite(rusqlite::Error),
+    Storage(String),
     Zbus(zbus::Error),
     Zvariant(zvariant::Error),
 }
@@ -23,25 +35,39 @@ impl DBusError for Error {
         &self,
         msg: &zbus::message::Header<'_>,
     ) -> zbus::Result<zbus::message::Message> {
-        let message = zbus::message::Message::error(msg, self.name())?
-            .build(&self.description().unwrap_or(""))?;
+        let message = zbus::message::Message::error(msg, self.name())?.build(&self.to_string())?;
         Ok(message)
     }
 
     fn name(&self) -> zbus_names::ErrorName<'_> {
         match self {
+            Error::AccessDenied(_) => zbus_names::ErrorName::from_static_str_unchecked(
+                "org.freedesktop.DBus.Error.AccessDenied",
+            ),
             Error::AlgorithmUnsupported(_) => zbus_names::ErrorName::from_static_str_unchecked(
                 "org.freedesktop.DBus.Error.NotSupported",
             ),
+            Error::ItemExists(_) | Error::CollectionAliasExists(_) => {
+                zbus_names::ErrorName::from_static_str_unchecked(
+                    "org.freedesktop.Secret.Error.AlreadyExists",
+                )
+            }
             Error::IsLocked(_) => zbus_names::ErrorName::from_static_str_unchecked(
                 "org.freedesktop.Secret.Error.IsLocked",
             ),
-            Error::InvalidArgs(_, _) => zbus_names::ErrorName::from_static_str_unchecked(
-                "org.freedesktop.DBus.Error.InvalidArgs",
-            ),
-            Error::NoSession(_) => zbus_names::ErrorName::from_static_str_unchecked(
-                "org.freedesktop.Secret.Error.NoSession",
+            Error::InvalidArgs { .. } | Error::InvalidSecret(_) | Error::Zvariant(_) => {
+                zbus_names::ErrorName::from_static_str_unchecked(
+                    "org.freedesktop.DBus.Error.InvalidArgs",
+                )
+            }
+            Error::Io(_) => zbus_names::ErrorName::from_static_str_unchecked(
+                "org.freedesktop.DBus.Error.IOError",
             ),
+            Error::NoSession(_) | Error::SessionIsClosed => {
+                zbus_names::ErrorName::from_static_str_unchecked(
+                    "org.freedesktop.Secret.Error.NoSession",
+                )
+            }
             // Although `org.freedesktop.DBus.Error.UnknownObject` would also work here,
             // the secret service spec defines a more precise error for these cases.
             // https://specifications.freedesktop.org/secret-service-spec/latest/errors.html#id-1.3.5.5
@@ -50,20 +76,40 @@ impl DBusError for Error {
                     "org.freedesktop.Secret.Error.NoSuchObject",
                 )
             }
-            _ => zbus_names::ErrorName::from_static_str_unchecked(
-                "org.freedesktop.DBus.Error.Failed",
+            Error::Config(_) => zbus_names::ErrorName::from_static_str_unchecked(
+                "dev.tomasfarias.SecretService.Error.Config",
             ),
+            Error::Storage(_) | Error::Json(_) | Error::Sqlite(_) => {
+                zbus_names::ErrorName::from_static_str_unchecked(
+                    "dev.tomasfarias.SecretService.Error.Storage",
+                )
+            }
+            // Errors of other D-Bus services keep their names when passed on.
+            Error::Zbus(zbus::Error::MethodError(name, _, _)) => name.as_ref(),
+            Error::Zbus(zbus::Error::FDO(inner)) => inner.name(),
+            Error::Zbus(_) => {
+                zbus_names::ErrorName::from_static_str_unchecked("org.freedesktop.zbus.Error")
+            }
         }
     }
+
+    /// The message of errors that carry one, as the full description is only built
+    /// when replying, see `create_reply`.
     fn description(&self) -> Option<&str> {
-        let description = format!("{}", self).leak();
-        Some(description)
+        match self {
+            Error::InvalidArgs { reason, .. } => Some(reason),
+            Error::InvalidSecret(msg) | Error::Storage(msg) => Some(msg),
+            Error::Zbus(zbus::Error::MethodError(_, description, _)) => description.as_deref(),
+            Error::Zbus(zbus::Error::FDO(inner)) => inner.description(),
+            _ => None,
+        }
     }
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
+            Error::AccessDenied(object) => write!(f, "Access to '{}' was denied", object),
             Error::AlgorithmUnsupported(algorithm) => write!(
                 f,
                 "Cannot open a session with unsupported algorithm: '{}'",
@@ -74,9 +120,16 @@ impl fmt::Display for Error {
                 "The object '{}' must be unlocked before this action can be carried out",
                 object_path
             ),
-            Error::InvalidArgs(method, msg) => {
-                write!(f, "Invalid arguments received for '{}': {}", method, msg)
-            }
+            Error::InvalidArgs {
+                method,
+                argument,
+                reason,
+            } => write!(
+                f,
+                "Invalid argument '{}' received for '{}': {}",
+                argument, method, reason
+            ),
+            Error::InvalidSecret(msg) => write!(f, "Invalid secret received: {}", msg),
             Error::ItemExists(object_path) => write!(
                 f,
                 "The item '{}' already exists and not asked to replace",
@@ -86,6 +139,8 @@ impl fmt::Display for Error {
                 write!(f, "A collection with alias '{}' already exists", alias)
             }
             Error::Config(inner) => write!(f, "{}", inner),
+            Error::Io(inner) => write!(f, "{}", inner),
+            Error::Json(inner) => write!(f, "{}", inner),
             Error::NoSuchObject(object)
             | Error::ItemIsDeleted(object)
             | Error::CollectionIsDeleted(object) => {
@@ -95,6 +150,8 @@ impl fmt::Display for Error {
                 write!(f, "A session '{}' does not exist", object_path)
             }
             Error::SessionIsClosed => write!(f, "Session cannot be used as it is closed"),
+            Error::Sqlite(inner) => write!(f, "{}", inner),
+            Error::Storage(msg) => write!(f, "Storage error: {}", msg),
 
             Error::Zbus(inner) => write!(f, "{}", inner),
             Error::Zvariant(inner) => write!(f, "{}", inner),
@@ -102,9 +159,23 @@ impl fmt::Display for Error {
     }
 }
 
+/// Used by property setters, as zbus only lets them fail with `zbus::fdo::Error`.
+///
+/// Errors named after a standard D-Bus error keep their name. Others can't have our own
+/// D-Bus error names, so the name is kept at the start of the message instead. This is why
+/// writing a property of a locked object fails with `org.freedesktop.DBus.Error.Failed`,
+/// with a message starting with `org.freedesktop.Secret.Error.IsLocked`, and not with
+/// `IsLocked` itself: zbus replies to `org.freedesktop.DBus.Properties.Set` on its own.
 impl From<Error> for zbus::fdo::Error {
     fn from(value: Error) -> zbus::fdo::Error {
-        zbus::fdo::Error::Failed(format!("{}", value))
+        let message = value.to_string();
+        match value.name().as_str() {
+            "org.freedesktop.DBus.Error.AccessDenied" => zbus::fdo::Error::AccessDenied(message),
+            "org.freedesktop.DBus.Error.InvalidArgs" => zbus::fdo::Error::InvalidArgs(message),
+            "org.freedesktop.DBus.Error.IOError" => zbus::fdo::Error::IOError(message),
+            "org.freedesktop.DBus.Error.NotSupported" => zbus::fdo::Error::NotSupported(message),
+            name => zbus::fdo::Error::Failed(format!("{}: {}", name, message)),
+        }
     }
 }
 
@@ -126,8 +197,172 @@ impl From<config::ConfigError> for Error {
     }
 }
 
+impl From<io::Error> for Error {
+    fn from(value: io::Error) -> Error {
+        Error::Io(value)
+    }
+}
+
+impl From<rusqlite::Error> for Error {
+    fn from(value: rusqlite::Error) -> Error {
+        Error::Sqlite(value)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(value: serde_json::Error) -> Error {
+        Error::Json(value)
+    }
+}
+
 impl From<hkdf::InvalidLength> for Error {
     fn from(value: hkdf::InvalidLength) -> Error {
-        Error::InvalidArgs("OpenSession".to_owned(), format!("{}", value))
+        Error::InvalidArgs {
+            method: "OpenSession",
+            argument: "input",
+            reason: format!("{}", value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_every_error_has_a_precise_name() {
+        let cases = [
+            (
+                Error::AccessDenied("/item".to_owned()),
+                "org.freedesktop.DBus.Error.AccessDenied",
+            ),
+            (
+                Error::AlgorithmUnsupported("rot13".to_owned()),
+                "org.freedesktop.DBus.Error.NotSupported",
+            ),
+            (
+                Error::ItemExists("/item".to_owned()),
+                "org.freedesktop.Secret.Error.AlreadyExists",
+            ),
+            (
+                Error::CollectionAliasExists("default".to_owned()),
+                "org.freedesktop.Secret.Error.AlreadyExists",
+            ),
+            (
+                Error::ItemIsDeleted("/item".to_owned()),
+                "org.freedesktop.Secret.Error.NoSuchObject",
+            ),
+            (
+                Error::CollectionIsDeleted("/collection".to_owned()),
+                "org.freedesktop.Secret.Error.NoSuchObject",
+            ),
+            (
+                Error::NoSuchObject("/collection".to_owned()),
+                "org.freedesktop.Secret.Error.NoSuchObject",
+            ),
+            (
+                Error::Config(config::ConfigError::NotFound("key".to_owned())),
+                "dev.tomasfarias.SecretService.Error.Config",
+            ),
+            (
+                Error::IsLocked("/collection".to_owned()),
+                "org.freedesktop.Secret.Error.IsLocked",
+            ),
+            (
+                Error::InvalidArgs {
+                    method: "OpenSession",
+                    argument: "input",
+                    reason: "Invalid public key".to_owned(),
+                },
+                "org.freedesktop.DBus.Error.InvalidArgs",
+            ),
+            (
+                Error::InvalidSecret("Bad padding".to_owned()),
+                "org.freedesktop.DBus.Error.InvalidArgs",
+            ),
+            (
+                Error::Io(io::Error::from(io::ErrorKind::NotFound)),
+                "org.freedesktop.DBus.Error.IOError",
+            ),
+            (
+                Error::Json(serde_json::from_str::<u64>("").unwrap_err()),
+                "dev.tomasfarias.SecretService.Error.Storage",
+            ),
+            (
+                Error::Sqlite(rusqlite::Error::QueryReturnedNoRows),
+                "dev.tomasfarias.SecretService.Error.Storage",
+            ),
+            (
+                Error::Storage("Corrupted".to_owned()),
+                "dev.tomasfarias.SecretService.Error.Storage",
+            ),
+            (
+                Error::NoSession("/session".to_owned()),
+                "org.freedesktop.Secret.Error.NoSession",
+            ),
+            (
+                Error::SessionIsClosed,
+                "org.freedesktop.Secret.Error.NoSession",
+            ),
+            (
+                Error::Zbus(zbus::Error::FDO(Box::new(zbus::fdo::Error::AccessDenied(
+                    "Denied".to_owned(),
+                )))),
+                "org.freedesktop.DBus.Error.AccessDenied",
+            ),
+            (
+                Error::Zbus(zbus::Error::InterfaceNotFound),
+                "org.freedesktop.zbus.Error",
+            ),
+            (
+                Error::Zvariant(zvariant::Error::IncorrectType),
+                "org.freedesktop.DBus.Error.InvalidArgs",
+            ),
+        ];
+
+        for (error, name) in cases {
+            assert_eq!(error.name().as_str(), name, "for {:?}", error);
+        }
+    }
+
+    #[test]
+    fn test_reply_carries_name_and_message() {
+        let call = zbus::message::Message::method_call("/org/freedesktop/secrets", "Unlock")
+            .unwrap()
+            .build(&())
+            .unwrap();
+        let error = Error::InvalidArgs {
+            method: "OpenSession",
+            argument: "input",
+            reason: "Invalid public key".to_owned(),
+        };
+
+        let reply = error.create_reply(&call.header()).unwrap();
+        let header = reply.header();
+        assert_eq!(
+            header.error_name().unwrap().as_str(),
+            "org.freedesktop.DBus.Error.InvalidArgs"
+        );
+        assert_eq!(
+            reply.body().deserialize::<&str>().unwrap(),
+            "Invalid argument 'input' received for 'OpenSession': Invalid public key"
+        );
+        assert_eq!(error.description(), Some("Invalid public key"));
+    }
+
+    #[test]
+    fn test_property_setter_errors_keep_their_name() {
+        let invalid_args = zbus::fdo::Error::from(Error::InvalidSecret("Bad padding".to_owned()));
+        assert_eq!(
+            invalid_args,
+            zbus::fdo::Error::InvalidArgs("Invalid secret received: Bad padding".to_owned())
+        );
+
+        match zbus::fdo::Error::from(Error::IsLocked("/collection".to_owned())) {
+            zbus::fdo::Error::Failed(message) => {
+                assert!(message.starts_with("org.freedesktop.Secret.Error.IsLocked: "))
+            }
+            error => panic!("Expected IsLocked to be passed as Failed, got: {error}"),
+        }
     }
 }
@@ -0,0 +1,220 @@
+//! Inverted index from item attributes to the items that have them.
+//!
+//! `SearchItems` matches items having all of the queried attributes, so a search
+//! only needs to intersect the items found for each (key, value) pair in the query,
+//! starting from the smallest set, instead of comparing every item's attributes.
+use std::collections;
+
+#[derive(Debug)]
+struct IndexedItem {
+    attributes: collections::HashMap<String, String>,
+    collection_id: uuid::Uuid,
+}
+
+#[derive(Debug, Default)]
+pub struct AttributeIndex {
+    items: collections::HashMap<zvariant::OwnedObjectPath, IndexedItem>,
+    postings:
+        collections::HashMap<(String, String), collections::HashSet<zvariant::OwnedObjectPath>>,
+}
+
+impl AttributeIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Index the item at `item_path`, replacing any attributes it was indexed with before.
+    pub fn insert(
+        &mut self,
+        item_path: zvariant::OwnedObjectPath,
+        collection_id: uuid::Uuid,
+        attributes: collections::HashMap<String, String>,
+    ) {
+        self.remove(&item_path);
+
+        for (key, value) in attributes.iter() {
+            self.postings
+                .entry((key.to_owned(), value.to_owned()))
+                .or_default()
+                .insert(item_path.clone());
+        }
+
+        self.items.insert(
+            item_path,
+            IndexedItem {
+                attributes,
+                collection_id,
+            },
+        );
+    }
+
+    /// Stop indexing the item at `item_path`, returning whether it was indexed.
+    pub fn remove(&mut self, item_path: &zvariant::OwnedObjectPath) -> bool {
+        let Some(indexed_item) = self.items.remove(item_path) else {
+            return false;
+        };
+
+        for (key, value) in indexed_item.attributes {
+            let posting_key = (key, value);
+            if let Some(posting) = self.postings.get_mut(&posting_key) {
+                posting.remove(item_path);
+                if posting.is_empty() {
+                    self.postings.remove(&posting_key);
+                }
+            }
+        }
+
+        true
+    }
+
+    pub fn attributes(
+        &self,
+        item_path: &zvariant::OwnedObjectPath,
+    ) -> Option<&collections::HashMap<String, String>> {
+        self.items
+            .get(item_path)
+            .map(|indexed_item| &indexed_item.attributes)
+    }
+
+    /// Find items having all of `attributes`, along with the id of their collection.
+    ///
+    /// An empty query matches every item.
+    pub fn search(
+        &self,
+        attributes: &collections::HashMap<String, String>,
+    ) -> Vec<(&zvariant::OwnedObjectPath, uuid::Uuid)> {
+        let mut postings = Vec::with_capacity(attributes.len());
+        for (key, value) in attributes.iter() {
+            match self.postings.get(&(key.to_owned(), value.to_owned())) {
+                Some(posting) => postings.push(posting),
+                // No item has this attribute, so no item can have all of them.
+                None => return Vec::new(),
+            }
+        }
+        postings.sort_unstable_by_key(|posting| posting.len());
+
+        let candidates: Box<dyn Iterator<Item = &zvariant::OwnedObjectPath>> =
+            match postings.split_first() {
+                Some((smallest, rest)) => {
+                    Box::new(smallest.iter().filter(|item_path| {
+                        rest.iter().all(|posting| posting.contains(*item_path))
+                    }))
+                }
+                None => Box::new(self.items.keys()),
+            };
+
+        candidates
+            .map(|item_path| (item_path, self.items[item_path].collection_id))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item_path(name: &str) -> zvariant::OwnedObjectPath {
+        zvariant::ObjectPath::try_from(format!("/org/freedesktop/secrets/collection/test/{name}"))
+            .unwrap()
+            .into()
+    }
+
+    fn attributes(pairs: &[(&str, &str)]) -> collections::HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    fn search(
+        index: &AttributeIndex,
+        pairs: &[(&str, &str)],
+    ) -> collections::HashSet<zvariant::OwnedObjectPath> {
+        index
+            .search(&attributes(pairs))
+            .into_iter()
+            .map(|(item_path, _)| item_path.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_search_intersects_attributes() {
+        let collection_id = uuid::Uuid::new_v4();
+        let mut index = AttributeIndex::new();
+        index.insert(
+            item_path("bar"),
+            collection_id,
+            attributes(&[("service", "foo"), ("user", "bar")]),
+        );
+        index.insert(
+            item_path("baz"),
+            collection_id,
+            attributes(&[("service", "foo"), ("user", "baz")]),
+        );
+
+        assert_eq!(
+            search(&index, &[("service", "foo")]),
+            collections::HashSet::from([item_path("bar"), item_path("baz")])
+        );
+        assert_eq!(
+            search(&index, &[("service", "foo"), ("user", "baz")]),
+            collections::HashSet::from([item_path("baz")])
+        );
+        assert_eq!(search(&index, &[]).len(), 2);
+        assert!(search(&index, &[("service", "other")]).is_empty());
+        assert!(search(&index, &[("service", "foo"), ("user", "qux")]).is_empty());
+        assert_eq!(
+            index.search(&attributes(&[("user", "bar")]))[0].1,
+            collection_id
+        );
+    }
+
+    #[test]
+    fn test_insert_replaces_attributes() {
+        let mut index = AttributeIndex::new();
+        index.insert(
+            item_path("item"),
+            uuid::Uuid::new_v4(),
+            attributes(&[("key", "old")]),
+        );
+        index.insert(
+            item_path("item"),
+            uuid::Uuid::new_v4(),
+            attributes(&[("key", "new")]),
+        );
+
+        assert_eq!(index.len(), 1);
+        assert!(search(&index, &[("key", "old")]).is_empty());
+        assert_eq!(
+            search(&index, &[("key", "new")]),
+            collections::HashSet::from([item_path("item")])
+        );
+        assert!(!index
+            .postings
+            .contains_key(&("key".to_owned(), "old".to_owned())));
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut index = AttributeIndex::new();
+        index.insert(
+            item_path("item"),
+            uuid::Uuid::new_v4(),
+            attributes(&[("key", "value")]),
+        );
+
+        assert!(index.remove(&item_path("item")));
+        assert!(!index.remove(&item_path("item")));
+        assert!(index.is_empty());
+        assert!(index.postings.is_empty());
+        assert!(search(&index, &[]).is_empty());
+    }
+}
@@ -0,0 +1,1928 @@
+//! KeePass KDBX 4 databases served as collections.
+//!
+//! A KDBX 4 file starts with a header of type-length-value fields, which names the cipher,
+//! compression, and key derivation function of the database. It's followed by its SHA-256
+//! digest, and its HMAC-SHA-256, which tells whether the key was right. The payload comes
+//! next as blocks, each with its own HMAC, and is decrypted with AES-256-CBC or ChaCha20.
+//! Once decompressed, it holds an inner header and the XML document with groups and entries.
+//! Values marked as protected in the document are further encrypted with a ChaCha20 stream,
+//! in the order they appear.
+//!
+//! The key of a database is the SHA-256 digest of the digest of its password and of its key
+//! file, either of which may be missing, and it is transformed with Argon2 or AES-KDF.
+//!
+//! Every group of a database except for the recycle bin maps to a
+//! `storage::StoredCollection`, and the entries right in it to its items: the title of an
+//! entry is the label of its item, the password its secret, and every field other than the
+//! standard and protected ones an attribute. As the whole database is encrypted, nothing
+//! but its file name is known about it until it's unlocked, not even its subgroups. While
+//! unlocked, the document is kept in memory, and every change is written back to the file,
+//! keeping anything that doesn't map to a collection, like protected fields, as it was.
+use std::collections;
+use std::fmt;
+use std::fs;
+use std::io::{Read, Write};
+use std::path;
+use std::time;
+
+use aes::cipher::{
+    block_padding, BlockDecryptMut, BlockEncrypt, BlockEncryptMut, KeyInit, KeyIvInit, StreamCipher,
+};
+use base64::Engine;
+use hmac::Mac;
+use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event};
+use sha2::Digest;
+
+use crate::error;
+use crate::storage;
+
+type Aes256CbcEnc = cbc::Encryptor<aes::Aes256>;
+type Aes256CbcDec = cbc::Decryptor<aes::Aes256>;
+type HmacSha256 = hmac::Hmac<sha2::Sha256>;
+
+const SIGNATURE: [u32; 2] = [0x9aa2d903, 0xb54bfb67];
+const MAJOR_VERSION: u32 = 4;
+
+const HEADER_END: u8 = 0;
+const HEADER_CIPHER_ID: u8 = 2;
+const HEADER_COMPRESSION_FLAGS: u8 = 3;
+const HEADER_MASTER_SEED: u8 = 4;
+const HEADER_ENCRYPTION_IV: u8 = 7;
+const HEADER_KDF_PARAMETERS: u8 = 11;
+
+const INNER_HEADER_END: u8 = 0;
+const INNER_HEADER_RANDOM_STREAM_ID: u8 = 1;
+const INNER_HEADER_RANDOM_STREAM_KEY: u8 = 2;
+const INNER_HEADER_BINARY: u8 = 3;
+/// Id of ChaCha20 as the stream that protects values, the only one used by KDBX 4.
+const RANDOM_STREAM_CHACHA20: u32 = 3;
+
+const CIPHER_AES256: uuid::Uuid = uuid::Uuid::from_u128(0x31c1f2e6_bf71_4350_be58_05216afc5aff);
+const CIPHER_CHACHA20: uuid::Uuid = uuid::Uuid::from_u128(0xd6038a2b_8b6f_4cb5_a524_339a31dbb59a);
+const KDF_AES: uuid::Uuid = uuid::Uuid::from_u128(0xc9d9f39a_628a_4460_bf74_0d08c18a4fea);
+const KDF_ARGON2D: uuid::Uuid = uuid::Uuid::from_u128(0xef636ddf_8c29_444b_91f7_a9a403e30a0c);
+const KDF_ARGON2ID: uuid::Uuid = uuid::Uuid::from_u128(0x9e298b19_56db_4773_b23d_fc3ec6f0a1e6);
+
+/// Blocks of the payload are written in chunks of at most 1 MiB, like KeePass does.
+const BLOCK_SIZE: usize = 1024 * 1024;
+/// Seconds from 0001-01-01, the epoch of KDBX 4 timestamps, to the UNIX epoch.
+const EPOCH_OFFSET: i64 = 62_135_596_800;
+/// Fields of an entry that are not attributes of its item.
+const STANDARD_FIELDS: [&str; 5] = ["Title", "UserName", "Password", "URL", "Notes"];
+
+/// A KeePass database to serve as collections, as found in the configuration.
+#[derive(Clone, Debug, Default, PartialEq, serde::Deserialize)]
+pub struct DatabaseConfig {
+    pub path: path::PathBuf,
+    #[serde(default)]
+    pub keyfile: Option<path::PathBuf>,
+    /// Whether the database is only protected by its key file, so that it's unlocked
+    /// without asking for a password.
+    #[serde(default)]
+    pub passwordless: bool,
+}
+
+/// Contents of an unlocked database.
+pub struct Contents {
+    /// Collection of the root group, followed by those of its subgroups.
+    pub collections: Vec<storage::StoredCollection>,
+    /// Secrets of the items of all `collections`, by item id.
+    pub secrets: collections::HashMap<uuid::Uuid, zeroize::Zeroizing<Vec<u8>>>,
+}
+
+/// A KDBX 4 file, which can be read and written once unlocked.
+pub struct Database {
+    /// Id of the collection of the root group, derived from the path of the database so
+    /// that it doesn't change across restarts, and is known while the database is locked.
+    /// Collections of other groups have the UUID of their group as id.
+    pub id: uuid::Uuid,
+    keyfile: Option<path::PathBuf>,
+    path: path::PathBuf,
+    /// Only set while the database is unlocked.
+    unlocked: Option<Unlocked>,
+}
+
+impl fmt::Debug for Database {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Database")
+            .field("id", &self.id)
+            .field("keyfile", &self.keyfile)
+            .field("path", &self.path)
+            .field("unlocked", &self.unlocked.is_some())
+            .finish()
+    }
+}
+
+impl Database {
+    pub fn new(path: &path::Path, keyfile: Option<&path::Path>) -> Self {
+        let digest = sha2::Sha256::digest(path.as_os_str().as_encoded_bytes());
+        let id = uuid::Builder::from_random_bytes(
+            digest[..16].try_into().expect("digest is 32 bytes long"),
+        )
+        .into_uuid();
+
+        Self {
+            id,
+            keyfile: keyfile.map(path::Path::to_owned),
+            path: path.to_owned(),
+            unlocked: None,
+        }
+    }
+
+    /// Label of the collection until the database is unlocked: the name of its file.
+    pub fn label(&self) -> String {
+        self.path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    /// Read the database, decrypting it with `password` and the configured key file.
+    ///
+    /// An empty password is left out of the key if there is a key file, as that's how
+    /// databases protected only by a key file are opened. Returns `None` if the key is wrong.
+    pub fn unlock(&mut self, password: &[u8]) -> Result<Option<Contents>, error::Error> {
+        let key = self.composite_key(password)?;
+        let Some(unlocked) = Unlocked::read(&fs::read(&self.path)?, &key)? else {
+            return Ok(None);
+        };
+
+        let contents = unlocked.contents(self.id)?;
+        self.unlocked = Some(unlocked);
+        Ok(Some(contents))
+    }
+
+    /// Forget the decrypted document and the key.
+    pub fn lock(&mut self) {
+        self.unlocked = None;
+    }
+
+    /// Write `collection`, the root group or one of its subgroups, and the `secrets` of its
+    /// items back to the unlocked database.
+    ///
+    /// The file is only written if anything changed. Secrets must be UTF-8 text, as KeePass
+    /// fields can't hold anything else.
+    pub fn write(
+        &mut self,
+        collection: &storage::StoredCollection,
+        secrets: &collections::HashMap<uuid::Uuid, zeroize::Zeroizing<Vec<u8>>>,
+    ) -> Result<(), error::Error> {
+        let unlocked = self
+            .unlocked
+            .as_mut()
+            .ok_or_else(|| error::Error::IsLocked(self.id.to_string()))?;
+
+        let group_id = (collection.id != self.id).then(|| encode_uuid(&collection.id));
+        let mut document = unlocked.document.clone();
+        if !update_document(&mut document, group_id.as_deref(), collection, secrets)? {
+            return Ok(());
+        }
+
+        let previous_document = std::mem::replace(&mut unlocked.document, document);
+        let written = unlocked.to_bytes().and_then(|contents| {
+            storage::write_atomically(&self.path, &contents)?;
+            Ok(())
+        });
+        if written.is_err() {
+            unlocked.document = previous_document;
+        }
+        written
+    }
+
+    /// Change the password of the database from `original` to `password`, keeping its key
+    /// file, and derive its key again with a new salt.
+    ///
+    /// Returns whether `original` was correct. The database is left locked or unlocked,
+    /// as it was.
+    pub fn change_password(
+        &mut self,
+        original: &[u8],
+        password: &[u8],
+    ) -> Result<bool, error::Error> {
+        let original = self.composite_key(original)?;
+        let Some(mut unlocked) = Unlocked::read(&fs::read(&self.path)?, &original)? else {
+            return Ok(false);
+        };
+
+        let mut kdf = VariantDictionary::parse(unlocked.header.field(HEADER_KDF_PARAMETERS)?)?;
+        if let Some(Variant::Bytes(salt)) = kdf.get("S") {
+            let mut new_salt = vec![0u8; salt.len()];
+            getrandom::getrandom(&mut new_salt)
+                .expect("system random number generator unavailable");
+            kdf.set("S", Variant::Bytes(new_salt));
+        }
+        unlocked
+            .header
+            .set_field(HEADER_KDF_PARAMETERS, kdf.to_bytes());
+        unlocked.transformed_key = transform_key(&kdf, &*self.composite_key(password)?)?;
+
+        storage::write_atomically(&self.path, &unlocked.to_bytes()?)?;
+        if self.unlocked.is_some() {
+            self.unlocked = Some(unlocked);
+        }
+
+        Ok(true)
+    }
+
+    fn composite_key(&self, password: &[u8]) -> Result<zeroize::Zeroizing<[u8; 32]>, error::Error> {
+        let keyfile = self
+            .keyfile
+            .as_ref()
+            .map(|keyfile| fs::read(keyfile).map(zeroize::Zeroizing::new))
+            .transpose()?;
+        composite_key(password, keyfile.as_deref().map(Vec::as_slice))
+    }
+}
+
+/// The decrypted contents of a database, along with all that's needed to write it back.
+#[derive(Clone)]
+struct Unlocked {
+    /// Attachments in the inner header, kept as they were read.
+    binaries: Vec<Vec<u8>>,
+    document: Element,
+    header: Header,
+    transformed_key: zeroize::Zeroizing<[u8; 32]>,
+}
+
+impl Unlocked {
+    /// Decrypt `contents` with `key`, or return `None` if the key is wrong.
+    fn read(contents: &[u8], key: &[u8; 32]) -> Result<Option<Self>, error::Error> {
+        let mut reader = Reader::new(contents);
+        let header = Header::read(&mut reader)?;
+        let header_bytes = &contents[..reader.offset];
+        if reader.bytes(32)? != sha2::Sha256::digest(header_bytes).as_slice() {
+            return Err(error::Error::Storage(
+                "Header of KeePass database is corrupted".to_owned(),
+            ));
+        }
+        let header_hmac = reader.bytes(32)?;
+
+        let kdf = VariantDictionary::parse(header.field(HEADER_KDF_PARAMETERS)?)?;
+        let transformed_key = transform_key(&kdf, key)?;
+        let keys = Keys::new(header.field(HEADER_MASTER_SEED)?, &transformed_key);
+        if keys.hmac(u64::MAX, &[header_bytes]).as_slice() != header_hmac {
+            return Ok(None);
+        }
+
+        let mut encrypted = Vec::new();
+        for index in 0.. {
+            let hmac = reader.bytes(32)?;
+            let length = reader.u32()?;
+            let data = reader.bytes(length as usize)?;
+            if keys
+                .hmac(index, &[&index.to_le_bytes(), &length.to_le_bytes(), data])
+                .as_slice()
+                != hmac
+            {
+                return Err(error::Error::Storage(format!(
+                    "Block {index} of KeePass database is corrupted"
+                )));
+            }
+            if data.is_empty() {
+                break;
+            }
+            encrypted.extend_from_slice(data);
+        }
+
+        let iv = header.field(HEADER_ENCRYPTION_IV)?;
+        let mut payload = zeroize::Zeroizing::new(match header.cipher()? {
+            CIPHER_AES256 => Aes256CbcDec::new(keys.cipher_key.as_ref().into(), iv.into())
+                .decrypt_padded_vec_mut::<block_padding::Pkcs7>(&encrypted)
+                .map_err(|_| {
+                    error::Error::Storage("Payload of KeePass database is corrupted".to_owned())
+                })?,
+            _ => {
+                chacha20::ChaCha20::new(keys.cipher_key.as_ref().into(), iv.into())
+                    .apply_keystream(&mut encrypted);
+                encrypted
+            }
+        });
+        if header.compressed()? {
+            payload = zeroize::Zeroizing::new(gunzip(&payload)?);
+        }
+
+        let mut reader = Reader::new(&payload);
+        let mut binaries = Vec::new();
+        let mut stream = None;
+        loop {
+            let field_type = reader.bytes(1)?[0];
+            let length = reader.u32()?;
+            let data = reader.bytes(length as usize)?;
+            match field_type {
+                INNER_HEADER_END => break,
+                INNER_HEADER_RANDOM_STREAM_ID if data != RANDOM_STREAM_CHACHA20.to_le_bytes() => {
+                    return Err(error::Error::Storage(format!(
+                        "Unsupported protected value stream {data:?} in KeePass database"
+                    )));
+                }
+                INNER_HEADER_RANDOM_STREAM_KEY => stream = Some(protected_values_stream(data)),
+                INNER_HEADER_BINARY => binaries.push(data.to_vec()),
+                _ => {}
+            }
+        }
+        let mut stream = stream.ok_or_else(|| {
+            error::Error::Storage("KeePass database has no protected value stream key".to_owned())
+        })?;
+
+        let xml = std::str::from_utf8(&payload[reader.offset..]).map_err(|_| {
+            error::Error::Storage("Document of KeePass database is not UTF-8".to_owned())
+        })?;
+        let mut document = Element::parse(xml)?;
+        document.for_each_protected(&mut |element| {
+            let mut value = base64::engine::general_purpose::STANDARD
+                .decode(element.text.trim())
+                .map_err(|_| {
+                    error::Error::Storage(format!(
+                        "Protected value in KeePass database is not base64: {}",
+                        element.name
+                    ))
+                })?;
+            stream.apply_keystream(&mut value);
+            element.text = String::from_utf8(value).map_err(|_| {
+                error::Error::Storage(format!(
+                    "Protected value in KeePass database is not UTF-8: {}",
+                    element.name
+                ))
+            })?;
+            Ok(())
+        })?;
+
+        Ok(Some(Self {
+            binaries,
+            document,
+            header,
+            transformed_key,
+        }))
+    }
+
+    /// Encrypt the database again, with a new master seed, IV and protected value stream.
+    fn to_bytes(&self) -> Result<Vec<u8>, error::Error> {
+        let mut header = self.header.clone();
+        let cipher = header.cipher()?;
+        let mut master_seed = [0u8; 32];
+        let mut iv = vec![0u8; if cipher == CIPHER_AES256 { 16 } else { 12 }];
+        let mut stream_key = zeroize::Zeroizing::new([0u8; 64]);
+        for random in [&mut master_seed[..], &mut iv, stream_key.as_mut()] {
+            getrandom::getrandom(random).expect("system random number generator unavailable");
+        }
+        header.set_field(HEADER_MASTER_SEED, master_seed.to_vec());
+        header.set_field(HEADER_ENCRYPTION_IV, iv.clone());
+
+        let mut stream = protected_values_stream(stream_key.as_ref());
+        let mut document = self.document.clone();
+        document.for_each_protected(&mut |element| {
+            let mut value = std::mem::take(&mut element.text).into_bytes();
+            stream.apply_keystream(&mut value);
+            element.text = base64::engine::general_purpose::STANDARD.encode(value);
+            Ok(())
+        })?;
+
+        let mut payload = zeroize::Zeroizing::new(Vec::new());
+        let mut write_field = |field_type: u8, data: &[u8]| {
+            payload.push(field_type);
+            payload.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            payload.extend_from_slice(data);
+        };
+        write_field(
+            INNER_HEADER_RANDOM_STREAM_ID,
+            &RANDOM_STREAM_CHACHA20.to_le_bytes(),
+        );
+        write_field(INNER_HEADER_RANDOM_STREAM_KEY, stream_key.as_ref());
+        for binary in &self.binaries {
+            write_field(INNER_HEADER_BINARY, binary);
+        }
+        write_field(INNER_HEADER_END, &[]);
+        payload.extend_from_slice(document.to_xml().as_bytes());
+        if header.compressed()? {
+            payload = zeroize::Zeroizing::new(gzip(&payload));
+        }
+
+        let keys = Keys::new(&master_seed, &self.transformed_key);
+        let encrypted = if cipher == CIPHER_AES256 {
+            Aes256CbcEnc::new(keys.cipher_key.as_ref().into(), iv.as_slice().into())
+                .encrypt_padded_vec_mut::<block_padding::Pkcs7>(&payload)
+        } else {
+            let mut encrypted = payload.to_vec();
+            chacha20::ChaCha20::new(keys.cipher_key.as_ref().into(), iv.as_slice().into())
+                .apply_keystream(&mut encrypted);
+            encrypted
+        };
+
+        let mut contents = header.to_bytes();
+        let header_hmac = keys.hmac(u64::MAX, &[&contents]);
+        contents.extend_from_slice(&sha2::Sha256::digest(&contents));
+        contents.extend_from_slice(&header_hmac);
+        for (index, block) in (0..).zip(encrypted.chunks(BLOCK_SIZE).chain([&[][..]])) {
+            let length = (block.len() as u32).to_le_bytes();
+            contents.extend_from_slice(&keys.hmac(index, &[&index.to_le_bytes(), &length, block]));
+            contents.extend_from_slice(&length);
+            contents.extend_from_slice(block);
+        }
+
+        Ok(contents)
+    }
+
+    /// Map the root group of the document to a collection with `id`, and its subgroups to
+    /// collections with the UUIDs of the groups.
+    fn contents(&self, id: uuid::Uuid) -> Result<Contents, error::Error> {
+        let root = root_group(&self.document)?;
+        let recycle_bin = recycle_bin(&self.document);
+        let mut groups = Vec::new();
+        find_groups(root, recycle_bin.as_deref(), &mut groups);
+
+        let mut collections = Vec::new();
+        let mut secrets = collections::HashMap::new();
+        for group in groups {
+            let id = if std::ptr::eq(group, root) {
+                id
+            } else if let Some(id) = group.child_text("UUID").and_then(decode_uuid) {
+                id
+            } else {
+                log::warn!("Skipping group without a valid UUID in KeePass database");
+                continue;
+            };
+            let (created, modified) = times(group);
+            let mut collection = storage::StoredCollection {
+                created,
+                id,
+                items: collections::HashMap::new(),
+                label: group.child_text("Name").unwrap_or_default().to_owned(),
+                modified,
+            };
+
+            for entry in group.children.iter().filter(|child| child.name == "Entry") {
+                let Some(id) = entry.child_text("UUID").and_then(decode_uuid) else {
+                    log::warn!("Skipping entry without a valid UUID in KeePass database");
+                    continue;
+                };
+                let (created, modified) = times(entry);
+                let attributes = attribute_fields(entry)
+                    .map(|(key, value)| (key.to_owned(), value.to_owned()))
+                    .collect();
+
+                secrets.insert(
+                    id,
+                    zeroize::Zeroizing::new(field(entry, "Password").unwrap_or_default().into()),
+                );
+                collection.items.insert(
+                    id,
+                    storage::StoredItem {
+                        application: None,
+                        attributes,
+                        content_type: "text/plain; charset=utf8".to_owned(),
+                        created,
+                        id,
+                        label: field(entry, "Title").unwrap_or_default().to_owned(),
+                        modified,
+                    },
+                );
+            }
+            collections.push(collection);
+        }
+
+        Ok(Contents {
+            collections,
+            secrets,
+        })
+    }
+}
+
+/// Derive the key of a database from `password` and the contents of its `keyfile`.
+fn composite_key(
+    password: &[u8],
+    keyfile: Option<&[u8]>,
+) -> Result<zeroize::Zeroizing<[u8; 32]>, error::Error> {
+    let mut hasher = sha2::Sha256::new();
+    if !password.is_empty() || keyfile.is_none() {
+        hasher.update(sha2::Sha256::digest(password));
+    }
+    if let Some(keyfile) = keyfile {
+        hasher.update(keyfile_key(keyfile)?.as_ref());
+    }
+
+    let mut key = zeroize::Zeroizing::new([0u8; 32]);
+    key.copy_from_slice(&hasher.finalize());
+    Ok(key)
+}
+
+/// Key held by a key file: XML key files hold it in base64 (version 1.0) or hex (version
+/// 2.0), other files are either the key itself, in binary or hex, or hashed to get it.
+fn keyfile_key(contents: &[u8]) -> Result<zeroize::Zeroizing<[u8; 32]>, error::Error> {
+    let invalid = || error::Error::Storage("Invalid KeePass key file".to_owned());
+    let xml_key = std::str::from_utf8(contents)
+        .ok()
+        .and_then(|xml| Element::parse(xml).ok())
+        .filter(|document| document.name == "KeyFile");
+
+    let key = match (xml_key, contents.len()) {
+        (Some(document), _) => {
+            let data = document
+                .child("Key")
+                .and_then(|key| key.child_text("Data"))
+                .ok_or_else(invalid)?;
+            match document
+                .child("Meta")
+                .and_then(|meta| meta.child_text("Version"))
+            {
+                Some(version) if version.starts_with("2.") => {
+                    let hex: String = data.split_whitespace().collect();
+                    decode_hex(&hex).ok_or_else(invalid)?
+                }
+                _ => base64::engine::general_purpose::STANDARD
+                    .decode(data.trim())
+                    .map_err(|_| invalid())?,
+            }
+        }
+        (None, 32) => contents.to_vec(),
+        (None, 64) => match std::str::from_utf8(contents).ok().and_then(decode_hex) {
+            Some(key) => key,
+            None => sha2::Sha256::digest(contents).to_vec(),
+        },
+        (None, _) => sha2::Sha256::digest(contents).to_vec(),
+    };
+
+    let mut keyfile_key = zeroize::Zeroizing::new([0u8; 32]);
+    if key.len() != keyfile_key.len() {
+        return Err(invalid());
+    }
+    keyfile_key.copy_from_slice(&key);
+    Ok(keyfile_key)
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Transform the composite `key` with the key derivation function in `kdf`.
+fn transform_key(
+    kdf: &VariantDictionary,
+    key: &[u8; 32],
+) -> Result<zeroize::Zeroizing<[u8; 32]>, error::Error> {
+    let invalid = |name: &str| {
+        error::Error::Storage(format!(
+            "Invalid KDF parameter '{name}' in KeePass database"
+        ))
+    };
+    let bytes = |name: &str| match kdf.get(name) {
+        Some(Variant::Bytes(bytes)) => Ok(bytes.as_slice()),
+        _ => Err(invalid(name)),
+    };
+    let number = |name: &str| match kdf.get(name) {
+        Some(Variant::U32(value)) => Ok(u64::from(*value)),
+        Some(Variant::U64(value)) => Ok(*value),
+        _ => Err(invalid(name)),
+    };
+
+    let mut transformed_key = zeroize::Zeroizing::new([0u8; 32]);
+    let kdf_id = uuid::Uuid::from_slice(bytes("$UUID")?).map_err(|_| invalid("$UUID"))?;
+    match kdf_id {
+        KDF_ARGON2D | KDF_ARGON2ID => {
+            let algorithm = if kdf_id == KDF_ARGON2D {
+                argon2::Algorithm::Argon2d
+            } else {
+                argon2::Algorithm::Argon2id
+            };
+            let version = match number("V")? {
+                0x10 => argon2::Version::V0x10,
+                0x13 => argon2::Version::V0x13,
+                _ => return Err(invalid("V")),
+            };
+            let params = argon2::Params::new(
+                u32::try_from(number("M")? / 1024).map_err(|_| invalid("M"))?,
+                u32::try_from(number("I")?).map_err(|_| invalid("I"))?,
+                u32::try_from(number("P")?).map_err(|_| invalid("P"))?,
+                Some(32),
+            )
+            .map_err(|e| {
+                error::Error::Storage(format!("Invalid KDF parameters in KeePass database: {e}"))
+            })?;
+
+            argon2::Argon2::new(algorithm, version, params)
+                .hash_password_into(key, bytes("S")?, transformed_key.as_mut())
+                .map_err(|e| {
+                    error::Error::Storage(format!("Failed to derive key of KeePass database: {e}"))
+                })?;
+        }
+        KDF_AES => {
+            let cipher = aes::Aes256::new_from_slice(bytes("S")?).map_err(|_| invalid("S"))?;
+            let mut blocks = zeroize::Zeroizing::new(*key);
+            let (first, second) = blocks.split_at_mut(16);
+            for _ in 0..number("R")? {
+                cipher.encrypt_block(first.into());
+                cipher.encrypt_block(second.into());
+            }
+            transformed_key.copy_from_slice(&sha2::Sha256::digest(blocks.as_ref()));
+        }
+        _ => {
+            return Err(error::Error::Storage(format!(
+                "Unsupported KDF {kdf_id} in KeePass database"
+            )))
+        }
+    }
+
+    Ok(transformed_key)
+}
+
+/// Keys derived from the master seed and the transformed key of a database.
+struct Keys {
+    cipher_key: zeroize::Zeroizing<[u8; 32]>,
+    hmac_key: zeroize::Zeroizing<[u8; 64]>,
+}
+
+impl Keys {
+    fn new(master_seed: &[u8], transformed_key: &[u8; 32]) -> Self {
+        let mut cipher_key = zeroize::Zeroizing::new([0u8; 32]);
+        cipher_key.copy_from_slice(
+            &sha2::Sha256::new()
+                .chain_update(master_seed)
+                .chain_update(transformed_key)
+                .finalize(),
+        );
+        let mut hmac_key = zeroize::Zeroizing::new([0u8; 64]);
+        hmac_key.copy_from_slice(
+            &sha2::Sha512::new()
+                .chain_update(master_seed)
+                .chain_update(transformed_key)
+                .chain_update([1])
+                .finalize(),
+        );
+
+        Self {
+            cipher_key,
+            hmac_key,
+        }
+    }
+
+    /// HMAC-SHA-256 of `data` with the key of the block at `index`. The header uses the
+    /// key of the last possible block.
+    fn hmac(&self, index: u64, data: &[&[u8]]) -> [u8; 32] {
+        let block_key = sha2::Sha512::new()
+            .chain_update(index.to_le_bytes())
+            .chain_update(self.hmac_key.as_ref())
+            .finalize();
+        let mut mac = <HmacSha256 as Mac>::new_from_slice(&block_key)
+            .expect("HMAC can take keys of any size");
+        for data in data {
+            mac.update(data);
+        }
+        mac.finalize().into_bytes().into()
+    }
+}
+
+/// The outer header of a database.
+#[derive(Clone, Debug)]
+struct Header {
+    version: u32,
+    /// Fields in the order they were read, except for the end of the header.
+    fields: Vec<(u8, Vec<u8>)>,
+}
+
+impl Header {
+    fn read(reader: &mut Reader<'_>) -> Result<Self, error::Error> {
+        if [reader.u32()?, reader.u32()?] != SIGNATURE {
+            return Err(error::Error::Storage(
+                "Not a KeePass database: signature doesn't match".to_owned(),
+            ));
+        }
+        let version = reader.u32()?;
+        if version >> 16 != MAJOR_VERSION {
+            return Err(error::Error::Storage(format!(
+                "Unsupported KeePass database version {}.{}",
+                version >> 16,
+                version & 0xffff
+            )));
+        }
+
+        let mut fields = Vec::new();
+        loop {
+            let field_type = reader.bytes(1)?[0];
+            let length = reader.u32()?;
+            let data = reader.bytes(length as usize)?;
+            if field_type == HEADER_END {
+                break;
+            }
+            fields.push((field_type, data.to_vec()));
+        }
+
+        Ok(Self { version, fields })
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        for value in SIGNATURE.into_iter().chain([self.version]) {
+            bytes.extend_from_slice(&value.to_le_bytes());
+        }
+        let end = (HEADER_END, b"\r\n\r\n".to_vec());
+        for (field_type, data) in self.fields.iter().chain([&end]) {
+            bytes.push(*field_type);
+            bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(data);
+        }
+        bytes
+    }
+
+    fn field(&self, field_type: u8) -> Result<&[u8], error::Error> {
+        self.fields
+            .iter()
+            .find(|(t, _)| *t == field_type)
+            .map(|(_, data)| data.as_slice())
+            .ok_or_else(|| {
+                error::Error::Storage(format!(
+                    "KeePass database is missing header field {field_type}"
+                ))
+            })
+    }
+
+    fn set_field(&mut self, field_type: u8, data: Vec<u8>) {
+        match self.fields.iter_mut().find(|(t, _)| *t == field_type) {
+            Some((_, field)) => *field = data,
+            None => self.fields.push((field_type, data)),
+        }
+    }
+
+    fn cipher(&self) -> Result<uuid::Uuid, error::Error> {
+        let iv_length = self.field(HEADER_ENCRYPTION_IV)?.len();
+        match uuid::Uuid::from_slice(self.field(HEADER_CIPHER_ID)?) {
+            Ok(CIPHER_AES256) if iv_length == 16 => Ok(CIPHER_AES256),
+            Ok(CIPHER_CHACHA20) if iv_length == 12 => Ok(CIPHER_CHACHA20),
+            _ => Err(error::Error::Storage(
+                "Unsupported cipher in KeePass database".to_owned(),
+            )),
+        }
+    }
+
+    fn compressed(&self) -> Result<bool, error::Error> {
+        match self.field(HEADER_COMPRESSION_FLAGS)? {
+            [0, 0, 0, 0] => Ok(false),
+            [1, 0, 0, 0] => Ok(true),
+            flags => Err(error::Error::Storage(format!(
+                "Unsupported compression {flags:?} in KeePass database"
+            ))),
+        }
+    }
+}
+
+/// A value of a `VariantDictionary`.
+#[derive(Clone, Debug, PartialEq)]
+enum Variant {
+    U32(u32),
+    U64(u64),
+    Bool(bool),
+    I32(i32),
+    I64(i64),
+    String(String),
+    Bytes(Vec<u8>),
+}
+
+/// Typed key-value pairs, as used for the parameters of the key derivation function.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct VariantDictionary(Vec<(String, Variant)>);
+
+impl VariantDictionary {
+    const VERSION: u16 = 0x0100;
+
+    fn parse(bytes: &[u8]) -> Result<Self, error::Error> {
+        let invalid =
+            || error::Error::Storage("Invalid KDF parameters in KeePass database".to_owned());
+        let mut reader = Reader::new(bytes);
+        let version = u16::from_le_bytes(reader.bytes(2)?.try_into().expect("read 2 bytes"));
+        if version & 0xff00 != Self::VERSION & 0xff00 {
+            return Err(invalid());
+        }
+
+        let mut entries = Vec::new();
+        loop {
+            let value_type = reader.bytes(1)?[0];
+            if value_type == 0 {
+                break;
+            }
+            let name_length = reader.u32()? as usize;
+            let name =
+                String::from_utf8(reader.bytes(name_length)?.to_vec()).map_err(|_| invalid())?;
+            let value_length = reader.u32()? as usize;
+            let value = reader.bytes(value_length)?;
+
+            let value = match (value_type, value.len()) {
+                (0x04, 4) => Variant::U32(u32::from_le_bytes(value.try_into().expect("4 bytes"))),
+                (0x05, 8) => Variant::U64(u64::from_le_bytes(value.try_into().expect("8 bytes"))),
+                (0x08, 1) => Variant::Bool(value[0] != 0),
+                (0x0c, 4) => Variant::I32(i32::from_le_bytes(value.try_into().expect("4 bytes"))),
+                (0x0d, 8) => Variant::I64(i64::from_le_bytes(value.try_into().expect("8 bytes"))),
+                (0x18, _) => {
+                    Variant::String(String::from_utf8(value.to_vec()).map_err(|_| invalid())?)
+                }
+                (0x42, _) => Variant::Bytes(value.to_vec()),
+                _ => return Err(invalid()),
+            };
+            entries.push((name, value));
+        }
+
+        Ok(Self(entries))
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Self::VERSION.to_le_bytes().to_vec();
+        for (name, value) in &self.0 {
+            let (value_type, value) = match value {
+                Variant::U32(value) => (0x04, value.to_le_bytes().to_vec()),
+                Variant::U64(value) => (0x05, value.to_le_bytes().to_vec()),
+                Variant::Bool(value) => (0x08, vec![u8::from(*value)]),
+                Variant::I32(value) => (0x0c, value.to_le_bytes().to_vec()),
+                Variant::I64(value) => (0x0d, value.to_le_bytes().to_vec()),
+                Variant::String(value) => (0x18, value.as_bytes().to_vec()),
+                Variant::Bytes(value) => (0x42, value.clone()),
+            };
+            bytes.push(value_type);
+            bytes.extend_from_slice(&(name.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(name.as_bytes());
+            bytes.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(&value);
+        }
+        bytes.push(0);
+        bytes
+    }
+
+    fn get(&self, name: &str) -> Option<&Variant> {
+        self.0
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value)
+    }
+
+    fn set(&mut self, name: &str, value: Variant) {
+        match self.0.iter_mut().find(|(n, _)| n == name) {
+            Some((_, v)) => *v = value,
+            None => self.0.push((name.to_owned(), value)),
+        }
+    }
+}
+
+/// The stream that protects values of the document, keyed with the inner header's key.
+fn protected_values_stream(key: &[u8]) -> chacha20::ChaCha20 {
+    let digest = sha2::Sha512::digest(key);
+    chacha20::ChaCha20::new(digest[..32].into(), digest[32..44].into())
+}
+
+/// Decompress a gzip payload, checking its CRC-32 and length.
+fn gunzip(data: &[u8]) -> Result<Vec<u8>, error::Error> {
+    let mut inflated = Vec::new();
+    flate2::read::GzDecoder::new(data)
+        .read_to_end(&mut inflated)
+        .map_err(|e| {
+            error::Error::Storage(format!("Invalid gzip payload in KeePass database: {e}"))
+        })?;
+    Ok(inflated)
+}
+
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder
+        .write_all(data)
+        .expect("writing to a vector doesn't fail");
+    encoder.finish().expect("writing to a vector doesn't fail")
+}
+
+/// An XML element of the document.
+///
+/// The document never mixes text and elements, so an element has either children or text.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Element {
+    name: String,
+    attributes: Vec<(String, String)>,
+    children: Vec<Element>,
+    text: String,
+}
+
+impl Element {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    fn with_text(name: &str, text: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            text: text.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    fn with_children(name: &str, children: Vec<Element>) -> Self {
+        Self {
+            name: name.to_owned(),
+            children,
+            ..Default::default()
+        }
+    }
+
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|child| child.name == name)
+    }
+
+    fn child_mut(&mut self, name: &str) -> Option<&mut Element> {
+        self.children.iter_mut().find(|child| child.name == name)
+    }
+
+    fn child_text(&self, name: &str) -> Option<&str> {
+        self.child(name).map(|child| child.text.as_str())
+    }
+
+    /// Set the text of the first child called `name`, adding it if there is none.
+    fn set_child_text(&mut self, name: &str, text: &str) {
+        match self.child_mut(name) {
+            Some(child) => child.text = text.to_owned(),
+            None => self.children.push(Element::with_text(name, text)),
+        }
+    }
+
+    /// Call `f` on every element with protected text, in document order.
+    fn for_each_protected<F>(&mut self, f: &mut F) -> Result<(), error::Error>
+    where
+        F: FnMut(&mut Element) -> Result<(), error::Error>,
+    {
+        if self.attribute("Protected") == Some("True") {
+            f(self)?;
+        }
+        for child in &mut self.children {
+            child.for_each_protected(f)?;
+        }
+        Ok(())
+    }
+
+    fn parse(xml: &str) -> Result<Self, error::Error> {
+        let invalid = |reason: &dyn fmt::Display| {
+            error::Error::Storage(format!("Invalid XML in KeePass database: {reason}"))
+        };
+        let mut reader = quick_xml::Reader::from_str(xml.trim_start_matches('\u{feff}'));
+        // Elements that were started but not ended yet, innermost last.
+        let mut open: Vec<Element> = Vec::new();
+        let mut root = None;
+
+        loop {
+            let event = reader
+                .read_event()
+                .map_err(|e| invalid(&format!("{e} at byte {}", reader.error_position())))?;
+            let element = match event {
+                Event::Start(start) => {
+                    open.push(
+                        Element::from_start(&start, reader.decoder()).map_err(|e| invalid(&e))?,
+                    );
+                    continue;
+                }
+                Event::Empty(start) => {
+                    Element::from_start(&start, reader.decoder()).map_err(|e| invalid(&e))?
+                }
+                Event::End(_) => {
+                    let mut element = open.pop().expect("end names are checked by the reader");
+                    // Whitespace between child elements is only indentation.
+                    if !element.children.is_empty() {
+                        element.text.clear();
+                    }
+                    element
+                }
+                Event::Text(text) => {
+                    let text = text.unescape().map_err(|e| invalid(&e))?;
+                    match open.last_mut() {
+                        Some(element) => element.text.push_str(&text),
+                        None if text.trim().is_empty() => {}
+                        None => return Err(invalid(&"text outside of the root element")),
+                    }
+                    continue;
+                }
+                Event::CData(data) => {
+                    let text = data.decode().map_err(|e| invalid(&e))?;
+                    if let Some(element) = open.last_mut() {
+                        element.text.push_str(&text);
+                    }
+                    continue;
+                }
+                Event::Eof => break,
+                // Declarations, comments, processing instructions and document types.
+                _ => continue,
+            };
+
+            match open.last_mut() {
+                Some(parent) => parent.children.push(element),
+                None if root.is_none() => root = Some(element),
+                None => return Err(invalid(&"content after the root element")),
+            }
+        }
+
+        if let Some(element) = open.last() {
+            return Err(invalid(&format!("expected end of '{}'", element.name)));
+        }
+        root.ok_or_else(|| invalid(&"no root element"))
+    }
+
+    fn from_start(
+        start: &BytesStart<'_>,
+        decoder: quick_xml::encoding::Decoder,
+    ) -> Result<Self, quick_xml::Error> {
+        let mut element = Element::new(&decoder.decode(start.name().as_ref())?);
+        for attribute in start.attributes() {
+            let attribute = attribute?;
+            element.attributes.push((
+                decoder.decode(attribute.key.as_ref())?.into_owned(),
+                attribute.unescape_value()?.into_owned(),
+            ));
+        }
+        Ok(element)
+    }
+
+    fn to_xml(&self) -> String {
+        let mut writer = quick_xml::Writer::new_with_indent(Vec::new(), b'\t', 1);
+        let declaration = BytesDecl::new("1.0", Some("utf-8"), Some("yes"));
+        writer
+            .write_event(Event::Decl(declaration))
+            .and_then(|_| self.write(&mut writer))
+            .expect("writing to a Vec doesn't fail");
+        let mut xml = String::from_utf8(writer.into_inner()).expect("the document is UTF-8");
+        xml.push('\n');
+        xml
+    }
+
+    fn write(&self, writer: &mut quick_xml::Writer<Vec<u8>>) -> std::io::Result<()> {
+        let start = BytesStart::new(self.name.as_str()).with_attributes(
+            self.attributes
+                .iter()
+                .map(|(name, value)| (name.as_str(), value.as_str())),
+        );
+
+        if !self.children.is_empty() {
+            writer.write_event(Event::Start(start))?;
+            for child in &self.children {
+                child.write(writer)?;
+            }
+        } else if !self.text.is_empty() {
+            writer.write_event(Event::Start(start))?;
+            writer.write_event(Event::Text(BytesText::new(&self.text)))?;
+        } else {
+            return writer.write_event(Event::Empty(start));
+        }
+        writer.write_event(Event::End(BytesEnd::new(self.name.as_str())))
+    }
+}
+
+fn root_group(document: &Element) -> Result<&Element, error::Error> {
+    document
+        .child("Root")
+        .and_then(|root| root.child("Group"))
+        .ok_or_else(|| error::Error::Storage("KeePass database has no root group".to_owned()))
+}
+
+fn root_group_mut(document: &mut Element) -> Result<&mut Element, error::Error> {
+    document
+        .child_mut("Root")
+        .and_then(|root| root.child_mut("Group"))
+        .ok_or_else(|| error::Error::Storage("KeePass database has no root group".to_owned()))
+}
+
+/// UUID of the recycle bin group, if the database has one.
+fn recycle_bin(document: &Element) -> Option<String> {
+    let meta = document.child("Meta")?;
+    if meta.child_text("RecycleBinEnabled") != Some("True") {
+        return None;
+    }
+    meta.child_text("RecycleBinUUID").map(str::to_owned)
+}
+
+/// Collect `group` and its subgroups, except for the recycle bin.
+fn find_groups<'a>(group: &'a Element, recycle_bin: Option<&str>, groups: &mut Vec<&'a Element>) {
+    groups.push(group);
+    for child in &group.children {
+        if child.name == "Group" && child.child_text("UUID") != recycle_bin {
+            find_groups(child, recycle_bin, groups);
+        }
+    }
+}
+
+/// Find the subgroup of `group` with `id`, at any depth, except for the recycle bin.
+fn find_group_mut<'a>(
+    group: &'a mut Element,
+    recycle_bin: Option<&str>,
+    id: &str,
+) -> Option<&'a mut Element> {
+    for child in &mut group.children {
+        if child.name != "Group" || child.child_text("UUID") == recycle_bin {
+            continue;
+        }
+        if child.child_text("UUID") == Some(id) {
+            return Some(child);
+        }
+        if let Some(group) = find_group_mut(child, recycle_bin, id) {
+            return Some(group);
+        }
+    }
+    None
+}
+
+/// Find the entry with `id` right in `group`.
+fn find_entry_mut<'a>(group: &'a mut Element, id: &str) -> Option<&'a mut Element> {
+    group
+        .children
+        .iter_mut()
+        .find(|child| child.name == "Entry" && child.child_text("UUID") == Some(id))
+}
+
+/// Remove the entries right in `group` for which `keep` is false. Returns the UUIDs of the
+/// removed entries.
+fn remove_entries<F>(group: &mut Element, keep: F) -> Vec<String>
+where
+    F: Fn(&Element) -> bool,
+{
+    let mut removed = Vec::new();
+    group.children.retain(|child| {
+        if child.name != "Entry" || keep(child) {
+            return true;
+        }
+        removed.push(child.child_text("UUID").unwrap_or_default().to_owned());
+        false
+    });
+    removed
+}
+
+/// Fields of an entry, as key and value pairs.
+fn fields(entry: &Element) -> impl Iterator<Item = (&str, &str)> {
+    entry
+        .children
+        .iter()
+        .filter(|child| child.name == "String")
+        .map(|field| {
+            (
+                field.child_text("Key").unwrap_or_default(),
+                field.child_text("Value").unwrap_or_default(),
+            )
+        })
+}
+
+/// Fields of an entry served as attributes of its item: all but the standard fields, and
+/// those KeePass protects in memory, like the TOTP seeds of KeePassXC, as attributes can
+/// be read by anyone, even while the collection is locked.
+fn attribute_fields(entry: &Element) -> impl Iterator<Item = (&str, &str)> {
+    entry
+        .children
+        .iter()
+        .filter(|child| child.name == "String" && !is_protected(child))
+        .map(|field| {
+            (
+                field.child_text("Key").unwrap_or_default(),
+                field.child_text("Value").unwrap_or_default(),
+            )
+        })
+        .filter(|(key, _)| !STANDARD_FIELDS.contains(key))
+}
+
+/// Whether the value of a field is protected in memory.
+fn is_protected(field: &Element) -> bool {
+    field
+        .child("Value")
+        .and_then(|value| value.attribute("Protected"))
+        == Some("True")
+}
+
+fn field<'a>(entry: &'a Element, key: &str) -> Option<&'a str> {
+    fields(entry)
+        .find(|(k, _)| *k == key)
+        .map(|(_, value)| value)
+}
+
+/// Set the field `key` of an entry, adding it after the other fields if it's missing.
+fn set_field(entry: &mut Element, key: &str, value: &str, protected: bool) {
+    let existing = entry
+        .children
+        .iter_mut()
+        .find(|child| child.name == "String" && child.child_text("Key") == Some(key));
+    if let Some(existing) = existing {
+        existing.set_child_text("Value", value);
+        return;
+    }
+
+    let mut value = Element::with_text("Value", value);
+    if protected {
+        value
+            .attributes
+            .push(("Protected".to_owned(), "True".to_owned()));
+    }
+    let field = Element::with_children("String", vec![Element::with_text("Key", key), value]);
+    let position = entry
+        .children
+        .iter()
+        .rposition(|child| child.name == "String" || child.name == "Times")
+        .map_or(entry.children.len(), |position| position + 1);
+    entry.children.insert(position, field);
+}
+
+fn times(element: &Element) -> (u64, u64) {
+    let times = element.child("Times");
+    let time = |name| {
+        times
+            .and_then(|times| times.child_text(name))
+            .map_or(0, decode_time)
+    };
+    (time("CreationTime"), time("LastModificationTime"))
+}
+
+fn set_modified(element: &mut Element, modified: u64) {
+    if element.child("Times").is_none() {
+        element.children.push(Element::new("Times"));
+    }
+    element
+        .child_mut("Times")
+        .expect("added if missing")
+        .set_child_text("LastModificationTime", &encode_time(modified));
+}
+
+fn decode_uuid(text: &str) -> Option<uuid::Uuid> {
+    let bytes = base64::engine::general_purpose::STANDARD
+        .decode(text.trim())
+        .ok()?;
+    uuid::Uuid::from_slice(&bytes).ok()
+}
+
+fn encode_uuid(id: &uuid::Uuid) -> String {
+    base64::engine::general_purpose::STANDARD.encode(id.as_bytes())
+}
+
+/// Seconds since the UNIX epoch of a KDBX 4 timestamp: base64 of the little-endian seconds
+/// since 0001-01-01. Timestamps that can't be read, or are before the UNIX epoch, are 0.
+fn decode_time(text: &str) -> u64 {
+    base64::engine::general_purpose::STANDARD
+        .decode(text.trim())
+        .ok()
+        .and_then(|bytes| <[u8; 8]>::try_from(bytes).ok())
+        .map_or(0, |bytes| {
+            u64::try_from(i64::from_le_bytes(bytes) - EPOCH_OFFSET).unwrap_or(0)
+        })
+}
+
+fn encode_time(seconds: u64) -> String {
+    let seconds = i64::try_from(seconds).unwrap_or(i64::MAX - EPOCH_OFFSET) + EPOCH_OFFSET;
+    base64::engine::general_purpose::STANDARD.encode(seconds.to_le_bytes())
+}
+
+/// A new entry for `item`, with the fields KeePass gives to every entry.
+fn new_entry(item: &storage::StoredItem, secret: &str) -> Element {
+    let mut entry = Element::with_children(
+        "Entry",
+        vec![
+            Element::with_text("UUID", &encode_uuid(&item.id)),
+            Element::with_text("IconID", "0"),
+            Element::with_children(
+                "Times",
+                vec![
+                    Element::with_text("CreationTime", &encode_time(item.created)),
+                    Element::with_text("LastModificationTime", &encode_time(item.modified)),
+                    Element::with_text("LastAccessTime", &encode_time(item.modified)),
+                    Element::with_text("ExpiryTime", &encode_time(item.modified)),
+                    Element::with_text("Expires", "False"),
+                    Element::with_text("UsageCount", "0"),
+                    Element::with_text("LocationChanged", &encode_time(item.created)),
+                ],
+            ),
+        ],
+    );
+    for (key, value) in [
+        ("Title", item.label.as_str()),
+        ("UserName", ""),
+        ("URL", ""),
+        ("Notes", ""),
+    ] {
+        set_field(&mut entry, key, value, false);
+    }
+    set_field(&mut entry, "Password", secret, true);
+    let mut attributes: Vec<_> = item
+        .attributes
+        .iter()
+        .filter(|(key, _)| !STANDARD_FIELDS.contains(&key.as_str()))
+        .collect();
+    attributes.sort();
+    for (key, value) in attributes {
+        set_field(&mut entry, key, value, false);
+    }
+    entry.children.push(Element::with_children(
+        "AutoType",
+        vec![
+            Element::with_text("Enabled", "True"),
+            Element::with_text("DataTransferObfuscation", "0"),
+        ],
+    ));
+    entry.children.push(Element::new("History"));
+    entry
+}
+
+/// Update an existing entry to match `item`, returning whether anything changed.
+fn update_entry(entry: &mut Element, item: &storage::StoredItem, secret: &str) -> bool {
+    let mut changed = false;
+    if field(entry, "Title") != Some(item.label.as_str()) {
+        set_field(entry, "Title", &item.label, false);
+        changed = true;
+    }
+    if field(entry, "Password") != Some(secret) {
+        set_field(entry, "Password", secret, true);
+        changed = true;
+    }
+
+    // Protected fields are not attributes, so they are kept as they are, and neither they
+    // nor the standard fields are overwritten by attributes named like them.
+    let protected: collections::HashSet<String> = entry
+        .children
+        .iter()
+        .filter(|child| child.name == "String" && is_protected(child))
+        .map(|field| field.child_text("Key").unwrap_or_default().to_owned())
+        .collect();
+    let item_attributes: collections::HashMap<&str, &str> = item
+        .attributes
+        .iter()
+        .map(|(key, value)| (key.as_str(), value.as_str()))
+        .filter(|(key, _)| !STANDARD_FIELDS.contains(key) && !protected.contains(*key))
+        .collect();
+    if attribute_fields(entry).collect::<collections::HashMap<_, _>>() != item_attributes {
+        entry.children.retain(|child| {
+            child.name != "String"
+                || is_protected(child)
+                || STANDARD_FIELDS.contains(&child.child_text("Key").unwrap_or_default())
+        });
+        let mut attributes: Vec<_> = item_attributes.into_iter().collect();
+        attributes.sort();
+        for (key, value) in attributes {
+            set_field(entry, key, value, false);
+        }
+        changed = true;
+    }
+
+    if changed {
+        set_modified(entry, item.modified);
+    }
+    changed
+}
+
+/// Update the group of `document` with `group_id`, or the root group if `None`, to match
+/// `collection` and the `secrets` of its items, returning whether anything changed.
+fn update_document(
+    document: &mut Element,
+    group_id: Option<&str>,
+    collection: &storage::StoredCollection,
+    secrets: &collections::HashMap<uuid::Uuid, zeroize::Zeroizing<Vec<u8>>>,
+) -> Result<bool, error::Error> {
+    let recycle_bin = recycle_bin(document);
+    let root = root_group_mut(document)?;
+    let group = match group_id {
+        Some(group_id) => find_group_mut(root, recycle_bin.as_deref(), group_id)
+            .ok_or_else(|| error::Error::NoSuchObject(collection.id.to_string()))?,
+        None => root,
+    };
+    let mut changed = false;
+
+    if group.child_text("Name") != Some(collection.label.as_str()) {
+        group.set_child_text("Name", &collection.label);
+        set_modified(group, collection.modified);
+        changed = true;
+    }
+
+    let removed = remove_entries(group, |entry| {
+        entry
+            .child_text("UUID")
+            .and_then(decode_uuid)
+            .is_none_or(|id| collection.items.contains_key(&id))
+    });
+    changed |= !removed.is_empty();
+
+    let mut items: Vec<&storage::StoredItem> = collection.items.values().collect();
+    items.sort_by_key(|item| (item.created, item.id));
+    for item in items {
+        let secret = secrets.get(&item.id).ok_or_else(|| {
+            error::Error::Storage(format!("Missing secret of item '{}'", item.id))
+        })?;
+        let secret = std::str::from_utf8(secret).map_err(|_| {
+            error::Error::Storage(format!(
+                "Secret of item '{}' is not text, which KeePass databases can't hold",
+                item.id
+            ))
+        })?;
+
+        match find_entry_mut(group, &encode_uuid(&item.id)) {
+            Some(entry) => changed |= update_entry(entry, item, secret),
+            None => {
+                // Entries come before subgroups.
+                let position = group
+                    .children
+                    .iter()
+                    .position(|child| child.name == "Group")
+                    .unwrap_or(group.children.len());
+                group.children.insert(position, new_entry(item, secret));
+                changed = true;
+            }
+        }
+    }
+
+    if !removed.is_empty() {
+        let now = time::SystemTime::now()
+            .duration_since(time::SystemTime::UNIX_EPOCH)
+            .expect("current SystemTime before UNIX EPOCH")
+            .as_secs();
+        let root = document
+            .child_mut("Root")
+            .expect("document has a root group");
+        if root.child("DeletedObjects").is_none() {
+            root.children.push(Element::new("DeletedObjects"));
+        }
+        let deleted_objects = root.child_mut("DeletedObjects").expect("added if missing");
+        for id in removed {
+            deleted_objects.children.push(Element::with_children(
+                "DeletedObject",
+                vec![
+                    Element::with_text("UUID", &id),
+                    Element::with_text("DeletionTime", &encode_time(now)),
+                ],
+            ));
+        }
+    }
+
+    Ok(changed)
+}
+
+/// Reads the little-endian values of a database, failing once it runs out of bytes.
+struct Reader<'a> {
+    buffer: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(buffer: &'a [u8]) -> Self {
+        Self { buffer, offset: 0 }
+    }
+
+    fn bytes(&mut self, length: usize) -> Result<&'a [u8], error::Error> {
+        let bytes = self
+            .offset
+            .checked_add(length)
+            .and_then(|end| self.buffer.get(self.offset..end))
+            .ok_or_else(|| error::Error::Storage("Truncated KeePass database".to_owned()))?;
+        self.offset += length;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, error::Error> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_le_bytes(
+            bytes.try_into().expect("read exactly 4 bytes"),
+        ))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    pub const PASSWORD: &[u8] = b"password";
+    pub const MAIL: uuid::Uuid = uuid::Uuid::from_u128(0x0e0a1f57_8d2c_4a6b_9c1e_2f3a4b5c6d7e);
+    const CHAT: uuid::Uuid = uuid::Uuid::from_u128(0x1f1b2068_9e3d_4b7c_8d2f_3a4b5c6d7e8f);
+    const TRASHED: uuid::Uuid = uuid::Uuid::from_u128(0x2a2c3179_af4e_4c8d_9e3a_4b5c6d7e8f9a);
+    pub const CHAT_GROUP: uuid::Uuid = uuid::Uuid::from_u128(2);
+    const OTP: &str = "otpauth://totp/Mail?secret=JBSWY3DPEHPK3PXP";
+    const RECYCLE_BIN: uuid::Uuid = uuid::Uuid::from_u128(0x3b3d428a_b05f_4d9e_af4b_5c6d7e8f9aab);
+
+    /// A document as KeePassXC writes it, with an entry in the root group, one in the
+    /// `CHAT_GROUP` subgroup, and one in the recycle bin.
+    ///
+    /// The first entry keeps a previous version in its history, whose protected password
+    /// comes before the current one in the stream, and has a protected TOTP seed, an
+    /// attachment and auto-type settings, which are not mapped to the item but must be kept.
+    fn document() -> String {
+        let entry = |id: &uuid::Uuid, title: &str, password: &str, extra: &str| {
+            format!(
+                "<Entry><UUID>{}</UUID><Times>\
+                 <CreationTime>{}</CreationTime>\
+                 <LastModificationTime>{}</LastModificationTime></Times>\
+                 <String><Key>Title</Key><Value>{title}</Value></String>\
+                 <String><Key>UserName</Key><Value>alice</Value></String>\
+                 <String><Key>Password</Key><Value Protected=\"True\">{password}</Value></String>\
+                 {extra}</Entry>",
+                encode_uuid(id),
+                encode_time(1_700_000_000),
+                encode_time(1_700_000_100),
+            )
+        };
+        format!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\" standalone=\"yes\"?>\n\
+             <KeePassFile>\
+             <Meta><Generator>KeePassXC</Generator>\
+             <RecycleBinEnabled>True</RecycleBinEnabled>\
+             <RecycleBinUUID>{recycle_bin}</RecycleBinUUID>\
+             <CustomData><Item><Key>FDO_SECRETS_EXPOSED</Key><Value>true</Value></Item></CustomData>\
+             </Meta>\
+             <Root><Group><UUID>{root}</UUID><Name>Passwords</Name>\
+             <Times><CreationTime>{created}</CreationTime>\
+             <LastModificationTime>{modified}</LastModificationTime></Times>\
+             {mail}\
+             <Group><UUID>{subgroup}</UUID><Name>Chat</Name>{chat}</Group>\
+             <Group><UUID>{recycle_bin}</UUID><Name>Recycle Bin</Name>{trashed}</Group>\
+             </Group></Root>\
+             </KeePassFile>",
+            recycle_bin = encode_uuid(&RECYCLE_BIN),
+            root = encode_uuid(&uuid::Uuid::from_u128(1)),
+            subgroup = encode_uuid(&CHAT_GROUP),
+            created = encode_time(1_600_000_000),
+            modified = encode_time(1_600_000_100),
+            mail = entry(
+                &MAIL,
+                "Mail",
+                "hunter2",
+                &format!(
+                    "<String><Key>service</Key><Value>imap &amp; smtp</Value></String>\
+                     <String><Key>otp</Key><Value Protected=\"True\">{OTP}</Value></String>\
+                     <Binary><Key>notes.txt</Key><Value Ref=\"0\"/></Binary>\
+                     <AutoType><Enabled>True</Enabled>\
+                     <DataTransferObfuscation>0</DataTransferObfuscation></AutoType>\
+                     <History>{}</History>",
+                    entry(&MAIL, "Mail", "hunter1", "")
+                ),
+            ),
+            chat = entry(&CHAT, "Chat", "p&lt;ssword", "<History/>"),
+            trashed = entry(&TRASHED, "Old", "old", "<History/>"),
+        )
+    }
+
+    /// Argon2id parameters small enough for tests.
+    fn argon2_parameters() -> VariantDictionary {
+        VariantDictionary(vec![
+            (
+                "$UUID".to_owned(),
+                Variant::Bytes(KDF_ARGON2ID.as_bytes().to_vec()),
+            ),
+            ("S".to_owned(), Variant::Bytes(vec![7; 32])),
+            ("P".to_owned(), Variant::U32(1)),
+            ("M".to_owned(), Variant::U64(8 * 1024)),
+            ("I".to_owned(), Variant::U64(1)),
+            ("V".to_owned(), Variant::U32(0x13)),
+        ])
+    }
+
+    fn aes_kdf_parameters() -> VariantDictionary {
+        VariantDictionary(vec![
+            (
+                "$UUID".to_owned(),
+                Variant::Bytes(KDF_AES.as_bytes().to_vec()),
+            ),
+            ("S".to_owned(), Variant::Bytes(vec![9; 32])),
+            ("R".to_owned(), Variant::U64(100)),
+        ])
+    }
+
+    /// Write a database encrypted with `cipher`, whose key is derived from `key` with `kdf`.
+    fn write_fixture(
+        path: &path::Path,
+        cipher: uuid::Uuid,
+        kdf: VariantDictionary,
+        key: &[u8; 32],
+    ) {
+        let iv_length = if cipher == CIPHER_AES256 { 16 } else { 12 };
+        let unlocked = Unlocked {
+            binaries: vec![b"\x01attachment".to_vec()],
+            document: Element::parse(&document()).unwrap(),
+            header: Header {
+                version: 0x0004_0001,
+                fields: vec![
+                    (HEADER_CIPHER_ID, cipher.as_bytes().to_vec()),
+                    (HEADER_COMPRESSION_FLAGS, vec![1, 0, 0, 0]),
+                    (HEADER_MASTER_SEED, vec![0; 32]),
+                    (HEADER_ENCRYPTION_IV, vec![0; iv_length]),
+                    (HEADER_KDF_PARAMETERS, kdf.to_bytes()),
+                ],
+            },
+            transformed_key: transform_key(&kdf, key).unwrap(),
+        };
+        fs::write(path, unlocked.to_bytes().unwrap()).unwrap();
+    }
+
+    /// Write the database of `document`, protected by `PASSWORD` alone, for other tests.
+    pub fn write_database(path: &path::Path) {
+        write_fixture(
+            path,
+            CIPHER_CHACHA20,
+            argon2_parameters(),
+            &composite_key(PASSWORD, None).unwrap(),
+        );
+    }
+
+    fn secret(value: &str) -> zeroize::Zeroizing<Vec<u8>> {
+        zeroize::Zeroizing::new(value.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn test_gzip() {
+        let data = document().into_bytes();
+        assert_eq!(gunzip(&gzip(&data)).unwrap(), data);
+
+        let mut corrupted = gzip(&data);
+        let length = corrupted.len();
+        corrupted[length - 8] ^= 1;
+        assert!(gunzip(&corrupted).is_err());
+        // Without the length in its trailer.
+        assert!(gunzip(&gzip(&data)[..length - 4]).is_err());
+        assert!(gunzip(b"not gzip at all").is_err());
+    }
+
+    #[test]
+    fn test_xml() {
+        let document = Element::parse(&document()).unwrap();
+        assert_eq!(Element::parse(&document.to_xml()).unwrap(), document);
+
+        let element = Element::parse(
+            "<a b='1 &lt; 2'><!-- comment --><c><![CDATA[<d>]]></c><e>&#x41;&#66;&apos;</e><f/></a>",
+        )
+        .unwrap();
+        assert_eq!(element.attribute("b"), Some("1 < 2"));
+        assert_eq!(element.child_text("c"), Some("<d>"));
+        assert_eq!(element.child_text("e"), Some("AB'"));
+        assert_eq!(element.child("f"), Some(&Element::new("f")));
+
+        assert!(Element::parse("<a><b></a>").is_err());
+        assert!(Element::parse("<a>&unknown;</a>").is_err());
+        assert!(Element::parse("<a/><b/>").is_err());
+    }
+
+    #[test]
+    fn test_time() {
+        assert_eq!(decode_time(&encode_time(1_700_000_000)), 1_700_000_000);
+        // 0001-01-01 is before the UNIX epoch.
+        assert_eq!(decode_time("AAAAAAAAAAA="), 0);
+        assert_eq!(decode_time("invalid"), 0);
+    }
+
+    #[test]
+    fn test_unlock() {
+        let dir = tempfile::tempdir().unwrap();
+
+        for (cipher, kdf) in [
+            (CIPHER_AES256, argon2_parameters()),
+            (CIPHER_CHACHA20, aes_kdf_parameters()),
+        ] {
+            let path = dir.path().join("Passwords.kdbx");
+            write_fixture(&path, cipher, kdf, &composite_key(PASSWORD, None).unwrap());
+
+            let mut database = Database::new(&path, None);
+            assert_eq!(database.label(), "Passwords");
+            assert_eq!(database.id, Database::new(&path, None).id);
+            assert!(database.unlock(b"wrong-password").unwrap().is_none());
+
+            let contents = database.unlock(PASSWORD).unwrap().unwrap();
+            // The recycle bin, and its entries, are left out.
+            let [collection, chat] = contents.collections.as_slice() else {
+                panic!("expected the root group and one subgroup");
+            };
+            assert_eq!(collection.id, database.id);
+            assert_eq!(collection.label, "Passwords");
+            assert_eq!(
+                (collection.created, collection.modified),
+                (1_600_000_000, 1_600_000_100)
+            );
+            assert_eq!(collection.items.len(), 1);
+            assert_eq!(chat.id, CHAT_GROUP);
+            assert_eq!(chat.label, "Chat");
+            assert_eq!(chat.items.len(), 1);
+
+            let mail = &collection.items[&MAIL];
+            assert_eq!(mail.label, "Mail");
+            assert_eq!(
+                mail.attributes,
+                collections::HashMap::from([("service".to_owned(), "imap & smtp".to_owned())])
+            );
+            assert_eq!(
+                (mail.created, mail.modified),
+                (1_700_000_000, 1_700_000_100)
+            );
+            assert_eq!(chat.items[&CHAT].label, "Chat");
+            assert!(chat.items[&CHAT].attributes.is_empty());
+            assert_eq!(contents.secrets[&MAIL], secret("hunter2"));
+            assert_eq!(contents.secrets[&CHAT], secret("p<ssword"));
+        }
+
+        let path = dir.path().join("invalid.kdbx");
+        fs::write(&path, b"not a database").unwrap();
+        assert!(Database::new(&path, None).unlock(PASSWORD).is_err());
+    }
+
+    #[test]
+    fn test_unlock_with_keyfile() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("Passwords.kdbx");
+        let keyfile = dir.path().join("Passwords.keyx");
+        fs::write(
+            &keyfile,
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<KeyFile>\
+             <Meta><Version>2.0</Version></Meta>\
+             <Key><Data Hash=\"a\">\n\
+             00010203 04050607 08090A0B 0C0D0E0F\n\
+             10111213 14151617 18191A1B 1C1D1E1F\n\
+             </Data></Key></KeyFile>",
+        )
+        .unwrap();
+        let key = (0..32).collect::<Vec<u8>>();
+        assert_eq!(
+            keyfile_key(&fs::read(&keyfile).unwrap()).unwrap().as_ref(),
+            &key[..]
+        );
+        assert_eq!(keyfile_key(&key).unwrap().as_ref(), &key[..]);
+        assert_eq!(
+            keyfile_key(b"any other file").unwrap().as_ref(),
+            sha2::Sha256::digest(b"any other file").as_slice()
+        );
+
+        // Protected by the key file alone.
+        write_fixture(
+            &path,
+            CIPHER_CHACHA20,
+            argon2_parameters(),
+            &composite_key(b"", Some(&key)).unwrap(),
+        );
+        assert!(Database::new(&path, None).unlock(b"").unwrap().is_none());
+        let mut database = Database::new(&path, Some(&keyfile));
+        assert!(database.unlock(b"").unwrap().is_some());
+
+        // Protected by both a password and the key file.
+        write_fixture(
+            &path,
+            CIPHER_CHACHA20,
+            argon2_parameters(),
+            &composite_key(PASSWORD, Some(&key)).unwrap(),
+        );
+        assert!(database.unlock(b"").unwrap().is_none());
+        assert!(Database::new(&path, None)
+            .unlock(PASSWORD)
+            .unwrap()
+            .is_none());
+        assert!(database.unlock(PASSWORD).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_write() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("Passwords.kdbx");
+        write_fixture(
+            &path,
+            CIPHER_AES256,
+            argon2_parameters(),
+            &composite_key(PASSWORD, None).unwrap(),
+        );
+
+        let mut database = Database::new(&path, None);
+        let Contents {
+            collections,
+            mut secrets,
+        } = database.unlock(PASSWORD).unwrap().unwrap();
+        let [mut collection, mut chat] = <[_; 2]>::try_from(collections).unwrap();
+
+        // Nothing changed, so nothing is written.
+        let contents = fs::read(&path).unwrap();
+        database.write(&collection, &secrets).unwrap();
+        database.write(&chat, &secrets).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), contents);
+
+        let added = uuid::Uuid::new_v4();
+        collection.label = "Renamed".to_owned();
+        chat.label = "Messages".to_owned();
+        chat.items.remove(&CHAT);
+        let mail = collection.items.get_mut(&MAIL).unwrap();
+        mail.label = "Work mail".to_owned();
+        mail.attributes.insert("user".to_owned(), "bob".to_owned());
+        // Attributes named like protected or standard fields don't overwrite them.
+        mail.attributes
+            .insert("otp".to_owned(), "otpauth://totp/Evil".to_owned());
+        mail.attributes
+            .insert("UserName".to_owned(), "mallory".to_owned());
+        mail.modified = 1_800_000_000;
+        collection.items.insert(
+            added,
+            storage::StoredItem {
+                application: None,
+                attributes: collections::HashMap::from([("b".to_owned(), "2".to_owned())]),
+                content_type: "text/plain".to_owned(),
+                created: 1_800_000_000,
+                id: added,
+                label: "Added".to_owned(),
+                modified: 1_800_000_000,
+            },
+        );
+        secrets.insert(MAIL, secret("correct horse"));
+        secrets.insert(added, secret("battery staple"));
+        database.write(&collection, &secrets).unwrap();
+        database.write(&chat, &secrets).unwrap();
+
+        // Secrets that aren't text can't be written.
+        secrets.insert(added, zeroize::Zeroizing::new(vec![0xff]));
+        assert!(database.write(&collection, &secrets).is_err());
+
+        let mut reopened = Database::new(&path, None);
+        let contents = reopened.unlock(PASSWORD).unwrap().unwrap();
+        let [collection, chat] = contents.collections.as_slice() else {
+            panic!("expected the root group and one subgroup");
+        };
+        assert_eq!(collection.label, "Renamed");
+        assert_eq!(collection.items.len(), 2);
+        let mail = &collection.items[&MAIL];
+        assert_eq!(mail.label, "Work mail");
+        assert_eq!(
+            mail.attributes,
+            collections::HashMap::from([
+                ("service".to_owned(), "imap & smtp".to_owned()),
+                ("user".to_owned(), "bob".to_owned()),
+            ])
+        );
+        assert_eq!(mail.modified, 1_800_000_000);
+        assert_eq!(collection.items[&added].label, "Added");
+        assert_eq!(chat.label, "Messages");
+        assert!(chat.items.is_empty());
+        assert_eq!(contents.secrets[&MAIL], secret("correct horse"));
+        assert_eq!(contents.secrets[&added], secret("battery staple"));
+
+        // Anything that doesn't map to the collection is kept as it was.
+        let unlocked = reopened.unlocked.as_ref().unwrap();
+        assert_eq!(unlocked.binaries, vec![b"\x01attachment".to_vec()]);
+        let meta = unlocked.document.child("Meta").unwrap();
+        assert_eq!(meta.child_text("Generator"), Some("KeePassXC"));
+        assert!(meta.child("CustomData").is_some());
+        let mut document = unlocked.document.clone();
+        let root = root_group_mut(&mut document).unwrap();
+        let recycle_bin = find_group_mut(root, None, &encode_uuid(&RECYCLE_BIN)).unwrap();
+        let trashed = find_entry_mut(recycle_bin, &encode_uuid(&TRASHED)).unwrap();
+        assert_eq!(field(trashed, "Password"), Some("old"));
+        let mail_entry = find_entry_mut(root, &encode_uuid(&MAIL)).unwrap();
+        assert_eq!(field(mail_entry, "UserName"), Some("alice"));
+        let otp = mail_entry
+            .children
+            .iter()
+            .find(|child| child.name == "String" && child.child_text("Key") == Some("otp"))
+            .unwrap();
+        assert_eq!(otp.child_text("Value"), Some(OTP));
+        assert!(is_protected(otp));
+        assert!(mail_entry.child("History").is_some());
+
+        let deleted_objects = unlocked
+            .document
+            .child("Root")
+            .and_then(|root| root.child("DeletedObjects"))
+            .unwrap();
+        assert_eq!(
+            deleted_objects.children[0].child_text("UUID"),
+            Some(encode_uuid(&CHAT).as_str())
+        );
+
+        database.lock();
+        assert!(matches!(
+            database.write(collection, &secrets),
+            Err(error::Error::IsLocked(_))
+        ));
+    }
+
+    #[test]
+    fn test_change_password() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("Passwords.kdbx");
+        write_fixture(
+            &path,
+            CIPHER_CHACHA20,
+            argon2_parameters(),
+            &composite_key(PASSWORD, None).unwrap(),
+        );
+
+        let mut database = Database::new(&path, None);
+        assert!(!database
+            .change_password(b"wrong-password", b"new-password")
+            .unwrap());
+        assert!(database.change_password(PASSWORD, b"new-password").unwrap());
+        // It was locked, and stays locked.
+        assert!(database.unlocked.is_none());
+
+        assert!(database.unlock(PASSWORD).unwrap().is_none());
+        let contents = database.unlock(b"new-password").unwrap().unwrap();
+        assert_eq!(contents.secrets[&MAIL], secret("hunter2"));
+
+        let kdf = VariantDictionary::parse(
+            database
+                .unlocked
+                .as_ref()
+                .unwrap()
+                .header
+                .field(HEADER_KDF_PARAMETERS)
+                .unwrap(),
+        )
+        .unwrap();
+        assert_ne!(kdf.get("S"), Some(&Variant::Bytes(vec![7; 32])));
+    }
+}
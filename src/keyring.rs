@@ -0,0 +1,900 @@
+//! Reading and writing gnome-keyring's binary `.keyring` files.
+//!
+//! A keyring file starts with the `GnomeKeyring\n\r\0\n` header, followed by the name,
+//! timestamps and key derivation parameters of the keyring, and the attributes of each
+//! item, with their values hashed so they can be searched while the keyring is locked.
+//! The rest of every item, including its secret and its attributes in the clear, is kept
+//! in a block encrypted with AES-128-CBC. Its key and IV are derived from the keyring
+//! password by iterating SHA-256 over it and a salt, and the block starts with an MD5
+//! digest of its contents, used to tell whether the password was right.
+//!
+//! Integers are big-endian `u32`, timestamps are seconds as two `u32` (high half first),
+//! and strings are prefixed by their length, with `0xffffffff` standing for NULL.
+//!
+//! Each file maps to a `storage::StoredCollection`, along with the secrets of its items.
+//! Item types are mapped to `xdg:schema` attributes like gnome-keyring does, and access
+//! control lists are not imported, as there is no equivalent to them. `import` and `export`
+//! move keyrings in and out of any `storage::Storage`, and `import_file` imports the files
+//! listed in the `import_keyrings` setting when the server starts, recording which ones
+//! were imported.
+use std::collections;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path;
+
+use aes::cipher::{block_padding, BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use sha2::Digest;
+
+use crate::error;
+use crate::storage;
+
+type Aes128CbcEnc = cbc::Encryptor<aes::Aes128>;
+type Aes128CbcDec = cbc::Decryptor<aes::Aes128>;
+
+const HEADER: &[u8] = b"GnomeKeyring\n\r\0\n";
+/// Major and minor versions, followed by the crypto (AES) and hash (MD5) algorithms.
+const VERSION: [u8; 4] = [0, 0, 0, 0];
+const SALT_LENGTH: usize = 8;
+const BLOCK_LENGTH: usize = 16;
+/// Range from which gnome-keyring picks the number of iterations to derive the key.
+const HASH_ITERATIONS: std::ops::Range<u32> = 1000..4096;
+
+const ATTRIBUTE_STRING: u32 = 0;
+const ATTRIBUTE_UINT32: u32 = 1;
+
+/// Item types of gnome-keyring, and the `xdg:schema` each one stands for.
+const ITEM_TYPES: [(u32, &str); 6] = [
+    (0, "org.freedesktop.Secret.Generic"),
+    (1, "org.gnome.keyring.NetworkPassword"),
+    (2, "org.gnome.keyring.Note"),
+    (3, "org.gnome.keyring.ChainedKeyring"),
+    (4, "org.gnome.keyring.EncryptionKey"),
+    (0x100, "org.gnome.keyring.PkStorage"),
+];
+const SCHEMA_ATTRIBUTE: &str = "xdg:schema";
+
+/// Content type given to imported secrets, as the format doesn't keep one.
+const CONTENT_TYPE: &str = "text/plain; charset=utf8";
+
+/// A keyring file to import, from the `import_keyrings` setting.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize)]
+pub struct ImportConfig {
+    pub path: path::PathBuf,
+    /// Password of the keyring, which also protects the imported collection.
+    ///
+    /// It is read in plaintext from the configuration file, so the file should only be
+    /// readable by its owner, and the entry removed once the keyring is imported.
+    pub password: String,
+}
+
+/// A collection read from, or to be written to, a keyring file.
+pub struct Keyring {
+    pub collection: storage::StoredCollection,
+    /// Secrets of the items of `collection`, by item id.
+    pub secrets: collections::HashMap<uuid::Uuid, zeroize::Zeroizing<Vec<u8>>>,
+}
+
+impl fmt::Debug for Keyring {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Keyring")
+            .field("collection", &self.collection)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Parse the keyring file in `contents`, decrypting its items with `password`.
+///
+/// Returns `None` if `password` is not the password of the keyring. Imported collections
+/// and items are given new ids, as keyring files only number their items.
+pub fn read(contents: &[u8], password: &[u8]) -> Result<Option<Keyring>, error::Error> {
+    let mut reader = Reader::new(contents);
+    if reader.bytes(HEADER.len())? != HEADER {
+        return Err(error::Error::Storage(
+            "Not a gnome-keyring file: header doesn't match".to_owned(),
+        ));
+    }
+    let version = reader.bytes(VERSION.len())?;
+    if version != VERSION {
+        return Err(error::Error::Storage(format!(
+            "Unsupported gnome-keyring file version or algorithms: {version:?}"
+        )));
+    }
+
+    let label = reader.utf8()?.unwrap_or_default();
+    let created = reader.time()?;
+    let modified = reader.time()?;
+    let _flags = reader.u32()?;
+    let _lock_timeout = reader.u32()?;
+    let hash_iterations = reader.u32()?;
+    let salt = reader.bytes(SALT_LENGTH)?;
+    for _ in 0..4 {
+        reader.u32()?;
+    }
+
+    // Only the type of each item is needed from the hashed attributes, as the encrypted
+    // block holds them in the clear.
+    let n_items = reader.u32()?;
+    let mut item_types = Vec::new();
+    for _ in 0..n_items {
+        let _id = reader.u32()?;
+        item_types.push(reader.u32()?);
+        for _ in 0..reader.u32()? {
+            reader.utf8()?;
+            match reader.u32()? {
+                ATTRIBUTE_STRING => {
+                    reader.string()?;
+                }
+                ATTRIBUTE_UINT32 => {
+                    reader.u32()?;
+                }
+                attribute_type => return Err(unknown_attribute_type(attribute_type)),
+            }
+        }
+    }
+
+    let n_encrypted = reader.u32()? as usize;
+    let encrypted = reader.bytes(n_encrypted)?;
+    if n_encrypted < BLOCK_LENGTH || !n_encrypted.is_multiple_of(BLOCK_LENGTH) {
+        return Err(error::Error::Storage(format!(
+            "Encrypted block of gnome-keyring file has invalid length {n_encrypted}"
+        )));
+    }
+
+    let (key, iv) = derive_key(password, salt, hash_iterations);
+    let decrypted = zeroize::Zeroizing::new(
+        Aes128CbcDec::new(key.as_ref().into(), &iv.into())
+            .decrypt_padded_vec_mut::<block_padding::NoPadding>(encrypted)
+            .expect("length is a multiple of the block length"),
+    );
+    if decrypted[..16] != md5::Md5::digest(&decrypted[16..])[..] {
+        return Ok(None);
+    }
+
+    let mut reader = Reader::new(&decrypted[16..]);
+    let mut collection = storage::StoredCollection {
+        created,
+        id: uuid::Uuid::new_v4(),
+        items: collections::HashMap::new(),
+        label,
+        modified,
+    };
+    let mut secrets = collections::HashMap::new();
+    for item_type in item_types {
+        let label = reader.utf8()?.unwrap_or_default();
+        let secret = zeroize::Zeroizing::new(reader.string()?.unwrap_or_default().to_vec());
+        let created = reader.time()?;
+        let modified = reader.time()?;
+        reader.string()?;
+        for _ in 0..4 {
+            reader.u32()?;
+        }
+
+        let mut attributes = collections::HashMap::new();
+        for _ in 0..reader.u32()? {
+            let name = reader.utf8()?.unwrap_or_default();
+            let value = match reader.u32()? {
+                ATTRIBUTE_STRING => reader.utf8()?.unwrap_or_default(),
+                ATTRIBUTE_UINT32 => reader.u32()?.to_string(),
+                attribute_type => return Err(unknown_attribute_type(attribute_type)),
+            };
+            attributes.insert(name, value);
+        }
+        if let Some((_, schema)) = ITEM_TYPES.iter().find(|(t, _)| *t == item_type) {
+            attributes
+                .entry(SCHEMA_ATTRIBUTE.to_owned())
+                .or_insert_with(|| (*schema).to_owned());
+        }
+
+        for _ in 0..reader.u32()? {
+            let _types_allowed = reader.u32()?;
+            let _display_name = reader.string()?;
+            let _pathname = reader.string()?;
+            reader.string()?;
+            reader.u32()?;
+        }
+
+        let item = storage::StoredItem {
+            application: None,
+            attributes,
+            content_type: CONTENT_TYPE.to_owned(),
+            created,
+            id: uuid::Uuid::new_v4(),
+            label,
+            modified,
+        };
+        secrets.insert(item.id, secret);
+        collection.items.insert(item.id, item);
+    }
+
+    Ok(Some(Keyring {
+        collection,
+        secrets,
+    }))
+}
+
+/// Write `keyring` as a keyring file encrypted with `password`.
+///
+/// Items are written in the order they were created, and the type of each is taken from
+/// its `xdg:schema` attribute.
+pub fn write(keyring: &Keyring, password: &[u8]) -> Result<Vec<u8>, error::Error> {
+    let mut salt = [0u8; SALT_LENGTH];
+    getrandom::getrandom(&mut salt).expect("system random number generator unavailable");
+    let mut random = [0u8; 4];
+    getrandom::getrandom(&mut random).expect("system random number generator unavailable");
+    let hash_iterations = HASH_ITERATIONS.start
+        + u32::from_be_bytes(random) % (HASH_ITERATIONS.end - HASH_ITERATIONS.start);
+
+    let collection = &keyring.collection;
+    let mut items: Vec<&storage::StoredItem> = collection.items.values().collect();
+    items.sort_by_key(|item| (item.created, item.id));
+
+    let mut writer = Writer::default();
+    writer.bytes(HEADER);
+    writer.bytes(&VERSION);
+    writer.string(Some(collection.label.as_bytes()));
+    writer.time(collection.created);
+    writer.time(collection.modified);
+    // Flags, and lock timeout.
+    writer.u32(0);
+    writer.u32(0);
+    writer.u32(hash_iterations);
+    writer.bytes(&salt);
+    for _ in 0..4 {
+        writer.u32(0);
+    }
+
+    writer.u32(items.len() as u32);
+    for (id, item) in (1..).zip(items.iter()) {
+        writer.u32(id);
+        writer.u32(item_type(item));
+        writer.u32(item.attributes.len() as u32);
+        for (name, value) in sorted_attributes(item) {
+            writer.string(Some(name.as_bytes()));
+            writer.u32(ATTRIBUTE_STRING);
+            writer.string(Some(hex(&md5::Md5::digest(value.as_bytes())).as_bytes()));
+        }
+    }
+
+    // Room for the MD5 digest of the rest of the block.
+    let mut block = Writer::default();
+    block.bytes(&[0u8; 16]);
+    for item in items {
+        let secret = keyring.secrets.get(&item.id).ok_or_else(|| {
+            error::Error::Storage(format!("Missing secret of item '{}'", item.id))
+        })?;
+        block.string(Some(item.label.as_bytes()));
+        block.string(Some(secret));
+        block.time(item.created);
+        block.time(item.modified);
+        block.string(None);
+        for _ in 0..4 {
+            block.u32(0);
+        }
+        block.u32(item.attributes.len() as u32);
+        for (name, value) in sorted_attributes(item) {
+            block.string(Some(name.as_bytes()));
+            block.u32(ATTRIBUTE_STRING);
+            block.string(Some(value.as_bytes()));
+        }
+        // No access control list.
+        block.u32(0);
+    }
+    let mut block = block.0;
+    block.resize(block.len().next_multiple_of(BLOCK_LENGTH), 0);
+    let digest = md5::Md5::digest(&block[16..]);
+    block[..16].copy_from_slice(&digest);
+
+    let (key, iv) = derive_key(password, &salt, hash_iterations);
+    let encrypted = Aes128CbcEnc::new(key.as_ref().into(), &iv.into())
+        .encrypt_padded_vec_mut::<block_padding::NoPadding>(&block);
+    zeroize::Zeroize::zeroize(&mut block);
+
+    writer.u32(encrypted.len() as u32);
+    writer.bytes(&encrypted);
+
+    Ok(writer.0)
+}
+
+/// Import the keyring file in `contents` into `storage`, as a new collection protected by
+/// `password`, the password of the keyring, and leave it unlocked.
+///
+/// Returns the imported collection, or `None` if `password` is wrong.
+pub async fn import(
+    storage: &dyn storage::Storage,
+    contents: &[u8],
+    password: &[u8],
+) -> Result<Option<storage::StoredCollection>, error::Error> {
+    let Some(keyring) = read(contents, password)? else {
+        return Ok(None);
+    };
+    store(storage, keyring, password).await.map(Some)
+}
+
+/// Import the keyring file of `config` into `storage`, unless it was imported before.
+///
+/// Completed imports are recorded by the canonical path of their file in the JSON file at
+/// `record_path`, so that each file is only imported once, however many times the server
+/// is started, and a collection deleted afterwards stays deleted. Returns the imported
+/// collection, or `None` if it was already imported or the password is wrong.
+pub async fn import_file(
+    storage: &dyn storage::Storage,
+    config: &ImportConfig,
+    record_path: &path::Path,
+) -> Result<Option<storage::StoredCollection>, error::Error> {
+    let path = fs::canonicalize(&config.path)?;
+    let mut imported: collections::BTreeSet<path::PathBuf> = match fs::read(record_path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => collections::BTreeSet::new(),
+        contents => serde_json::from_slice(&contents?)?,
+    };
+    if imported.contains(&path) {
+        log::debug!("Keyring '{}' was already imported", path.display());
+        return Ok(None);
+    }
+
+    let password = config.password.as_bytes();
+    let Some(keyring) = read(&fs::read(&path)?, password)? else {
+        log::warn!("Wrong password to import keyring '{}'", path.display());
+        return Ok(None);
+    };
+    let collection = store(storage, keyring, password).await?;
+    log::info!(
+        "Imported keyring '{}' as collection '{}' with {} items",
+        path.display(),
+        collection.id,
+        collection.items.len()
+    );
+
+    imported.insert(path);
+    storage::write_atomically(record_path, &serde_json::to_vec(&imported)?)?;
+
+    Ok(Some(collection))
+}
+
+/// Add the collection of `keyring` to `storage`, protected by `password`, and leave it
+/// unlocked.
+async fn store(
+    storage: &dyn storage::Storage,
+    keyring: Keyring,
+    password: &[u8],
+) -> Result<storage::StoredCollection, error::Error> {
+    let collection = keyring.collection;
+    storage
+        .create_collection(collection.clone(), password)
+        .await?;
+
+    for item in collection.items.values() {
+        let created = storage
+            .create_item(&collection.id, item.clone(), &keyring.secrets[&item.id])
+            .await;
+        if let Err(e) = created {
+            // Don't leave a partially imported keyring behind.
+            storage.delete_collection(&collection.id).await?;
+            return Err(e);
+        }
+    }
+    // Creating items bumped the timestamp of the collection to theirs.
+    storage
+        .touch_collection(&collection.id, collection.modified)
+        .await?;
+
+    Ok(collection)
+}
+
+/// Export an unlocked collection of `storage` as a keyring file encrypted with `password`.
+pub async fn export(
+    storage: &dyn storage::Storage,
+    collection_id: &uuid::Uuid,
+    password: &[u8],
+) -> Result<Vec<u8>, error::Error> {
+    let collection = storage
+        .collection(collection_id)
+        .await
+        .ok_or_else(|| error::Error::NoSuchObject(collection_id.to_string()))?;
+
+    let mut secrets = collections::HashMap::new();
+    for item_id in collection.items.keys() {
+        secrets.insert(*item_id, storage.read_secret(collection_id, item_id).await?);
+    }
+
+    write(
+        &Keyring {
+            collection,
+            secrets,
+        },
+        password,
+    )
+}
+
+fn unknown_attribute_type(attribute_type: u32) -> error::Error {
+    error::Error::Storage(format!(
+        "Unknown attribute type {attribute_type} in gnome-keyring file"
+    ))
+}
+
+/// Type of gnome-keyring item matching the `xdg:schema` of `item`, or generic if none does.
+fn item_type(item: &storage::StoredItem) -> u32 {
+    item.attributes
+        .get(SCHEMA_ATTRIBUTE)
+        .and_then(|schema| ITEM_TYPES.iter().find(|(_, s)| s == schema))
+        .map_or(0, |(item_type, _)| *item_type)
+}
+
+fn sorted_attributes(item: &storage::StoredItem) -> Vec<(&String, &String)> {
+    let mut attributes: Vec<_> = item.attributes.iter().collect();
+    attributes.sort();
+    attributes
+}
+
+/// Derive the AES-128 key and IV to encrypt a keyring with.
+///
+/// The first SHA-256 digest is taken over `password` followed by `salt`, and each of the
+/// following iterations over the previous digest. Its two halves are the key and the IV.
+fn derive_key(
+    password: &[u8],
+    salt: &[u8],
+    iterations: u32,
+) -> (zeroize::Zeroizing<[u8; 16]>, [u8; 16]) {
+    let mut digest = sha2::Sha256::new()
+        .chain_update(password)
+        .chain_update(salt)
+        .finalize();
+    for _ in 1..iterations {
+        digest = sha2::Sha256::digest(digest);
+    }
+
+    let mut key = zeroize::Zeroizing::new([0u8; 16]);
+    let mut iv = [0u8; 16];
+    key.copy_from_slice(&digest[..16]);
+    iv.copy_from_slice(&digest[16..]);
+    zeroize::Zeroize::zeroize(digest.as_mut_slice());
+
+    (key, iv)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Reads the big-endian values of a keyring file, failing once it runs out of bytes.
+struct Reader<'a> {
+    buffer: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(buffer: &'a [u8]) -> Self {
+        Self { buffer, offset: 0 }
+    }
+
+    fn bytes(&mut self, length: usize) -> Result<&'a [u8], error::Error> {
+        let bytes = self
+            .offset
+            .checked_add(length)
+            .and_then(|end| self.buffer.get(self.offset..end))
+            .ok_or_else(|| error::Error::Storage("Truncated gnome-keyring file".to_owned()))?;
+        self.offset += length;
+        Ok(bytes)
+    }
+
+    fn u32(&mut self) -> Result<u32, error::Error> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_be_bytes(
+            bytes.try_into().expect("read exactly 4 bytes"),
+        ))
+    }
+
+    fn time(&mut self) -> Result<u64, error::Error> {
+        let high = self.u32()? as u64;
+        let low = self.u32()? as u64;
+        Ok((high << 32) | low)
+    }
+
+    fn string(&mut self) -> Result<Option<&'a [u8]>, error::Error> {
+        match self.u32()? {
+            u32::MAX => Ok(None),
+            length => self.bytes(length as usize).map(Some),
+        }
+    }
+
+    fn utf8(&mut self) -> Result<Option<String>, error::Error> {
+        self.string()?
+            .map(|bytes| {
+                String::from_utf8(bytes.to_vec()).map_err(|_| {
+                    error::Error::Storage("Invalid UTF-8 string in gnome-keyring file".to_owned())
+                })
+            })
+            .transpose()
+    }
+}
+
+/// Writes the big-endian values of a keyring file.
+#[derive(Default)]
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.0.extend_from_slice(bytes);
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes(&value.to_be_bytes());
+    }
+
+    fn time(&mut self, value: u64) {
+        self.u32((value >> 32) as u32);
+        self.u32(value as u32);
+    }
+
+    fn string(&mut self, value: Option<&[u8]>) {
+        match value {
+            Some(value) => {
+                self.u32(value.len() as u32);
+                self.bytes(value);
+            }
+            None => self.u32(u32::MAX),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::storage::Storage as _;
+
+    const PASSWORD: &[u8] = b"password";
+
+    fn be(value: u32) -> [u8; 4] {
+        value.to_be_bytes()
+    }
+
+    fn string(value: &[u8]) -> Vec<u8> {
+        [&be(value.len() as u32)[..], value].concat()
+    }
+
+    /// Build a keyring file field by field, as laid out by the format specification.
+    ///
+    /// It has a generic item with an `xdg:schema`, and a network password item with a
+    /// `uint32` attribute, a NULL label, and an access control list entry.
+    fn fixture() -> Vec<u8> {
+        let salt = [1, 2, 3, 4, 5, 6, 7, 8];
+        let hash_iterations = 1234;
+
+        let mut file = b"GnomeKeyring\n\r\0\n".to_vec();
+        file.extend([0, 0, 0, 0]);
+        file.extend(string(b"login"));
+        file.extend([be(0), be(1_700_000_000), be(0), be(1_700_000_100)].concat());
+        file.extend([be(1), be(300), be(hash_iterations)].concat());
+        file.extend(salt);
+        file.extend([be(0); 4].concat());
+
+        file.extend(be(2));
+        file.extend([be(1), be(0), be(2)].concat());
+        file.extend(string(b"service"));
+        file.extend(be(0));
+        file.extend(string(hex(&md5::Md5::digest(b"email")).as_bytes()));
+        file.extend(string(b"xdg:schema"));
+        file.extend(be(0));
+        file.extend(string(
+            hex(&md5::Md5::digest(b"org.example.Password")).as_bytes(),
+        ));
+        file.extend([be(2), be(1), be(2)].concat());
+        file.extend(string(b"server"));
+        file.extend(be(0));
+        file.extend(string(hex(&md5::Md5::digest(b"example.org")).as_bytes()));
+        file.extend(string(b"port"));
+        file.extend(be(1));
+        file.extend(be(443 ^ 0x18273645));
+
+        let mut block = vec![0u8; 16];
+        block.extend(string(b"Email password"));
+        block.extend(string(b"hunter2"));
+        block.extend([be(0), be(1_700_000_010), be(0), be(1_700_000_020)].concat());
+        block.extend(be(u32::MAX));
+        block.extend([be(0); 4].concat());
+        block.extend(be(2));
+        block.extend(string(b"service"));
+        block.extend(be(0));
+        block.extend(string(b"email"));
+        block.extend(string(b"xdg:schema"));
+        block.extend(be(0));
+        block.extend(string(b"org.example.Password"));
+        block.extend(be(0));
+
+        block.extend(be(u32::MAX));
+        block.extend(string(&[0x00, 0xff, 0x80]));
+        block.extend([be(0), be(1_700_000_030), be(0), be(1_700_000_040)].concat());
+        block.extend(be(u32::MAX));
+        block.extend([be(0); 4].concat());
+        block.extend(be(2));
+        block.extend(string(b"server"));
+        block.extend(be(0));
+        block.extend(string(b"example.org"));
+        block.extend(string(b"port"));
+        block.extend(be(1));
+        block.extend(be(443));
+        block.extend(be(1));
+        block.extend(be(3));
+        block.extend(string(b"Browser"));
+        block.extend(string(b"/usr/bin/browser"));
+        block.extend(be(u32::MAX));
+        block.extend(be(0));
+
+        block.resize(block.len().next_multiple_of(16), 0);
+        let digest = md5::Md5::digest(&block[16..]);
+        block[..16].copy_from_slice(&digest);
+
+        let mut digest = sha2::Sha256::new()
+            .chain_update(PASSWORD)
+            .chain_update(salt)
+            .finalize();
+        for _ in 1..hash_iterations {
+            digest = sha2::Sha256::digest(digest);
+        }
+        let encrypted = Aes128CbcEnc::new(digest[..16].into(), digest[16..].into())
+            .encrypt_padded_vec_mut::<block_padding::NoPadding>(&block);
+        file.extend(be(encrypted.len() as u32));
+        file.extend(encrypted);
+
+        file
+    }
+
+    /// Items of `keyring` along with their secrets, in the order they were created.
+    fn items(keyring: &Keyring) -> Vec<(storage::StoredItem, Vec<u8>)> {
+        let mut items: Vec<_> = keyring
+            .collection
+            .items
+            .values()
+            .map(|item| (item.clone(), keyring.secrets[&item.id].to_vec()))
+            .collect();
+        items.sort_by_key(|(item, _)| item.created);
+        items
+    }
+
+    #[test]
+    fn test_read_fixture() -> Result<(), error::Error> {
+        let data_dir = tempfile::tempdir()?;
+        let fixture_path = data_dir.path().join("login.keyring");
+        fs::write(&fixture_path, fixture())?;
+        let contents = fs::read(&fixture_path)?;
+
+        assert!(read(&contents, b"wrong-password")?.is_none());
+
+        let keyring = read(&contents, PASSWORD)?.unwrap();
+        assert_eq!(keyring.collection.label, "login");
+        assert_eq!(keyring.collection.created, 1_700_000_000);
+        assert_eq!(keyring.collection.modified, 1_700_000_100);
+
+        let items = items(&keyring);
+        assert_eq!(items.len(), 2);
+
+        let (email, secret) = &items[0];
+        assert_eq!(email.label, "Email password");
+        assert_eq!(email.created, 1_700_000_010);
+        assert_eq!(email.modified, 1_700_000_020);
+        assert_eq!(
+            email.attributes,
+            collections::HashMap::from([
+                ("service".to_owned(), "email".to_owned()),
+                ("xdg:schema".to_owned(), "org.example.Password".to_owned()),
+            ])
+        );
+        assert_eq!(secret, b"hunter2");
+
+        let (network, secret) = &items[1];
+        assert_eq!(network.label, "");
+        assert_eq!(network.created, 1_700_000_030);
+        assert_eq!(network.modified, 1_700_000_040);
+        assert_eq!(
+            network.attributes,
+            collections::HashMap::from([
+                ("server".to_owned(), "example.org".to_owned()),
+                ("port".to_owned(), "443".to_owned()),
+                (
+                    "xdg:schema".to_owned(),
+                    "org.gnome.keyring.NetworkPassword".to_owned()
+                ),
+            ])
+        );
+        assert_eq!(secret, &[0x00, 0xff, 0x80]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_round_trip() -> Result<(), error::Error> {
+        let data_dir = tempfile::tempdir()?;
+        let keyring = read(&fixture(), PASSWORD)?.unwrap();
+
+        let exported_path = data_dir.path().join("exported.keyring");
+        fs::write(&exported_path, write(&keyring, b"new-password")?)?;
+        let contents = fs::read(&exported_path)?;
+
+        assert!(contents.starts_with(HEADER));
+        assert!(read(&contents, PASSWORD)?.is_none());
+        let exported = read(&contents, b"new-password")?.unwrap();
+
+        assert_eq!(exported.collection.label, keyring.collection.label);
+        assert_eq!(exported.collection.created, keyring.collection.created);
+        assert_eq!(exported.collection.modified, keyring.collection.modified);
+        let without_ids = |keyring: &Keyring| {
+            items(keyring)
+                .into_iter()
+                .map(|(item, secret)| (uuid::Uuid::nil(), item, secret))
+                .map(|(id, item, secret)| (storage::StoredItem { id, ..item }, secret))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(without_ids(&exported), without_ids(&keyring));
+
+        // Network passwords are written with their item type.
+        assert!(contents
+            .windows(8)
+            .any(|window| window == [&be(2)[..], &be(1)[..]].concat()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_empty_keyring_round_trip() -> Result<(), error::Error> {
+        let keyring = Keyring {
+            collection: storage::StoredCollection {
+                created: 1,
+                id: uuid::Uuid::new_v4(),
+                items: collections::HashMap::new(),
+                label: "empty".to_owned(),
+                modified: 2,
+            },
+            secrets: collections::HashMap::new(),
+        };
+
+        let exported = read(&write(&keyring, PASSWORD)?, PASSWORD)?.unwrap();
+        assert_eq!(exported.collection.label, "empty");
+        assert_eq!(exported.collection.created, 1);
+        assert_eq!(exported.collection.modified, 2);
+        assert!(exported.collection.items.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_invalid_files() {
+        let contents = fixture();
+
+        assert!(matches!(
+            read(b"NotAKeyring\n\r\0\n\0\0\0\0", PASSWORD),
+            Err(error::Error::Storage(_))
+        ));
+        for length in [0, HEADER.len(), contents.len() / 2, contents.len() - 1] {
+            assert!(
+                matches!(
+                    read(&contents[..length], PASSWORD),
+                    Err(error::Error::Storage(_))
+                ),
+                "Reading {length} bytes of the fixture should fail"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_import_and_export_keyring() -> Result<(), error::Error> {
+        let data_dir = tempfile::tempdir()?;
+        let storage = storage::files::FileStorage::open(data_dir.path())?;
+
+        let collection = storage::tests::new_collection("collection");
+        let item = storage::tests::new_item("item");
+        storage
+            .create_collection(collection.clone(), b"password")
+            .await?;
+        storage
+            .create_item(&collection.id, item.clone(), b"secret")
+            .await?;
+
+        let contents = export(&storage, &collection.id, b"keyring-password").await?;
+        assert!(import(&storage, &contents, b"wrong-password")
+            .await?
+            .is_none());
+        let imported = import(&storage, &contents, b"keyring-password")
+            .await?
+            .unwrap();
+        assert_ne!(imported.id, collection.id);
+        assert_eq!(imported.label, "collection");
+        let imported_item = imported.items.values().next().unwrap().clone();
+        assert_eq!(imported_item.label, "item");
+        // Items without a schema are exported, and so imported back, as generic secrets.
+        let mut attributes = item.attributes.clone();
+        attributes.insert(
+            "xdg:schema".to_owned(),
+            "org.freedesktop.Secret.Generic".to_owned(),
+        );
+        assert_eq!(imported_item.attributes, attributes);
+        assert_eq!(
+            (imported_item.created, imported_item.modified),
+            (item.created, item.modified)
+        );
+
+        // The imported collection is kept, protected by the password of the keyring.
+        drop(storage);
+        let storage = storage::files::FileStorage::open(data_dir.path())?;
+        assert_eq!(
+            storage.collection(&imported.id).await.unwrap().modified,
+            imported.modified
+        );
+        assert!(
+            storage
+                .unlock_collection(&imported.id, b"keyring-password")
+                .await?
+        );
+        assert_eq!(
+            storage
+                .read_secret(&imported.id, &imported_item.id)
+                .await?
+                .as_slice(),
+            b"secret"
+        );
+
+        // Secrets can't be exported from locked collections.
+        storage.lock_collection(&collection.id).await?;
+        assert!(matches!(
+            export(&storage, &collection.id, b"keyring-password").await,
+            Err(error::Error::IsLocked(_))
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_import_file_only_once() -> Result<(), error::Error> {
+        let data_dir = tempfile::tempdir()?;
+        let keyring_path = data_dir.path().join("login.keyring");
+        fs::write(&keyring_path, fixture())?;
+        let record_path = data_dir.path().join("imported_keyrings.json");
+        let storage = storage::files::FileStorage::open(&data_dir.path().join("storage"))?;
+
+        let wrong_password = ImportConfig {
+            path: keyring_path.clone(),
+            password: "wrong-password".to_owned(),
+        };
+        assert!(import_file(&storage, &wrong_password, &record_path)
+            .await?
+            .is_none());
+        assert!(storage.collections().await.is_empty());
+        assert!(!record_path.exists());
+
+        let config = ImportConfig {
+            path: keyring_path,
+            password: "password".to_owned(),
+        };
+        let imported = import_file(&storage, &config, &record_path).await?.unwrap();
+        assert_eq!(imported.label, "login");
+        assert_eq!(imported.items.len(), 2);
+        assert!(!storage.is_locked(&imported.id).await);
+
+        // Importing it again, as when the server is restarted, leaves it as it is.
+        drop(storage);
+        let storage = storage::files::FileStorage::open(&data_dir.path().join("storage"))?;
+        assert!(import_file(&storage, &config, &record_path)
+            .await?
+            .is_none());
+        assert_eq!(storage.collections().await, vec![imported.clone()]);
+        assert!(storage.unlock_collection(&imported.id, PASSWORD).await?);
+
+        // Nor is it imported again once its collection is deleted.
+        storage.delete_collection(&imported.id).await?;
+        assert!(import_file(&storage, &config, &record_path)
+            .await?
+            .is_none());
+        assert!(storage.collections().await.is_empty());
+
+        let missing = ImportConfig {
+            path: data_dir.path().join("missing.keyring"),
+            ..config
+        };
+        assert!(matches!(
+            import_file(&storage, &missing, &record_path).await,
+            Err(error::Error::Io(_))
+        ));
+
+        Ok(())
+    }
+}
@@ -1,10 +1,18 @@
 use std::env;
 use std::path;
 
+pub mod access;
 pub mod error;
+pub mod index;
+pub mod kdbx;
+pub mod keyring;
 pub mod object;
 pub mod secret;
 pub mod server;
+pub mod storage;
+
+/// File of `storage_path` recording the keyrings of `import_keyrings` already imported.
+const IMPORTED_KEYRINGS_FILE: &str = "imported_keyrings.json";
 
 #[tokio::main]
 async fn main() -> Result<(), error::Error> {
@@ -16,6 +24,8 @@ async fn main() -> Result<(), error::Error> {
     let mut builder = config::Config::builder()
         .set_default("log_level", "INFO")?
         .set_default("dbus_name", "org.freedesktop.secrets")?
+        .set_default("storage_path", default_storage_path())?
+        .set_default("storage_backend", "files")?
         .add_source(config::Environment::with_prefix("sss"));
 
     builder = if config_path.exists() {
@@ -40,8 +50,85 @@ async fn main() -> Result<(), error::Error> {
         .get("dbus_name")
         .expect("dus_name defaults to 'org.freedesktop.secrets'");
 
-    let server = server::SecretServiceServer::new(&dbus_name, event_listener::Event::new()).await?;
+    let storage_path: path::PathBuf = settings
+        .get("storage_path")
+        .expect("storage_path defaults to XDG data directory");
+    let storage: Box<dyn storage::Storage> = match settings
+        .get_string("storage_backend")
+        .expect("storage_backend defaults to 'files'")
+        .as_str()
+    {
+        "files" => Box::new(storage::files::FileStorage::open(&storage_path)?),
+        "sqlite" => Box::new(storage::sqlite::SqliteStorage::open(&storage_path)?),
+        backend => {
+            return Err(config::ConfigError::Message(format!(
+                "Unknown storage_backend '{backend}', expected 'files' or 'sqlite'"
+            ))
+            .into())
+        }
+    };
+
+    let databases = match settings.get::<Vec<kdbx::DatabaseConfig>>("kdbx_databases") {
+        Err(config::ConfigError::NotFound(_)) => Vec::new(),
+        databases => databases?,
+    };
+    let mut storage = storage::keepass::KdbxStorage::new(storage);
+    for database in databases {
+        storage.open(&database)?;
+    }
+
+    let keyrings = match settings.get::<Vec<keyring::ImportConfig>>("import_keyrings") {
+        Err(config::ConfigError::NotFound(_)) => Vec::new(),
+        keyrings => keyrings?,
+    };
+    let import_record_path = storage_path.join(IMPORTED_KEYRINGS_FILE);
+    for keyring in keyrings {
+        // A keyring that can't be imported shouldn't keep the server from starting.
+        if let Err(e) = keyring::import_file(&storage, &keyring, &import_record_path).await {
+            log::error!("Failed to import keyring '{}': {e}", keyring.path.display());
+        }
+    }
+
+    // Without a prompter, only collections without a password can be unlocked.
+    let prompter = settings
+        .get_string("prompter_command")
+        .ok()
+        .and_then(|command| {
+            object::prompt::Prompter::new(command.split_whitespace().map(String::from).collect())
+        });
+
+    let policy = match settings.get::<access::Policy>("access") {
+        Err(config::ConfigError::NotFound(_)) => access::Policy::default(),
+        policy => policy?,
+    };
+
+    let server = server::SecretServiceServer::new(
+        &dbus_name,
+        storage,
+        prompter,
+        policy,
+        event_listener::Event::new(),
+    )
+    .await?;
     server.run().await?;
 
     Ok(())
 }
+
+/// Default location for storage: `$XDG_DATA_HOME/secret-service-server`.
+///
+/// Falls back to `$HOME/.local/share` as the XDG data directory if `XDG_DATA_HOME` is not set.
+fn default_storage_path() -> String {
+    let mut storage_path = match env::var("XDG_DATA_HOME") {
+        Ok(data_folder) if !data_folder.is_empty() => path::PathBuf::from(data_folder),
+        _ => {
+            let mut data_folder = path::PathBuf::from(env::var("HOME").unwrap_or_default());
+            data_folder.push(".local");
+            data_folder.push("share");
+            data_folder
+        }
+    };
+    storage_path.push("secret-service-server");
+
+    storage_path.to_string_lossy().into_owned()
+}
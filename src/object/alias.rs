@@ -0,0 +1,156 @@
+use std::collections;
+use std::fmt;
+
+use crate::error;
+use crate::object::collection;
+use crate::object::item;
+use crate::object::DbusObject;
+use crate::secret;
+
+const ALIASES_PATH: &str = "/org/freedesktop/secrets/aliases";
+
+/// Object path under which the collection aliased as `name` is also available.
+///
+/// Alias names may contain characters not allowed in object paths, so, like gnome-keyring,
+/// any character other than an ASCII letter or digit is escaped as `_` followed by its bytes
+/// in hex.
+pub fn object_path(name: &str) -> zvariant::OwnedObjectPath {
+    let mut object_path = ALIASES_PATH.to_owned();
+    object_path.push('/');
+
+    for byte in name.bytes() {
+        if byte.is_ascii_alphanumeric() {
+            object_path.push(byte as char);
+        } else {
+            object_path.push_str(&format!("_{byte:02x}"));
+        }
+    }
+
+    zvariant::ObjectPath::from_str_unchecked(&object_path).into()
+}
+
+/// An alias of a collection, implementing `org.freedesktop.Secret.Collection` by forwarding
+/// to the collection it points to.
+///
+/// The target is held directly instead of being looked up in the object server, as the
+/// properties of an alias are read while the object server is locked to serve it. So,
+/// re-pointing an alias with `SetAlias` serves a new `Alias` in its place.
+pub struct Alias {
+    pub name: String,
+    target: zbus::object_server::InterfaceRef<collection::Collection>,
+}
+
+impl fmt::Debug for Alias {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Alias")
+            .field("name", &self.name)
+            .field("target", &self.target.signal_emitter().path())
+            .finish()
+    }
+}
+
+impl DbusObject for Alias {
+    fn get_object_path(&self) -> zvariant::OwnedObjectPath {
+        object_path(&self.name)
+    }
+}
+
+impl Alias {
+    pub fn new(
+        name: &str,
+        target: zbus::object_server::InterfaceRef<collection::Collection>,
+    ) -> Self {
+        Self {
+            name: name.to_owned(),
+            target,
+        }
+    }
+}
+
+#[zbus::interface(name = "org.freedesktop.Secret.Collection")]
+impl Alias {
+    /// CreateItem method
+    async fn create_item(
+        &self,
+        properties: item::ItemReadWriteProperties,
+        secret: secret::Secret,
+        replace: bool,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+        #[zbus(object_server)] object_server: &zbus::ObjectServer,
+    ) -> Result<(zvariant::OwnedObjectPath, zvariant::OwnedObjectPath), error::Error> {
+        let collection = self.target.get().await;
+        let (item_path, prompt_path) = collection
+            .create_item(
+                properties,
+                secret,
+                replace,
+                header,
+                self.target.signal_emitter().clone(),
+                object_server,
+            )
+            .await?;
+
+        Ok((item_path, prompt_path.into()))
+    }
+
+    /// Delete method
+    async fn delete(
+        &self,
+        #[zbus(object_server)] object_server: &zbus::ObjectServer,
+    ) -> Result<zvariant::OwnedObjectPath, error::Error> {
+        let collection = self.target.get().await;
+        let prompt_path = collection
+            .delete(object_server, self.target.signal_emitter().clone())
+            .await?;
+
+        Ok(prompt_path.into())
+    }
+
+    /// SearchItems method
+    async fn search_items(
+        &self,
+        attributes: collections::HashMap<String, String>,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+    ) -> Result<Vec<zvariant::OwnedObjectPath>, error::Error> {
+        self.target
+            .get()
+            .await
+            .search_items(attributes, header)
+            .await
+    }
+
+    /// Created property
+    #[zbus(property)]
+    async fn created(&self) -> u64 {
+        self.target.get().await.created
+    }
+
+    /// Items property
+    #[zbus(property)]
+    async fn items(&self) -> Vec<zvariant::OwnedObjectPath> {
+        self.target.get().await.items()
+    }
+
+    /// Label property
+    #[zbus(property)]
+    async fn label(&self) -> String {
+        self.target.get().await.label.clone()
+    }
+
+    #[zbus(property)]
+    async fn set_label(&mut self, value: &str) -> zbus::fdo::Result<()> {
+        self.target.get_mut().await.set_label(value).await
+    }
+
+    /// Locked property
+    #[zbus(property)]
+    async fn locked(&self) -> bool {
+        self.target.get().await.locked().await
+    }
+
+    /// Modified property
+    #[zbus(property)]
+    async fn modified(&self) -> u64 {
+        self.target.get().await.modified()
+    }
+}
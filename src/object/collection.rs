@@ -1,25 +1,49 @@
 use std::collections;
-use std::iter::Iterator;
-use std::time;
+use std::sync;
 
+use futures::FutureExt;
+
+use crate::access;
 use crate::error;
+use crate::index;
+use crate::object;
+use crate::object::alias;
 use crate::object::item;
+use crate::object::prompt;
 use crate::object::service;
+use crate::object::session;
 use crate::object::{DbusChildObject, DbusObject, DbusParentObject};
 use crate::secret;
+use crate::storage;
+
+/// Object path of the collection with `id`.
+pub fn object_path(id: &uuid::Uuid) -> zvariant::OwnedObjectPath {
+    let mut object_path = "/org/freedesktop/secrets/collection/".to_owned();
+    object_path.push_str(
+        id.as_simple()
+            .encode_lower(&mut uuid::Uuid::encode_buffer()),
+    );
+
+    zvariant::ObjectPath::from_str_unchecked(&object_path).into()
+}
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug)]
 pub struct Collection {
-    pub alias: Option<String>,
+    pub access: sync::Arc<access::AccessControl>,
+    /// Aliases of all collections, shared with `Service`.
+    aliases: sync::Arc<sync::Mutex<collections::HashMap<String, zvariant::OwnedObjectPath>>>,
+    pub connection: zbus::Connection,
     pub created: u64,
     pub id: uuid::Uuid,
     pub label: String,
-    pub locked: bool,
-    pub items: collections::HashSet<zvariant::OwnedObjectPath>,
-    pub items_with_attributes:
-        collections::HashMap<zvariant::OwnedObjectPath, collections::HashSet<(String, String)>>,
-    pub modified: u64,
+    pub index: sync::Arc<sync::Mutex<index::AttributeIndex>>,
+    pub items: object::Children,
+    /// Shared with items, so that they can bump it without locking the collection.
+    pub modified: sync::Arc<sync::atomic::AtomicU64>,
     pub parent_path: zvariant::OwnedObjectPath,
+    /// All collections, shared with `Service`.
+    siblings: object::Children,
+    pub storage: sync::Arc<dyn storage::Storage>,
 }
 
 #[derive(zvariant::DeserializeDict, zvariant::SerializeDict, zvariant::Type)]
@@ -31,34 +55,14 @@ pub struct CollectionReadWriteProperties {
 
 impl DbusObject for Collection {
     fn get_object_path(&self) -> zvariant::OwnedObjectPath {
-        if let Some(alias) = &self.alias {
-            if alias == "default" {
-                return zvariant::ObjectPath::from_str_unchecked(
-                    "/org/freedesktop/secrets/aliases/default",
-                )
-                .into();
-            }
-        }
-
-        let mut object_path = "/org/freedesktop/secrets/collection/".to_owned();
-        object_path.push_str(
-            self.id
-                .as_simple()
-                .encode_lower(&mut uuid::Uuid::encode_buffer()),
-        );
-
-        zvariant::ObjectPath::from_str_unchecked(&object_path).into()
+        object_path(&self.id)
     }
 }
 
 impl DbusParentObject for Collection {
-    fn get_children(&self) -> &collections::HashSet<zvariant::OwnedObjectPath> {
+    fn get_children(&self) -> &object::Children {
         &self.items
     }
-
-    fn get_mut_children(&mut self) -> &mut collections::HashSet<zvariant::OwnedObjectPath> {
-        &mut self.items
-    }
 }
 
 impl DbusChildObject for Collection {
@@ -67,180 +71,466 @@ impl DbusChildObject for Collection {
     fn get_parent_path(&self) -> zvariant::ObjectPath<'_> {
         self.parent_path.as_ref()
     }
+
+    fn get_siblings(&self) -> &object::Children {
+        &self.siblings
+    }
 }
 
 impl Collection {
-    pub fn new(
-        id: uuid::Uuid,
-        label: &str,
-        alias: Option<&str>,
-        service: &service::Service,
-    ) -> Self {
-        let created = time::SystemTime::now()
-            .duration_since(time::SystemTime::UNIX_EPOCH)
-            .expect("current SystemTime before UNIX EPOCH")
-            .as_secs();
+    pub fn new(id: uuid::Uuid, label: &str, service: &service::Service) -> Self {
+        let created = object::timestamp();
 
         Self {
             id,
-            alias: alias.map(|s| s.to_owned()),
+            access: service.access.clone(),
+            aliases: service.aliases.clone(),
             created,
-            items: collections::HashSet::new(),
+            items: object::Children::default(),
             label: label.to_owned(),
-            locked: false,
-            items_with_attributes: collections::HashMap::new(),
-            modified: created,
+            connection: service.connection.clone(),
+            index: service.index.clone(),
+            modified: sync::Arc::new(sync::atomic::AtomicU64::new(created)),
             parent_path: service.get_object_path().clone(),
+            siblings: service.collections.clone(),
+            storage: service.storage.clone(),
         }
     }
 
-    pub fn new_default(service: &service::Service) -> Self {
-        let created = time::SystemTime::now()
-            .duration_since(time::SystemTime::UNIX_EPOCH)
-            .expect("current SystemTime before UNIX EPOCH")
-            .as_secs();
-
+    pub fn from_stored(stored: &storage::StoredCollection, service: &service::Service) -> Self {
         Self {
-            id: uuid::Uuid::new_v4(),
-            alias: Some("default".to_string()),
-            created,
-            items: collections::HashSet::new(),
-            label: "default".to_string(),
-            locked: false,
-            items_with_attributes: collections::HashMap::new(),
-            modified: created,
+            id: stored.id,
+            access: service.access.clone(),
+            aliases: service.aliases.clone(),
+            created: stored.created,
+            items: object::Children::default(),
+            label: stored.label.clone(),
+            connection: service.connection.clone(),
+            index: service.index.clone(),
+            modified: sync::Arc::new(sync::atomic::AtomicU64::new(stored.modified)),
             parent_path: service.get_object_path().clone(),
+            siblings: service.collections.clone(),
+            storage: service.storage.clone(),
+        }
+    }
+
+    /// A collection for `stored`, served by the same service as this one.
+    pub fn sibling(&self, stored: &storage::StoredCollection) -> Self {
+        Self {
+            id: stored.id,
+            access: self.access.clone(),
+            aliases: self.aliases.clone(),
+            created: stored.created,
+            items: object::Children::default(),
+            label: stored.label.clone(),
+            connection: self.connection.clone(),
+            index: self.index.clone(),
+            modified: sync::Arc::new(sync::atomic::AtomicU64::new(stored.modified)),
+            parent_path: self.parent_path.clone(),
+            siblings: self.siblings.clone(),
+            storage: self.storage.clone(),
+        }
+    }
+
+    /// Convert to `storage::StoredCollection`, without any items.
+    pub fn to_stored(&self) -> storage::StoredCollection {
+        storage::StoredCollection {
+            created: self.created,
+            id: self.id,
+            items: collections::HashMap::new(),
+            label: self.label.clone(),
+            modified: self.modified(),
         }
     }
 
-    pub fn insert_item<'a, I>(
-        &mut self,
+    /// Emitter for signals that this collection sends about itself.
+    pub fn emitter(&self) -> zbus::Result<zbus::object_server::SignalEmitter<'static>> {
+        zbus::object_server::SignalEmitter::new(&self.connection, self.get_object_path())
+    }
+
+    /// Emitter for signals that `Service` sends about this collection.
+    pub fn parent_emitter(&self) -> zbus::Result<zbus::object_server::SignalEmitter<'_>> {
+        zbus::object_server::SignalEmitter::new(&self.connection, self.parent_path.as_ref())
+    }
+
+    /// Fail with `IsLocked` if the collection is locked.
+    async fn ensure_unlocked(&self) -> Result<(), error::Error> {
+        if self.locked().await {
+            return Err(error::Error::IsLocked(self.get_object_path().to_string()));
+        }
+        Ok(())
+    }
+
+    /// Bump the `Modified` timestamp after a change to the collection or one of its items.
+    pub async fn touch(&self, modified: u64) -> Result<(), error::Error> {
+        self.storage.touch_collection(&self.id, modified).await?;
+        self.modified
+            .store(modified, sync::atomic::Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Add an item to the collection, and index its attributes.
+    pub fn insert_item(
+        &self,
         item_object_path: zvariant::OwnedObjectPath,
-        attributes: I,
-        replace: bool,
-    ) where
-        I: Iterator<Item = (&'a str, &'a str)>,
-    {
-        let attributes_set: collections::HashSet<(String, String)> = attributes
-            .map(|(key, value)| (key.to_owned(), value.to_owned()))
-            .collect();
+        attributes: collections::HashMap<String, String>,
+    ) {
+        self.items
+            .lock()
+            .expect("lock is not poisoned")
+            .insert(item_object_path.clone());
+        self.index.lock().expect("lock is not poisoned").insert(
+            item_object_path,
+            self.id,
+            attributes,
+        );
+    }
+
+    /// Update the collection served on `interface`, and its items, to match storage.
+    ///
+    /// Items only found in storage are served, items no longer in storage are removed, and
+    /// the rest are updated in place.
+    pub async fn reload(
+        interface: &zbus::object_server::InterfaceRef<Collection>,
+        object_server: &zbus::ObjectServer,
+    ) -> Result<(), error::Error> {
+        let emitter = interface.signal_emitter();
+        let (storage, id) = {
+            let collection = interface.get().await;
+            (collection.storage.clone(), collection.id)
+        };
+        let Some(stored) = storage.collection(&id).await else {
+            return Ok(());
+        };
+
+        if interface.get().await.label != stored.label {
+            let mut collection = interface.get_mut().await;
+            collection.label = stored.label;
+            collection.label_changed(emitter).await?;
+        }
 
-        if replace {
-            self.items_with_attributes
-                .retain(|_, value| value == &attributes_set);
+        let collection = interface.get().await;
+        let mut stored_items = stored.items;
+        let mut items_changed = false;
+        for item_path in collection.items() {
+            let item_interface =
+                item::Item::get_interface_from_object_path(&item_path.as_ref(), object_server)
+                    .await?;
+            let item_id = item_interface.get().await.id;
+
+            let Some(stored_item) = stored_items.remove(&item_id) else {
+                collection
+                    .items
+                    .lock()
+                    .expect("lock is not poisoned")
+                    .remove(&item_path);
+                collection
+                    .index
+                    .lock()
+                    .expect("lock is not poisoned")
+                    .remove(&item_path);
+                object_server.remove::<item::Item, _>(&item_path).await?;
+                emitter.item_deleted(&item_path.as_ref()).await?;
+                items_changed = true;
+                continue;
+            };
+
+            let mut item = item_interface.get_mut().await;
+            let item_emitter = item_interface.signal_emitter();
+            let mut item_changed = false;
+            if item.label != stored_item.label {
+                item.label = stored_item.label;
+                item.label_changed(item_emitter).await?;
+                item_changed = true;
+            }
+            if item.attributes != stored_item.attributes {
+                collection
+                    .index
+                    .lock()
+                    .expect("lock is not poisoned")
+                    .insert(
+                        item_path.clone(),
+                        collection.id,
+                        stored_item.attributes.clone(),
+                    );
+                item.attributes = stored_item.attributes;
+                item.attributes_changed(item_emitter).await?;
+                item_changed = true;
+            }
+            if item.modified != stored_item.modified {
+                item.modified = stored_item.modified;
+                item.modified_changed(item_emitter).await?;
+                item_changed = true;
+            }
+            if item_changed {
+                emitter.item_changed(&item_path.as_ref()).await?;
+            }
+        }
 
-            self.items
-                .retain(|value| self.items_with_attributes.contains_key(value));
+        for stored_item in stored_items.into_values() {
+            let item = item::Item::from_stored(stored_item, &collection);
+            let attributes = item.attributes.clone();
+            let (item_path, _) = item.serve_at(object_server).await?;
+            collection.insert_item(item_path.clone(), attributes);
+            emitter.item_created(&item_path.as_ref()).await?;
+            items_changed = true;
+        }
+        if items_changed {
+            collection.items_changed(emitter).await?;
         }
 
-        self.items.insert(item_object_path.clone());
-        self.items_with_attributes
-            .insert(item_object_path.clone(), attributes_set);
+        Ok(())
+    }
+
+    /// Find an item in the collection with exactly the given attributes.
+    pub fn find_item_with_attributes(
+        &self,
+        attributes: &collections::HashMap<String, String>,
+    ) -> Option<zvariant::OwnedObjectPath> {
+        let index = self.index.lock().expect("lock is not poisoned");
+
+        index
+            .search(attributes)
+            .into_iter()
+            .find(|(item_path, collection_id)| {
+                *collection_id == self.id && index.attributes(item_path) == Some(attributes)
+            })
+            .map(|(item_path, _)| item_path.clone())
+    }
+
+    /// Replace the label and secret of the item served by `item_interface`, keeping its
+    /// attributes and object path.
+    async fn replace_item(
+        &self,
+        item_interface: &zbus::object_server::InterfaceRef<item::Item>,
+        label: &str,
+        plaintext: &[u8],
+        content_type: &str,
+        emitter: &zbus::object_server::SignalEmitter<'_>,
+    ) -> Result<(), error::Error> {
+        self.ensure_unlocked().await?;
+        let mut item = item_interface.get_mut().await;
+        let modified = item.replace(label, plaintext, content_type).await?;
+        self.touch(modified).await?;
+
+        let item_emitter = item_interface.signal_emitter();
+        item.label_changed(item_emitter).await?;
+        item.modified_changed(item_emitter).await?;
+        self.modified_changed(emitter).await?;
+        let item_path = item.get_object_path();
+        emitter.item_changed(&item_path.as_ref()).await?;
+
+        log::info!("Replaced item on '{item_path}'");
+        Ok(())
     }
 }
 
 #[zbus::interface(name = "org.freedesktop.Secret.Collection")]
 impl Collection {
     /// CreateItem method
-    async fn create_item(
-        &mut self,
+    pub async fn create_item(
+        &self,
         properties: item::ItemReadWriteProperties,
         secret: secret::Secret,
         replace: bool,
+        #[zbus(header)] header: zbus::message::Header<'_>,
         #[zbus(signal_emitter)] emitter: zbus::object_server::SignalEmitter<'_>,
         #[zbus(object_server)] object_server: &zbus::ObjectServer,
-    ) -> Result<(zvariant::ObjectPath<'_>, zvariant::ObjectPath<'_>), error::Error> {
-        let attributes: Vec<(String, String)> = properties
-            .attributes
-            .iter()
-            .map(|(key, value)| (key.to_owned(), value.to_owned()))
-            .collect();
+    ) -> Result<(zvariant::OwnedObjectPath, zvariant::ObjectPath<'_>), error::Error> {
+        self.ensure_unlocked().await?;
+        let session = session::Session::get_owned_by(
+            &secret.session.as_ref(),
+            header.sender(),
+            object_server,
+        )
+        .await?;
+        let plaintext = zeroize::Zeroizing::new(
+            session.decrypt(secret.value.as_slice(), secret.parameters.as_slice())?,
+        );
+
+        let application = self.access.caller(&header).await?;
+
+        // An item with the same attributes is updated in place, keeping its object path.
+        if let Some(item_path) = replace
+            .then(|| self.find_item_with_attributes(&properties.attributes))
+            .flatten()
+        {
+            let item_interface =
+                item::Item::get_interface_from_object_path(&item_path.as_ref(), object_server)
+                    .await?;
+            let confirmation = self
+                .access
+                .confirmation(&application, &*item_interface.get().await);
+            let Some(confirmation) = confirmation else {
+                self.access
+                    .ensure_allowed(&application, &*item_interface.get().await)?;
+                self.replace_item(
+                    &item_interface,
+                    &properties.label,
+                    &plaintext,
+                    &secret.content_type,
+                    &emitter,
+                )
+                .await?;
+                return Ok((item_path, prompt::Prompt::none()));
+            };
+
+            let access = self.access.clone();
+            let connection = self.connection.clone();
+            let collection_path = self.get_object_path();
+            let action: prompt::PromptAction = Box::new(move |window_id| {
+                async move {
+                    if !access.confirm(&confirmation, &window_id).await? {
+                        return Ok(None);
+                    }
+
+                    let object_server = connection.object_server();
+                    let collection_interface = Collection::get_interface_from_object_path(
+                        &collection_path.as_ref(),
+                        object_server,
+                    )
+                    .await?;
+                    let item_interface = item::Item::get_interface_from_object_path(
+                        &item_path.as_ref(),
+                        object_server,
+                    )
+                    .await?;
+                    collection_interface
+                        .get()
+                        .await
+                        .replace_item(
+                            &item_interface,
+                            &properties.label,
+                            &plaintext,
+                            &secret.content_type,
+                            collection_interface.signal_emitter(),
+                        )
+                        .await?;
+                    Ok(Some(zvariant::Value::from(item_path).try_into()?))
+                }
+                .boxed()
+            });
+            let (prompt_path, _) = prompt::Prompt::new(action).serve_at(object_server).await?;
+
+            return Ok((prompt::Prompt::none().into(), prompt_path.into()));
+        }
 
         let item_id = uuid::Uuid::new_v4();
         let new_item = item::Item::new(
             item_id,
-            secret,
             &properties.label,
-            attributes
-                .iter()
-                .map(|(key, value)| (key.as_str(), value.as_str())),
+            properties.attributes.clone(),
+            &secret.content_type,
+            application.executable_str(),
             self,
-            object_server,
+        );
+        self.storage
+            .create_item(&self.id, new_item.to_stored(), &plaintext)
+            .await?;
+        let item_created = new_item.created;
+        let (item_path, _) = new_item.serve_at(object_server).await?;
+        self.insert_item(item_path.clone(), properties.attributes);
+        self.touch(item_created).await?;
+
+        emitter.item_created(&item_path.as_ref()).await?;
+        self.items_changed(&emitter).await?;
+        self.modified_changed(&emitter).await?;
+        service::Service::collection_changed(
+            &self.parent_emitter()?,
+            &self.get_object_path().as_ref(),
         )
         .await?;
-        let (item_path, is_new) = new_item.serve_at(object_server).await?;
-
-        if is_new {
-            emitter.item_created().await?;
-        } else {
-            emitter.item_changed().await?;
-        }
-        service::Service::collection_changed(&emitter).await?;
 
         log::info!("Created new item on '{item_path}'");
-        self.insert_item(
-            item_path.clone(),
-            attributes
-                .iter()
-                .map(|(key, value)| (key.as_str(), value.as_str())),
-            replace,
-        );
-
-        Ok((
-            item_path.into(),
-            zvariant::ObjectPath::from_str_unchecked("/"),
-        ))
+        Ok((item_path, prompt::Prompt::none()))
     }
 
     /// Delete method
+    ///
+    /// Items are removed here instead of through `Item::delete`, so that none of them
+    /// has to lock this collection while it is locked for this call.
     pub async fn delete(
-        &mut self,
+        &self,
         #[zbus(object_server)] object_server: &zbus::ObjectServer,
         #[zbus(signal_emitter)] emitter: zbus::object_server::SignalEmitter<'_>,
     ) -> Result<zvariant::ObjectPath<'_>, error::Error> {
-        for item in self.get_mut_children().iter() {
-            if let Ok(item_interface) =
-                item::Item::get_interface_from_object_path(item, object_server).await
-            {
-                let mut item = item_interface.get_mut().await;
-                item.delete(object_server, item_interface.signal_emitter().to_owned())
-                    .await?;
-            }
+        let collection_path = self.get_object_path();
+        self.storage.delete_collection(&self.id).await?;
+
+        let item_paths: Vec<zvariant::OwnedObjectPath> = self
+            .items
+            .lock()
+            .expect("lock is not poisoned")
+            .drain()
+            .collect();
+        for item_path in item_paths {
+            self.index
+                .lock()
+                .expect("lock is not poisoned")
+                .remove(&item_path);
+            object_server.remove::<item::Item, _>(&item_path).await?;
+            emitter.item_deleted(&item_path.as_ref()).await?;
         }
 
         self.remove::<Collection>(object_server).await?;
+        let mut alias_names = Vec::new();
+        self.aliases
+            .lock()
+            .expect("lock is not poisoned")
+            .retain(|alias_name, aliased_path| {
+                if *aliased_path != collection_path {
+                    return true;
+                }
+                alias_names.push(alias_name.clone());
+                false
+            });
+        for alias_name in alias_names {
+            object_server
+                .remove::<alias::Alias, _>(alias::object_path(&alias_name))
+                .await?;
+        }
 
-        let removed = self.remove_from_parent(object_server).await;
-
-        if removed {
-            let collection_path = self.get_object_path();
+        if self.remove_from_parent() {
             log::info!("Deleted collection on '{collection_path}'");
-            service::Service::collection_deleted(&emitter).await?;
+            let parent_emitter = self.parent_emitter()?;
+            service::Service::collection_deleted(&parent_emitter, &collection_path.as_ref())
+                .await?;
+            self.parent_properties_changed(
+                &parent_emitter,
+                collections::HashMap::from([(
+                    "Collections",
+                    zvariant::Value::from(self.get_sibling_paths()),
+                )]),
+            )
+            .await?;
         }
 
-        Ok(zvariant::ObjectPath::from_str_unchecked("/"))
+        Ok(prompt::Prompt::none())
     }
 
     /// SearchItems method
-    pub fn search_items(
+    ///
+    /// Matches items that have all of the given attributes, regardless of any other
+    /// attributes they may have. So, an empty query matches every item.
+    pub async fn search_items(
         &self,
         attributes: collections::HashMap<String, String>,
-    ) -> Vec<zvariant::ObjectPath<'_>> {
-        let attributes_set: collections::HashSet<(String, String)> = attributes
-            .iter()
-            .map(|(key, value)| (key.to_owned(), value.to_owned()))
-            .collect();
-
-        self.items_with_attributes
-            .iter()
-            .filter_map(|(key, value)| {
-                if value == &attributes_set {
-                    Some(key.as_ref())
-                } else {
-                    None
-                }
+        #[zbus(header)] header: zbus::message::Header<'_>,
+    ) -> Result<Vec<zvariant::OwnedObjectPath>, error::Error> {
+        let application = self.access.caller(&header).await?;
+        let index = self.index.lock().expect("lock is not poisoned");
+
+        Ok(index
+            .search(&attributes)
+            .into_iter()
+            .filter(|(item_path, collection_id)| {
+                *collection_id == self.id
+                    && index
+                        .attributes(item_path)
+                        .is_some_and(|attributes| self.access.is_visible(&application, attributes))
             })
-            .collect()
+            .map(|(item_path, _)| item_path.clone())
+            .collect())
     }
 
     /// Created property
@@ -251,11 +541,8 @@ impl Collection {
 
     /// Items property
     #[zbus(property)]
-    fn items(&self) -> Vec<zvariant::ObjectPath<'_>> {
-        self.get_children()
-            .iter()
-            .map(|path| path.as_ref())
-            .collect()
+    pub fn items(&self) -> Vec<zvariant::OwnedObjectPath> {
+        self.get_children_paths()
     }
 
     /// Label property
@@ -265,146 +552,74 @@ impl Collection {
     }
 
     #[zbus(property)]
-    fn set_label(&mut self, value: &str) {
+    pub async fn set_label(&mut self, value: &str) -> zbus::fdo::Result<()> {
+        self.ensure_unlocked().await?;
+        let modified = object::timestamp();
+        self.storage
+            .update_collection(&self.id, value, modified)
+            .await?;
         self.label = value.to_owned();
+        self.modified
+            .store(modified, sync::atomic::Ordering::Relaxed);
+
+        self.modified_changed(&self.emitter()?).await?;
+        service::Service::collection_changed(
+            &self.parent_emitter()?,
+            &self.get_object_path().as_ref(),
+        )
+        .await?;
+        Ok(())
     }
 
     /// Locked property
     #[zbus(property)]
-    fn locked(&self) -> bool {
-        self.locked
+    pub async fn locked(&self) -> bool {
+        self.storage.is_locked(&self.id).await
     }
 
     /// Modified property
     #[zbus(property)]
-    fn modified(&self) -> u64 {
-        self.modified
+    pub fn modified(&self) -> u64 {
+        self.modified.load(sync::atomic::Ordering::Relaxed)
     }
 
     /// ItemChanged signal
     #[zbus(signal)]
-    pub async fn item_changed(emitter: &zbus::object_server::SignalEmitter<'_>)
-        -> zbus::Result<()>;
+    pub async fn item_changed(
+        emitter: &zbus::object_server::SignalEmitter<'_>,
+        item: &zvariant::ObjectPath<'_>,
+    ) -> zbus::Result<()>;
 
     /// ItemCreated signal
     #[zbus(signal)]
-    async fn item_created(emitter: &zbus::object_server::SignalEmitter<'_>) -> zbus::Result<()>;
+    async fn item_created(
+        emitter: &zbus::object_server::SignalEmitter<'_>,
+        item: &zvariant::ObjectPath<'_>,
+    ) -> zbus::Result<()>;
 
     /// ItemDeleted signal
     #[zbus(signal)]
-    pub async fn item_deleted(emitter: &zbus::object_server::SignalEmitter<'_>)
-        -> zbus::Result<()>;
+    pub async fn item_deleted(
+        emitter: &zbus::object_server::SignalEmitter<'_>,
+        item: &zvariant::ObjectPath<'_>,
+    ) -> zbus::Result<()>;
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::object::session;
+    use crate::object::testing::{create_collection, open_plain_session, run_service_server};
     use crate::secret;
-    use crate::server;
+    use futures::{FutureExt, StreamExt};
 
     use std::str;
     use std::time;
-    use uuid;
-
-    /// Run a `org.freedesktop.Secret.Service` server.
-    ///
-    /// This coroutine is meant to be awaited at the beginning of each test
-    /// function that will be making calls to test the server.
-    /// It returns a handle that **must** be aborted once the test is done,
-    /// as otherwise the task **runs forever**.
-    async fn run_service_server() -> (String, tokio::task::JoinHandle<()>) {
-        let start_event = event_listener::Event::new();
-        let start_event_listener = start_event.listen();
-        let mut dbus_name = "org.freedesktop.secrets-test-".to_owned();
-        let dbus_id = uuid::Uuid::new_v4();
-        dbus_name.push_str(
-            dbus_id
-                .as_simple()
-                .encode_lower(&mut uuid::Uuid::encode_buffer()),
-        );
-
-        let cloned_dbus_name = dbus_name.clone();
-        let run_server_handle = tokio::spawn(async move {
-            let server = server::SecretServiceServer::new(&cloned_dbus_name, start_event)
-                .await
-                .unwrap();
-            server.run().await.unwrap();
-        });
-
-        if let Err(_) =
-            tokio::time::timeout(time::Duration::from_secs(10), start_event_listener).await
-        {
-            if run_server_handle.is_finished() {
-                run_server_handle.await.unwrap();
-                panic!("Server exited early without an error");
-            } else {
-                panic!("Took to long to start test dbus server");
-            }
-        }
-
-        (dbus_name, run_server_handle)
-    }
-
-    async fn create_collection(
-        dbus_name: &str,
-        label: &str,
-    ) -> Result<zvariant::OwnedObjectPath, error::Error> {
-        let connection = zbus::Connection::session().await?;
-        let collection_properties = collections::HashMap::from([(
-            "org.freedesktop.Secret.Collection.Label",
-            zvariant::Value::new(label),
-        )]);
-
-        let reply = connection
-            .call_method(
-                Some(dbus_name),
-                "/org/freedesktop/secrets",
-                Some("org.freedesktop.Secret.Service"),
-                "CreateCollection",
-                &(collection_properties, ""),
-            )
-            .await
-            .unwrap();
-
-        let body = reply.body();
-        let (collection_object_path, _): (zvariant::ObjectPath<'_>, zvariant::ObjectPath<'_>) =
-            body.deserialize().unwrap();
-
-        Ok(collection_object_path.into())
-    }
-
-    async fn open_plain_session(
-        dbus_name: &str,
-    ) -> Result<zvariant::OwnedObjectPath, error::Error> {
-        let connection = zbus::Connection::session().await?;
-        let plain_key: Vec<u8> = Vec::new();
-
-        let reply = connection
-            .call_method(
-                Some(dbus_name),
-                "/org/freedesktop/secrets",
-                Some("org.freedesktop.Secret.Service"),
-                "OpenSession",
-                &("plain", zvariant::Value::from(plain_key)),
-            )
-            .await
-            .unwrap();
-
-        let body = reply.body();
-        let (_, session_path): (zvariant::Value, zvariant::ObjectPath<'_>) =
-            body.deserialize().unwrap();
-
-        Ok(session_path.into())
-    }
 
     async fn open_dh_session(
+        connection: &zbus::Connection,
         dbus_name: &str,
     ) -> Result<(zvariant::OwnedObjectPath, session::Algorithm), error::Error> {
-        let connection = zbus::Connection::session().await?;
-
-        let secret = x25519_dalek::EphemeralSecret::random();
-        let public_key = x25519_dalek::PublicKey::from(&secret);
+        let key_pair = session::DhKeyPair::generate();
 
         let reply = connection
             .call_method(
@@ -414,7 +629,7 @@ mod tests {
                 "OpenSession",
                 &(
                     "dh-ietf1024-sha256-aes128-cbc-pkcs7",
-                    zvariant::Value::from(public_key.as_bytes().to_vec()),
+                    zvariant::Value::from(key_pair.public_key_bytes()),
                 ),
             )
             .await
@@ -423,34 +638,20 @@ mod tests {
         let body = reply.body();
         let (algorithm_output, session_path): (zvariant::Value, zvariant::ObjectPath<'_>) =
             body.deserialize().unwrap();
-        let server_public_key_bytes: Vec<u8> = algorithm_output.try_into()?;
-        let server_public_key: [u8; 32] = server_public_key_bytes.try_into().unwrap();
-
-        let shared_secret =
-            secret.diffie_hellman(&x25519_dalek::PublicKey::from(server_public_key));
-        let shared_secret_bytes = shared_secret.to_bytes();
-
-        let mut shared_secret_padded = vec![0u8; 128 - shared_secret_bytes.len()];
-        shared_secret_padded.extend_from_slice(&shared_secret_bytes);
-
-        let info = [];
-        let salt = None;
-
-        let (_, hk) = hkdf::Hkdf::<sha2::Sha256>::extract(salt, &shared_secret_padded);
-        let mut key = [0; 16];
-        hk.expand(&info, &mut key).unwrap();
+        let server_public_key: Vec<u8> = algorithm_output.try_into()?;
+        let key = key_pair.derive_aes_key(&server_public_key)?;
 
         Ok((session_path.into(), session::Algorithm::Dh { aes_key: key }))
     }
 
     #[tokio::test]
     async fn test_create_item() -> Result<(), error::Error> {
-        let (dbus_name, run_server_handle) = run_service_server().await;
-        let session_path = open_plain_session(dbus_name.as_str()).await?;
+        let (dbus_name, run_server_handle) = run_service_server(Default::default()).await;
+        let connection = zbus::Connection::session().await?;
+        let session_path = open_plain_session(&connection, dbus_name.as_str()).await?;
         let collection_object_path =
             create_collection(dbus_name.as_str(), "test-collection-label").await?;
 
-        let connection = zbus::Connection::session().await?;
         let item_properties = item::ItemReadWriteProperties {
             attributes: collections::HashMap::new(),
             label: "test-item-label".to_owned(),
@@ -524,12 +725,12 @@ mod tests {
 
     #[tokio::test]
     async fn test_create_item_encrypted() -> Result<(), error::Error> {
-        let (dbus_name, run_server_handle) = run_service_server().await;
-        let (session_path, algorithm) = open_dh_session(dbus_name.as_str()).await?;
+        let (dbus_name, run_server_handle) = run_service_server(Default::default()).await;
+        let connection = zbus::Connection::session().await?;
+        let (session_path, algorithm) = open_dh_session(&connection, dbus_name.as_str()).await?;
         let collection_object_path =
             create_collection(dbus_name.as_str(), "test-collection-label").await?;
 
-        let connection = zbus::Connection::session().await?;
         let item_properties = item::ItemReadWriteProperties {
             attributes: collections::HashMap::new(),
             label: "test-item-encrypted-label".to_owned(),
@@ -539,8 +740,8 @@ mod tests {
         let (encrypted_secret, iv) = algorithm.encrypt(plaintext_secret.as_bytes());
         let secret = secret::Secret {
             session: session_path.clone(),
-            value: encrypted_secret.into(),
-            parameters: iv.into(),
+            value: encrypted_secret,
+            parameters: iv,
             content_type: "text/plain; charset=utf8".to_string(),
         };
         let reply = connection
@@ -592,7 +793,7 @@ mod tests {
         let decrypted_secret = str::from_utf8(&algorithm.decrypt(
             new_secret.value.as_slice(),
             new_secret.parameters.as_slice(),
-        ))
+        )?)
         .unwrap()
         .to_string();
 
@@ -608,32 +809,70 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_search_items() -> Result<(), error::Error> {
-        let (dbus_name, run_server_handle) = run_service_server().await;
-        let session_path = open_plain_session(dbus_name.as_str()).await?;
+    async fn test_encrypted_secrets_use_random_ivs_and_reject_malformed_input(
+    ) -> Result<(), error::Error> {
+        let (dbus_name, run_server_handle) = run_service_server(Default::default()).await;
+        let connection = zbus::Connection::session().await?;
+        let (session_path, algorithm) = open_dh_session(&connection, dbus_name.as_str()).await?;
         let collection_object_path =
             create_collection(dbus_name.as_str(), "test-collection-label").await?;
 
-        let connection = zbus::Connection::session().await?;
-        let item_attributes = collections::HashMap::from([
-            ("key-one".to_string(), "value-one".to_string()),
-            ("key-two".to_string(), "value-two".to_string()),
-        ]);
+        let plaintext_secret = b"a-very-important-and-secure-secret";
+        let (ciphertext, iv) = algorithm.encrypt(plaintext_secret);
+
+        for (value, parameters) in [
+            // IVs of the wrong length.
+            (ciphertext.clone(), iv[..8].to_vec()),
+            (ciphertext.clone(), Vec::new()),
+            (ciphertext.clone(), [iv.as_slice(), &[0u8]].concat()),
+            // Bad padding, as these are not a whole number of blocks.
+            (ciphertext[..ciphertext.len() - 1].to_vec(), iv.clone()),
+            (Vec::new(), iv.clone()),
+        ] {
+            let item_properties = item::ItemReadWriteProperties {
+                attributes: collections::HashMap::new(),
+                label: "test-item-label".to_owned(),
+            };
+            let secret = secret::Secret {
+                session: session_path.clone(),
+                value,
+                parameters,
+                content_type: "text/plain; charset=utf8".to_string(),
+            };
+
+            let result = connection
+                .call_method(
+                    Some(dbus_name.as_str()),
+                    &collection_object_path,
+                    Some("org.freedesktop.Secret.Collection"),
+                    "CreateItem",
+                    &(item_properties, secret, false),
+                )
+                .await;
+
+            match result {
+                Err(zbus::Error::MethodError(name, _, _)) => {
+                    assert_eq!(name.as_str(), "org.freedesktop.DBus.Error.InvalidArgs")
+                }
+                _ => panic!("Expected CreateItem to fail with InvalidArgs"),
+            }
+        }
+
+        // The server is still around to take well-formed secrets.
         let item_properties = item::ItemReadWriteProperties {
-            attributes: item_attributes.clone(),
+            attributes: collections::HashMap::new(),
             label: "test-item-label".to_owned(),
         };
-
         let secret = secret::Secret {
-            session: session_path,
-            value: "a-very-important-secret".into(),
-            parameters: Vec::new(),
+            session: session_path.clone(),
+            value: ciphertext,
+            parameters: iv,
             content_type: "text/plain; charset=utf8".to_string(),
         };
         let reply = connection
             .call_method(
                 Some(dbus_name.as_str()),
-                collection_object_path.as_str(),
+                &collection_object_path,
                 Some("org.freedesktop.Secret.Collection"),
                 "CreateItem",
                 &(item_properties, secret, false),
@@ -642,28 +881,804 @@ mod tests {
             .unwrap();
 
         let body = reply.body();
-        let (item_object_path, _): (zvariant::ObjectPath<'_>, zvariant::ObjectPath<'_>) =
+        let (item_object_path, _): (zvariant::OwnedObjectPath, zvariant::ObjectPath<'_>) =
             body.deserialize().unwrap();
 
+        let mut returned_ivs = Vec::new();
+        for _ in 0..2 {
+            let reply = connection
+                .call_method(
+                    Some(dbus_name.as_str()),
+                    &item_object_path,
+                    Some("org.freedesktop.Secret.Item"),
+                    "GetSecret",
+                    &(session_path.as_ref()),
+                )
+                .await
+                .unwrap();
+
+            let body = reply.body();
+            let returned_secret = body.deserialize::<secret::Secret>().unwrap();
+            assert_eq!(
+                algorithm.decrypt(&returned_secret.value, &returned_secret.parameters)?,
+                plaintext_secret
+            );
+            returned_ivs.push(returned_secret.parameters);
+        }
+
+        assert_eq!(returned_ivs[0].len(), 16);
+        assert_ne!(returned_ivs[0], returned_ivs[1]);
+
+        run_server_handle.abort();
+        assert!(run_server_handle.await.unwrap_err().is_cancelled());
+
+        Ok(())
+    }
+
+    /// Call `GetSecret` on an item, returning the decrypted secret and its content type.
+    async fn get_secret_bytes(
+        connection: &zbus::Connection,
+        dbus_name: &str,
+        item_object_path: &zvariant::ObjectPath<'_>,
+        session_path: &zvariant::OwnedObjectPath,
+        algorithm: &session::Algorithm,
+    ) -> Result<(Vec<u8>, String), error::Error> {
+        let reply = connection
+            .call_method(
+                Some(dbus_name),
+                item_object_path,
+                Some("org.freedesktop.Secret.Item"),
+                "GetSecret",
+                &(session_path.as_ref()),
+            )
+            .await
+            .unwrap();
+
+        let body = reply.body();
+        let secret = body.deserialize::<secret::Secret>().unwrap();
+
+        Ok((
+            algorithm.decrypt(&secret.value, &secret.parameters)?,
+            secret.content_type,
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_binary_secrets_and_content_types_round_trip() -> Result<(), error::Error> {
+        let (dbus_name, run_server_handle) = run_service_server(Default::default()).await;
+        let connection = zbus::Connection::session().await?;
+        let plain_session_path = open_plain_session(&connection, dbus_name.as_str()).await?;
+        let (dh_session_path, algorithm) = open_dh_session(&connection, dbus_name.as_str()).await?;
+        let collection_object_path =
+            create_collection(dbus_name.as_str(), "test-collection-label").await?;
+
+        // Neither of these are valid UTF-8.
+        let mut token = [0u8; 32];
+        getrandom::getrandom(&mut token).unwrap();
+        token[0] = 0xff;
+        let der_key: Vec<u8> = vec![0x30, 0x82, 0x01, 0x0a, 0x02, 0x82, 0x01, 0x01, 0x00, 0xc3];
+
+        let item_properties = item::ItemReadWriteProperties {
+            attributes: collections::HashMap::new(),
+            label: "test-item-label".to_owned(),
+        };
+        let secret = secret::Secret {
+            session: plain_session_path.clone(),
+            value: token.to_vec(),
+            parameters: Vec::new(),
+            content_type: "application/octet-stream".to_owned(),
+        };
         let reply = connection
             .call_method(
                 Some(dbus_name.as_str()),
-                collection_object_path.as_str(),
+                &collection_object_path,
                 Some("org.freedesktop.Secret.Collection"),
-                "SearchItems",
-                &(item_attributes),
+                "CreateItem",
+                &(item_properties, secret, false),
             )
             .await
             .unwrap();
 
         let body = reply.body();
-        let found_items: Vec<zvariant::ObjectPath<'_>> = body.deserialize().unwrap();
+        let (item_object_path, _): (zvariant::OwnedObjectPath, zvariant::ObjectPath<'_>) =
+            body.deserialize().unwrap();
 
-        assert_eq!(found_items.len(), 1);
-        assert_eq!(found_items.get(0).unwrap(), &item_object_path);
+        assert_eq!(
+            get_secret_bytes(
+                &connection,
+                &dbus_name,
+                &item_object_path,
+                &plain_session_path,
+                &session::Algorithm::Plain
+            )
+            .await?,
+            (token.to_vec(), "application/octet-stream".to_owned())
+        );
+        assert_eq!(
+            get_secret_bytes(
+                &connection,
+                &dbus_name,
+                &item_object_path,
+                &dh_session_path,
+                &algorithm
+            )
+            .await?,
+            (token.to_vec(), "application/octet-stream".to_owned())
+        );
 
-        run_server_handle.abort();
-        assert!(run_server_handle.await.unwrap_err().is_cancelled());
+        let (value, parameters) = algorithm.encrypt(&der_key);
+        let secret = secret::Secret {
+            session: dh_session_path.clone(),
+            value,
+            parameters,
+            content_type: "application/pkcs8".to_owned(),
+        };
+        connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                &item_object_path,
+                Some("org.freedesktop.Secret.Item"),
+                "SetSecret",
+                &(secret),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            get_secret_bytes(
+                &connection,
+                &dbus_name,
+                &item_object_path,
+                &dh_session_path,
+                &algorithm
+            )
+            .await?,
+            (der_key.clone(), "application/pkcs8".to_owned())
+        );
+
+        let reply = connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                "/org/freedesktop/secrets",
+                Some("org.freedesktop.Secret.Service"),
+                "GetSecrets",
+                &(vec![&item_object_path], plain_session_path.as_ref()),
+            )
+            .await
+            .unwrap();
+
+        let body = reply.body();
+        let secrets: collections::HashMap<zvariant::OwnedObjectPath, secret::Secret> =
+            body.deserialize().unwrap();
+        assert_eq!(secrets[&item_object_path].value, der_key);
+        assert_eq!(secrets[&item_object_path].content_type, "application/pkcs8");
+
+        run_server_handle.abort();
+        assert!(run_server_handle.await.unwrap_err().is_cancelled());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_search_items() -> Result<(), error::Error> {
+        let (dbus_name, run_server_handle) = run_service_server(Default::default()).await;
+        let connection = zbus::Connection::session().await?;
+        let session_path = open_plain_session(&connection, dbus_name.as_str()).await?;
+        let collection_object_path =
+            create_collection(dbus_name.as_str(), "test-collection-label").await?;
+
+        let item_attributes = collections::HashMap::from([
+            ("key-one".to_string(), "value-one".to_string()),
+            ("key-two".to_string(), "value-two".to_string()),
+        ]);
+        let item_properties = item::ItemReadWriteProperties {
+            attributes: item_attributes.clone(),
+            label: "test-item-label".to_owned(),
+        };
+
+        let secret = secret::Secret {
+            session: session_path,
+            value: "a-very-important-secret".into(),
+            parameters: Vec::new(),
+            content_type: "text/plain; charset=utf8".to_string(),
+        };
+        let reply = connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                collection_object_path.as_str(),
+                Some("org.freedesktop.Secret.Collection"),
+                "CreateItem",
+                &(item_properties, secret, false),
+            )
+            .await
+            .unwrap();
+
+        let body = reply.body();
+        let (item_object_path, _): (zvariant::ObjectPath<'_>, zvariant::ObjectPath<'_>) =
+            body.deserialize().unwrap();
+
+        let reply = connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                collection_object_path.as_str(),
+                Some("org.freedesktop.Secret.Collection"),
+                "SearchItems",
+                &(item_attributes),
+            )
+            .await
+            .unwrap();
+
+        let body = reply.body();
+        let found_items: Vec<zvariant::ObjectPath<'_>> = body.deserialize().unwrap();
+
+        assert_eq!(found_items.len(), 1);
+        assert_eq!(found_items.first().unwrap(), &item_object_path);
+
+        run_server_handle.abort();
+        assert!(run_server_handle.await.unwrap_err().is_cancelled());
+
+        Ok(())
+    }
+
+    async fn create_item_with_attributes(
+        connection: &zbus::Connection,
+        dbus_name: &str,
+        collection_object_path: &zvariant::ObjectPath<'_>,
+        session_path: &zvariant::OwnedObjectPath,
+        attributes: &[(&str, &str)],
+    ) -> Result<zvariant::OwnedObjectPath, error::Error> {
+        call_create_item(
+            connection,
+            dbus_name,
+            collection_object_path,
+            session_path,
+            attributes,
+            "test-item-label",
+            "a-very-important-secret",
+            false,
+        )
+        .await
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn call_create_item(
+        connection: &zbus::Connection,
+        dbus_name: &str,
+        collection_object_path: &zvariant::ObjectPath<'_>,
+        session_path: &zvariant::OwnedObjectPath,
+        attributes: &[(&str, &str)],
+        label: &str,
+        plaintext_secret: &str,
+        replace: bool,
+    ) -> Result<zvariant::OwnedObjectPath, error::Error> {
+        let item_properties = item::ItemReadWriteProperties {
+            attributes: attributes
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+            label: label.to_owned(),
+        };
+        let secret = secret::Secret {
+            session: session_path.clone(),
+            value: plaintext_secret.into(),
+            parameters: Vec::new(),
+            content_type: "text/plain; charset=utf8".to_string(),
+        };
+
+        let reply = connection
+            .call_method(
+                Some(dbus_name),
+                collection_object_path,
+                Some("org.freedesktop.Secret.Collection"),
+                "CreateItem",
+                &(item_properties, secret, replace),
+            )
+            .await
+            .unwrap();
+
+        let body = reply.body();
+        let (item_object_path, _): (zvariant::ObjectPath<'_>, zvariant::ObjectPath<'_>) =
+            body.deserialize().unwrap();
+
+        Ok(item_object_path.into())
+    }
+
+    async fn get_plain_secret(
+        connection: &zbus::Connection,
+        dbus_name: &str,
+        item_object_path: &zvariant::ObjectPath<'_>,
+        session_path: &zvariant::OwnedObjectPath,
+    ) -> Result<String, error::Error> {
+        let reply = connection
+            .call_method(
+                Some(dbus_name),
+                item_object_path,
+                Some("org.freedesktop.Secret.Item"),
+                "GetSecret",
+                &(session_path.as_ref()),
+            )
+            .await
+            .unwrap();
+
+        let body = reply.body();
+        let secret = body.deserialize::<secret::Secret>().unwrap();
+
+        Ok(String::from_utf8(secret.value).unwrap())
+    }
+
+    async fn search_collection_items(
+        dbus_name: &str,
+        collection_object_path: &zvariant::ObjectPath<'_>,
+        attributes: &[(&str, &str)],
+    ) -> Result<collections::HashSet<zvariant::OwnedObjectPath>, error::Error> {
+        let connection = zbus::Connection::session().await?;
+        let attributes: collections::HashMap<&str, &str> = attributes.iter().copied().collect();
+
+        let reply = connection
+            .call_method(
+                Some(dbus_name),
+                collection_object_path,
+                Some("org.freedesktop.Secret.Collection"),
+                "SearchItems",
+                &(attributes),
+            )
+            .await
+            .unwrap();
+
+        let body = reply.body();
+        let found_items: Vec<zvariant::OwnedObjectPath> = body.deserialize().unwrap();
+
+        Ok(found_items.into_iter().collect())
+    }
+
+    #[tokio::test]
+    async fn test_search_items_matches_attribute_subsets() -> Result<(), error::Error> {
+        let (dbus_name, run_server_handle) = run_service_server(Default::default()).await;
+        let connection = zbus::Connection::session().await?;
+        let session_path = open_plain_session(&connection, dbus_name.as_str()).await?;
+        let collection_object_path =
+            create_collection(dbus_name.as_str(), "test-collection-label").await?;
+
+        let bar_item_path = create_item_with_attributes(
+            &connection,
+            &dbus_name,
+            &collection_object_path,
+            &session_path,
+            &[("service", "foo"), ("user", "bar")],
+        )
+        .await?;
+        let baz_item_path = create_item_with_attributes(
+            &connection,
+            &dbus_name,
+            &collection_object_path,
+            &session_path,
+            &[("service", "foo"), ("user", "baz")],
+        )
+        .await?;
+
+        let partial =
+            search_collection_items(&dbus_name, &collection_object_path, &[("service", "foo")])
+                .await?;
+        let full = search_collection_items(
+            &dbus_name,
+            &collection_object_path,
+            &[("service", "foo"), ("user", "bar")],
+        )
+        .await?;
+        let empty = search_collection_items(&dbus_name, &collection_object_path, &[]).await?;
+        let wrong_value =
+            search_collection_items(&dbus_name, &collection_object_path, &[("service", "other")])
+                .await?;
+        let extra_attribute = search_collection_items(
+            &dbus_name,
+            &collection_object_path,
+            &[("service", "foo"), ("user", "bar"), ("extra", "value")],
+        )
+        .await?;
+
+        run_server_handle.abort();
+        assert!(run_server_handle.await.unwrap_err().is_cancelled());
+
+        assert_eq!(
+            partial,
+            collections::HashSet::from([bar_item_path.clone(), baz_item_path.clone()])
+        );
+        assert_eq!(full, collections::HashSet::from([bar_item_path.clone()]));
+        assert_eq!(
+            empty,
+            collections::HashSet::from([bar_item_path, baz_item_path])
+        );
+        assert!(wrong_value.is_empty());
+        assert!(extra_attribute.is_empty());
+
+        Ok(())
+    }
+
+    async fn get_property<T>(
+        dbus_name: &str,
+        object_path: &zvariant::ObjectPath<'_>,
+        interface: &str,
+        property: &str,
+    ) -> Result<T, error::Error>
+    where
+        T: TryFrom<zvariant::OwnedValue, Error = zvariant::Error>,
+    {
+        let connection = zbus::Connection::session().await?;
+        let reply = connection
+            .call_method(
+                Some(dbus_name),
+                object_path,
+                Some("org.freedesktop.DBus.Properties"),
+                "Get",
+                &(interface, property),
+            )
+            .await
+            .unwrap();
+
+        let body = reply.body();
+        let value: zvariant::OwnedValue = body.deserialize()?;
+        Ok(value.try_into()?)
+    }
+
+    #[tokio::test]
+    async fn test_set_item_attributes_updates_search() -> Result<(), error::Error> {
+        let (dbus_name, run_server_handle) = run_service_server(Default::default()).await;
+        let connection = zbus::Connection::session().await?;
+        let session_path = open_plain_session(&connection, dbus_name.as_str()).await?;
+        let collection_object_path =
+            create_collection(dbus_name.as_str(), "test-collection-label").await?;
+
+        let item_object_path = create_item_with_attributes(
+            &connection,
+            &dbus_name,
+            &collection_object_path,
+            &session_path,
+            &[("service", "foo"), ("user", "bar")],
+        )
+        .await?;
+        let unrelated_item_path = create_item_with_attributes(
+            &connection,
+            &dbus_name,
+            &collection_object_path,
+            &session_path,
+            &[("service", "foo"), ("user", "baz")],
+        )
+        .await?;
+
+        let collection_proxy = zbus::Proxy::new(
+            &connection,
+            dbus_name.as_str(),
+            collection_object_path.as_ref(),
+            "org.freedesktop.Secret.Collection",
+        )
+        .await?;
+        let mut item_changed = collection_proxy.receive_signal("ItemChanged").await?;
+
+        let new_attributes = collections::HashMap::from([("service", "qux"), ("user", "bar")]);
+        connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                &item_object_path,
+                Some("org.freedesktop.DBus.Properties"),
+                "Set",
+                &(
+                    "org.freedesktop.Secret.Item",
+                    "Attributes",
+                    zvariant::Value::from(new_attributes),
+                ),
+            )
+            .await
+            .unwrap();
+
+        item_changed.next().await.unwrap();
+
+        let old_service =
+            search_collection_items(&dbus_name, &collection_object_path, &[("service", "foo")])
+                .await?;
+        let new_service =
+            search_collection_items(&dbus_name, &collection_object_path, &[("service", "qux")])
+                .await?;
+        let unchanged_attribute =
+            search_collection_items(&dbus_name, &collection_object_path, &[("user", "bar")])
+                .await?;
+
+        let reply = connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                "/org/freedesktop/secrets",
+                Some("org.freedesktop.Secret.Service"),
+                "SearchItems",
+                &(collections::HashMap::from([("service", "qux")])),
+            )
+            .await
+            .unwrap();
+
+        let body = reply.body();
+        let (service_unlocked, _): (
+            Vec<zvariant::OwnedObjectPath>,
+            Vec<zvariant::OwnedObjectPath>,
+        ) = body.deserialize().unwrap();
+
+        let attributes: collections::HashMap<String, String> = get_property(
+            &dbus_name,
+            &item_object_path,
+            "org.freedesktop.Secret.Item",
+            "Attributes",
+        )
+        .await?;
+
+        run_server_handle.abort();
+        assert!(run_server_handle.await.unwrap_err().is_cancelled());
+
+        assert_eq!(
+            old_service,
+            collections::HashSet::from([unrelated_item_path])
+        );
+        assert_eq!(
+            new_service,
+            collections::HashSet::from([item_object_path.clone()])
+        );
+        assert_eq!(
+            unchanged_attribute,
+            collections::HashSet::from([item_object_path.clone()])
+        );
+        assert_eq!(service_unlocked, vec![item_object_path]);
+        assert_eq!(attributes.get("service").map(String::as_str), Some("qux"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_set_item_label_bumps_modified() -> Result<(), error::Error> {
+        let (dbus_name, run_server_handle) = run_service_server(Default::default()).await;
+        let connection = zbus::Connection::session().await?;
+        let session_path = open_plain_session(&connection, dbus_name.as_str()).await?;
+        let collection_object_path =
+            create_collection(dbus_name.as_str(), "test-collection-label").await?;
+
+        let item_object_path = create_item_with_attributes(
+            &connection,
+            &dbus_name,
+            &collection_object_path,
+            &session_path,
+            &[("service", "foo")],
+        )
+        .await?;
+
+        let item_created: u64 = get_property(
+            &dbus_name,
+            &item_object_path,
+            "org.freedesktop.Secret.Item",
+            "Created",
+        )
+        .await?;
+        let collection_modified: u64 = get_property(
+            &dbus_name,
+            &collection_object_path,
+            "org.freedesktop.Secret.Collection",
+            "Modified",
+        )
+        .await?;
+
+        // Timestamps have a resolution of a second.
+        tokio::time::sleep(time::Duration::from_millis(1100)).await;
+
+        let collection_proxy = zbus::Proxy::new(
+            &connection,
+            dbus_name.as_str(),
+            collection_object_path.as_ref(),
+            "org.freedesktop.Secret.Collection",
+        )
+        .await?;
+        let mut item_changed = collection_proxy.receive_signal("ItemChanged").await?;
+
+        connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                &item_object_path,
+                Some("org.freedesktop.DBus.Properties"),
+                "Set",
+                &(
+                    "org.freedesktop.Secret.Item",
+                    "Label",
+                    zvariant::Value::from("new-item-label"),
+                ),
+            )
+            .await
+            .unwrap();
+
+        item_changed.next().await.unwrap();
+
+        let item_label: String = get_property(
+            &dbus_name,
+            &item_object_path,
+            "org.freedesktop.Secret.Item",
+            "Label",
+        )
+        .await?;
+        let item_modified: u64 = get_property(
+            &dbus_name,
+            &item_object_path,
+            "org.freedesktop.Secret.Item",
+            "Modified",
+        )
+        .await?;
+        let new_collection_modified: u64 = get_property(
+            &dbus_name,
+            &collection_object_path,
+            "org.freedesktop.Secret.Collection",
+            "Modified",
+        )
+        .await?;
+
+        run_server_handle.abort();
+        assert!(run_server_handle.await.unwrap_err().is_cancelled());
+
+        assert_eq!(item_label, "new-item-label");
+        assert!(item_modified > item_created);
+        assert!(new_collection_modified > collection_modified);
+        assert_eq!(new_collection_modified, item_modified);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_create_item_replace_updates_item_in_place() -> Result<(), error::Error> {
+        let (dbus_name, run_server_handle) = run_service_server(Default::default()).await;
+        let connection = zbus::Connection::session().await?;
+        let session_path = open_plain_session(&connection, dbus_name.as_str()).await?;
+        let collection_object_path =
+            create_collection(dbus_name.as_str(), "test-collection-label").await?;
+
+        let item_object_path = create_item_with_attributes(
+            &connection,
+            &dbus_name,
+            &collection_object_path,
+            &session_path,
+            &[("service", "foo"), ("user", "bar")],
+        )
+        .await?;
+        // Shares some, but not all, attributes with the replaced item.
+        let unrelated_item_path = create_item_with_attributes(
+            &connection,
+            &dbus_name,
+            &collection_object_path,
+            &session_path,
+            &[("service", "foo")],
+        )
+        .await?;
+
+        let collection_proxy = zbus::Proxy::new(
+            &connection,
+            dbus_name.as_str(),
+            collection_object_path.as_ref(),
+            "org.freedesktop.Secret.Collection",
+        )
+        .await?;
+        let mut item_changed = collection_proxy.receive_signal("ItemChanged").await?;
+        let mut item_created = collection_proxy.receive_signal("ItemCreated").await?;
+
+        let replaced_item_path = call_create_item(
+            &connection,
+            &dbus_name,
+            &collection_object_path,
+            &session_path,
+            &[("service", "foo"), ("user", "bar")],
+            "replaced-item-label",
+            "a-replaced-secret",
+            true,
+        )
+        .await?;
+
+        item_changed.next().await.unwrap();
+
+        let items: Vec<zvariant::OwnedObjectPath> = get_property(
+            &dbus_name,
+            &collection_object_path,
+            "org.freedesktop.Secret.Collection",
+            "Items",
+        )
+        .await?;
+        let label: String = get_property(
+            &dbus_name,
+            &item_object_path,
+            "org.freedesktop.Secret.Item",
+            "Label",
+        )
+        .await?;
+        let replaced_secret =
+            get_plain_secret(&connection, &dbus_name, &item_object_path, &session_path).await?;
+        let unrelated_secret =
+            get_plain_secret(&connection, &dbus_name, &unrelated_item_path, &session_path).await?;
+        let found_items =
+            search_collection_items(&dbus_name, &collection_object_path, &[("service", "foo")])
+                .await?;
+
+        run_server_handle.abort();
+        assert!(run_server_handle.await.unwrap_err().is_cancelled());
+
+        assert_eq!(replaced_item_path, item_object_path);
+        assert_eq!(
+            items.into_iter().collect::<collections::HashSet<_>>(),
+            collections::HashSet::from([item_object_path.clone(), unrelated_item_path.clone()])
+        );
+        assert_eq!(label, "replaced-item-label");
+        assert_eq!(replaced_secret, "a-replaced-secret");
+        assert_eq!(unrelated_secret, "a-very-important-secret");
+        assert_eq!(
+            found_items,
+            collections::HashSet::from([item_object_path, unrelated_item_path])
+        );
+        assert!(item_created.next().now_or_never().is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_create_item_without_replace_creates_new_item() -> Result<(), error::Error> {
+        let (dbus_name, run_server_handle) = run_service_server(Default::default()).await;
+        let connection = zbus::Connection::session().await?;
+        let session_path = open_plain_session(&connection, dbus_name.as_str()).await?;
+        let collection_object_path =
+            create_collection(dbus_name.as_str(), "test-collection-label").await?;
+
+        let item_object_path = create_item_with_attributes(
+            &connection,
+            &dbus_name,
+            &collection_object_path,
+            &session_path,
+            &[("service", "foo")],
+        )
+        .await?;
+
+        let new_item_path = call_create_item(
+            &connection,
+            &dbus_name,
+            &collection_object_path,
+            &session_path,
+            &[("service", "foo")],
+            "new-item-label",
+            "a-new-secret",
+            false,
+        )
+        .await?;
+
+        // Replacing only applies to items with exactly the same attributes.
+        let other_item_path = call_create_item(
+            &connection,
+            &dbus_name,
+            &collection_object_path,
+            &session_path,
+            &[("service", "foo"), ("user", "bar")],
+            "other-item-label",
+            "another-secret",
+            true,
+        )
+        .await?;
+
+        let items: Vec<zvariant::OwnedObjectPath> = get_property(
+            &dbus_name,
+            &collection_object_path,
+            "org.freedesktop.Secret.Collection",
+            "Items",
+        )
+        .await?;
+        let original_secret =
+            get_plain_secret(&connection, &dbus_name, &item_object_path, &session_path).await?;
+        let new_secret =
+            get_plain_secret(&connection, &dbus_name, &new_item_path, &session_path).await?;
+
+        run_server_handle.abort();
+        assert!(run_server_handle.await.unwrap_err().is_cancelled());
+
+        assert_ne!(new_item_path, item_object_path);
+        assert_ne!(other_item_path, item_object_path);
+        assert_eq!(
+            items.into_iter().collect::<collections::HashSet<_>>(),
+            collections::HashSet::from([item_object_path, new_item_path, other_item_path])
+        );
+        assert_eq!(original_secret, "a-very-important-secret");
+        assert_eq!(new_secret, "a-new-secret");
 
         Ok(())
     }
@@ -0,0 +1,481 @@
+//! Implementation of gnome-keyring's `org.gnome.keyring.InternalUnsupportedGuiltRiddenInterface`.
+//!
+//! Tools like Seahorse use it to manage the master passwords of collections without
+//! going through a `Prompt`. It's served next to `org.freedesktop.Secret.Service`, and
+//! every password is passed as a `secret::Secret` encrypted over a session of the caller.
+use std::sync;
+
+use crate::error;
+use crate::object::collection;
+use crate::object::service;
+use crate::object::session;
+use crate::object::DbusObject;
+use crate::secret;
+use crate::storage;
+
+#[derive(Debug)]
+pub struct Internal {
+    storage: sync::Arc<dyn storage::Storage>,
+}
+
+impl DbusObject for Internal {
+    fn get_object_path(&self) -> zvariant::OwnedObjectPath {
+        zvariant::ObjectPath::from_static_str_unchecked(service::SERVICE_PATH).into()
+    }
+}
+
+impl Internal {
+    pub fn new(storage: sync::Arc<dyn storage::Storage>) -> Self {
+        Self { storage }
+    }
+
+    /// Decrypt a password sent over a session owned by the sender of the message with `header`.
+    async fn decrypt_password(
+        password: &secret::Secret,
+        header: &zbus::message::Header<'_>,
+        object_server: &zbus::ObjectServer,
+    ) -> Result<zeroize::Zeroizing<Vec<u8>>, error::Error> {
+        let session = session::Session::get_owned_by(
+            &password.session.as_ref(),
+            header.sender(),
+            object_server,
+        )
+        .await?;
+
+        Ok(zeroize::Zeroizing::new(session.decrypt(
+            password.value.as_slice(),
+            password.parameters.as_slice(),
+        )?))
+    }
+
+    /// Find the collection on `object_path`, which may also be an alias of it.
+    ///
+    /// Returns its id and object path, along with the `Service` interface.
+    async fn get_collection(
+        object_path: &zvariant::ObjectPath<'_>,
+        object_server: &zbus::ObjectServer,
+    ) -> Result<
+        (
+            uuid::Uuid,
+            zvariant::OwnedObjectPath,
+            zbus::object_server::InterfaceRef<service::Service>,
+        ),
+        error::Error,
+    > {
+        let service_interface = service::Service::get_interface_from_object_path(
+            &zvariant::ObjectPath::from_static_str_unchecked(service::SERVICE_PATH),
+            object_server,
+        )
+        .await?;
+        let collection_path = service_interface
+            .get()
+            .await
+            .resolve_alias_path(object_path);
+        let collection_interface = collection::Collection::get_interface_from_object_path(
+            &collection_path.as_ref(),
+            object_server,
+        )
+        .await?;
+        let collection_id = collection_interface.get().await.id;
+
+        Ok((collection_id, collection_path, service_interface))
+    }
+}
+
+#[zbus::interface(name = "org.gnome.keyring.InternalUnsupportedGuiltRiddenInterface")]
+impl Internal {
+    /// CreateWithMasterPassword method
+    async fn create_with_master_password(
+        &self,
+        properties: collection::CollectionReadWriteProperties,
+        master: secret::Secret,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+        #[zbus(object_server)] object_server: &zbus::ObjectServer,
+    ) -> Result<zvariant::OwnedObjectPath, error::Error> {
+        let password = Internal::decrypt_password(&master, &header, object_server).await?;
+        let service_interface = service::Service::get_interface_from_object_path(
+            &zvariant::ObjectPath::from_static_str_unchecked(service::SERVICE_PATH),
+            object_server,
+        )
+        .await?;
+
+        let service = service_interface.get().await;
+        service
+            .add_collection(
+                &properties.label,
+                None,
+                &password,
+                object_server,
+                service_interface.signal_emitter(),
+            )
+            .await
+    }
+
+    /// UnlockWithMasterPassword method
+    async fn unlock_with_master_password(
+        &self,
+        collection: zvariant::ObjectPath<'_>,
+        master: secret::Secret,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+        #[zbus(object_server)] object_server: &zbus::ObjectServer,
+    ) -> Result<(), error::Error> {
+        let password = Internal::decrypt_password(&master, &header, object_server).await?;
+        let (collection_id, collection_path, service_interface) =
+            Internal::get_collection(&collection, object_server).await?;
+
+        if !self.storage.is_locked(&collection_id).await {
+            return Ok(());
+        }
+        if !self
+            .storage
+            .unlock_collection(&collection_id, &password)
+            .await?
+        {
+            log::warn!("Wrong master password to unlock '{collection_path}'");
+            return Err(error::Error::AccessDenied(collection_path.to_string()));
+        }
+
+        log::info!("Unlocked collection on '{collection_path}' with its master password");
+        service::Service::notify_lock_changed(
+            &collection_path.as_ref(),
+            object_server,
+            service_interface.signal_emitter(),
+        )
+        .await
+    }
+
+    /// ChangeWithMasterPassword method
+    async fn change_with_master_password(
+        &self,
+        collection: zvariant::ObjectPath<'_>,
+        original: secret::Secret,
+        master: secret::Secret,
+        #[zbus(header)] header: zbus::message::Header<'_>,
+        #[zbus(object_server)] object_server: &zbus::ObjectServer,
+    ) -> Result<(), error::Error> {
+        let original = Internal::decrypt_password(&original, &header, object_server).await?;
+        let password = Internal::decrypt_password(&master, &header, object_server).await?;
+        let (collection_id, collection_path, _) =
+            Internal::get_collection(&collection, object_server).await?;
+
+        if !self
+            .storage
+            .change_password(&collection_id, &original, &password)
+            .await?
+        {
+            log::warn!("Wrong master password to change that of '{collection_path}'");
+            return Err(error::Error::AccessDenied(collection_path.to_string()));
+        }
+
+        log::info!("Changed the master password of collection on '{collection_path}'");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object::testing::{self, run_service_server};
+
+    use std::collections;
+
+    /// Call a method of the internal interface, returning the name of the error it failed with.
+    async fn call_internal<B>(
+        connection: &zbus::Connection,
+        dbus_name: &str,
+        method: &str,
+        body: &B,
+    ) -> Result<zbus::message::Message, String>
+    where
+        B: serde::Serialize + zvariant::DynamicType,
+    {
+        connection
+            .call_method(
+                Some(dbus_name),
+                service::SERVICE_PATH,
+                Some("org.gnome.keyring.InternalUnsupportedGuiltRiddenInterface"),
+                method,
+                body,
+            )
+            .await
+            .map_err(|err| match err {
+                zbus::Error::MethodError(name, _, _) => name.as_str().to_owned(),
+                err => panic!("Expected {method} to fail with a method error, got: {err}"),
+            })
+    }
+
+    /// Open a session with the `plain` algorithm, returning its object path.
+    async fn open_session(
+        connection: &zbus::Connection,
+        dbus_name: &str,
+    ) -> Result<zvariant::OwnedObjectPath, error::Error> {
+        let reply = connection
+            .call_method(
+                Some(dbus_name),
+                service::SERVICE_PATH,
+                Some("org.freedesktop.Secret.Service"),
+                "OpenSession",
+                &("plain", zvariant::Value::from(Vec::<u8>::new())),
+            )
+            .await?;
+        let (_, session_path): (zvariant::OwnedValue, zvariant::OwnedObjectPath) =
+            reply.body().deserialize()?;
+        Ok(session_path)
+    }
+
+    async fn set_locked(
+        connection: &zbus::Connection,
+        dbus_name: &str,
+        collection_path: &zvariant::ObjectPath<'_>,
+        locked: bool,
+    ) -> Result<Vec<zvariant::OwnedObjectPath>, error::Error> {
+        let reply = connection
+            .call_method(
+                Some(dbus_name),
+                service::SERVICE_PATH,
+                Some("org.freedesktop.Secret.Service"),
+                if locked { "Lock" } else { "Unlock" },
+                &(vec![collection_path]),
+            )
+            .await?;
+        let (changed, _): (Vec<zvariant::OwnedObjectPath>, zvariant::OwnedObjectPath) =
+            reply.body().deserialize()?;
+        Ok(changed)
+    }
+
+    #[tokio::test]
+    async fn test_manage_collections_with_master_passwords() -> Result<(), error::Error> {
+        let (dbus_name, run_server_handle) = run_service_server(Default::default()).await;
+        let connection = zbus::Connection::session().await?;
+
+        let session_path = open_session(&connection, &dbus_name).await?;
+        let password = |value: &str| secret::Secret {
+            session: session_path.clone(),
+            value: value.into(),
+            parameters: Vec::new(),
+            content_type: "text/plain".to_owned(),
+        };
+
+        let properties = collections::HashMap::from([(
+            "org.freedesktop.Secret.Collection.Label",
+            zvariant::Value::from("provisioned"),
+        )]);
+        let reply = call_internal(
+            &connection,
+            &dbus_name,
+            "CreateWithMasterPassword",
+            &(properties, password("password")),
+        )
+        .await
+        .unwrap();
+        let collection_path: zvariant::OwnedObjectPath = reply.body().deserialize()?;
+
+        // Created collections are left unlocked.
+        assert_eq!(
+            set_locked(&connection, &dbus_name, &collection_path, true).await?,
+            vec![collection_path.clone()]
+        );
+
+        assert_eq!(
+            call_internal(
+                &connection,
+                &dbus_name,
+                "UnlockWithMasterPassword",
+                &(&collection_path, password("wrong-password")),
+            )
+            .await
+            .unwrap_err(),
+            "org.freedesktop.DBus.Error.AccessDenied"
+        );
+        call_internal(
+            &connection,
+            &dbus_name,
+            "UnlockWithMasterPassword",
+            &(&collection_path, password("password")),
+        )
+        .await
+        .unwrap();
+        // Already unlocked.
+        assert!(set_locked(&connection, &dbus_name, &collection_path, false)
+            .await?
+            .is_empty());
+
+        assert_eq!(
+            call_internal(
+                &connection,
+                &dbus_name,
+                "ChangeWithMasterPassword",
+                &(
+                    &collection_path,
+                    password("wrong-password"),
+                    password("new-password")
+                ),
+            )
+            .await
+            .unwrap_err(),
+            "org.freedesktop.DBus.Error.AccessDenied"
+        );
+        call_internal(
+            &connection,
+            &dbus_name,
+            "ChangeWithMasterPassword",
+            &(
+                &collection_path,
+                password("password"),
+                password("new-password"),
+            ),
+        )
+        .await
+        .unwrap();
+
+        set_locked(&connection, &dbus_name, &collection_path, true).await?;
+        assert_eq!(
+            call_internal(
+                &connection,
+                &dbus_name,
+                "UnlockWithMasterPassword",
+                &(&collection_path, password("password")),
+            )
+            .await
+            .unwrap_err(),
+            "org.freedesktop.DBus.Error.AccessDenied"
+        );
+        call_internal(
+            &connection,
+            &dbus_name,
+            "UnlockWithMasterPassword",
+            &(&collection_path, password("new-password")),
+        )
+        .await
+        .unwrap();
+
+        // Passwords must be sent over a session of the caller.
+        let other_connection = zbus::Connection::session().await?;
+        assert_eq!(
+            call_internal(
+                &other_connection,
+                &dbus_name,
+                "UnlockWithMasterPassword",
+                &(&collection_path, password("new-password")),
+            )
+            .await
+            .unwrap_err(),
+            "org.freedesktop.Secret.Error.NoSession"
+        );
+
+        run_server_handle.abort();
+        assert!(run_server_handle.await.unwrap_err().is_cancelled());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unlock_kdbx_collection() -> Result<(), error::Error> {
+        let kdbx_dir = tempfile::tempdir().unwrap();
+        let kdbx_path = kdbx_dir.path().join("Passwords.kdbx");
+        crate::kdbx::tests::write_database(&kdbx_path);
+        let collection_path = format!(
+            "/org/freedesktop/secrets/collection/{}",
+            crate::kdbx::Database::new(&kdbx_path, None).id.as_simple()
+        );
+        let (dbus_name, run_server_handle) = run_service_server(testing::ServerOptions {
+            kdbx_databases: vec![kdbx_path],
+            ..Default::default()
+        })
+        .await;
+        let connection = zbus::Connection::session().await?;
+        let session_path = open_session(&connection, &dbus_name).await?;
+
+        let collection_property = |name: &'static str| {
+            let connection = connection.clone();
+            let dbus_name = dbus_name.clone();
+            let collection_path = collection_path.clone();
+            async move {
+                let reply = connection
+                    .call_method(
+                        Some(dbus_name.as_str()),
+                        collection_path.as_str(),
+                        Some("org.freedesktop.DBus.Properties"),
+                        "Get",
+                        &("org.freedesktop.Secret.Collection", name),
+                    )
+                    .await?;
+                Ok::<zvariant::OwnedValue, error::Error>(reply.body().deserialize()?)
+            }
+        };
+        // Nothing but the file name is known until the database is unlocked.
+        assert_eq!(
+            String::try_from(collection_property("Label").await?)?,
+            "Passwords"
+        );
+        assert!(
+            Vec::<zvariant::OwnedObjectPath>::try_from(collection_property("Items").await?)?
+                .is_empty()
+        );
+
+        call_internal(
+            &connection,
+            &dbus_name,
+            "UnlockWithMasterPassword",
+            &(
+                zvariant::ObjectPath::try_from(collection_path.as_str())?,
+                secret::Secret {
+                    session: session_path,
+                    value: crate::kdbx::tests::PASSWORD.to_vec(),
+                    parameters: Vec::new(),
+                    content_type: "text/plain".to_owned(),
+                },
+            ),
+        )
+        .await
+        .unwrap();
+
+        // The items of the database are served once it's unlocked, and its subgroup as a
+        // collection of its own.
+        assert_eq!(
+            Vec::<zvariant::OwnedObjectPath>::try_from(collection_property("Items").await?)?.len(),
+            1
+        );
+        let chat_path = format!(
+            "/org/freedesktop/secrets/collection/{}",
+            crate::kdbx::tests::CHAT_GROUP.as_simple()
+        );
+        let reply = connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                "/org/freedesktop/secrets",
+                Some("org.freedesktop.DBus.Properties"),
+                "Get",
+                &("org.freedesktop.Secret.Service", "Collections"),
+            )
+            .await?;
+        let collection_paths = Vec::<zvariant::OwnedObjectPath>::try_from(
+            reply.body().deserialize::<zvariant::OwnedValue>()?,
+        )?;
+        assert!(collection_paths
+            .iter()
+            .any(|collection| collection.as_str() == chat_path));
+        let reply = connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                chat_path.as_str(),
+                Some("org.freedesktop.DBus.Properties"),
+                "GetAll",
+                &("org.freedesktop.Secret.Collection",),
+            )
+            .await?;
+        let chat: collections::HashMap<String, zvariant::OwnedValue> =
+            reply.body().deserialize()?;
+        assert_eq!(String::try_from(chat["Label"].clone())?, "Chat");
+        assert!(!bool::try_from(chat["Locked"].clone())?);
+        assert_eq!(
+            Vec::<zvariant::OwnedObjectPath>::try_from(chat["Items"].clone())?.len(),
+            1
+        );
+
+        run_server_handle.abort();
+        assert!(run_server_handle.await.unwrap_err().is_cancelled());
+
+        Ok(())
+    }
+}
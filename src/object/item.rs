@@ -1,23 +1,41 @@
 use std::collections;
-use std::iter::Iterator;
-use std::time;
+use std::sync;
 
+use futures::FutureExt;
+
+use crate::access;
 use crate::error;
+use crate::index;
+use crate::object;
 use crate::object::collection;
+use crate::object::prompt;
 use crate::object::session;
 use crate::object::{DbusChildObject, DbusObject};
 use crate::secret;
+use crate::storage;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug)]
 pub struct Item {
+    access: sync::Arc<access::AccessControl>,
+    /// Executable of the application that created the item, if known.
+    pub application: Option<String>,
     pub attributes: collections::HashMap<String, String>,
+    pub collection_id: uuid::Uuid,
+    /// `Modified` timestamp of the collection, shared with `Collection`.
+    collection_modified: sync::Arc<sync::atomic::AtomicU64>,
+    connection: zbus::Connection,
+    pub content_type: String,
     pub created: u64,
     pub id: uuid::Uuid,
+    index: sync::Arc<sync::Mutex<index::AttributeIndex>>,
     pub label: String,
+    /// Whether the item was locked on its own, see `is_locked` for its effective lock state.
     pub locked: bool,
     pub modified: u64,
     pub parent_path: zvariant::OwnedObjectPath,
-    secret: String,
+    /// All items in the collection, shared with `Collection`.
+    siblings: object::Children,
+    storage: sync::Arc<dyn storage::Storage>,
 }
 
 #[derive(zvariant::DeserializeDict, zvariant::SerializeDict, zvariant::Type)]
@@ -29,6 +47,19 @@ pub struct ItemReadWriteProperties {
     pub label: String,
 }
 
+/// Object path of the item with `id`, in the collection with `collection_id`.
+pub fn object_path(collection_id: &uuid::Uuid, id: &uuid::Uuid) -> zvariant::OwnedObjectPath {
+    let mut object_path = collection::object_path(collection_id).as_str().to_owned();
+
+    object_path.push('/');
+    object_path.push_str(
+        id.as_simple()
+            .encode_lower(&mut uuid::Uuid::encode_buffer()),
+    );
+
+    zvariant::ObjectPath::from_str_unchecked(&object_path).into()
+}
+
 impl DbusObject for Item {
     fn get_object_path(&self) -> zvariant::OwnedObjectPath {
         let mut object_path = self.parent_path.as_str().to_owned();
@@ -50,136 +81,356 @@ impl DbusChildObject for Item {
     fn get_parent_path(&self) -> zvariant::ObjectPath<'_> {
         self.parent_path.as_ref()
     }
+
+    fn get_siblings(&self) -> &object::Children {
+        &self.siblings
+    }
 }
 
 impl Item {
-    pub async fn new<'a, I>(
+    pub fn new(
         id: uuid::Uuid,
-        secret: secret::Secret,
         label: &str,
-        attributes: I,
+        attributes: collections::HashMap<String, String>,
+        content_type: &str,
+        application: Option<String>,
         collection: &collection::Collection,
-        object_server: &zbus::ObjectServer,
-    ) -> Result<Self, error::Error>
-    where
-        I: Iterator<Item = (&'a str, &'a str)>,
-    {
-        let created = time::SystemTime::now()
-            .duration_since(time::SystemTime::UNIX_EPOCH)
-            .expect("current SystemTime before UNIX EPOCH")
-            .as_secs();
-
-        let session_interface = session::Session::get_interface_from_object_path(
-            &secret.session.as_ref(),
-            object_server,
-        )
-        .await?;
-        let session = session_interface.get().await;
-
-        let plaintext = if session.is_encrypted() {
-            let iv = secret.parameters;
-            let plaintext = session.decrypt(secret.value.as_slice(), iv.as_slice());
-            String::from_utf8(plaintext).unwrap()
-        } else {
-            String::from_utf8(secret.value).unwrap()
-        };
+    ) -> Self {
+        let created = object::timestamp();
 
-        Ok(Self {
-            attributes: collections::HashMap::from_iter(
-                attributes.map(|(key, value)| (key.to_string(), value.to_string())),
-            ),
+        Self {
+            access: collection.access.clone(),
+            application,
+            attributes,
+            collection_id: collection.id,
+            collection_modified: collection.modified.clone(),
+            connection: collection.connection.clone(),
+            content_type: content_type.to_owned(),
             created,
             id,
+            index: collection.index.clone(),
             label: label.to_owned(),
             locked: false,
             modified: created,
             parent_path: collection.get_object_path().clone(),
-            secret: plaintext,
+            siblings: collection.items.clone(),
+            storage: collection.storage.clone(),
+        }
+    }
+
+    pub fn from_stored(stored: storage::StoredItem, collection: &collection::Collection) -> Self {
+        Self {
+            access: collection.access.clone(),
+            application: stored.application,
+            attributes: stored.attributes,
+            collection_id: collection.id,
+            collection_modified: collection.modified.clone(),
+            connection: collection.connection.clone(),
+            content_type: stored.content_type,
+            created: stored.created,
+            id: stored.id,
+            index: collection.index.clone(),
+            label: stored.label,
+            locked: false,
+            modified: stored.modified,
+            parent_path: collection.get_object_path().clone(),
+            siblings: collection.items.clone(),
+            storage: collection.storage.clone(),
+        }
+    }
+
+    pub fn to_stored(&self) -> storage::StoredItem {
+        storage::StoredItem {
+            application: self.application.clone(),
+            attributes: self.attributes.clone(),
+            content_type: self.content_type.clone(),
+            created: self.created,
+            id: self.id,
+            label: self.label.clone(),
+            modified: self.modified,
+        }
+    }
+
+    /// Whether the item is locked, either on its own or because its collection is.
+    pub async fn is_locked(&self) -> bool {
+        self.locked || self.storage.is_locked(&self.collection_id).await
+    }
+
+    /// Fail with `IsLocked` if the item is locked.
+    async fn ensure_unlocked(&self) -> Result<(), error::Error> {
+        if self.is_locked().await {
+            return Err(error::Error::IsLocked(self.get_object_path().to_string()));
+        }
+        Ok(())
+    }
+
+    /// Fail with `AccessDenied` unless the sender of the message with `header` may access
+    /// the item, or with `IsLocked` if the user must confirm it first.
+    pub async fn ensure_allowed(
+        &self,
+        header: &zbus::message::Header<'_>,
+    ) -> Result<(), error::Error> {
+        let application = self.access.caller(header).await?;
+        self.access.ensure_allowed(&application, self)
+    }
+
+    /// Like `ensure_allowed`, for property writes, which only come with a `header` when
+    /// they are made over D-Bus. Writes made any other way are denied.
+    async fn ensure_property_write_allowed(
+        &self,
+        header: Option<&zbus::message::Header<'_>>,
+    ) -> Result<(), error::Error> {
+        let header =
+            header.ok_or_else(|| error::Error::AccessDenied(self.get_object_path().to_string()))?;
+        self.ensure_allowed(header).await
+    }
+
+    /// Delete the item from storage and stop serving it, without checking access.
+    async fn delete_unchecked(
+        &self,
+        object_server: &zbus::ObjectServer,
+    ) -> Result<(), error::Error> {
+        let modified = object::timestamp();
+        self.storage
+            .delete_item(&self.collection_id, &self.id, modified)
+            .await?;
+        self.index
+            .lock()
+            .expect("lock is not poisoned")
+            .remove(&self.get_object_path());
+        self.remove::<Item>(object_server).await?;
+
+        if self.remove_from_parent() {
+            let item_path = self.get_object_path();
+            log::info!("Deleted item on '{item_path}'");
+            collection::Collection::item_deleted(&self.parent_emitter()?, &item_path.as_ref())
+                .await?;
+            self.touch_collection(modified, true).await?;
+        }
+        Ok(())
+    }
+
+    /// Decrypt the stored secret and encrypt it for transfer over `session`.
+    ///
+    /// Fails with `IsLocked` if the item is locked.
+    pub async fn get_secret_with_session(
+        &self,
+        session: &session::Session,
+    ) -> Result<secret::Secret, error::Error> {
+        self.ensure_unlocked().await?;
+        let plaintext = self
+            .storage
+            .read_secret(&self.collection_id, &self.id)
+            .await?;
+        let (value, parameters) = session.encrypt(&plaintext);
+
+        Ok(secret::Secret {
+            session: session.get_object_path(),
+            value,
+            parameters,
+            content_type: self.content_type.clone(),
         })
     }
 
-    pub fn get_secret_with_session(&self, session: &session::Session) -> secret::Secret {
-        let secret = if session.is_encrypted() {
-            let (ciphertext, iv) = session.encrypt(self.secret.as_bytes());
-            secret::Secret {
-                session: session.get_object_path(),
-                value: ciphertext,
-                parameters: iv,
-                content_type: "text/plain; charset=utf8".to_owned(),
-            }
-        } else {
-            secret::Secret {
-                session: session.get_object_path(),
-                value: self.secret.as_bytes().to_vec(),
-                parameters: Vec::new(),
-                content_type: "text/plain; charset=utf8".to_owned(),
-            }
-        };
-        secret
+    /// Replace the label and secret of the item, as `CreateItem` does when asked to replace.
+    ///
+    /// Returns the new `Modified` timestamp.
+    pub async fn replace(
+        &mut self,
+        label: &str,
+        secret: &[u8],
+        content_type: &str,
+    ) -> Result<u64, error::Error> {
+        self.ensure_unlocked().await?;
+        let modified = object::timestamp();
+        let mut stored = self.to_stored();
+        stored.label = label.to_owned();
+        stored.content_type = content_type.to_owned();
+        stored.modified = modified;
+        self.storage
+            .create_item(&self.collection_id, stored, secret)
+            .await?;
+
+        self.label = label.to_owned();
+        self.content_type = content_type.to_owned();
+        self.modified = modified;
+        Ok(modified)
     }
 
-    pub fn set_secret_with_session(&mut self, secret: secret::Secret, session: &session::Session) {
-        // TODO: Check for decryption errors.
-        let plaintext = if session.is_encrypted() {
-            let iv = secret.parameters;
-            let plaintext = session.decrypt(secret.value.as_slice(), iv.as_slice());
-            String::from_utf8(plaintext).unwrap()
-        } else {
-            String::from_utf8(secret.value).unwrap()
-        };
-        self.secret = plaintext;
+    /// Emitter for signals that this item sends about itself.
+    pub fn emitter(&self) -> zbus::Result<zbus::object_server::SignalEmitter<'static>> {
+        zbus::object_server::SignalEmitter::new(&self.connection, self.get_object_path())
+    }
+
+    /// Emitter for signals that `Collection` sends about this item.
+    pub fn parent_emitter(&self) -> zbus::Result<zbus::object_server::SignalEmitter<'_>> {
+        zbus::object_server::SignalEmitter::new(&self.connection, self.parent_path.as_ref())
+    }
+
+    /// Bump the collection's `Modified` timestamp to `modified`, and emit `PropertiesChanged`
+    /// for it, along with `Items` if `items_changed`.
+    ///
+    /// The collection is updated through the state it shares with its items, so that it
+    /// isn't locked.
+    async fn touch_collection(
+        &self,
+        modified: u64,
+        items_changed: bool,
+    ) -> Result<(), error::Error> {
+        self.storage
+            .touch_collection(&self.collection_id, modified)
+            .await?;
+        self.collection_modified
+            .store(modified, sync::atomic::Ordering::Relaxed);
+
+        let mut properties =
+            collections::HashMap::from([("Modified", zvariant::Value::from(modified))]);
+        if items_changed {
+            properties.insert("Items", zvariant::Value::from(self.get_sibling_paths()));
+        }
+        self.parent_properties_changed(&self.parent_emitter()?, properties)
+            .await?;
+        Ok(())
+    }
+
+    /// Emit `PropertiesChanged` for the item's `Modified` timestamp, bump the collection's
+    /// to match, and emit `ItemChanged`.
+    ///
+    /// Called after every change to the item, besides its removal.
+    async fn notify_changed(&self) -> Result<(), error::Error> {
+        self.modified_changed(&self.emitter()?).await?;
+        self.touch_collection(self.modified, false).await?;
+
+        collection::Collection::item_changed(
+            &self.parent_emitter()?,
+            &self.get_object_path().as_ref(),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Lock or unlock the item on its own, returning whether its own lock state changed.
+    ///
+    /// Its collection may still keep the item locked, see `is_locked`. The item's own lock
+    /// only lasts while it is served, so it is not stored and doesn't change `Modified`.
+    pub async fn set_locked(&mut self, locked: bool) -> Result<bool, error::Error> {
+        if self.locked == locked {
+            return Ok(false);
+        }
+
+        self.locked = locked;
+
+        self.locked_changed(&self.emitter()?).await?;
+        collection::Collection::item_changed(
+            &self.parent_emitter()?,
+            &self.get_object_path().as_ref(),
+        )
+        .await?;
+        Ok(true)
+    }
+
+    pub async fn set_secret_with_session(
+        &mut self,
+        secret: secret::Secret,
+        session: &session::Session,
+    ) -> Result<(), error::Error> {
+        self.ensure_unlocked().await?;
+        let plaintext = zeroize::Zeroizing::new(
+            session.decrypt(secret.value.as_slice(), secret.parameters.as_slice())?,
+        );
+        let modified = object::timestamp();
+        self.storage
+            .write_secret(
+                &self.collection_id,
+                &self.id,
+                &plaintext,
+                &secret.content_type,
+                modified,
+            )
+            .await?;
+        self.content_type = secret.content_type;
+        self.modified = modified;
+
+        Ok(())
     }
 }
 
 #[zbus::interface(name = "org.freedesktop.Secret.Item")]
 impl Item {
     /// Delete method
+    ///
+    /// Items of other applications are deleted through a prompt, once the user confirms.
     pub async fn delete(
-        &mut self,
+        &self,
+        #[zbus(header)] header: zbus::message::Header<'_>,
         #[zbus(object_server)] object_server: &zbus::ObjectServer,
-        #[zbus(signal_emitter)] emitter: zbus::object_server::SignalEmitter<'_>,
     ) -> Result<zvariant::ObjectPath<'_>, error::Error> {
-        self.remove::<Item>(object_server).await?;
-        let removed = self.remove_from_parent(object_server).await;
+        let application = self.access.caller(&header).await?;
+        let Some(confirmation) = self.access.confirmation(&application, self) else {
+            self.access.ensure_allowed(&application, self)?;
+            self.delete_unchecked(object_server).await?;
+            return Ok(prompt::Prompt::none());
+        };
 
-        if removed {
-            let item_path = self.get_object_path();
-            log::info!("Deleted item on '{item_path}'");
-            collection::Collection::item_deleted(&emitter).await?;
-        }
+        let access = self.access.clone();
+        let connection = self.connection.clone();
+        let action: prompt::PromptAction = Box::new(move |window_id| {
+            async move {
+                if !access.confirm(&confirmation, &window_id).await? {
+                    return Ok(None);
+                }
+
+                let object_server = connection.object_server();
+                let item_interface = Item::get_interface_from_object_path(
+                    &confirmation.item_path.as_ref(),
+                    object_server,
+                )
+                .await?;
+                item_interface
+                    .get()
+                    .await
+                    .delete_unchecked(object_server)
+                    .await?;
+                Ok(Some(zvariant::Value::from("").try_into()?))
+            }
+            .boxed()
+        });
+        let (prompt_path, _) = prompt::Prompt::new(action).serve_at(object_server).await?;
 
-        Ok(zvariant::ObjectPath::from_str_unchecked("/"))
+        Ok(prompt_path.into())
     }
 
     /// GetSecret method
     pub async fn get_secret(
         &self,
         session: zvariant::ObjectPath<'_>,
+        #[zbus(header)] header: zbus::message::Header<'_>,
         #[zbus(object_server)] object_server: &zbus::ObjectServer,
     ) -> Result<secret::Secret, error::Error> {
-        let session_path = session;
-        let session_interface =
-            session::Session::get_interface_from_object_path(&session_path, object_server).await?;
-        let session = session_interface.get().await;
+        let session =
+            session::Session::get_owned_by(&session, header.sender(), object_server).await?;
+        self.ensure_allowed(&header).await?;
 
-        let secret = self.get_secret_with_session(&session);
-        Ok(secret)
+        self.get_secret_with_session(&session).await
     }
 
     /// SetSecret method
     pub async fn set_secret(
         &mut self,
         secret: secret::Secret,
+        #[zbus(header)] header: zbus::message::Header<'_>,
         #[zbus(object_server)] object_server: &zbus::ObjectServer,
-        #[zbus(signal_emitter)] emitter: zbus::object_server::SignalEmitter<'_>,
     ) -> Result<(), error::Error> {
-        let session_path = secret.session.as_ref();
-        let session_interface =
-            session::Session::get_interface_from_object_path(&session_path, object_server).await?;
-        let session = session_interface.get().await;
+        let session = session::Session::get_owned_by(
+            &secret.session.as_ref(),
+            header.sender(),
+            object_server,
+        )
+        .await?;
+        self.ensure_allowed(&header).await?;
 
-        self.set_secret_with_session(secret, &session);
-        collection::Collection::item_changed(&emitter).await?;
+        self.set_secret_with_session(secret, &session).await?;
+        self.notify_changed().await?;
 
         Ok(())
     }
@@ -194,8 +445,29 @@ impl Item {
     }
 
     #[zbus(property)]
-    async fn set_attributes(&mut self, value: collections::HashMap<String, String>) {
+    async fn set_attributes(
+        &mut self,
+        value: collections::HashMap<String, String>,
+        #[zbus(header)] header: Option<zbus::message::Header<'_>>,
+    ) -> zbus::fdo::Result<()> {
+        self.ensure_unlocked().await?;
+        self.ensure_property_write_allowed(header.as_ref()).await?;
+        let modified = object::timestamp();
+        let mut stored = self.to_stored();
+        stored.attributes = value.clone();
+        stored.modified = modified;
+        self.storage.write_item(&self.collection_id, stored).await?;
+
+        self.index.lock().expect("lock is not poisoned").insert(
+            self.get_object_path(),
+            self.collection_id,
+            value.clone(),
+        );
         self.attributes = value;
+        self.modified = modified;
+
+        self.notify_changed().await?;
+        Ok(())
     }
 
     /// Created property
@@ -211,14 +483,30 @@ impl Item {
     }
 
     #[zbus(property)]
-    fn set_label(&mut self, value: &str) {
+    async fn set_label(
+        &mut self,
+        value: &str,
+        #[zbus(header)] header: Option<zbus::message::Header<'_>>,
+    ) -> zbus::fdo::Result<()> {
+        self.ensure_unlocked().await?;
+        self.ensure_property_write_allowed(header.as_ref()).await?;
+        let modified = object::timestamp();
+        let mut stored = self.to_stored();
+        stored.label = value.to_owned();
+        stored.modified = modified;
+        self.storage.write_item(&self.collection_id, stored).await?;
+
         self.label = value.to_owned();
+        self.modified = modified;
+
+        self.notify_changed().await?;
+        Ok(())
     }
 
     /// Locked property
     #[zbus(property)]
-    pub fn locked(&self) -> bool {
-        self.locked
+    pub async fn locked(&self) -> bool {
+        self.is_locked().await
     }
 
     /// Modified property
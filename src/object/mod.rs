@@ -1,15 +1,36 @@
+use std::borrow;
 use std::collections;
+use std::sync;
+use std::time;
 
+pub mod alias;
 pub mod collection;
+pub mod internal;
 pub mod item;
+pub mod prompt;
 pub mod service;
 pub mod session;
+#[cfg(test)]
+pub mod testing;
 
 use crate::error;
 
+/// Current time as seconds since the UNIX epoch, as used by `Created` and `Modified` properties.
+pub fn timestamp() -> u64 {
+    time::SystemTime::now()
+        .duration_since(time::SystemTime::UNIX_EPOCH)
+        .expect("current SystemTime before UNIX EPOCH")
+        .as_secs()
+}
+
 /// Trait implemented for Secret Service Dbus objects.
 ///
 /// Provides methods to serve, remove, and identify an object in Dbus.
+///
+/// Serving and removing objects waits for any `GetManagedObjects` call, which reads every
+/// object while blocking changes to the object server. So, methods doing either must not
+/// hold an exclusive lock on an interface: they take `&self` and keep mutable state behind
+/// their own locks.
 pub trait DbusObject: zbus::object_server::Interface {
     fn get_object_path(&self) -> zvariant::OwnedObjectPath;
 
@@ -39,6 +60,7 @@ pub trait DbusObject: zbus::object_server::Interface {
         }
     }
 
+    /// Get the interface served on `object_path`, or fail with `NoSuchObject`.
     fn get_interface_from_object_path<'p>(
         object_path: &'p zvariant::ObjectPath<'_>,
         object_server: &'p zbus::ObjectServer,
@@ -49,23 +71,39 @@ pub trait DbusObject: zbus::object_server::Interface {
         Self: Sized,
     {
         async move {
-            let interface_ref = object_server.interface::<_, Self>(object_path).await?;
-            Ok(interface_ref)
+            match object_server.interface::<_, Self>(object_path).await {
+                Err(zbus::Error::InterfaceNotFound) => {
+                    Err(error::Error::NoSuchObject(object_path.to_string()))
+                }
+                interface_ref => Ok(interface_ref?),
+            }
         }
     }
 }
 
+/// Object paths of the children of a Secret Service Dbus object.
+///
+/// Shared between a parent and its children, so that children can remove themselves
+/// from their parent without locking the parent's interface. Parents lock their children
+/// when handling some methods, so a child locking its parent could deadlock.
+pub type Children = sync::Arc<sync::Mutex<collections::HashSet<zvariant::OwnedObjectPath>>>;
+
 /// Trait implemented by Secret Service Dbus objects that parent other objects.
 ///
 /// For example, an object implementing the `org.freedesktop.Secret.Service` interface
 /// will have a set of collections as children, and said collections can be parents of
 /// items.
-/// Methods in this trait allow access to said children mutable and immutable access to
-/// said children.
 pub trait DbusParentObject {
-    fn get_children(&self) -> &collections::HashSet<zvariant::OwnedObjectPath>;
+    fn get_children(&self) -> &Children;
 
-    fn get_mut_children(&mut self) -> &mut collections::HashSet<zvariant::OwnedObjectPath>;
+    fn get_children_paths(&self) -> Vec<zvariant::OwnedObjectPath> {
+        self.get_children()
+            .lock()
+            .expect("lock is not poisoned")
+            .iter()
+            .cloned()
+            .collect()
+    }
 }
 
 /// Trait implemented by Secret Service Dbus objects that are children of other objects.
@@ -74,6 +112,18 @@ pub trait DbusChildObject: DbusObject {
 
     fn get_parent_path(&self) -> zvariant::ObjectPath<'_>;
 
+    /// The children of this object's parent, shared with the parent.
+    fn get_siblings(&self) -> &Children;
+
+    fn get_sibling_paths(&self) -> Vec<zvariant::OwnedObjectPath> {
+        self.get_siblings()
+            .lock()
+            .expect("lock is not poisoned")
+            .iter()
+            .cloned()
+            .collect()
+    }
+
     fn get_parent_interface(
         &self,
         object_server: &zbus::ObjectServer,
@@ -86,17 +136,31 @@ pub trait DbusChildObject: DbusObject {
         }
     }
 
-    fn remove_from_parent(
+    /// Remove this object from its parent's children, returning whether it was there.
+    fn remove_from_parent(&self) -> bool {
+        self.get_siblings()
+            .lock()
+            .expect("lock is not poisoned")
+            .remove(&self.get_object_path())
+    }
+
+    /// Emit `PropertiesChanged` for `properties` of this object's parent.
+    ///
+    /// Unlike the `<property>_changed` methods generated by zbus, the parent's interface
+    /// doesn't have to be locked, so the new values are given by the caller.
+    fn parent_properties_changed(
         &self,
-        object_server: &zbus::ObjectServer,
-    ) -> impl std::future::Future<Output = bool> + Send {
-        async {
-            if let Ok(parent_interface) = self.get_parent_interface(object_server).await {
-                let mut parent = parent_interface.get_mut().await;
-                parent.get_mut_children().remove(&self.get_object_path())
-            } else {
-                false
-            }
+        parent_emitter: &zbus::object_server::SignalEmitter<'_>,
+        properties: collections::HashMap<&str, zvariant::Value<'_>>,
+    ) -> impl std::future::Future<Output = zbus::Result<()>> + Send {
+        async move {
+            zbus::fdo::Properties::properties_changed(
+                parent_emitter,
+                <Self::Parent as zbus::object_server::Interface>::name(),
+                properties,
+                borrow::Cow::Borrowed(&[]),
+            )
+            .await
         }
     }
 }
@@ -0,0 +1,401 @@
+//! Implementation of `org.freedesktop.Secret.Prompt` D-Bus interface.
+//!
+//! Methods that require user interaction return the object path of a `Prompt`
+//! instead of completing right away. The client then calls `Prompt` to start the
+//! interaction, and is notified of the outcome through the `Completed` signal.
+//! Once completed or dismissed, the `Prompt` is no longer served.
+//!
+//! The interaction itself is delegated to a `Prompter`: an external program that
+//! asks the user for a password.
+use std::fmt;
+use std::process;
+use std::sync;
+
+use futures::future;
+
+use crate::error;
+use crate::object::DbusObject;
+
+/// Outcome of a `PromptAction`: `None` means the prompt was dismissed.
+pub type PromptResult = Result<Option<zvariant::OwnedValue>, error::Error>;
+
+/// The work carried out when a client calls `Prompt`.
+///
+/// It's called with the window id passed by the client, and resolves to the
+/// result to send in the `Completed` signal.
+pub type PromptAction =
+    Box<dyn FnOnce(String) -> future::BoxFuture<'static, PromptResult> + Send + Sync>;
+
+pub struct Prompt {
+    id: uuid::Uuid,
+    state: sync::Mutex<PromptState>,
+}
+
+/// Mutable state of a `Prompt`, behind a lock so that its methods only need `&self`.
+struct PromptState {
+    /// Taken once the client calls `Prompt`.
+    action: Option<PromptAction>,
+    task: Option<tokio::task::AbortHandle>,
+}
+
+impl fmt::Debug for Prompt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Prompt")
+            .field("id", &self.id)
+            .field(
+                "prompted",
+                &self
+                    .state
+                    .lock()
+                    .expect("lock is not poisoned")
+                    .action
+                    .is_none(),
+            )
+            .finish()
+    }
+}
+
+impl DbusObject for Prompt {
+    fn get_object_path(&self) -> zvariant::OwnedObjectPath {
+        let mut object_path = "/org/freedesktop/secrets/prompt/".to_owned();
+        object_path.push_str(
+            self.id
+                .as_simple()
+                .encode_lower(&mut uuid::Uuid::encode_buffer()),
+        );
+        zvariant::ObjectPath::from_str_unchecked(&object_path).into()
+    }
+}
+
+impl Prompt {
+    pub fn new(action: PromptAction) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4(),
+            state: sync::Mutex::new(PromptState {
+                action: Some(action),
+                task: None,
+            }),
+        }
+    }
+
+    /// Object path returned by methods that complete without prompting.
+    pub fn none() -> zvariant::ObjectPath<'static> {
+        zvariant::ObjectPath::from_static_str_unchecked("/")
+    }
+
+    /// Stop serving the `Prompt` at `prompt_path` and emit `Completed`.
+    ///
+    /// Nothing is emitted if the `Prompt` was already removed, as that means it
+    /// has already completed or been dismissed.
+    async fn complete(
+        connection: &zbus::Connection,
+        prompt_path: &zvariant::ObjectPath<'_>,
+        result: Option<zvariant::OwnedValue>,
+    ) -> Result<(), error::Error> {
+        if connection
+            .object_server()
+            .remove::<Prompt, _>(prompt_path)
+            .await
+            .is_err()
+        {
+            return Ok(());
+        }
+
+        let emitter = zbus::object_server::SignalEmitter::new(connection, prompt_path)?;
+        match result {
+            Some(result) => {
+                log::info!("Completed prompt on '{prompt_path}'");
+                Prompt::completed(&emitter, false, &result).await?
+            }
+            None => {
+                log::info!("Dismissed prompt on '{prompt_path}'");
+                Prompt::completed(&emitter, true, &zvariant::Value::from("")).await?
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Asks the user for passwords, or to confirm an action, by running an external program.
+///
+/// The program is called with the message to display as its last argument, and
+/// with `WINDOWID` set to the window id passed by the client, if any. It must
+/// print the password to stdout, if one was asked for, and exit with a non-zero
+/// status if the user cancels.
+#[derive(Clone, Debug)]
+pub struct Prompter {
+    command: Vec<String>,
+}
+
+impl Prompter {
+    /// Create a `Prompter` running `command`, or `None` if `command` is empty.
+    pub fn new(command: Vec<String>) -> Option<Self> {
+        if command.is_empty() {
+            None
+        } else {
+            Some(Self { command })
+        }
+    }
+
+    /// Ask the user for a password, returning `None` if they cancelled.
+    pub async fn ask_password(
+        &self,
+        message: &str,
+        window_id: &str,
+    ) -> Result<Option<zeroize::Zeroizing<Vec<u8>>>, error::Error> {
+        let output = self.run(message, window_id).await?;
+        let mut password = zeroize::Zeroizing::new(output.stdout);
+
+        if !output.status.success() {
+            log::info!("Prompter exited with {}", output.status);
+            return Ok(None);
+        }
+
+        if password.last() == Some(&b'\n') {
+            password.pop();
+        }
+
+        Ok(Some(password))
+    }
+
+    /// Ask the user to confirm an action, returning whether they did.
+    pub async fn confirm(&self, message: &str, window_id: &str) -> Result<bool, error::Error> {
+        let output = self.run(message, window_id).await?;
+        // Whatever was printed is not needed, and may be a password typed by mistake.
+        drop(zeroize::Zeroizing::new(output.stdout));
+
+        if !output.status.success() {
+            log::info!("Prompter exited with {}", output.status);
+        }
+        Ok(output.status.success())
+    }
+
+    async fn run(&self, message: &str, window_id: &str) -> Result<process::Output, error::Error> {
+        let mut command = tokio::process::Command::new(&self.command[0]);
+        command
+            .args(&self.command[1..])
+            .arg(message)
+            .stdin(process::Stdio::null())
+            .stdout(process::Stdio::piped())
+            .kill_on_drop(true);
+        if !window_id.is_empty() {
+            command.env("WINDOWID", window_id);
+        }
+
+        Ok(command.output().await?)
+    }
+}
+
+#[zbus::interface(name = "org.freedesktop.Secret.Prompt")]
+impl Prompt {
+    /// Prompt method
+    async fn prompt(
+        &self,
+        window_id: &str,
+        #[zbus(connection)] connection: &zbus::Connection,
+    ) -> Result<(), error::Error> {
+        let mut state = self.state.lock().expect("lock is not poisoned");
+        let Some(action) = state.action.take() else {
+            log::warn!("Prompt on '{}' was already started", self.get_object_path());
+            return Ok(());
+        };
+
+        let connection = connection.clone();
+        let prompt_path = self.get_object_path();
+        let window_id = window_id.to_owned();
+
+        let task = tokio::spawn(async move {
+            let result = match action(window_id).await {
+                Ok(result) => result,
+                Err(e) => {
+                    log::error!("Prompt on '{prompt_path}' failed: {e}");
+                    None
+                }
+            };
+
+            if let Err(e) = Prompt::complete(&connection, &prompt_path.as_ref(), result).await {
+                log::error!("Failed to complete prompt on '{prompt_path}': {e}");
+            }
+        });
+        state.task = Some(task.abort_handle());
+
+        Ok(())
+    }
+
+    /// Dismiss method
+    async fn dismiss(
+        &self,
+        #[zbus(connection)] connection: &zbus::Connection,
+    ) -> Result<(), error::Error> {
+        {
+            let mut state = self.state.lock().expect("lock is not poisoned");
+            state.action = None;
+            if let Some(task) = state.task.take() {
+                task.abort();
+            }
+        }
+
+        Prompt::complete(connection, &self.get_object_path().as_ref(), None).await
+    }
+
+    /// Completed signal
+    #[zbus(signal)]
+    pub async fn completed(
+        emitter: &zbus::object_server::SignalEmitter<'_>,
+        dismissed: bool,
+        result: &zvariant::Value<'_>,
+    ) -> zbus::Result<()>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{FutureExt, StreamExt};
+
+    /// Serve a `Prompt` with `action` on a new connection.
+    ///
+    /// Returns the connection serving the `Prompt`, which must be kept alive
+    /// for the duration of the test, and the `Prompt`'s object path.
+    async fn serve_prompt(
+        action: PromptAction,
+    ) -> Result<(zbus::Connection, zvariant::OwnedObjectPath), error::Error> {
+        let connection = zbus::Connection::session().await?;
+        let (prompt_path, _) = Prompt::new(action)
+            .serve_at(connection.object_server())
+            .await?;
+
+        Ok((connection, prompt_path))
+    }
+
+    async fn prompt_proxy<'p>(
+        server_connection: &zbus::Connection,
+        prompt_path: &'p zvariant::OwnedObjectPath,
+    ) -> Result<zbus::Proxy<'p>, error::Error> {
+        let connection = zbus::Connection::session().await?;
+        let proxy = zbus::Proxy::new(
+            &connection,
+            server_connection.unique_name().unwrap().to_owned(),
+            prompt_path.as_ref(),
+            "org.freedesktop.Secret.Prompt",
+        )
+        .await?;
+
+        Ok(proxy)
+    }
+
+    #[tokio::test]
+    async fn test_prompt_completes_with_action_result() -> Result<(), error::Error> {
+        let (server_connection, prompt_path) = serve_prompt(Box::new(|window_id| {
+            async move { Ok(Some(zvariant::Value::from(window_id).try_into()?)) }.boxed()
+        }))
+        .await?;
+
+        let proxy = prompt_proxy(&server_connection, &prompt_path).await?;
+        let mut completed = proxy.receive_signal("Completed").await?;
+
+        proxy.call_method("Prompt", &("window-1")).await?;
+
+        let signal = completed.next().await.unwrap();
+        let body = signal.body();
+        let (dismissed, result): (bool, zvariant::Value<'_>) = body.deserialize()?;
+        let result: String = result.downcast()?;
+
+        assert!(!dismissed);
+        assert_eq!(result, "window-1");
+        assert!(server_connection
+            .object_server()
+            .interface::<_, Prompt>(&prompt_path)
+            .await
+            .is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_prompt_completes_dismissed_when_action_declines() -> Result<(), error::Error> {
+        let (server_connection, prompt_path) =
+            serve_prompt(Box::new(|_| async { Ok(None) }.boxed())).await?;
+
+        let proxy = prompt_proxy(&server_connection, &prompt_path).await?;
+        let mut completed = proxy.receive_signal("Completed").await?;
+
+        proxy.call_method("Prompt", &("")).await?;
+
+        let signal = completed.next().await.unwrap();
+        let body = signal.body();
+        let (dismissed, _): (bool, zvariant::Value<'_>) = body.deserialize()?;
+
+        assert!(dismissed);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_dismiss_prompt() -> Result<(), error::Error> {
+        let (action_ran_sender, action_ran) = tokio::sync::oneshot::channel::<()>();
+        let (server_connection, prompt_path) = serve_prompt(Box::new(move |_| {
+            async move {
+                let _ = action_ran_sender.send(());
+                Ok(Some(zvariant::Value::from(true).try_into()?))
+            }
+            .boxed()
+        }))
+        .await?;
+
+        let proxy = prompt_proxy(&server_connection, &prompt_path).await?;
+        let mut completed = proxy.receive_signal("Completed").await?;
+
+        proxy.call_method("Dismiss", &()).await?;
+
+        let signal = completed.next().await.unwrap();
+        let body = signal.body();
+        let (dismissed, _): (bool, zvariant::Value<'_>) = body.deserialize()?;
+
+        assert!(dismissed);
+        // Dismissing drops the action without running it.
+        assert!(action_ran.await.is_err());
+        assert!(proxy.call_method("Prompt", &("")).await.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_prompter_reads_password() -> Result<(), error::Error> {
+        let prompter = Prompter::new(vec![
+            "sh".to_owned(),
+            "-c".to_owned(),
+            "echo \"$WINDOWID:$0\"".to_owned(),
+        ])
+        .unwrap();
+
+        let password = prompter.ask_password("message", "window-1").await?;
+
+        assert_eq!(password.unwrap().as_slice(), b"window-1:message");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_prompter_cancelled() -> Result<(), error::Error> {
+        let prompter = Prompter::new(vec!["false".to_owned()]).unwrap();
+
+        let password = prompter.ask_password("message", "").await?;
+
+        assert!(password.is_none());
+        assert!(Prompter::new(Vec::new()).is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_prompter_confirms() -> Result<(), error::Error> {
+        let confirming = Prompter::new(vec!["true".to_owned()]).unwrap();
+        let cancelling = Prompter::new(vec!["false".to_owned()]).unwrap();
+
+        assert!(confirming.confirm("message", "").await?);
+        assert!(!cancelling.confirm("message", "").await?);
+
+        Ok(())
+    }
+}
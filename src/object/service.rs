@@ -1,36 +1,388 @@
 use std::collections;
 use std::str;
+use std::sync;
 
-use futures::{stream, StreamExt};
+use futures::{stream, FutureExt, StreamExt};
 
+use crate::access;
 use crate::error;
+use crate::index;
+use crate::object;
+use crate::object::alias;
 use crate::object::collection;
-use crate::object::collection::CollectionSignals;
 use crate::object::item;
+use crate::object::prompt;
 use crate::object::session;
 use crate::object::{DbusChildObject, DbusObject, DbusParentObject};
 
 use crate::secret;
+use crate::storage;
+
+pub const SERVICE_PATH: &str = "/org/freedesktop/secrets";
+/// How many times the user may enter a wrong password before an unlock prompt is dismissed.
+const UNLOCK_ATTEMPTS: usize = 3;
 
 /// Secret Service struct implementing `org.freedesktop.Secret.Service` interface.
-#[derive(Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+#[derive(Debug)]
 pub struct Service {
-    aliases: collections::HashMap<String, zvariant::OwnedObjectPath>,
-    pub collections: collections::HashSet<zvariant::OwnedObjectPath>,
+    /// Access control to items, shared with collections and items.
+    pub access: sync::Arc<access::AccessControl>,
+    /// Collections by alias, shared with collections to remove their aliases when deleted.
+    pub aliases: sync::Arc<sync::Mutex<collections::HashMap<String, zvariant::OwnedObjectPath>>>,
+    pub collections: object::Children,
+    /// Connection serving the service, used by objects to emit signals outside of method calls.
+    pub connection: zbus::Connection,
+    /// Attributes of the items in all collections.
+    pub index: sync::Arc<sync::Mutex<index::AttributeIndex>>,
+    prompter: Option<prompt::Prompter>,
+    /// Open sessions, to close them once their client disconnects.
+    pub sessions: sync::Arc<sync::Mutex<session::SessionOwners>>,
+    pub storage: sync::Arc<dyn storage::Storage>,
+}
+
+/// A locked collection that can only be unlocked with a password.
+#[derive(Debug)]
+struct PendingUnlock {
+    collection_id: uuid::Uuid,
+    collection_path: zvariant::OwnedObjectPath,
+    label: String,
+    /// Objects requested to be unlocked that belong to the collection.
+    objects: Vec<zvariant::OwnedObjectPath>,
 }
 
 impl Service {
-    pub fn new() -> Self {
+    pub fn new(
+        connection: zbus::Connection,
+        storage: sync::Arc<dyn storage::Storage>,
+        prompter: Option<prompt::Prompter>,
+        policy: access::Policy,
+    ) -> Self {
         Self {
-            aliases: collections::HashMap::new(),
-            collections: collections::HashSet::new(),
+            access: sync::Arc::new(access::AccessControl::new(
+                connection.clone(),
+                policy,
+                prompter.clone(),
+            )),
+            aliases: sync::Arc::new(sync::Mutex::new(collections::HashMap::new())),
+            collections: object::Children::default(),
+            connection,
+            index: sync::Arc::new(sync::Mutex::new(index::AttributeIndex::new())),
+            prompter,
+            sessions: sync::Arc::new(sync::Mutex::new(session::SessionOwners::new())),
+            storage,
         }
     }
-}
 
-impl Default for Service {
-    fn default() -> Self {
-        Self::new()
+    /// Create and serve a new collection protected by `password`.
+    pub async fn add_collection(
+        &self,
+        label: &str,
+        alias: Option<&str>,
+        password: &[u8],
+        object_server: &zbus::ObjectServer,
+        emitter: &zbus::object_server::SignalEmitter<'_>,
+    ) -> Result<zvariant::OwnedObjectPath, error::Error> {
+        let new_collection = collection::Collection::new(uuid::Uuid::new_v4(), label, self);
+        self.storage
+            .create_collection(new_collection.to_stored(), password)
+            .await?;
+
+        self.serve_new_collection(new_collection, alias, object_server, emitter)
+            .await
+    }
+
+    /// Create and serve a new collection without a password, which is unlocked without
+    /// asking the user.
+    pub async fn add_passwordless_collection(
+        &self,
+        label: &str,
+        alias: Option<&str>,
+        object_server: &zbus::ObjectServer,
+        emitter: &zbus::object_server::SignalEmitter<'_>,
+    ) -> Result<zvariant::OwnedObjectPath, error::Error> {
+        let new_collection = collection::Collection::new(uuid::Uuid::new_v4(), label, self);
+        self.storage
+            .create_passwordless_collection(new_collection.to_stored())
+            .await?;
+
+        self.serve_new_collection(new_collection, alias, object_server, emitter)
+            .await
+    }
+
+    /// Create and serve a new collection that is only kept in memory, and can't be locked.
+    pub async fn add_ephemeral_collection(
+        &self,
+        label: &str,
+        alias: Option<&str>,
+        object_server: &zbus::ObjectServer,
+        emitter: &zbus::object_server::SignalEmitter<'_>,
+    ) -> Result<zvariant::OwnedObjectPath, error::Error> {
+        let new_collection = collection::Collection::new(uuid::Uuid::new_v4(), label, self);
+        self.storage
+            .create_ephemeral_collection(new_collection.to_stored())
+            .await?;
+
+        self.serve_new_collection(new_collection, alias, object_server, emitter)
+            .await
+    }
+
+    /// Serve a collection that was just created in storage, and point `alias` to it.
+    async fn serve_new_collection(
+        &self,
+        new_collection: collection::Collection,
+        alias: Option<&str>,
+        object_server: &zbus::ObjectServer,
+        emitter: &zbus::object_server::SignalEmitter<'_>,
+    ) -> Result<zvariant::OwnedObjectPath, error::Error> {
+        if let Some(alias) = alias {
+            self.storage
+                .set_alias(alias, Some(&new_collection.id))
+                .await?;
+        };
+
+        let (collection_path, _) = new_collection.serve_at(object_server).await?;
+
+        Service::collection_created(emitter, &collection_path.as_ref()).await?;
+
+        log::info!("Created new collection on '{collection_path}'");
+        self.collections
+            .lock()
+            .expect("lock is not poisoned")
+            .insert(collection_path.clone());
+        self.collections_changed(emitter).await?;
+        if let Some(alias) = alias {
+            self.serve_alias(alias, collection_path.clone(), object_server)
+                .await?;
+        };
+
+        Ok(collection_path)
+    }
+
+    /// Bump the `Modified` timestamp of the collection on `collection_path` after it was
+    /// locked or unlocked, along with the collections linked to it in storage, and emit
+    /// `PropertiesChanged` for them and for their items, which are locked along with them.
+    ///
+    /// Unlocking may also have read the collections anew from storage, as with KeePass
+    /// databases, so their labels and items are updated to match it, and linked collections
+    /// only found now are served.
+    pub async fn notify_lock_changed(
+        collection_path: &zvariant::ObjectPath<'_>,
+        object_server: &zbus::ObjectServer,
+        emitter: &zbus::object_server::SignalEmitter<'_>,
+    ) -> Result<(), error::Error> {
+        let collection_interface =
+            collection::Collection::get_interface_from_object_path(collection_path, object_server)
+                .await?;
+        let (storage, collection_id) = {
+            let collection = collection_interface.get().await;
+            (collection.storage.clone(), collection.id)
+        };
+
+        for linked_id in storage.linked_collections(&collection_id).await {
+            let linked_path = collection::object_path(&linked_id);
+            if let Ok(linked_interface) = collection::Collection::get_interface_from_object_path(
+                &linked_path.as_ref(),
+                object_server,
+            )
+            .await
+            {
+                Service::notify_collection_lock_changed(&linked_interface, object_server, emitter)
+                    .await?;
+                continue;
+            }
+
+            let Some(stored_collection) = storage.collection(&linked_id).await else {
+                continue;
+            };
+            let linked_collection = collection_interface.get().await.sibling(&stored_collection);
+            let linked_interface = Service::serve_stored_collection(
+                linked_collection,
+                stored_collection,
+                object_server,
+            )
+            .await?;
+            log::info!("Found collection on '{linked_path}'");
+
+            let linked_collection = linked_interface.get().await;
+            linked_collection
+                .get_siblings()
+                .lock()
+                .expect("lock is not poisoned")
+                .insert(linked_path.clone());
+            Service::collection_created(emitter, &linked_path.as_ref()).await?;
+            linked_collection
+                .parent_properties_changed(
+                    emitter,
+                    collections::HashMap::from([(
+                        "Collections",
+                        zvariant::Value::from(linked_collection.get_sibling_paths()),
+                    )]),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Emit the signals of `notify_lock_changed` for one collection.
+    async fn notify_collection_lock_changed(
+        collection_interface: &zbus::object_server::InterfaceRef<collection::Collection>,
+        object_server: &zbus::ObjectServer,
+        emitter: &zbus::object_server::SignalEmitter<'_>,
+    ) -> Result<(), error::Error> {
+        collection::Collection::reload(collection_interface, object_server).await?;
+        let collection = collection_interface.get().await;
+        collection.touch(object::timestamp()).await?;
+
+        let collection_emitter = collection_interface.signal_emitter();
+        collection.locked_changed(collection_emitter).await?;
+        collection.modified_changed(collection_emitter).await?;
+
+        for item_path in collection.items() {
+            let item_interface =
+                item::Item::get_interface_from_object_path(&item_path.as_ref(), object_server)
+                    .await?;
+            item_interface
+                .get()
+                .await
+                .locked_changed(item_interface.signal_emitter())
+                .await?;
+        }
+
+        Service::collection_changed(emitter, &collection.get_object_path().as_ref()).await?;
+        Ok(())
+    }
+
+    /// Serve `collection`, made from `stored_collection`, along with the items of the latter.
+    ///
+    /// It's left to the caller to add the collection to the children of the service.
+    async fn serve_stored_collection(
+        collection: collection::Collection,
+        stored_collection: storage::StoredCollection,
+        object_server: &zbus::ObjectServer,
+    ) -> Result<zbus::object_server::InterfaceRef<collection::Collection>, error::Error> {
+        for stored_item in stored_collection.items.into_values() {
+            let item = item::Item::from_stored(stored_item, &collection);
+            let attributes = item.attributes.clone();
+            let (item_path, _) = item.serve_at(object_server).await?;
+
+            collection.insert_item(item_path, attributes);
+        }
+
+        let (collection_path, _) = collection.serve_at(object_server).await?;
+        collection::Collection::get_interface_from_object_path(
+            &collection_path.as_ref(),
+            object_server,
+        )
+        .await
+    }
+
+    /// Point `alias` to the collection on `collection_path`, serving an alias object for it
+    /// in place of any previous one.
+    async fn serve_alias(
+        &self,
+        alias: &str,
+        collection_path: zvariant::OwnedObjectPath,
+        object_server: &zbus::ObjectServer,
+    ) -> Result<(), error::Error> {
+        let collection_interface = collection::Collection::get_interface_from_object_path(
+            &collection_path.as_ref(),
+            object_server,
+        )
+        .await?;
+
+        let previous_path = self
+            .aliases
+            .lock()
+            .expect("lock is not poisoned")
+            .insert(alias.to_owned(), collection_path.clone());
+        match previous_path {
+            Some(previous_path) if previous_path == collection_path => return Ok(()),
+            Some(_) => {
+                object_server
+                    .remove::<alias::Alias, _>(alias::object_path(alias))
+                    .await?;
+            }
+            None => {}
+        }
+        alias::Alias::new(alias, collection_interface)
+            .serve_at(object_server)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Object path of the collection that `object_path` points to, if it's the path of an alias.
+    ///
+    /// Any other object path is returned unchanged.
+    pub fn resolve_alias_path(
+        &self,
+        object_path: &zvariant::ObjectPath<'_>,
+    ) -> zvariant::OwnedObjectPath {
+        self.aliases
+            .lock()
+            .expect("lock is not poisoned")
+            .iter()
+            .find(|(alias, _)| alias::object_path(alias).as_str() == object_path.as_str())
+            .map(|(_, collection_path)| collection_path.clone())
+            .unwrap_or_else(|| object_path.clone().into())
+    }
+
+    /// Object path of the collection that `alias` points to, if any.
+    pub fn aliased_collection(&self, alias: &str) -> Option<zvariant::OwnedObjectPath> {
+        self.aliases
+            .lock()
+            .expect("lock is not poisoned")
+            .get(alias)
+            .cloned()
+    }
+
+    /// Serve all collections, and their items, found in storage.
+    pub async fn load_collections(
+        &mut self,
+        object_server: &zbus::ObjectServer,
+    ) -> Result<(), error::Error> {
+        let stored_aliases = self.storage.aliases().await;
+
+        // Collections without a password don't need to wait for the user. Unlocking a
+        // KeePass database is what reads its items, and the collections of its groups.
+        for stored_collection in self.storage.collections().await {
+            if self.storage.is_passwordless(&stored_collection.id).await
+                && self
+                    .storage
+                    .unlock_collection(&stored_collection.id, b"")
+                    .await?
+            {
+                log::info!(
+                    "Unlocked collection '{}' without a password",
+                    stored_collection.label
+                );
+            }
+        }
+
+        for stored_collection in self.storage.collections().await {
+            let collection = collection::Collection::from_stored(&stored_collection, self);
+            let collection_id = stored_collection.id;
+            let collection_interface =
+                Service::serve_stored_collection(collection, stored_collection, object_server)
+                    .await?;
+            let collection_path = collection_interface.get().await.get_object_path();
+            log::info!("Loaded collection on '{collection_path}'");
+
+            self.collections
+                .lock()
+                .expect("lock is not poisoned")
+                .insert(collection_path.clone());
+            for (alias, _) in stored_aliases
+                .iter()
+                .filter(|(_, aliased_id)| **aliased_id == collection_id)
+            {
+                self.serve_alias(alias, collection_path.clone(), object_server)
+                    .await?;
+            }
+        }
+
+        Ok(())
     }
 }
 
@@ -41,13 +393,9 @@ impl DbusObject for Service {
 }
 
 impl DbusParentObject for Service {
-    fn get_children(&self) -> &collections::HashSet<zvariant::OwnedObjectPath> {
+    fn get_children(&self) -> &object::Children {
         &self.collections
     }
-
-    fn get_mut_children(&mut self) -> &mut collections::HashSet<zvariant::OwnedObjectPath> {
-        &mut self.collections
-    }
 }
 
 #[zbus::interface(name = "org.freedesktop.Secret.Service")]
@@ -59,13 +407,10 @@ impl Service {
         alias: &str,
         #[zbus(object_server)] object_server: &zbus::ObjectServer,
         #[zbus(signal_emitter)] emitter: zbus::object_server::SignalEmitter<'_>,
-    ) -> Result<(zvariant::OwnedObjectPath, zvariant::ObjectPath<'_>), error::Error> {
+    ) -> Result<(zvariant::OwnedObjectPath, zvariant::OwnedObjectPath), error::Error> {
         let collection_alias = if !alias.is_empty() {
-            if let Some(collection_path) = self.aliases.get(alias) {
-                return Ok((
-                    collection_path.clone(),
-                    zvariant::ObjectPath::from_str_unchecked("/"),
-                ));
+            if let Some(collection_path) = self.aliased_collection(alias) {
+                return Ok((collection_path, prompt::Prompt::none().into()));
             }
 
             Some(alias)
@@ -73,32 +418,55 @@ impl Service {
             None
         };
 
-        let collection_id = uuid::Uuid::new_v4();
-        let new_collection = match collection_alias {
-            Some("default") => collection::Collection::new_default(self),
-            Some(_) | None => collection::Collection::new(
-                collection_id,
-                &properties.label,
-                collection_alias,
-                self,
-            ),
+        let Some(prompter) = self.prompter.clone() else {
+            log::warn!(
+                "Creating collection '{}' without a password, as there is no prompter to ask for one",
+                properties.label
+            );
+            let collection_path = self
+                .add_passwordless_collection(
+                    &properties.label,
+                    collection_alias,
+                    object_server,
+                    &emitter,
+                )
+                .await?;
+            return Ok((collection_path, prompt::Prompt::none().into()));
         };
 
-        let (collection_path, _) = new_collection.serve_at(object_server).await?;
+        let connection = emitter.connection().clone();
+        let label = properties.label;
+        let collection_alias = collection_alias.map(|alias| alias.to_owned());
+        let action: prompt::PromptAction = Box::new(move |window_id| {
+            async move {
+                let message = format!("Choose a password for the new collection '{label}'");
+                let Some(password) = prompter.ask_password(&message, &window_id).await? else {
+                    return Ok(None);
+                };
 
-        emitter.collection_created().await?;
+                let service_interface = connection
+                    .object_server()
+                    .interface::<_, Service>(SERVICE_PATH)
+                    .await?;
+                let collection_path = service_interface
+                    .get()
+                    .await
+                    .add_collection(
+                        &label,
+                        collection_alias.as_deref(),
+                        &password,
+                        connection.object_server(),
+                        service_interface.signal_emitter(),
+                    )
+                    .await?;
 
-        log::info!("Created new collection on '{collection_path}'");
-        self.collections.insert(collection_path.clone());
-        if let Some(collection_alias) = collection_alias {
-            self.aliases
-                .insert(collection_alias.to_string(), collection_path.clone());
-        };
+                Ok(Some(zvariant::Value::from(collection_path).try_into()?))
+            }
+            .boxed()
+        });
+        let (prompt_path, _) = prompt::Prompt::new(action).serve_at(object_server).await?;
 
-        Ok((
-            collection_path,
-            zvariant::ObjectPath::from_str_unchecked("/"),
-        ))
+        Ok((prompt::Prompt::none().into(), prompt_path))
     }
 
     /// GetSecrets method
@@ -106,11 +474,13 @@ impl Service {
         &self,
         items: Vec<zvariant::OwnedObjectPath>,
         session: zvariant::ObjectPath<'_>,
+        #[zbus(header)] header: zbus::message::Header<'_>,
         #[zbus(object_server)] object_server: &zbus::ObjectServer,
     ) -> Result<collections::HashMap<zvariant::OwnedObjectPath, secret::Secret>, error::Error> {
-        let session_interface =
-            session::Session::get_interface_from_object_path(&session, object_server).await?;
-        let session = *(session_interface.get().await);
+        let session =
+            session::Session::get_owned_by(&session, header.sender(), object_server).await?;
+        let session = &session;
+        let application = &self.access.caller(&header).await?;
 
         let mut tasks = stream::FuturesUnordered::new();
 
@@ -128,23 +498,13 @@ impl Service {
 
                 let item = item_interface.get().await;
 
-                if item.locked {
-                    return None;
-                }
-
-                let collection_interface = match item.get_parent_interface(object_server).await {
-                    Ok(interface) => interface,
-                    Err(_) => {
-                        return None;
-                    }
-                };
-                let collection = collection_interface.get().await;
-
-                if collection.locked {
+                // Items that need the user's confirmation can only be read one at a time
+                // with `GetSecret`, so that the service is not held while the user is asked.
+                if item.is_locked().await || !self.access.is_allowed(application, &item) {
                     return None;
                 }
 
-                let secret = item.get_secret_with_session(&session);
+                let secret = item.get_secret_with_session(session).await.ok()?;
                 Some((item.get_object_path(), secret))
             });
         }
@@ -170,16 +530,23 @@ impl Service {
         let mut locked = Vec::new();
 
         for object in objects.iter() {
+            let object = &self.resolve_alias_path(object).into();
             if let Ok(collection_interface) =
                 collection::Collection::get_interface_from_object_path(object, object_server).await
             {
-                let mut collection = collection_interface.get_mut().await;
-                if !collection.locked {
-                    collection.locked = true;
-
-                    emitter.collection_changed().await?;
+                let collection = collection_interface.get().await;
+                let collection_path = collection.get_object_path();
+                let is_locked = self.storage.is_locked(&collection.id).await;
+                if !is_locked && self.storage.lock_collection(&collection.id).await? {
+                    drop(collection);
+                    Service::notify_lock_changed(
+                        &collection_path.as_ref(),
+                        object_server,
+                        &emitter,
+                    )
+                    .await?;
 
-                    locked.push(collection.get_object_path());
+                    locked.push(collection_path);
                 }
                 continue;
             }
@@ -188,60 +555,184 @@ impl Service {
                 item::Item::get_interface_from_object_path(object, object_server).await
             {
                 let mut item = item_interface.get_mut().await;
-                if !item.locked {
-                    item.locked = true;
-
-                    emitter.item_changed().await?;
-
+                if item.set_locked(true).await? {
                     locked.push(item.get_object_path());
                 }
                 continue;
             }
         }
 
-        Ok((locked, zvariant::ObjectPath::from_str_unchecked("/")))
+        Ok((locked, prompt::Prompt::none()))
     }
 
-    /// Lock method
+    /// Unlock method
+    ///
+    /// Collections protected by a password are unlocked through a prompt, which
+    /// completes with the objects that were unlocked. So are items of other applications,
+    /// once the user confirms the caller may access them.
     async fn unlock(
-        &mut self,
+        &self,
         objects: Vec<zvariant::ObjectPath<'_>>,
+        #[zbus(header)] header: zbus::message::Header<'_>,
         #[zbus(object_server)] object_server: &zbus::ObjectServer,
         #[zbus(signal_emitter)] emitter: zbus::object_server::SignalEmitter<'_>,
-    ) -> Result<(Vec<zvariant::OwnedObjectPath>, zvariant::ObjectPath<'_>), error::Error> {
+    ) -> Result<(Vec<zvariant::OwnedObjectPath>, zvariant::OwnedObjectPath), error::Error> {
+        let application = self.access.caller(&header).await?;
         let mut unlocked = Vec::new();
+        let mut pending: Vec<PendingUnlock> = Vec::new();
+        let mut confirmations: Vec<access::Confirmation> = Vec::new();
 
         for object in objects.iter() {
-            if let Ok(collection_interface) =
-                collection::Collection::get_interface_from_object_path(object, object_server).await
-            {
-                let mut collection = collection_interface.get_mut().await;
-                if collection.locked {
-                    collection.locked = false;
+            let object = &self.resolve_alias_path(object).into();
+            let mut confirmation = None;
+            let (collection_id, collection_path, item_was_locked) =
+                if let Ok(collection_interface) =
+                    collection::Collection::get_interface_from_object_path(object, object_server)
+                        .await
+                {
+                    let collection = collection_interface.get().await;
+                    (collection.id, collection.get_object_path(), false)
+                } else if let Ok(item_interface) =
+                    item::Item::get_interface_from_object_path(object, object_server).await
+                {
+                    let mut item = item_interface.get_mut().await;
+                    let item_was_locked = item.set_locked(false).await?;
+                    confirmation = self.access.confirmation(&application, &item);
+                    (
+                        item.collection_id,
+                        item.parent_path.clone(),
+                        item_was_locked,
+                    )
+                } else {
+                    continue;
+                };
 
-                    emitter.collection_changed().await?;
+            // Objects the user must confirm access to are unlocked once they do.
+            let confirmed_object = match confirmation {
+                Some(confirmation) => {
+                    confirmations.push(confirmation);
+                    None
+                }
+                None => Some(object.clone().into()),
+            };
 
-                    unlocked.push(collection.get_object_path());
+            if !self.storage.is_locked(&collection_id).await {
+                if item_was_locked {
+                    unlocked.extend(confirmed_object);
                 }
                 continue;
             }
 
-            if let Ok(item_interface) =
-                item::Item::get_interface_from_object_path(object, object_server).await
+            // Collections without a password are unlocked right away.
+            if self.storage.is_passwordless(&collection_id).await
+                && self.storage.unlock_collection(&collection_id, b"").await?
             {
-                let mut item = item_interface.get_mut().await;
-                if item.locked {
-                    item.locked = false;
-
-                    emitter.item_changed().await?;
+                Service::notify_lock_changed(&collection_path.as_ref(), object_server, &emitter)
+                    .await?;
+                unlocked.extend(confirmed_object);
+                continue;
+            }
 
-                    unlocked.push(item.get_object_path());
+            match pending
+                .iter_mut()
+                .find(|pending_unlock| pending_unlock.collection_id == collection_id)
+            {
+                Some(pending_unlock) => pending_unlock.objects.extend(confirmed_object),
+                None => {
+                    let collection_interface =
+                        collection::Collection::get_interface_from_object_path(
+                            &collection_path.as_ref(),
+                            object_server,
+                        )
+                        .await?;
+                    pending.push(PendingUnlock {
+                        collection_id,
+                        collection_path,
+                        label: collection_interface.get().await.label.clone(),
+                        objects: confirmed_object.into_iter().collect(),
+                    });
                 }
-                continue;
             }
         }
 
-        Ok((unlocked, zvariant::ObjectPath::from_str_unchecked("/")))
+        if pending.is_empty() && confirmations.is_empty() {
+            return Ok((unlocked, prompt::Prompt::none().into()));
+        }
+
+        let Some(prompter) = self.prompter.clone() else {
+            log::warn!("Cannot unlock collections protected by a password without a prompter");
+            return Ok((unlocked, prompt::Prompt::none().into()));
+        };
+
+        let access = self.access.clone();
+        let connection = emitter.connection().clone();
+        let storage = self.storage.clone();
+        let action: prompt::PromptAction = Box::new(move |window_id| {
+            async move {
+                let emitter = zbus::object_server::SignalEmitter::new(&connection, SERVICE_PATH)?;
+                let mut unlocked: Vec<zvariant::OwnedObjectPath> = Vec::new();
+
+                for pending_unlock in pending {
+                    // Unlocking a collection may have unlocked those linked to it.
+                    if !storage.is_locked(&pending_unlock.collection_id).await {
+                        unlocked.extend(pending_unlock.objects);
+                        continue;
+                    }
+
+                    let mut message = format!(
+                        "Enter the password to unlock collection '{}'",
+                        pending_unlock.label
+                    );
+
+                    for attempt in 1..=UNLOCK_ATTEMPTS {
+                        let Some(password) = prompter.ask_password(&message, &window_id).await?
+                        else {
+                            return Ok(None);
+                        };
+
+                        if storage
+                            .unlock_collection(&pending_unlock.collection_id, &password)
+                            .await?
+                        {
+                            break;
+                        }
+
+                        log::warn!(
+                            "Wrong password for collection '{}' ({attempt}/{UNLOCK_ATTEMPTS})",
+                            pending_unlock.label
+                        );
+                        if attempt == UNLOCK_ATTEMPTS {
+                            return Ok(None);
+                        }
+                        message = format!(
+                            "Wrong password. Enter the password to unlock collection '{}'",
+                            pending_unlock.label
+                        );
+                    }
+
+                    Service::notify_lock_changed(
+                        &pending_unlock.collection_path.as_ref(),
+                        connection.object_server(),
+                        &emitter,
+                    )
+                    .await?;
+                    unlocked.extend(pending_unlock.objects);
+                }
+
+                for confirmation in confirmations {
+                    if !access.confirm(&confirmation, &window_id).await? {
+                        return Ok(None);
+                    }
+                    unlocked.push(confirmation.item_path);
+                }
+
+                Ok(Some(zvariant::Value::from(unlocked).try_into()?))
+            }
+            .boxed()
+        });
+        let (prompt_path, _) = prompt::Prompt::new(action).serve_at(object_server).await?;
+
+        Ok((unlocked, prompt_path))
     }
 
     /// OpenSession method
@@ -249,60 +740,89 @@ impl Service {
         &self,
         algorithm: &str,
         input: zvariant::Value<'_>,
+        #[zbus(header)] header: zbus::message::Header<'_>,
         #[zbus(object_server)] object_server: &zbus::ObjectServer,
     ) -> Result<(zvariant::Value<'_>, zvariant::ObjectPath<'_>), error::Error> {
+        // Sessions belong to the client opening them, which is always known on a message bus.
+        let owner: zbus_names::OwnedUniqueName = header
+            .sender()
+            .ok_or_else(|| error::Error::InvalidArgs {
+                method: "OpenSession",
+                argument: "sender",
+                reason: "Cannot open a session for a message without a sender.".to_owned(),
+            })?
+            .to_owned()
+            .into();
+
         let (new_session, return_value) = match algorithm {
             "plain" => {
                 let public_key_bytes: Vec<u8> = input.try_into()?;
 
                 if let Ok(public_key) = str::from_utf8(&public_key_bytes) {
                     if !public_key.is_empty() {
-                        return Err(error::Error::InvalidArgs(
-                            "OpenSession".to_owned(),
-                            "Expected input to be '\"\"' when requesting a 'plain' session."
-                                .to_owned(),
-                        ));
+                        return Err(error::Error::InvalidArgs {
+                            method: "OpenSession",
+                            argument: "input",
+                            reason:
+                                "Expected input to be '\"\"' when requesting a 'plain' session."
+                                    .to_owned(),
+                        });
                     }
                 }
-                let session = session::Session::new_plain();
+                let session = session::Session::new_plain(owner.clone(), self.sessions.clone());
 
                 (session, zvariant::Value::from(""))
             }
             "dh-ietf1024-sha256-aes128-cbc-pkcs7" => {
                 let public_key_bytes: Vec<u8> = input.try_into()?;
-                let public_key = public_key_bytes.try_into().map_err(|_| {
-                    error::Error::InvalidArgs(
-                        "OpenSession".to_owned(),
-                        "Invalid key length".to_owned(),
-                    )
-                })?;
-                let (session, server_public_key) = session::Session::new_dh(public_key)?;
-                let mut public_key_vec: Vec<u8> = Vec::with_capacity(server_public_key.len());
-                public_key_vec.extend_from_slice(&server_public_key);
-
-                let v: zvariant::Value = server_public_key.to_vec().into();
+                let (session, server_public_key) = session::Session::new_dh(
+                    &public_key_bytes,
+                    owner.clone(),
+                    self.sessions.clone(),
+                )?;
 
-                (session, v)
+                (session, zvariant::Value::from(server_public_key))
             }
             algorithm => {
                 return Err(error::Error::AlgorithmUnsupported(algorithm.to_owned()));
             }
         };
-        let (session_path, _) = new_session.serve_at(object_server).await?;
+        // Track the session before serving it, so that a client disconnecting in between has its
+        // session closed by the NameOwnerChanged handler.
+        let session_path = new_session.get_object_path();
+        self.sessions
+            .lock()
+            .expect("lock is not poisoned")
+            .insert(owner.clone(), session_path.clone());
+        if let Err(err) = new_session.serve_at(object_server).await {
+            self.sessions
+                .lock()
+                .expect("lock is not poisoned")
+                .remove(&owner, &session_path);
+            return Err(err);
+        }
+        // The client may have disconnected before the session was served.
+        if !self
+            .sessions
+            .lock()
+            .expect("lock is not poisoned")
+            .contains(&owner, &session_path)
+        {
+            object_server
+                .remove::<session::Session, _>(&session_path)
+                .await?;
+            return Err(error::Error::NoSession(session_path.to_string()));
+        }
 
-        log::info!("Opened new session on '{session_path}'");
+        log::info!("Opened new session on '{session_path}' for '{owner}'");
 
         Ok((return_value, session_path.into()))
     }
 
     /// ReadAlias method
-    fn read_alias(&self, name: &str) -> zvariant::ObjectPath<'_> {
-        if let Some(matching_collection) = self.aliases.get(name) {
-            zvariant::ObjectPath::try_from(matching_collection.as_str())
-                .expect("existing path should not fail")
-        } else {
-            zvariant::ObjectPath::from_str_unchecked("/")
-        }
+    pub fn read_alias(&self, name: &str) -> zvariant::OwnedObjectPath {
+        self.aliased_collection(name)
+            .unwrap_or_else(|| prompt::Prompt::none().into())
     }
 
     /// SetAlias method
@@ -314,34 +834,32 @@ impl Service {
     ) -> Result<(), error::Error> {
         match collection.as_str() {
             "/" => {
-                if let Some(collection_path) = self.aliases.get(name) {
-                    let collection_interface =
-                        collection::Collection::get_interface_from_object_path(
-                            collection_path,
-                            object_server,
-                        )
-                        .await?;
+                if self.aliased_collection(name).is_none() {
+                    return Err(error::Error::NoSuchObject(name.to_owned()));
+                }
 
-                    let mut collection = collection_interface.get_mut().await;
-                    collection.alias = None;
-                    self.aliases.remove(name);
+                self.storage.set_alias(name, None).await?;
+                self.aliases
+                    .lock()
+                    .expect("lock is not poisoned")
+                    .remove(name);
+                object_server
+                    .remove::<alias::Alias, _>(alias::object_path(name))
+                    .await?;
 
-                    Ok(())
-                } else {
-                    Err(error::Error::NoSuchObject(name.to_owned()))
-                }
+                Ok(())
             }
             _ => {
+                let collection_path = self.resolve_alias_path(&collection);
                 let collection_interface = collection::Collection::get_interface_from_object_path(
-                    &collection,
+                    &collection_path.as_ref(),
                     object_server,
                 )
                 .await?;
-                let mut collection = collection_interface.get_mut().await;
-                collection.alias = Some(name.to_string());
-                self.aliases.remove(name);
-                self.aliases
-                    .insert(name.to_string(), collection.get_object_path());
+                let collection_id = collection_interface.get().await.id;
+                self.storage.set_alias(name, Some(&collection_id)).await?;
+                self.serve_alias(name, collection_path, object_server)
+                    .await?;
 
                 Ok(())
             }
@@ -349,186 +867,156 @@ impl Service {
     }
 
     /// SearchItems method
+    ///
+    /// Items are looked up in the storage, if it indexes attributes itself, or else in the
+    /// attribute index, so no collection or item has to be visited.
     async fn search_items(
         &self,
         attributes: collections::HashMap<String, String>,
-        #[zbus(object_server)] object_server: &zbus::ObjectServer,
-    ) -> (
-        Vec<zvariant::OwnedObjectPath>,
-        Vec<zvariant::OwnedObjectPath>,
-    ) {
+        #[zbus(header)] header: zbus::message::Header<'_>,
+    ) -> Result<
+        (
+            Vec<zvariant::OwnedObjectPath>,
+            Vec<zvariant::OwnedObjectPath>,
+        ),
+        error::Error,
+    > {
+        let application = self.access.caller(&header).await?;
         let mut unlocked = Vec::new();
         let mut locked = Vec::new();
 
-        for collection_path in self.collections.iter() {
-            if let Ok(collection_interface) =
-                collection::Collection::get_interface_from_object_path(
-                    &collection_path.as_ref(),
-                    object_server,
-                )
-                .await
-            {
-                let collection = collection_interface.get().await;
+        let stored = self.storage.search_items(&attributes).await?;
+        let found: Vec<(zvariant::OwnedObjectPath, uuid::Uuid)> = {
+            let index = self.index.lock().expect("lock is not poisoned");
+            let found: Vec<_> = match stored {
+                Some(stored) => stored
+                    .into_iter()
+                    .map(|(collection_id, item_id)| {
+                        (item::object_path(&collection_id, &item_id), collection_id)
+                    })
+                    .collect(),
+                None => index
+                    .search(&attributes)
+                    .into_iter()
+                    .map(|(item_path, collection_id)| (item_path.clone(), collection_id))
+                    .collect(),
+            };
+            found
+                .into_iter()
+                // Items the application may not see are left out of the results altogether.
+                .filter(|(item_path, _)| {
+                    index
+                        .attributes(item_path)
+                        .is_some_and(|attributes| self.access.is_visible(&application, attributes))
+                })
+                .collect()
+        };
 
-                for item_path in collection.search_items(attributes.clone()).iter() {
-                    if let Ok(item_interface) = item::Item::get_interface_from_object_path(
-                        &item_path.as_ref(),
-                        object_server,
-                    )
-                    .await
-                    {
-                        let item = item_interface.get().await;
-                        if item.locked {
-                            locked.push(item.get_object_path());
-                        } else {
-                            unlocked.push(item.get_object_path());
-                        }
-                    }
-                }
+        for (item_path, collection_id) in found {
+            if self.storage.is_locked(&collection_id).await {
+                locked.push(item_path);
+            } else {
+                unlocked.push(item_path);
             }
         }
 
-        (unlocked, locked)
+        Ok((unlocked, locked))
     }
 
     /// Collections property
     #[zbus(property)]
-    fn collections(&self) -> Vec<zvariant::ObjectPath<'_>> {
-        self.get_children()
-            .iter()
-            .map(|path| path.as_ref())
-            .collect()
+    fn collections(&self) -> Vec<zvariant::OwnedObjectPath> {
+        self.get_children_paths()
     }
 
     /// CollectionChanged signal
     #[zbus(signal)]
     pub async fn collection_changed(
         emitter: &zbus::object_server::SignalEmitter<'_>,
+        collection: &zvariant::ObjectPath<'_>,
     ) -> zbus::Result<()>;
 
     /// CollectionCreated signal
     #[zbus(signal)]
-    async fn collection_created(
+    pub async fn collection_created(
         emitter: &zbus::object_server::SignalEmitter<'_>,
+        collection: &zvariant::ObjectPath<'_>,
     ) -> zbus::Result<()>;
 
     /// CollectionDeleted signal
     #[zbus(signal)]
     pub async fn collection_deleted(
         emitter: &zbus::object_server::SignalEmitter<'_>,
+        collection: &zvariant::ObjectPath<'_>,
     ) -> zbus::Result<()>;
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::server;
+    use crate::object::testing::{self, create_collection, open_plain_session, run_service_server};
+    use crate::storage::Storage as _;
+    use std::fs;
+    use std::path;
     use std::time;
     use uuid;
 
-    /// Run a `org.freedesktop.Secret.Service` server.
+    /// Call `Prompt` on the prompt at `prompt_path`, and wait for it to complete.
     ///
-    /// This coroutine is meant to be awaited at the beginning of each test
-    /// function that will be making calls to test the server.
-    /// It returns a handle that **must** be aborted once the test is done,
-    /// as otherwise the task **runs forever**.
-    async fn run_service_server() -> (String, tokio::task::JoinHandle<()>) {
-        let start_event = event_listener::Event::new();
-        let start_event_listener = start_event.listen();
-        let mut dbus_name = "org.freedesktop.secrets-test-".to_owned();
-        let dbus_id = uuid::Uuid::new_v4();
-        dbus_name.push_str(
-            dbus_id
-                .as_simple()
-                .encode_lower(&mut uuid::Uuid::encode_buffer()),
-        );
-
-        let cloned_dbus_name = dbus_name.clone();
-        let run_server_handle = tokio::spawn(async move {
-            let server = server::SecretServiceServer::new(&cloned_dbus_name, start_event)
-                .await
-                .unwrap();
-            server.run().await.unwrap();
-        });
+    /// Returns whether the prompt was dismissed, and its result. was dismissed, and its result.
+    async fn complete_prompt(
+        dbus_name: &str,
+        prompt_path: &zvariant::ObjectPath<'_>,
+    ) -> Result<(bool, zvariant::OwnedValue), error::Error> {
+        let connection = zbus::Connection::session().await?;
+        let proxy = zbus::Proxy::new(
+            &connection,
+            dbus_name,
+            prompt_path,
+            "org.freedesktop.Secret.Prompt",
+        )
+        .await?;
+        let mut completed = proxy.receive_signal("Completed").await?;
+
+        proxy.call_method("Prompt", &("")).await?;
+
+        let signal = completed.next().await.unwrap();
+        let body = signal.body();
+        let (dismissed, result): (bool, zvariant::OwnedValue) = body.deserialize()?;
+
+        Ok((dismissed, result))
+    }
 
-        if let Err(_) =
-            tokio::time::timeout(time::Duration::from_secs(10), start_event_listener).await
-        {
-            if run_server_handle.is_finished() {
-                run_server_handle.await.unwrap();
-                panic!("Server exited early without an error");
-            } else {
-                panic!("Took to long to start test dbus server");
-            }
-        }
+    /// Wait for the next signal in `signals`.
+    ///
+    /// Returns the object path that emitted the signal, and the object path it carries.
+    async fn next_signal(
+        signals: &mut zbus::proxy::SignalStream<'_>,
+    ) -> Result<(zvariant::OwnedObjectPath, zvariant::OwnedObjectPath), error::Error> {
+        let signal = tokio::time::timeout(time::Duration::from_secs(10), signals.next())
+            .await
+            .expect("Took too long to receive signal")
+            .unwrap();
+        let header = signal.header();
+        let emitted_from = header.path().unwrap().to_owned();
+        let body = signal.body();
+        let object_path: zvariant::OwnedObjectPath = body.deserialize()?;
 
-        (dbus_name, run_server_handle)
+        Ok((emitted_from.into(), object_path))
     }
 
-    async fn create_collection(
-        dbus_name: &str,
-        label: &str,
-    ) -> Result<zvariant::OwnedObjectPath, error::Error> {
+    #[tokio::test]
+    async fn test_create_collection() -> Result<(), error::Error> {
+        let (dbus_name, run_server_handle) = run_service_server(Default::default()).await;
+
         let connection = zbus::Connection::session().await?;
-        let collection_properties = collections::HashMap::from([(
-            "org.freedesktop.Secret.Collection.Label",
-            zvariant::Value::new(label),
-        )]);
+        let collection_properties = collection::CollectionReadWriteProperties {
+            label: "test-label".to_string(),
+        };
 
         let reply = connection
             .call_method(
-                Some(dbus_name),
-                "/org/freedesktop/secrets",
-                Some("org.freedesktop.Secret.Service"),
-                "CreateCollection",
-                &(collection_properties, ""),
-            )
-            .await
-            .unwrap();
-
-        let body = reply.body();
-        let (collection_object_path, _): (zvariant::ObjectPath<'_>, zvariant::ObjectPath<'_>) =
-            body.deserialize().unwrap();
-
-        Ok(collection_object_path.into())
-    }
-
-    async fn open_plain_session(
-        dbus_name: &str,
-    ) -> Result<zvariant::OwnedObjectPath, error::Error> {
-        let connection = zbus::Connection::session().await?;
-        let plain_key: Vec<u8> = Vec::new();
-
-        let reply = connection
-            .call_method(
-                Some(dbus_name),
-                "/org/freedesktop/secrets",
-                Some("org.freedesktop.Secret.Service"),
-                "OpenSession",
-                &("plain", zvariant::Value::from(plain_key)),
-            )
-            .await
-            .unwrap();
-
-        let body = reply.body();
-        let (_, session_path): (zvariant::Value, zvariant::ObjectPath<'_>) =
-            body.deserialize().unwrap();
-
-        Ok(session_path.into())
-    }
-
-    #[tokio::test]
-    async fn test_create_collection() -> Result<(), error::Error> {
-        let (dbus_name, run_server_handle) = run_service_server().await;
-
-        let connection = zbus::Connection::session().await?;
-        let collection_properties = collection::CollectionReadWriteProperties {
-            label: "test-label".to_string(),
-        };
-
-        let reply = connection
-            .call_method(
-                Some(dbus_name.as_str()),
+                Some(dbus_name.as_str()),
                 "/org/freedesktop/secrets",
                 Some("org.freedesktop.Secret.Service"),
                 "CreateCollection",
@@ -573,7 +1061,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_create_collection_returns_existing_object_path() -> Result<(), error::Error> {
-        let (dbus_name, run_server_handle) = run_service_server().await;
+        let (dbus_name, run_server_handle) = run_service_server(Default::default()).await;
 
         let connection = zbus::Connection::session().await?;
         let collection_properties = collections::HashMap::from([(
@@ -630,7 +1118,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_collections_property() -> Result<(), error::Error> {
-        let (dbus_name, run_server_handle) = run_service_server().await;
+        let (dbus_name, run_server_handle) = run_service_server(Default::default()).await;
 
         let connection = zbus::Connection::session().await?;
         let collection_properties = collections::HashMap::from([(
@@ -671,22 +1159,25 @@ mod tests {
         let collections_value = body.deserialize::<zvariant::Value>().unwrap();
         let collections: Vec<zvariant::ObjectPath<'_>> = collections_value.downcast().unwrap();
 
+        let default_collection_path = read_alias(&connection, &dbus_name, "default").await?;
+
         run_server_handle.abort();
         assert!(run_server_handle.await.unwrap_err().is_cancelled());
 
-        // Includes default collection besides the one we have created.
-        assert_eq!(collections.len(), 2);
+        // Includes default and session collections besides the one we have created.
+        assert_eq!(collections.len(), 3);
         assert!(collections.contains(&collection_object_path));
-        let default_collection_path =
-            zvariant::ObjectPath::from_str_unchecked("/org/freedesktop/secrets/aliases/default");
-        assert!(collections.contains(&default_collection_path));
+        assert!(collections.contains(&default_collection_path.as_ref()));
+        assert!(default_collection_path
+            .as_str()
+            .starts_with("/org/freedesktop/secrets/collection/"));
 
         Ok(())
     }
 
     #[tokio::test]
     async fn test_read_alias() -> Result<(), error::Error> {
-        let (dbus_name, run_server_handle) = run_service_server().await;
+        let (dbus_name, run_server_handle) = run_service_server(Default::default()).await;
 
         let connection = zbus::Connection::session().await?;
         let collection_properties = collections::HashMap::from([(
@@ -739,7 +1230,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_set_alias_updates_collection_alias() -> Result<(), error::Error> {
-        let (dbus_name, run_server_handle) = run_service_server().await;
+        let (dbus_name, run_server_handle) = run_service_server(Default::default()).await;
 
         let connection = zbus::Connection::session().await?;
         let collection_properties = collections::HashMap::from([(
@@ -801,7 +1292,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_open_session_plain() -> Result<(), error::Error> {
-        let (dbus_name, run_server_handle) = run_service_server().await;
+        let (dbus_name, run_server_handle) = run_service_server(Default::default()).await;
 
         let connection = zbus::Connection::session().await?;
         let plain_key: Vec<u8> = Vec::new();
@@ -832,12 +1323,11 @@ mod tests {
 
     #[tokio::test]
     async fn test_open_session_dh() -> Result<(), error::Error> {
-        let (dbus_name, run_server_handle) = run_service_server().await;
+        let (dbus_name, run_server_handle) = run_service_server(Default::default()).await;
 
         let connection = zbus::Connection::session().await?;
 
-        let secret = x25519_dalek::EphemeralSecret::random();
-        let public_key = x25519_dalek::PublicKey::from(&secret);
+        let key_pair = session::DhKeyPair::generate();
 
         let reply = connection
             .call_method(
@@ -847,7 +1337,7 @@ mod tests {
                 "OpenSession",
                 &(
                     "dh-ietf1024-sha256-aes128-cbc-pkcs7",
-                    zvariant::Value::from(public_key.as_bytes().to_vec()),
+                    zvariant::Value::from(key_pair.public_key_bytes()),
                 ),
             )
             .await
@@ -856,46 +1346,66 @@ mod tests {
         let body = reply.body();
         let (algorithm_output, session_path): (zvariant::Value, zvariant::ObjectPath<'_>) =
             body.deserialize().unwrap();
-        let server_public_key_bytes: Vec<u8> = algorithm_output.try_into()?;
-        let server_public_key: [u8; 32] = server_public_key_bytes.try_into().unwrap();
+        let server_public_key: Vec<u8> = algorithm_output.try_into()?;
+
+        key_pair.derive_aes_key(&server_public_key)?;
+
+        run_server_handle.abort();
+        assert!(run_server_handle.await.unwrap_err().is_cancelled());
+
+        assert_eq!(server_public_key.len(), 128);
+        assert!(session_path.starts_with("/org/freedesktop/secrets/session/"));
 
-        let shared_secret =
-            secret.diffie_hellman(&x25519_dalek::PublicKey::from(server_public_key));
-        let shared_secret_bytes = shared_secret.to_bytes();
+        Ok(())
+    }
 
-        let mut shared_secret_padded = vec![0u8; 128 - shared_secret_bytes.len()];
-        shared_secret_padded.extend_from_slice(&shared_secret_bytes);
+    #[tokio::test]
+    async fn test_open_session_dh_rejects_invalid_public_key() -> Result<(), error::Error> {
+        let (dbus_name, run_server_handle) = run_service_server(Default::default()).await;
 
-        let info = [];
-        let salt = None;
+        let connection = zbus::Connection::session().await?;
 
-        let (_, hk) = hkdf::Hkdf::<sha2::Sha256>::extract(salt, &shared_secret_padded);
-        let mut key = [0; 16];
-        hk.expand(&info, &mut key).unwrap();
+        let result = connection
+            .call_method(
+                Some(dbus_name),
+                "/org/freedesktop/secrets",
+                Some("org.freedesktop.Secret.Service"),
+                "OpenSession",
+                &(
+                    "dh-ietf1024-sha256-aes128-cbc-pkcs7",
+                    zvariant::Value::from(vec![1u8]),
+                ),
+            )
+            .await;
 
         run_server_handle.abort();
         assert!(run_server_handle.await.unwrap_err().is_cancelled());
 
-        assert!(session_path.starts_with("/org/freedesktop/secrets/session/"));
+        match result {
+            Err(zbus::Error::MethodError(name, _, _)) => {
+                assert_eq!(name.as_str(), "org.freedesktop.DBus.Error.InvalidArgs")
+            }
+            _ => panic!("Expected OpenSession to fail with InvalidArgs"),
+        }
 
         Ok(())
     }
 
     #[tokio::test]
     async fn test_search_items() -> Result<(), error::Error> {
-        let (dbus_name, run_server_handle) = run_service_server().await;
-        let session_path = open_plain_session(dbus_name.as_str()).await?;
+        let (dbus_name, run_server_handle) = run_service_server(Default::default()).await;
+        let connection = zbus::Connection::session().await?;
+        let session_path = open_plain_session(&connection, dbus_name.as_str()).await?;
         let collection_one_object_path =
             create_collection(dbus_name.as_str(), "test-collection-1").await?;
         let collection_two_object_path =
             create_collection(dbus_name.as_str(), "test-collection-2").await?;
 
-        let connection = zbus::Connection::session().await?;
         let item_attributes =
             collections::HashMap::from([("key-one".to_string(), "value-one".to_string())]);
 
         let mut created_items: Vec<zvariant::OwnedObjectPath> = Vec::new();
-        for collection_object_path in vec![collection_one_object_path, collection_two_object_path] {
+        for collection_object_path in [collection_one_object_path, collection_two_object_path] {
             let item_properties = item::ItemReadWriteProperties {
                 attributes: item_attributes.clone(),
                 label: "test-item-label".to_owned(),
@@ -956,14 +1466,122 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_search_items_partial_empty_and_non_matching_queries() -> Result<(), error::Error>
+    {
+        check_search_queries(Default::default()).await
+    }
+
+    #[tokio::test]
+    async fn test_search_items_in_sqlite() -> Result<(), error::Error> {
+        check_search_queries(testing::ServerOptions {
+            sqlite: true,
+            ..Default::default()
+        })
+        .await
+    }
+
+    /// Search items with partial, empty and non-matching queries in a server run with
+    /// `options`.
+    async fn check_search_queries(options: testing::ServerOptions) -> Result<(), error::Error> {
+        let (dbus_name, run_server_handle) = run_service_server(options).await;
+        let connection = zbus::Connection::session().await?;
+        let session_path = open_plain_session(&connection, dbus_name.as_str()).await?;
+        let collection_one_object_path =
+            create_collection(dbus_name.as_str(), "test-collection-1").await?;
+        let collection_two_object_path =
+            create_collection(dbus_name.as_str(), "test-collection-2").await?;
+
+        let mut created_items: Vec<zvariant::OwnedObjectPath> = Vec::new();
+        for (collection_object_path, user) in [
+            (collection_one_object_path, "bar"),
+            (collection_two_object_path, "baz"),
+        ] {
+            let item_properties = item::ItemReadWriteProperties {
+                attributes: collections::HashMap::from([
+                    ("service".to_string(), "foo".to_string()),
+                    ("user".to_string(), user.to_string()),
+                ]),
+                label: "test-item-label".to_owned(),
+            };
+
+            let secret = secret::Secret {
+                session: session_path.clone(),
+                value: "a-very-important-secret".into(),
+                parameters: Vec::new(),
+                content_type: "text/plain; charset=utf8".to_string(),
+            };
+            let reply = connection
+                .call_method(
+                    Some(dbus_name.as_str()),
+                    collection_object_path.as_str(),
+                    Some("org.freedesktop.Secret.Collection"),
+                    "CreateItem",
+                    &(item_properties, secret, false),
+                )
+                .await
+                .unwrap();
+
+            let body = reply.body();
+            let (item_object_path, _): (zvariant::ObjectPath<'_>, zvariant::ObjectPath<'_>) =
+                body.deserialize().unwrap();
+
+            created_items.push(item_object_path.into());
+        }
+
+        let mut results = Vec::new();
+        for query in [
+            collections::HashMap::from([("service", "foo")]),
+            collections::HashMap::from([("service", "foo"), ("user", "baz")]),
+            collections::HashMap::new(),
+            collections::HashMap::from([("service", "other")]),
+            collections::HashMap::from([("user", "bar"), ("extra", "value")]),
+        ] {
+            let reply = connection
+                .call_method(
+                    Some(dbus_name.as_str()),
+                    "/org/freedesktop/secrets",
+                    Some("org.freedesktop.Secret.Service"),
+                    "SearchItems",
+                    &(query),
+                )
+                .await
+                .unwrap();
+
+            let body = reply.body();
+            let (unlocked, locked): (
+                Vec<zvariant::OwnedObjectPath>,
+                Vec<zvariant::OwnedObjectPath>,
+            ) = body.deserialize().unwrap();
+
+            assert!(locked.is_empty());
+            results.push(unlocked.into_iter().collect::<collections::HashSet<_>>());
+        }
+
+        run_server_handle.abort();
+        assert!(run_server_handle.await.unwrap_err().is_cancelled());
+
+        let all_items: collections::HashSet<_> = created_items.iter().cloned().collect();
+        assert_eq!(results[0], all_items);
+        assert_eq!(
+            results[1],
+            collections::HashSet::from([created_items[1].clone()])
+        );
+        assert_eq!(results[2], all_items);
+        assert!(results[3].is_empty());
+        assert!(results[4].is_empty());
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_lock_unlock() -> Result<(), error::Error> {
-        let (dbus_name, run_server_handle) = run_service_server().await;
-        let session_path = open_plain_session(dbus_name.as_str()).await?;
+        let (dbus_name, run_server_handle) = run_service_server(Default::default()).await;
+        let connection = zbus::Connection::session().await?;
+        let session_path = open_plain_session(&connection, dbus_name.as_str()).await?;
         let collection_object_path =
             create_collection(dbus_name.as_str(), "test-collection-1").await?;
 
-        let connection = zbus::Connection::session().await?;
         let item_attributes =
             collections::HashMap::from([("key-one".to_string(), "value-one".to_string())]);
 
@@ -1114,4 +1732,2244 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_collections_and_items_persist_across_restarts() -> Result<(), error::Error> {
+        let data_dir = tempfile::tempdir()?;
+        let (dbus_name, run_server_handle) = run_service_server(testing::ServerOptions {
+            data_dir: Some(data_dir.path().to_owned()),
+            ..Default::default()
+        })
+        .await;
+        let connection = zbus::Connection::session().await?;
+        let session_path = open_plain_session(&connection, dbus_name.as_str()).await?;
+
+        let collection_properties = collections::HashMap::from([(
+            "org.freedesktop.Secret.Collection.Label",
+            zvariant::Value::new("test-label"),
+        )]);
+        let collection_alias = "persisted-alias".to_owned();
+
+        let reply = connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                "/org/freedesktop/secrets",
+                Some("org.freedesktop.Secret.Service"),
+                "CreateCollection",
+                &(collection_properties, &collection_alias),
+            )
+            .await
+            .unwrap();
+
+        let body = reply.body();
+        let (collection_object_path, _): (zvariant::ObjectPath<'_>, zvariant::ObjectPath<'_>) =
+            body.deserialize().unwrap();
+
+        let item_attributes =
+            collections::HashMap::from([("key-one".to_string(), "value-one".to_string())]);
+        let item_properties = item::ItemReadWriteProperties {
+            attributes: item_attributes.clone(),
+            label: "test-item-label".to_owned(),
+        };
+        let secret = secret::Secret {
+            session: session_path.clone(),
+            value: "a-very-important-secret".into(),
+            parameters: Vec::new(),
+            content_type: "text/plain; charset=utf8".to_string(),
+        };
+
+        let reply = connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                &collection_object_path,
+                Some("org.freedesktop.Secret.Collection"),
+                "CreateItem",
+                &(item_properties, secret, false),
+            )
+            .await
+            .unwrap();
+
+        let body = reply.body();
+        let (item_object_path, _): (zvariant::ObjectPath<'_>, zvariant::ObjectPath<'_>) =
+            body.deserialize().unwrap();
+
+        run_server_handle.abort();
+        assert!(run_server_handle.await.unwrap_err().is_cancelled());
+
+        let (dbus_name, run_server_handle) = run_service_server(testing::ServerOptions {
+            data_dir: Some(data_dir.path().to_owned()),
+            ..Default::default()
+        })
+        .await;
+        let session_path = open_plain_session(&connection, dbus_name.as_str()).await?;
+
+        let reply = connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                "/org/freedesktop/secrets",
+                Some("org.freedesktop.Secret.Service"),
+                "ReadAlias",
+                &(&collection_alias),
+            )
+            .await
+            .unwrap();
+
+        let body = reply.body();
+        let loaded_collection_object_path: zvariant::ObjectPath<'_> = body.deserialize().unwrap();
+
+        let reply = connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                "/org/freedesktop/secrets",
+                Some("org.freedesktop.Secret.Service"),
+                "SearchItems",
+                &(item_attributes),
+            )
+            .await
+            .unwrap();
+
+        let body = reply.body();
+        let (unlocked, _): (
+            Vec<zvariant::OwnedObjectPath>,
+            Vec<zvariant::OwnedObjectPath>,
+        ) = body.deserialize().unwrap();
+
+        let reply = connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                &item_object_path,
+                Some("org.freedesktop.Secret.Item"),
+                "GetSecret",
+                &(session_path.as_ref()),
+            )
+            .await
+            .unwrap();
+
+        let body = reply.body();
+        let loaded_secret = body.deserialize::<secret::Secret>().unwrap();
+
+        run_server_handle.abort();
+        assert!(run_server_handle.await.unwrap_err().is_cancelled());
+
+        assert_eq!(loaded_collection_object_path, collection_object_path);
+        assert_eq!(unlocked, vec![item_object_path.into()]);
+        assert_eq!(loaded_secret.value, b"a-very-important-secret");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unlock_collection_with_password() -> Result<(), error::Error> {
+        let data_dir = tempfile::tempdir()?;
+        let password_path = data_dir.path().join("password");
+        fs::write(&password_path, "correct-password\n")?;
+        let prompter = prompt::Prompter::new(vec![
+            "sh".to_owned(),
+            "-c".to_owned(),
+            format!("cat '{}'", password_path.display()),
+        ]);
+
+        let (dbus_name, run_server_handle) = run_service_server(testing::ServerOptions {
+            data_dir: Some(data_dir.path().join("storage")),
+            prompter,
+            ..Default::default()
+        })
+        .await;
+        let connection = zbus::Connection::session().await?;
+        let session_path = open_plain_session(&connection, dbus_name.as_str()).await?;
+
+        let collection_properties = collections::HashMap::from([(
+            "org.freedesktop.Secret.Collection.Label",
+            zvariant::Value::new("test-label"),
+        )]);
+
+        let reply = connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                "/org/freedesktop/secrets",
+                Some("org.freedesktop.Secret.Service"),
+                "CreateCollection",
+                &(collection_properties, ""),
+            )
+            .await
+            .unwrap();
+
+        let body = reply.body();
+        let (no_collection_path, prompt_path): (
+            zvariant::ObjectPath<'_>,
+            zvariant::ObjectPath<'_>,
+        ) = body.deserialize().unwrap();
+
+        assert_eq!(no_collection_path.as_str(), "/");
+
+        let (dismissed, result) = complete_prompt(&dbus_name, &prompt_path).await?;
+        let collection_object_path: zvariant::OwnedObjectPath = result.try_into()?;
+
+        assert!(!dismissed);
+
+        let item_properties = item::ItemReadWriteProperties {
+            attributes: collections::HashMap::new(),
+            label: "test-item-label".to_owned(),
+        };
+        let secret = secret::Secret {
+            session: session_path.clone(),
+            value: "a-very-important-secret".into(),
+            parameters: Vec::new(),
+            content_type: "text/plain; charset=utf8".to_string(),
+        };
+        let reply = connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                &collection_object_path,
+                Some("org.freedesktop.Secret.Collection"),
+                "CreateItem",
+                &(item_properties, secret, false),
+            )
+            .await
+            .unwrap();
+
+        let body = reply.body();
+        let (item_object_path, _): (zvariant::ObjectPath<'_>, zvariant::ObjectPath<'_>) =
+            body.deserialize().unwrap();
+
+        connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                "/org/freedesktop/secrets",
+                Some("org.freedesktop.Secret.Service"),
+                "Lock",
+                &(vec![&collection_object_path]),
+            )
+            .await
+            .unwrap();
+
+        let locked_get_secret = connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                &item_object_path,
+                Some("org.freedesktop.Secret.Item"),
+                "GetSecret",
+                &(session_path.as_ref()),
+            )
+            .await;
+
+        match locked_get_secret {
+            Err(zbus::Error::MethodError(name, _, _)) => {
+                assert_eq!(name.as_str(), "org.freedesktop.Secret.Error.IsLocked")
+            }
+            _ => panic!("Expected GetSecret to fail with IsLocked"),
+        }
+
+        // A wrong password dismisses the prompt, and leaves the collection locked.
+        fs::write(&password_path, "wrong-password\n")?;
+
+        let reply = connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                "/org/freedesktop/secrets",
+                Some("org.freedesktop.Secret.Service"),
+                "Unlock",
+                &(vec![&collection_object_path]),
+            )
+            .await
+            .unwrap();
+
+        let body = reply.body();
+        let (unlocked, prompt_path): (Vec<zvariant::OwnedObjectPath>, zvariant::ObjectPath<'_>) =
+            body.deserialize().unwrap();
+
+        assert!(unlocked.is_empty());
+        assert_ne!(prompt_path.as_str(), "/");
+
+        let (dismissed, _) = complete_prompt(&dbus_name, &prompt_path).await?;
+
+        assert!(dismissed);
+
+        let reply = connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                &collection_object_path,
+                Some("org.freedesktop.DBus.Properties"),
+                "Get",
+                &(
+                    "org.freedesktop.Secret.Collection".to_string(),
+                    "Locked".to_string(),
+                ),
+            )
+            .await
+            .unwrap();
+
+        let body = reply.body();
+        let collection_value = body.deserialize::<zvariant::Value>().unwrap();
+        let collection_locked: bool = collection_value.downcast().unwrap();
+
+        assert!(collection_locked);
+
+        fs::write(&password_path, "correct-password\n")?;
+
+        let reply = connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                "/org/freedesktop/secrets",
+                Some("org.freedesktop.Secret.Service"),
+                "Unlock",
+                &(vec![&collection_object_path]),
+            )
+            .await
+            .unwrap();
+
+        let body = reply.body();
+        let (_, prompt_path): (Vec<zvariant::OwnedObjectPath>, zvariant::ObjectPath<'_>) =
+            body.deserialize().unwrap();
+
+        let (dismissed, result) = complete_prompt(&dbus_name, &prompt_path).await?;
+        let prompt_unlocked: Vec<zvariant::OwnedObjectPath> = result.try_into()?;
+
+        assert!(!dismissed);
+        assert_eq!(prompt_unlocked, vec![collection_object_path.clone()]);
+
+        let reply = connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                &item_object_path,
+                Some("org.freedesktop.Secret.Item"),
+                "GetSecret",
+                &(session_path.as_ref()),
+            )
+            .await
+            .unwrap();
+
+        let body = reply.body();
+        let unlocked_secret = body.deserialize::<secret::Secret>().unwrap();
+
+        run_server_handle.abort();
+        assert!(run_server_handle.await.unwrap_err().is_cancelled());
+
+        assert_eq!(unlocked_secret.value, b"a-very-important-secret");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_signals_carry_object_paths() -> Result<(), error::Error> {
+        let (dbus_name, run_server_handle) = run_service_server(Default::default()).await;
+        let connection = zbus::Connection::session().await?;
+        let session_path = open_plain_session(&connection, dbus_name.as_str()).await?;
+        let service_path = zvariant::OwnedObjectPath::from(
+            zvariant::ObjectPath::from_static_str_unchecked("/org/freedesktop/secrets"),
+        );
+
+        let service_proxy = zbus::Proxy::new(
+            &connection,
+            dbus_name.as_str(),
+            "/org/freedesktop/secrets",
+            "org.freedesktop.Secret.Service",
+        )
+        .await?;
+        let mut collection_created = service_proxy.receive_signal("CollectionCreated").await?;
+        let mut collection_changed = service_proxy.receive_signal("CollectionChanged").await?;
+        let mut collection_deleted = service_proxy.receive_signal("CollectionDeleted").await?;
+
+        let collection_object_path = create_collection(dbus_name.as_str(), "test-label").await?;
+
+        assert_eq!(
+            next_signal(&mut collection_created).await?,
+            (service_path.clone(), collection_object_path.clone())
+        );
+
+        let collection_proxy = zbus::Proxy::new(
+            &connection,
+            dbus_name.as_str(),
+            collection_object_path.as_ref(),
+            "org.freedesktop.Secret.Collection",
+        )
+        .await?;
+        let mut item_created = collection_proxy.receive_signal("ItemCreated").await?;
+        let mut item_changed = collection_proxy.receive_signal("ItemChanged").await?;
+        let mut item_deleted = collection_proxy.receive_signal("ItemDeleted").await?;
+
+        let item_properties = item::ItemReadWriteProperties {
+            attributes: collections::HashMap::new(),
+            label: "test-item-label".to_owned(),
+        };
+        let secret = secret::Secret {
+            session: session_path.clone(),
+            value: "a-very-important-secret".into(),
+            parameters: Vec::new(),
+            content_type: "text/plain; charset=utf8".to_string(),
+        };
+        let reply = connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                &collection_object_path,
+                Some("org.freedesktop.Secret.Collection"),
+                "CreateItem",
+                &(item_properties, &secret, false),
+            )
+            .await
+            .unwrap();
+
+        let body = reply.body();
+        let (item_object_path, _): (zvariant::OwnedObjectPath, zvariant::ObjectPath<'_>) =
+            body.deserialize().unwrap();
+
+        assert_eq!(
+            next_signal(&mut item_created).await?,
+            (collection_object_path.clone(), item_object_path.clone())
+        );
+        assert_eq!(
+            next_signal(&mut collection_changed).await?,
+            (service_path.clone(), collection_object_path.clone())
+        );
+
+        connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                &item_object_path,
+                Some("org.freedesktop.Secret.Item"),
+                "SetSecret",
+                &(secret),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            next_signal(&mut item_changed).await?,
+            (collection_object_path.clone(), item_object_path.clone())
+        );
+
+        // Signals about items are emitted by their collection, even when locking through `Service`.
+        connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                "/org/freedesktop/secrets",
+                Some("org.freedesktop.Secret.Service"),
+                "Lock",
+                &(vec![&item_object_path]),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            next_signal(&mut item_changed).await?,
+            (collection_object_path.clone(), item_object_path.clone())
+        );
+
+        connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                &item_object_path,
+                Some("org.freedesktop.Secret.Item"),
+                "Delete",
+                &(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            next_signal(&mut item_deleted).await?,
+            (collection_object_path.clone(), item_object_path.clone())
+        );
+
+        connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                &collection_object_path,
+                Some("org.freedesktop.Secret.Collection"),
+                "Delete",
+                &(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            next_signal(&mut collection_deleted).await?,
+            (service_path, collection_object_path)
+        );
+
+        run_server_handle.abort();
+        assert!(run_server_handle.await.unwrap_err().is_cancelled());
+
+        Ok(())
+    }
+
+    /// Call `method` with `body` and return the name of the error it failed with.
+    async fn call_method_error<B>(
+        connection: &zbus::Connection,
+        dbus_name: &str,
+        object_path: &zvariant::ObjectPath<'_>,
+        interface: &str,
+        method: &str,
+        body: &B,
+    ) -> String
+    where
+        B: serde::Serialize + zvariant::DynamicType,
+    {
+        match connection
+            .call_method(Some(dbus_name), object_path, Some(interface), method, body)
+            .await
+        {
+            Err(zbus::Error::MethodError(name, _, _)) => name.as_str().to_owned(),
+            Err(err) => panic!("Expected {method} to fail with a method error, got: {err}"),
+            Ok(_) => panic!("Expected {method} to fail"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failures_reply_with_precise_error_names() -> Result<(), error::Error> {
+        let (dbus_name, run_server_handle) = run_service_server(Default::default()).await;
+        let connection = zbus::Connection::session().await?;
+        let service_path = zvariant::ObjectPath::from_static_str_unchecked(SERVICE_PATH);
+        let service_interface = "org.freedesktop.Secret.Service";
+
+        assert_eq!(
+            call_method_error(
+                &connection,
+                &dbus_name,
+                &service_path,
+                service_interface,
+                "OpenSession",
+                &("rot13", zvariant::Value::from("")),
+            )
+            .await,
+            "org.freedesktop.DBus.Error.NotSupported"
+        );
+        // The input has the right signature, but not the type the algorithm expects.
+        assert_eq!(
+            call_method_error(
+                &connection,
+                &dbus_name,
+                &service_path,
+                service_interface,
+                "OpenSession",
+                &("plain", zvariant::Value::from(42u32)),
+            )
+            .await,
+            "org.freedesktop.DBus.Error.InvalidArgs"
+        );
+        assert_eq!(
+            call_method_error(
+                &connection,
+                &dbus_name,
+                &service_path,
+                service_interface,
+                "GetSecrets",
+                &(
+                    Vec::<zvariant::ObjectPath<'_>>::new(),
+                    zvariant::ObjectPath::from_static_str_unchecked(
+                        "/org/freedesktop/secrets/session/missing"
+                    ),
+                ),
+            )
+            .await,
+            "org.freedesktop.Secret.Error.NoSession"
+        );
+        assert_eq!(
+            call_method_error(
+                &connection,
+                &dbus_name,
+                &service_path,
+                service_interface,
+                "SetAlias",
+                &(
+                    "test-alias",
+                    zvariant::ObjectPath::from_static_str_unchecked(
+                        "/org/freedesktop/secrets/collection/missing"
+                    ),
+                ),
+            )
+            .await,
+            "org.freedesktop.Secret.Error.NoSuchObject"
+        );
+        assert_eq!(
+            call_method_error(
+                &connection,
+                &dbus_name,
+                &service_path,
+                service_interface,
+                "SetAlias",
+                &(
+                    "missing-alias",
+                    zvariant::ObjectPath::from_static_str_unchecked("/"),
+                ),
+            )
+            .await,
+            "org.freedesktop.Secret.Error.NoSuchObject"
+        );
+
+        run_server_handle.abort();
+        assert!(run_server_handle.await.unwrap_err().is_cancelled());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sessions_cannot_be_used_by_other_clients() -> Result<(), error::Error> {
+        let (dbus_name, run_server_handle) = run_service_server(Default::default()).await;
+        let connection = zbus::Connection::session().await?;
+        let other_connection = zbus::Connection::session().await?;
+        let session_path = open_plain_session(&connection, dbus_name.as_str()).await?;
+        let collection_object_path = create_collection(dbus_name.as_str(), "test-label").await?;
+
+        let item_properties = item::ItemReadWriteProperties {
+            attributes: collections::HashMap::new(),
+            label: "test-item-label".to_owned(),
+        };
+        let secret = secret::Secret {
+            session: session_path.clone(),
+            value: "a-very-important-secret".into(),
+            parameters: Vec::new(),
+            content_type: "text/plain; charset=utf8".to_string(),
+        };
+        let reply = connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                &collection_object_path,
+                Some("org.freedesktop.Secret.Collection"),
+                "CreateItem",
+                &(&item_properties, &secret, false),
+            )
+            .await
+            .unwrap();
+        let body = reply.body();
+        let (item_object_path, _): (zvariant::OwnedObjectPath, zvariant::ObjectPath<'_>) =
+            body.deserialize().unwrap();
+
+        let no_session = "org.freedesktop.Secret.Error.NoSession";
+        assert_eq!(
+            call_method_error(
+                &other_connection,
+                &dbus_name,
+                &collection_object_path,
+                "org.freedesktop.Secret.Collection",
+                "CreateItem",
+                &(&item_properties, &secret, false),
+            )
+            .await,
+            no_session
+        );
+        assert_eq!(
+            call_method_error(
+                &other_connection,
+                &dbus_name,
+                &item_object_path,
+                "org.freedesktop.Secret.Item",
+                "GetSecret",
+                &(session_path.as_ref()),
+            )
+            .await,
+            no_session
+        );
+        assert_eq!(
+            call_method_error(
+                &other_connection,
+                &dbus_name,
+                &item_object_path,
+                "org.freedesktop.Secret.Item",
+                "SetSecret",
+                &(&secret),
+            )
+            .await,
+            no_session
+        );
+        assert_eq!(
+            call_method_error(
+                &other_connection,
+                &dbus_name,
+                &zvariant::ObjectPath::from_static_str_unchecked("/org/freedesktop/secrets"),
+                "org.freedesktop.Secret.Service",
+                "GetSecrets",
+                &(vec![&item_object_path], session_path.as_ref()),
+            )
+            .await,
+            no_session
+        );
+        assert_eq!(
+            call_method_error(
+                &other_connection,
+                &dbus_name,
+                &session_path,
+                "org.freedesktop.Secret.Session",
+                "Close",
+                &(),
+            )
+            .await,
+            no_session
+        );
+
+        // The client that opened the session can still use it.
+        let reply = connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                &item_object_path,
+                Some("org.freedesktop.Secret.Item"),
+                "GetSecret",
+                &(session_path.as_ref()),
+            )
+            .await
+            .unwrap();
+        let body = reply.body();
+        let returned_secret: secret::Secret = body.deserialize().unwrap();
+        assert_eq!(returned_secret.value, b"a-very-important-secret");
+
+        run_server_handle.abort();
+        assert!(run_server_handle.await.unwrap_err().is_cancelled());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sessions_are_closed_when_client_disconnects() -> Result<(), error::Error> {
+        let (dbus_name, run_server_handle) = run_service_server(Default::default()).await;
+        let connection = zbus::Connection::session().await?;
+        let other_connection = zbus::Connection::session().await?;
+        let session_path = open_plain_session(&connection, dbus_name.as_str()).await?;
+        let other_session_path = open_plain_session(&other_connection, dbus_name.as_str()).await?;
+
+        connection.close().await?;
+
+        // Sessions of other clients are not found, so once the session is removed
+        // closing it fails with `UnknownObject` instead of `NoSession`.
+        let mut error_name = String::new();
+        for _ in 0..50 {
+            error_name = call_method_error(
+                &other_connection,
+                &dbus_name,
+                &session_path,
+                "org.freedesktop.Secret.Session",
+                "Close",
+                &(),
+            )
+            .await;
+
+            if error_name != "org.freedesktop.Secret.Error.NoSession" {
+                break;
+            }
+            tokio::time::sleep(time::Duration::from_millis(100)).await;
+        }
+        assert_eq!(error_name, "org.freedesktop.DBus.Error.UnknownObject");
+
+        // Sessions of clients still connected are left alone.
+        other_connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                &other_session_path,
+                Some("org.freedesktop.Secret.Session"),
+                "Close",
+                &(),
+            )
+            .await
+            .unwrap();
+
+        run_server_handle.abort();
+        assert!(run_server_handle.await.unwrap_err().is_cancelled());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_locked_collection_locks_its_items() -> Result<(), error::Error> {
+        let (dbus_name, run_server_handle) = run_service_server(Default::default()).await;
+        let connection = zbus::Connection::session().await?;
+        let session_path = open_plain_session(&connection, dbus_name.as_str()).await?;
+        let collection_object_path = create_collection(dbus_name.as_str(), "test-label").await?;
+
+        let item_properties = item::ItemReadWriteProperties {
+            attributes: collections::HashMap::new(),
+            label: "test-item-label".to_owned(),
+        };
+        let secret = secret::Secret {
+            session: session_path.clone(),
+            value: "a-very-important-secret".into(),
+            parameters: Vec::new(),
+            content_type: "text/plain; charset=utf8".to_string(),
+        };
+        let reply = connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                &collection_object_path,
+                Some("org.freedesktop.Secret.Collection"),
+                "CreateItem",
+                &(&item_properties, &secret, false),
+            )
+            .await
+            .unwrap();
+        let body = reply.body();
+        let (item_object_path, _): (zvariant::OwnedObjectPath, zvariant::ObjectPath<'_>) =
+            body.deserialize().unwrap();
+
+        connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                "/org/freedesktop/secrets",
+                Some("org.freedesktop.Secret.Service"),
+                "Lock",
+                &(vec![&collection_object_path]),
+            )
+            .await
+            .unwrap();
+
+        // Don't cache properties, so they are read as soon as each call returns.
+        let item_proxy: zbus::Proxy<'_> = zbus::proxy::Builder::new(&connection)
+            .destination(dbus_name.as_str())?
+            .path(item_object_path.as_ref())?
+            .interface("org.freedesktop.Secret.Item")?
+            .cache_properties(zbus::proxy::CacheProperties::No)
+            .build()
+            .await?;
+        let collection_proxy: zbus::Proxy<'_> = zbus::proxy::Builder::new(&connection)
+            .destination(dbus_name.as_str())?
+            .path(collection_object_path.as_ref())?
+            .interface("org.freedesktop.Secret.Collection")?
+            .cache_properties(zbus::proxy::CacheProperties::No)
+            .build()
+            .await?;
+        assert!(item_proxy.get_property::<bool>("Locked").await?);
+
+        let is_locked = "org.freedesktop.Secret.Error.IsLocked";
+        assert_eq!(
+            call_method_error(
+                &connection,
+                &dbus_name,
+                &item_object_path,
+                "org.freedesktop.Secret.Item",
+                "GetSecret",
+                &(session_path.as_ref()),
+            )
+            .await,
+            is_locked
+        );
+        assert_eq!(
+            call_method_error(
+                &connection,
+                &dbus_name,
+                &item_object_path,
+                "org.freedesktop.Secret.Item",
+                "SetSecret",
+                &(&secret),
+            )
+            .await,
+            is_locked
+        );
+        assert_eq!(
+            call_method_error(
+                &connection,
+                &dbus_name,
+                &collection_object_path,
+                "org.freedesktop.Secret.Collection",
+                "CreateItem",
+                &(&item_properties, &secret, false),
+            )
+            .await,
+            is_locked
+        );
+
+        // zbus replies to property writes itself, so they can't fail with `IsLocked`,
+        // which is only named at the start of the message.
+        for (object_path, interface) in [
+            (&item_object_path, "org.freedesktop.Secret.Item"),
+            (&collection_object_path, "org.freedesktop.Secret.Collection"),
+        ] {
+            match connection
+                .call_method(
+                    Some(dbus_name.as_str()),
+                    object_path,
+                    Some("org.freedesktop.DBus.Properties"),
+                    "Set",
+                    &(interface, "Label", zvariant::Value::from("new-label")),
+                )
+                .await
+            {
+                Err(zbus::Error::MethodError(name, Some(message), _)) => {
+                    assert_eq!(name.as_str(), "org.freedesktop.DBus.Error.Failed");
+                    assert!(message.starts_with(is_locked), "{message}");
+                }
+                result => panic!("Expected writing the label to fail, got: {result:?}"),
+            }
+        }
+        assert_eq!(
+            item_proxy.get_property::<String>("Label").await?,
+            "test-item-label"
+        );
+        assert_eq!(
+            collection_proxy.get_property::<String>("Label").await?,
+            "test-label"
+        );
+
+        let reply = connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                "/org/freedesktop/secrets",
+                Some("org.freedesktop.Secret.Service"),
+                "GetSecrets",
+                &(vec![&item_object_path], session_path.as_ref()),
+            )
+            .await
+            .unwrap();
+        let body = reply.body();
+        let secrets: collections::HashMap<zvariant::OwnedObjectPath, secret::Secret> =
+            body.deserialize().unwrap();
+        assert!(secrets.is_empty());
+
+        // Unlocking the collection unlocks its items too.
+        connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                "/org/freedesktop/secrets",
+                Some("org.freedesktop.Secret.Service"),
+                "Unlock",
+                &(vec![&collection_object_path]),
+            )
+            .await
+            .unwrap();
+
+        assert!(!item_proxy.get_property::<bool>("Locked").await?);
+        item_proxy.set_property("Label", "new-label").await.unwrap();
+        let reply = connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                &item_object_path,
+                Some("org.freedesktop.Secret.Item"),
+                "GetSecret",
+                &(session_path.as_ref()),
+            )
+            .await
+            .unwrap();
+        let body = reply.body();
+        let returned_secret: secret::Secret = body.deserialize().unwrap();
+        assert_eq!(returned_secret.value, b"a-very-important-secret");
+
+        run_server_handle.abort();
+        assert!(run_server_handle.await.unwrap_err().is_cancelled());
+
+        Ok(())
+    }
+
+    /// Call `ReadAlias` for `alias`.
+    async fn read_alias(
+        connection: &zbus::Connection,
+        dbus_name: &str,
+        alias: &str,
+    ) -> Result<zvariant::OwnedObjectPath, error::Error> {
+        let reply = connection
+            .call_method(
+                Some(dbus_name),
+                "/org/freedesktop/secrets",
+                Some("org.freedesktop.Secret.Service"),
+                "ReadAlias",
+                &(alias),
+            )
+            .await?;
+
+        let body = reply.body();
+        Ok(body.deserialize()?)
+    }
+
+    #[tokio::test]
+    async fn test_delete_populated_aliased_collection() -> Result<(), error::Error> {
+        let data_dir = tempfile::tempdir()?;
+        let (dbus_name, run_server_handle) = run_service_server(testing::ServerOptions {
+            data_dir: Some(data_dir.path().to_owned()),
+            ..Default::default()
+        })
+        .await;
+        let connection = zbus::Connection::session().await?;
+        let session_path = open_plain_session(&connection, dbus_name.as_str()).await?;
+
+        let collection_properties = collections::HashMap::from([(
+            "org.freedesktop.Secret.Collection.Label",
+            zvariant::Value::new("test-label"),
+        )]);
+        let reply = connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                "/org/freedesktop/secrets",
+                Some("org.freedesktop.Secret.Service"),
+                "CreateCollection",
+                &(collection_properties, "doomed-alias"),
+            )
+            .await
+            .unwrap();
+        let body = reply.body();
+        let (collection_object_path, _): (zvariant::OwnedObjectPath, zvariant::ObjectPath<'_>) =
+            body.deserialize().unwrap();
+
+        let mut item_object_paths = Vec::new();
+        for user in ["bar", "baz"] {
+            let item_properties = item::ItemReadWriteProperties {
+                attributes: collections::HashMap::from([
+                    ("service".to_owned(), "doomed".to_owned()),
+                    ("user".to_owned(), user.to_owned()),
+                ]),
+                label: format!("test-item-{user}"),
+            };
+            let secret = secret::Secret {
+                session: session_path.clone(),
+                value: "a-very-important-secret".into(),
+                parameters: Vec::new(),
+                content_type: "text/plain; charset=utf8".to_string(),
+            };
+            let reply = connection
+                .call_method(
+                    Some(dbus_name.as_str()),
+                    &collection_object_path,
+                    Some("org.freedesktop.Secret.Collection"),
+                    "CreateItem",
+                    &(item_properties, secret, false),
+                )
+                .await
+                .unwrap();
+            let body = reply.body();
+            let (item_object_path, _): (zvariant::OwnedObjectPath, zvariant::ObjectPath<'_>) =
+                body.deserialize().unwrap();
+            item_object_paths.push(item_object_path);
+        }
+
+        let collection_proxy = zbus::Proxy::new(
+            &connection,
+            dbus_name.as_str(),
+            collection_object_path.as_ref(),
+            "org.freedesktop.Secret.Collection",
+        )
+        .await?;
+        let service_proxy = zbus::Proxy::new(
+            &connection,
+            dbus_name.as_str(),
+            "/org/freedesktop/secrets",
+            "org.freedesktop.Secret.Service",
+        )
+        .await?;
+        let mut item_deleted = collection_proxy.receive_signal("ItemDeleted").await?;
+        let mut collection_deleted = service_proxy.receive_signal("CollectionDeleted").await?;
+
+        let reply = tokio::time::timeout(
+            time::Duration::from_secs(10),
+            collection_proxy.call_method("Delete", &()),
+        )
+        .await
+        .expect("Deleting a collection with items should not hang")?;
+        let body = reply.body();
+        let prompt: zvariant::OwnedObjectPath = body.deserialize()?;
+        assert_eq!(prompt.as_str(), "/");
+
+        let mut deleted_items = collections::HashSet::new();
+        for _ in 0..item_object_paths.len() {
+            let (emitted_from, item_object_path) = next_signal(&mut item_deleted).await?;
+            assert_eq!(emitted_from, collection_object_path);
+            deleted_items.insert(item_object_path);
+        }
+        assert_eq!(deleted_items, item_object_paths.iter().cloned().collect());
+        assert_eq!(
+            next_signal(&mut collection_deleted).await?.1,
+            collection_object_path
+        );
+
+        assert_eq!(
+            read_alias(&connection, &dbus_name, "doomed-alias")
+                .await?
+                .as_str(),
+            "/"
+        );
+        let service_collections: Vec<zvariant::OwnedObjectPath> =
+            service_proxy.get_property("Collections").await?;
+        assert!(!service_collections.contains(&collection_object_path));
+        assert_eq!(
+            call_method_error(
+                &connection,
+                &dbus_name,
+                &alias::object_path("doomed-alias").as_ref(),
+                "org.freedesktop.DBus.Properties",
+                "Get",
+                &("org.freedesktop.Secret.Collection", "Label"),
+            )
+            .await,
+            "org.freedesktop.DBus.Error.UnknownObject"
+        );
+
+        for item_object_path in item_object_paths.iter() {
+            assert_eq!(
+                call_method_error(
+                    &connection,
+                    &dbus_name,
+                    item_object_path,
+                    "org.freedesktop.Secret.Item",
+                    "GetSecret",
+                    &(session_path.as_ref()),
+                )
+                .await,
+                "org.freedesktop.DBus.Error.UnknownObject"
+            );
+        }
+
+        let reply = connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                "/org/freedesktop/secrets",
+                Some("org.freedesktop.Secret.Service"),
+                "SearchItems",
+                &(collections::HashMap::from([("service", "doomed")])),
+            )
+            .await
+            .unwrap();
+        let body = reply.body();
+        let (unlocked, locked): (
+            Vec<zvariant::OwnedObjectPath>,
+            Vec<zvariant::OwnedObjectPath>,
+        ) = body.deserialize().unwrap();
+        assert!(unlocked.is_empty());
+        assert!(locked.is_empty());
+
+        // Nothing of the collection comes back after a restart.
+        run_server_handle.abort();
+        assert!(run_server_handle.await.unwrap_err().is_cancelled());
+
+        let (dbus_name, run_server_handle) = run_service_server(testing::ServerOptions {
+            data_dir: Some(data_dir.path().to_owned()),
+            ..Default::default()
+        })
+        .await;
+        assert_eq!(
+            read_alias(&connection, &dbus_name, "doomed-alias")
+                .await?
+                .as_str(),
+            "/"
+        );
+        assert_eq!(
+            call_method_error(
+                &connection,
+                &dbus_name,
+                &collection_object_path,
+                "org.freedesktop.DBus.Properties",
+                "Get",
+                &("org.freedesktop.Secret.Collection", "Label"),
+            )
+            .await,
+            "org.freedesktop.DBus.Error.UnknownObject"
+        );
+
+        run_server_handle.abort();
+        assert!(run_server_handle.await.unwrap_err().is_cancelled());
+
+        Ok(())
+    }
+
+    /// Get the `Label` of the collection on `collection_path`.
+    async fn get_collection_label(
+        connection: &zbus::Connection,
+        dbus_name: &str,
+        collection_path: &zvariant::ObjectPath<'_>,
+    ) -> Result<String, error::Error> {
+        let reply = connection
+            .call_method(
+                Some(dbus_name),
+                collection_path,
+                Some("org.freedesktop.DBus.Properties"),
+                "Get",
+                &("org.freedesktop.Secret.Collection", "Label"),
+            )
+            .await?;
+
+        let body = reply.body();
+        let label: zvariant::Value<'_> = body.deserialize()?;
+        Ok(label.downcast()?)
+    }
+
+    #[tokio::test]
+    async fn test_aliases_are_served_at_their_own_object_paths() -> Result<(), error::Error> {
+        let (dbus_name, run_server_handle) = run_service_server(Default::default()).await;
+        let connection = zbus::Connection::session().await?;
+        let session_path = open_plain_session(&connection, dbus_name.as_str()).await?;
+        let default_alias_path = alias::object_path("default");
+        let login_alias_path = alias::object_path("my-login");
+        assert_eq!(
+            login_alias_path.as_str(),
+            "/org/freedesktop/secrets/aliases/my_2dlogin"
+        );
+
+        let default_collection_path = read_alias(&connection, &dbus_name, "default").await?;
+        assert!(default_collection_path
+            .as_str()
+            .starts_with("/org/freedesktop/secrets/collection/"));
+        assert_eq!(
+            get_collection_label(&connection, &dbus_name, &default_alias_path.as_ref()).await?,
+            "default"
+        );
+
+        let other_collection_path = create_collection(dbus_name.as_str(), "other").await?;
+        for (alias, collection_path) in [
+            ("default", &other_collection_path),
+            ("my-login", &default_collection_path),
+        ] {
+            connection
+                .call_method(
+                    Some(dbus_name.as_str()),
+                    "/org/freedesktop/secrets",
+                    Some("org.freedesktop.Secret.Service"),
+                    "SetAlias",
+                    &(alias, collection_path),
+                )
+                .await
+                .unwrap();
+        }
+
+        // Re-pointing an alias leaves the object paths of both collections untouched.
+        assert_eq!(
+            read_alias(&connection, &dbus_name, "default").await?,
+            other_collection_path
+        );
+        assert_eq!(
+            get_collection_label(&connection, &dbus_name, &default_alias_path.as_ref()).await?,
+            "other"
+        );
+        assert_eq!(
+            get_collection_label(&connection, &dbus_name, &default_collection_path.as_ref())
+                .await?,
+            "default"
+        );
+        assert_eq!(
+            get_collection_label(&connection, &dbus_name, &login_alias_path.as_ref()).await?,
+            "default"
+        );
+
+        // Items created through an alias belong to the collection it points to.
+        let item_properties = item::ItemReadWriteProperties {
+            attributes: collections::HashMap::from([("service".to_owned(), "alias".to_owned())]),
+            label: "test-item".to_owned(),
+        };
+        let secret = secret::Secret {
+            session: session_path.clone(),
+            value: "a-very-important-secret".into(),
+            parameters: Vec::new(),
+            content_type: "text/plain; charset=utf8".to_string(),
+        };
+        let reply = connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                &default_alias_path,
+                Some("org.freedesktop.Secret.Collection"),
+                "CreateItem",
+                &(item_properties, secret, false),
+            )
+            .await
+            .unwrap();
+        let body = reply.body();
+        let (item_object_path, _): (zvariant::OwnedObjectPath, zvariant::ObjectPath<'_>) =
+            body.deserialize().unwrap();
+        assert!(item_object_path
+            .as_str()
+            .starts_with(other_collection_path.as_str()));
+
+        let reply = connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                &default_alias_path,
+                Some("org.freedesktop.Secret.Collection"),
+                "SearchItems",
+                &(collections::HashMap::from([("service", "alias")])),
+            )
+            .await
+            .unwrap();
+        let body = reply.body();
+        let found: Vec<zvariant::OwnedObjectPath> = body.deserialize().unwrap();
+        assert_eq!(found, vec![item_object_path]);
+
+        // Alias object paths are accepted wherever a collection is expected.
+        let reply = connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                "/org/freedesktop/secrets",
+                Some("org.freedesktop.Secret.Service"),
+                "Lock",
+                &(vec![&login_alias_path]),
+            )
+            .await
+            .unwrap();
+        let body = reply.body();
+        let (locked, _): (Vec<zvariant::OwnedObjectPath>, zvariant::ObjectPath<'_>) =
+            body.deserialize().unwrap();
+        assert_eq!(locked, vec![default_collection_path.clone()]);
+
+        // Removing an alias stops serving it.
+        connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                "/org/freedesktop/secrets",
+                Some("org.freedesktop.Secret.Service"),
+                "SetAlias",
+                &("my-login", zvariant::ObjectPath::from_str_unchecked("/")),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            call_method_error(
+                &connection,
+                &dbus_name,
+                &login_alias_path.as_ref(),
+                "org.freedesktop.DBus.Properties",
+                "Get",
+                &("org.freedesktop.Secret.Collection", "Label"),
+            )
+            .await,
+            "org.freedesktop.DBus.Error.UnknownObject"
+        );
+
+        // Deleting through an alias deletes the collection, along with its aliases.
+        let reply = connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                &default_alias_path,
+                Some("org.freedesktop.Secret.Collection"),
+                "Delete",
+                &(),
+            )
+            .await
+            .unwrap();
+        let body = reply.body();
+        let prompt: zvariant::OwnedObjectPath = body.deserialize().unwrap();
+        assert_eq!(prompt.as_str(), "/");
+        assert_eq!(
+            read_alias(&connection, &dbus_name, "default")
+                .await?
+                .as_str(),
+            "/"
+        );
+        assert_eq!(
+            call_method_error(
+                &connection,
+                &dbus_name,
+                &other_collection_path.as_ref(),
+                "org.freedesktop.DBus.Properties",
+                "Get",
+                &("org.freedesktop.Secret.Collection", "Label"),
+            )
+            .await,
+            "org.freedesktop.DBus.Error.UnknownObject"
+        );
+
+        run_server_handle.abort();
+        assert!(run_server_handle.await.unwrap_err().is_cancelled());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_default_collection_is_passwordless_only_without_a_prompter(
+    ) -> Result<(), error::Error> {
+        let data_dir = tempfile::tempdir()?;
+        let connection = zbus::Connection::session().await?;
+
+        // Clients create it, and choose its password, when there is someone to ask.
+        let (dbus_name, run_server_handle) = run_service_server(testing::ServerOptions {
+            data_dir: Some(data_dir.path().to_owned()),
+            prompter: prompt::Prompter::new(vec!["true".to_owned()]),
+            ..Default::default()
+        })
+        .await;
+        assert_eq!(
+            read_alias(&connection, &dbus_name, "default")
+                .await?
+                .as_str(),
+            "/"
+        );
+        run_server_handle.abort();
+        assert!(run_server_handle.await.unwrap_err().is_cancelled());
+
+        // Otherwise, it has no password, and is unlocked as soon as it's loaded.
+        for _ in 0..2 {
+            let (dbus_name, run_server_handle) = run_service_server(testing::ServerOptions {
+                data_dir: Some(data_dir.path().to_owned()),
+                ..Default::default()
+            })
+            .await;
+            let default_collection_path = read_alias(&connection, &dbus_name, "default").await?;
+            assert_ne!(default_collection_path.as_str(), "/");
+            let reply = connection
+                .call_method(
+                    Some(dbus_name.as_str()),
+                    &default_collection_path,
+                    Some("org.freedesktop.DBus.Properties"),
+                    "Get",
+                    &("org.freedesktop.Secret.Collection", "Locked"),
+                )
+                .await?;
+            let locked: bool = reply.body().deserialize::<zvariant::Value>()?.try_into()?;
+            assert!(!locked);
+            run_server_handle.abort();
+            assert!(run_server_handle.await.unwrap_err().is_cancelled());
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_session_collection_is_neither_stored_nor_locked() -> Result<(), error::Error> {
+        let data_dir = tempfile::tempdir()?;
+        let (dbus_name, run_server_handle) = run_service_server(testing::ServerOptions {
+            data_dir: Some(data_dir.path().to_owned()),
+            ..Default::default()
+        })
+        .await;
+        let connection = zbus::Connection::session().await?;
+        let session_path = open_plain_session(&connection, dbus_name.as_str()).await?;
+
+        let session_collection_path = read_alias(&connection, &dbus_name, "session").await?;
+        assert_ne!(session_collection_path.as_str(), "/");
+        assert_ne!(
+            session_collection_path,
+            read_alias(&connection, &dbus_name, "default").await?
+        );
+
+        let item_properties = item::ItemReadWriteProperties {
+            attributes: collections::HashMap::from([("service".to_owned(), "token".to_owned())]),
+            label: "test-token".to_owned(),
+        };
+        let secret = secret::Secret {
+            session: session_path.clone(),
+            value: "a-short-lived-token".into(),
+            parameters: Vec::new(),
+            content_type: "text/plain; charset=utf8".to_string(),
+        };
+        connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                &alias::object_path("session"),
+                Some("org.freedesktop.Secret.Collection"),
+                "CreateItem",
+                &(item_properties, secret, false),
+            )
+            .await
+            .unwrap();
+
+        let reply = connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                "/org/freedesktop/secrets",
+                Some("org.freedesktop.Secret.Service"),
+                "Lock",
+                &(vec![&session_collection_path]),
+            )
+            .await
+            .unwrap();
+        let body = reply.body();
+        let (locked, _): (Vec<zvariant::OwnedObjectPath>, zvariant::ObjectPath<'_>) =
+            body.deserialize().unwrap();
+        assert!(locked.is_empty());
+
+        let collection_proxy: zbus::Proxy<'_> = zbus::proxy::Builder::new(&connection)
+            .destination(dbus_name.as_str())?
+            .path(session_collection_path.as_ref())?
+            .interface("org.freedesktop.Secret.Collection")?
+            .cache_properties(zbus::proxy::CacheProperties::No)
+            .build()
+            .await?;
+        assert!(!collection_proxy.get_property::<bool>("Locked").await?);
+
+        run_server_handle.abort();
+        assert!(run_server_handle.await.unwrap_err().is_cancelled());
+
+        // A new session collection is created on every start, without the previous items.
+        let (dbus_name, run_server_handle) = run_service_server(testing::ServerOptions {
+            data_dir: Some(data_dir.path().to_owned()),
+            ..Default::default()
+        })
+        .await;
+        let new_session_collection_path = read_alias(&connection, &dbus_name, "session").await?;
+        assert_ne!(new_session_collection_path.as_str(), "/");
+        assert_ne!(new_session_collection_path, session_collection_path);
+
+        let reply = connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                "/org/freedesktop/secrets",
+                Some("org.freedesktop.Secret.Service"),
+                "SearchItems",
+                &(collections::HashMap::from([("service", "token")])),
+            )
+            .await
+            .unwrap();
+        let body = reply.body();
+        let (unlocked, locked): (
+            Vec<zvariant::OwnedObjectPath>,
+            Vec<zvariant::OwnedObjectPath>,
+        ) = body.deserialize().unwrap();
+        assert!(unlocked.is_empty());
+        assert!(locked.is_empty());
+
+        run_server_handle.abort();
+        assert!(run_server_handle.await.unwrap_err().is_cancelled());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_object_manager_reports_collections_and_items() -> Result<(), error::Error> {
+        let (dbus_name, run_server_handle) = run_service_server(Default::default()).await;
+        let connection = zbus::Connection::session().await?;
+        let session_path = open_plain_session(&connection, dbus_name.as_str()).await?;
+        let object_manager = zbus::fdo::ObjectManagerProxy::builder(&connection)
+            .destination(dbus_name.as_str())?
+            .path("/org/freedesktop/secrets")?
+            .build()
+            .await?;
+        let mut interfaces_added = object_manager.receive_interfaces_added().await?;
+        let mut interfaces_removed = object_manager.receive_interfaces_removed().await?;
+
+        let collection_object_path = create_collection(dbus_name.as_str(), "test-label").await?;
+        let added = tokio::time::timeout(time::Duration::from_secs(10), interfaces_added.next())
+            .await
+            .expect("InterfacesAdded should be emitted for a new collection")
+            .unwrap();
+        let args = added.args()?;
+        assert_eq!(args.object_path().as_str(), collection_object_path.as_str());
+        let collection_properties = &args.interfaces_and_properties()
+            [&zbus_names::InterfaceName::from_static_str_unchecked(
+                "org.freedesktop.Secret.Collection",
+            )];
+        assert_eq!(
+            collection_properties["Label"],
+            zvariant::Value::from("test-label")
+        );
+
+        let item_properties = item::ItemReadWriteProperties {
+            attributes: collections::HashMap::from([("service".to_owned(), "managed".to_owned())]),
+            label: "test-item".to_owned(),
+        };
+        let secret = secret::Secret {
+            session: session_path.clone(),
+            value: "a-very-important-secret".into(),
+            parameters: Vec::new(),
+            content_type: "text/plain; charset=utf8".to_string(),
+        };
+        let reply = connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                &collection_object_path,
+                Some("org.freedesktop.Secret.Collection"),
+                "CreateItem",
+                &(item_properties, secret, false),
+            )
+            .await
+            .unwrap();
+        let body = reply.body();
+        let (item_object_path, _): (zvariant::OwnedObjectPath, zvariant::ObjectPath<'_>) =
+            body.deserialize().unwrap();
+        let added = tokio::time::timeout(time::Duration::from_secs(10), interfaces_added.next())
+            .await
+            .expect("InterfacesAdded should be emitted for a new item")
+            .unwrap();
+        assert_eq!(
+            added.args()?.object_path().as_str(),
+            item_object_path.as_str()
+        );
+
+        let managed_objects = object_manager.get_managed_objects().await.unwrap();
+        let collection_interface =
+            zbus_names::OwnedInterfaceName::try_from("org.freedesktop.Secret.Collection").unwrap();
+        let item_interface =
+            zbus_names::OwnedInterfaceName::try_from("org.freedesktop.Secret.Item").unwrap();
+        assert_eq!(
+            managed_objects[&collection_object_path][&collection_interface]["Items"],
+            zvariant::Value::from(vec![item_object_path.clone()])
+                .try_into()
+                .unwrap()
+        );
+        let item_attributes: collections::HashMap<String, String> = managed_objects
+            [&item_object_path][&item_interface]["Attributes"]
+            .try_clone()
+            .unwrap()
+            .try_into()
+            .unwrap();
+        assert_eq!(
+            item_attributes,
+            collections::HashMap::from([("service".to_owned(), "managed".to_owned())])
+        );
+        let default_collection_path = read_alias(&connection, &dbus_name, "default").await?;
+        assert!(managed_objects[&default_collection_path].contains_key(&collection_interface));
+        assert_eq!(
+            managed_objects[&alias::object_path("default")][&collection_interface]["Label"],
+            zvariant::Value::from("default").try_into().unwrap()
+        );
+
+        connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                &item_object_path,
+                Some("org.freedesktop.Secret.Item"),
+                "Delete",
+                &(),
+            )
+            .await
+            .unwrap();
+        let removed =
+            tokio::time::timeout(time::Duration::from_secs(10), interfaces_removed.next())
+                .await
+                .expect("InterfacesRemoved should be emitted for a deleted item")
+                .unwrap();
+        let args = removed.args()?;
+        assert_eq!(args.object_path().as_str(), item_object_path.as_str());
+        assert_eq!(
+            args.interfaces()
+                .iter()
+                .map(|interface| interface.as_str())
+                .collect::<Vec<_>>(),
+            vec!["org.freedesktop.Secret.Item"]
+        );
+
+        run_server_handle.abort();
+        assert!(run_server_handle.await.unwrap_err().is_cancelled());
+
+        Ok(())
+    }
+
+    /// Wait for `PropertiesChanged` signals in `signals` until all of `names` have changed.
+    ///
+    /// Returns the last value of each property in `names`.
+    async fn wait_for_properties(
+        signals: &mut zbus::fdo::PropertiesChangedStream,
+        names: &[&str],
+    ) -> collections::HashMap<String, zvariant::OwnedValue> {
+        let mut changed = collections::HashMap::new();
+
+        while !names.iter().all(|name| changed.contains_key(*name)) {
+            let signal = tokio::time::timeout(time::Duration::from_secs(10), signals.next())
+                .await
+                .expect("Took too long to receive PropertiesChanged")
+                .unwrap();
+            let args = signal.args().unwrap();
+            for (name, value) in args.changed_properties() {
+                if names.contains(name) {
+                    changed.insert(name.to_string(), value.try_to_owned().unwrap());
+                }
+            }
+        }
+
+        changed
+    }
+
+    #[tokio::test]
+    async fn test_mutations_emit_properties_changed() -> Result<(), error::Error> {
+        let (dbus_name, run_server_handle) = run_service_server(Default::default()).await;
+        let connection = zbus::Connection::session().await?;
+        let session_path = open_plain_session(&connection, dbus_name.as_str()).await?;
+        let properties_proxy = |path: zvariant::OwnedObjectPath| {
+            zbus::fdo::PropertiesProxy::builder(&connection)
+                .destination(dbus_name.clone())
+                .unwrap()
+                .path(path)
+                .unwrap()
+                .build()
+        };
+
+        let service_properties =
+            properties_proxy(zvariant::ObjectPath::from_static_str_unchecked(SERVICE_PATH).into())
+                .await?;
+        let mut service_changes = service_properties.receive_properties_changed().await?;
+        let collection_object_path = create_collection(dbus_name.as_str(), "test-label").await?;
+        let changed = wait_for_properties(&mut service_changes, &["Collections"]).await;
+        let collections: Vec<zvariant::OwnedObjectPath> =
+            changed["Collections"].try_clone()?.try_into()?;
+        assert!(collections.contains(&collection_object_path));
+
+        let collection_properties = properties_proxy(collection_object_path.clone()).await?;
+        let mut collection_changes = collection_properties.receive_properties_changed().await?;
+        let item_properties = item::ItemReadWriteProperties {
+            attributes: collections::HashMap::new(),
+            label: "test-item-label".to_owned(),
+        };
+        let secret = secret::Secret {
+            session: session_path.clone(),
+            value: "a-very-important-secret".into(),
+            parameters: Vec::new(),
+            content_type: "text/plain; charset=utf8".to_string(),
+        };
+        let reply = connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                &collection_object_path,
+                Some("org.freedesktop.Secret.Collection"),
+                "CreateItem",
+                &(&item_properties, &secret, false),
+            )
+            .await
+            .unwrap();
+        let body = reply.body();
+        let (item_object_path, _): (zvariant::OwnedObjectPath, zvariant::ObjectPath<'_>) =
+            body.deserialize().unwrap();
+        let changed = wait_for_properties(&mut collection_changes, &["Items", "Modified"]).await;
+        let items: Vec<zvariant::OwnedObjectPath> = changed["Items"].try_clone()?.try_into()?;
+        assert_eq!(items, vec![item_object_path.clone()]);
+
+        // Every change to the item bumps its `Modified` timestamp, and its collection's.
+        let item_proxy = zbus::Proxy::new(
+            &connection,
+            dbus_name.as_str(),
+            &item_object_path,
+            "org.freedesktop.Secret.Item",
+        )
+        .await?;
+        let item_properties = properties_proxy(item_object_path.clone()).await?;
+        let mut item_changes = item_properties.receive_properties_changed().await?;
+
+        let new_attributes = collections::HashMap::from([("attribute", "value")]);
+        for (name, result) in [
+            ("Label", item_proxy.set_property("Label", "new-label").await),
+            (
+                "Attributes",
+                item_proxy
+                    .set_property("Attributes", zvariant::Value::from(new_attributes.clone()))
+                    .await,
+            ),
+        ] {
+            result.unwrap();
+            let changed = wait_for_properties(&mut item_changes, &[name, "Modified"]).await;
+            let collection_changed =
+                wait_for_properties(&mut collection_changes, &["Modified"]).await;
+            assert_eq!(changed["Modified"], collection_changed["Modified"]);
+        }
+        assert_eq!(
+            item_proxy.get_property::<String>("Label").await?,
+            "new-label"
+        );
+        assert_eq!(
+            item_proxy
+                .get_property::<collections::HashMap<String, String>>("Attributes")
+                .await?,
+            collections::HashMap::from([("attribute".to_owned(), "value".to_owned())])
+        );
+
+        item_proxy.call_method("SetSecret", &(&secret)).await?;
+        let changed = wait_for_properties(&mut item_changes, &["Modified"]).await;
+        let collection_changed = wait_for_properties(&mut collection_changes, &["Modified"]).await;
+        assert_eq!(changed["Modified"], collection_changed["Modified"]);
+
+        // Locking the collection locks its items too.
+        for (method, locked) in [("Lock", true), ("Unlock", false)] {
+            connection
+                .call_method(
+                    Some(dbus_name.as_str()),
+                    "/org/freedesktop/secrets",
+                    Some("org.freedesktop.Secret.Service"),
+                    method,
+                    &(vec![&collection_object_path]),
+                )
+                .await
+                .unwrap();
+
+            let changed =
+                wait_for_properties(&mut collection_changes, &["Locked", "Modified"]).await;
+            assert_eq!(changed["Locked"], zvariant::Value::from(locked).try_into()?);
+            let changed = wait_for_properties(&mut item_changes, &["Locked"]).await;
+            assert_eq!(changed["Locked"], zvariant::Value::from(locked).try_into()?);
+            assert_eq!(item_proxy.cached_property::<bool>("Locked")?, Some(locked));
+        }
+
+        item_proxy.call_method("Delete", &()).await?;
+        let changed = wait_for_properties(&mut collection_changes, &["Items", "Modified"]).await;
+        let items: Vec<zvariant::OwnedObjectPath> = changed["Items"].try_clone()?.try_into()?;
+        assert!(items.is_empty());
+
+        connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                &collection_object_path,
+                Some("org.freedesktop.Secret.Collection"),
+                "Delete",
+                &(),
+            )
+            .await
+            .unwrap();
+        let changed = wait_for_properties(&mut service_changes, &["Collections"]).await;
+        let collections: Vec<zvariant::OwnedObjectPath> =
+            changed["Collections"].try_clone()?.try_into()?;
+        assert!(!collections.contains(&collection_object_path));
+
+        run_server_handle.abort();
+        assert!(run_server_handle.await.unwrap_err().is_cancelled());
+
+        Ok(())
+    }
+
+    /// Store a collection without a password, with an item created by `application`.
+    ///
+    /// Returns the object paths the collection and item are served on.
+    async fn store_item_of_application(
+        data_dir: &path::Path,
+        application: Option<&str>,
+        attributes: collections::HashMap<String, String>,
+    ) -> Result<(zvariant::OwnedObjectPath, zvariant::OwnedObjectPath), error::Error> {
+        let storage = storage::files::FileStorage::open(data_dir)?;
+        let collection_id = uuid::Uuid::new_v4();
+        storage
+            .create_passwordless_collection(storage::StoredCollection {
+                created: 1,
+                id: collection_id,
+                items: collections::HashMap::new(),
+                label: "test-label".to_owned(),
+                modified: 1,
+            })
+            .await?;
+        let item_id = uuid::Uuid::new_v4();
+        storage
+            .create_item(
+                &collection_id,
+                storage::StoredItem {
+                    application: application.map(str::to_owned),
+                    attributes,
+                    content_type: "text/plain".to_owned(),
+                    created: 1,
+                    id: item_id,
+                    label: "test-item-label".to_owned(),
+                    modified: 1,
+                },
+                b"a-very-important-secret",
+            )
+            .await?;
+
+        let collection_path = format!(
+            "/org/freedesktop/secrets/collection/{}",
+            collection_id.as_simple()
+        );
+        let item_path = format!("{collection_path}/{}", item_id.as_simple());
+        Ok((
+            zvariant::ObjectPath::try_from(collection_path)?.into(),
+            zvariant::ObjectPath::try_from(item_path)?.into(),
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_items_of_other_applications_need_confirmation() -> Result<(), error::Error> {
+        let data_dir = tempfile::tempdir()?;
+        let (_, item_object_path) = store_item_of_application(
+            data_dir.path(),
+            Some("/usr/bin/other-application"),
+            collections::HashMap::new(),
+        )
+        .await?;
+        // The user confirms once this file exists.
+        let confirmed_path = data_dir.path().join("confirmed");
+        let prompter = prompt::Prompter::new(vec![
+            "sh".to_owned(),
+            "-c".to_owned(),
+            format!("test -e '{}'", confirmed_path.display()),
+        ]);
+        let (dbus_name, run_server_handle) = run_service_server(testing::ServerOptions {
+            data_dir: Some(data_dir.path().to_owned()),
+            prompter,
+            ..Default::default()
+        })
+        .await;
+        let connection = zbus::Connection::session().await?;
+        let session_path = open_plain_session(&connection, dbus_name.as_str()).await?;
+
+        let get_secrets = || async {
+            let reply = connection
+                .call_method(
+                    Some(dbus_name.as_str()),
+                    "/org/freedesktop/secrets",
+                    Some("org.freedesktop.Secret.Service"),
+                    "GetSecrets",
+                    &(vec![&item_object_path], session_path.as_ref()),
+                )
+                .await
+                .unwrap();
+            let body = reply.body();
+            let secrets: collections::HashMap<zvariant::OwnedObjectPath, secret::Secret> =
+                body.deserialize().unwrap();
+            secrets
+        };
+
+        assert_eq!(
+            call_method_error(
+                &connection,
+                &dbus_name,
+                &item_object_path,
+                "org.freedesktop.Secret.Item",
+                "GetSecret",
+                &(session_path.as_ref()),
+            )
+            .await,
+            "org.freedesktop.Secret.Error.IsLocked"
+        );
+        assert!(get_secrets().await.is_empty());
+
+        // Access is confirmed by unlocking the item, through a prompt.
+        let unlock = || async {
+            let reply = connection
+                .call_method(
+                    Some(dbus_name.as_str()),
+                    "/org/freedesktop/secrets",
+                    Some("org.freedesktop.Secret.Service"),
+                    "Unlock",
+                    &(vec![&item_object_path]),
+                )
+                .await
+                .unwrap();
+            let body = reply.body();
+            let (unlocked, prompt_path): (
+                Vec<zvariant::OwnedObjectPath>,
+                zvariant::OwnedObjectPath,
+            ) = body.deserialize().unwrap();
+            assert!(unlocked.is_empty());
+            complete_prompt(&dbus_name, &prompt_path).await.unwrap()
+        };
+        let (dismissed, _) = unlock().await;
+        assert!(dismissed);
+        assert!(get_secrets().await.is_empty());
+
+        fs::write(&confirmed_path, "")?;
+        let (dismissed, result) = unlock().await;
+        assert!(!dismissed);
+        let unlocked: Vec<zvariant::OwnedObjectPath> = result.try_into()?;
+        assert_eq!(unlocked, vec![item_object_path.clone()]);
+        let reply = connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                &item_object_path,
+                Some("org.freedesktop.Secret.Item"),
+                "GetSecret",
+                &(session_path.as_ref()),
+            )
+            .await?;
+        let secret: secret::Secret = reply.body().deserialize()?;
+        assert_eq!(secret.value, b"a-very-important-secret");
+
+        // The user is not asked again.
+        fs::remove_file(&confirmed_path)?;
+        assert_eq!(get_secrets().await.len(), 1);
+
+        run_server_handle.abort();
+        assert!(run_server_handle.await.unwrap_err().is_cancelled());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_deleting_items_of_other_applications_needs_confirmation(
+    ) -> Result<(), error::Error> {
+        let data_dir = tempfile::tempdir()?;
+        let (_, item_object_path) = store_item_of_application(
+            data_dir.path(),
+            Some("/usr/bin/other-application"),
+            collections::HashMap::new(),
+        )
+        .await?;
+        let (dbus_name, run_server_handle) = run_service_server(testing::ServerOptions {
+            data_dir: Some(data_dir.path().to_owned()),
+            prompter: prompt::Prompter::new(vec!["true".to_owned()]),
+            ..Default::default()
+        })
+        .await;
+        let connection = zbus::Connection::session().await?;
+
+        let reply = connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                &item_object_path,
+                Some("org.freedesktop.Secret.Item"),
+                "Delete",
+                &(),
+            )
+            .await?;
+        let prompt_path: zvariant::OwnedObjectPath = reply.body().deserialize()?;
+        assert_ne!(prompt_path.as_str(), "/");
+        // Nothing is deleted until the user confirms.
+        assert!(connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                &item_object_path,
+                Some("org.freedesktop.DBus.Properties"),
+                "Get",
+                &("org.freedesktop.Secret.Item", "Label"),
+            )
+            .await
+            .is_ok());
+
+        let (dismissed, _) = complete_prompt(&dbus_name, &prompt_path).await?;
+        assert!(!dismissed);
+        assert_eq!(
+            call_method_error(
+                &connection,
+                &dbus_name,
+                &item_object_path,
+                "org.freedesktop.DBus.Properties",
+                "Get",
+                &("org.freedesktop.Secret.Item", "Label"),
+            )
+            .await,
+            "org.freedesktop.DBus.Error.UnknownObject"
+        );
+
+        run_server_handle.abort();
+        assert!(run_server_handle.await.unwrap_err().is_cancelled());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rules_limit_the_items_an_application_sees() -> Result<(), error::Error> {
+        let data_dir = tempfile::tempdir()?;
+        let (collection_object_path, other_item_path) = store_item_of_application(
+            data_dir.path(),
+            Some("/usr/bin/other-application"),
+            collections::HashMap::from([("origin".to_owned(), "test".to_owned())]),
+        )
+        .await?;
+        let executable = std::env::current_exe()?;
+        let policy = access::Policy {
+            default: access::Decision::Deny,
+            rules: vec![access::Rule {
+                application: executable
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned(),
+                attributes: collections::HashMap::from([("origin".to_owned(), "test".to_owned())]),
+            }],
+        };
+        let (dbus_name, run_server_handle) = run_service_server(testing::ServerOptions {
+            data_dir: Some(data_dir.path().to_owned()),
+            policy,
+            ..Default::default()
+        })
+        .await;
+        let connection = zbus::Connection::session().await?;
+        let session_path = open_plain_session(&connection, dbus_name.as_str()).await?;
+
+        // An item of its own, that the rule doesn't let it see.
+        let item_properties = item::ItemReadWriteProperties {
+            attributes: collections::HashMap::from([("origin".to_owned(), "other".to_owned())]),
+            label: "test-item-label".to_owned(),
+        };
+        let secret = secret::Secret {
+            session: session_path.clone(),
+            value: "a-very-important-secret".into(),
+            parameters: Vec::new(),
+            content_type: "text/plain; charset=utf8".to_string(),
+        };
+        let reply = connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                &collection_object_path,
+                Some("org.freedesktop.Secret.Collection"),
+                "CreateItem",
+                &(&item_properties, &secret, false),
+            )
+            .await
+            .unwrap();
+        let body = reply.body();
+        let (own_item_path, _): (zvariant::OwnedObjectPath, zvariant::ObjectPath<'_>) =
+            body.deserialize().unwrap();
+
+        let reply = connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                "/org/freedesktop/secrets",
+                Some("org.freedesktop.Secret.Service"),
+                "SearchItems",
+                &(collections::HashMap::<&str, &str>::new()),
+            )
+            .await?;
+        let (unlocked, locked): (
+            Vec<zvariant::OwnedObjectPath>,
+            Vec<zvariant::OwnedObjectPath>,
+        ) = reply.body().deserialize()?;
+        assert_eq!(unlocked, vec![other_item_path.clone()]);
+        assert!(locked.is_empty());
+
+        let reply = connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                &other_item_path,
+                Some("org.freedesktop.Secret.Item"),
+                "GetSecret",
+                &(session_path.as_ref()),
+            )
+            .await?;
+        let secret: secret::Secret = reply.body().deserialize()?;
+        assert_eq!(secret.value, b"a-very-important-secret");
+        assert_eq!(
+            call_method_error(
+                &connection,
+                &dbus_name,
+                &own_item_path,
+                "org.freedesktop.Secret.Item",
+                "GetSecret",
+                &(session_path.as_ref()),
+            )
+            .await,
+            "org.freedesktop.DBus.Error.AccessDenied"
+        );
+
+        run_server_handle.abort();
+        assert!(run_server_handle.await.unwrap_err().is_cancelled());
+
+        // Items record the application that created them.
+        let storage = storage::files::FileStorage::open(data_dir.path())?;
+        let own_item = storage
+            .collections()
+            .await
+            .into_iter()
+            .flat_map(|collection| collection.items.into_values())
+            .find(|item| {
+                own_item_path
+                    .as_str()
+                    .ends_with(&item.id.simple().to_string())
+            })
+            .unwrap();
+        assert_eq!(
+            own_item.application,
+            Some(executable.to_string_lossy().into_owned())
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_denied_applications_cannot_write_item_properties() -> Result<(), error::Error> {
+        let data_dir = tempfile::tempdir()?;
+        let (_, item_object_path) = store_item_of_application(
+            data_dir.path(),
+            Some("/usr/bin/other-application"),
+            collections::HashMap::from([("origin".to_owned(), "other".to_owned())]),
+        )
+        .await?;
+        let executable = std::env::current_exe()?;
+        let policy = access::Policy {
+            default: access::Decision::Deny,
+            rules: vec![access::Rule {
+                application: executable
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned(),
+                attributes: collections::HashMap::from([("origin".to_owned(), "test".to_owned())]),
+            }],
+        };
+        let (dbus_name, run_server_handle) = run_service_server(testing::ServerOptions {
+            data_dir: Some(data_dir.path().to_owned()),
+            policy,
+            ..Default::default()
+        })
+        .await;
+        let connection = zbus::Connection::session().await?;
+        let session_path = open_plain_session(&connection, dbus_name.as_str()).await?;
+
+        // Rewriting the attributes to match its rule doesn't give it access to the item.
+        assert_eq!(
+            call_method_error(
+                &connection,
+                &dbus_name,
+                &item_object_path,
+                "org.freedesktop.DBus.Properties",
+                "Set",
+                &(
+                    "org.freedesktop.Secret.Item",
+                    "Attributes",
+                    zvariant::Value::from(collections::HashMap::from([("origin", "test")])),
+                ),
+            )
+            .await,
+            "org.freedesktop.DBus.Error.AccessDenied"
+        );
+        assert_eq!(
+            call_method_error(
+                &connection,
+                &dbus_name,
+                &item_object_path,
+                "org.freedesktop.DBus.Properties",
+                "Set",
+                &(
+                    "org.freedesktop.Secret.Item",
+                    "Label",
+                    zvariant::Value::from("new-label"),
+                ),
+            )
+            .await,
+            "org.freedesktop.DBus.Error.AccessDenied"
+        );
+        assert_eq!(
+            call_method_error(
+                &connection,
+                &dbus_name,
+                &item_object_path,
+                "org.freedesktop.Secret.Item",
+                "GetSecret",
+                &(session_path.as_ref()),
+            )
+            .await,
+            "org.freedesktop.DBus.Error.AccessDenied"
+        );
+
+        run_server_handle.abort();
+        assert!(run_server_handle.await.unwrap_err().is_cancelled());
+
+        // Nothing was written.
+        let storage = storage::files::FileStorage::open(data_dir.path())?;
+        let item = storage
+            .collections()
+            .await
+            .into_iter()
+            .flat_map(|collection| collection.items.into_values())
+            .next()
+            .unwrap();
+        assert_eq!(item.label, "test-item-label");
+        assert_eq!(
+            item.attributes,
+            collections::HashMap::from([("origin".to_owned(), "other".to_owned())])
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_items_of_unknown_applications_are_allowed_without_a_prompter(
+    ) -> Result<(), error::Error> {
+        let data_dir = tempfile::tempdir()?;
+        // Like imported items, whose creator is not known.
+        let (_, unknown_item_path) =
+            store_item_of_application(data_dir.path(), None, collections::HashMap::new()).await?;
+        let (_, other_item_path) = store_item_of_application(
+            data_dir.path(),
+            Some("/usr/bin/other-application"),
+            collections::HashMap::new(),
+        )
+        .await?;
+        let (dbus_name, run_server_handle) = run_service_server(testing::ServerOptions {
+            data_dir: Some(data_dir.path().to_owned()),
+            ..Default::default()
+        })
+        .await;
+        let connection = zbus::Connection::session().await?;
+        let session_path = open_plain_session(&connection, dbus_name.as_str()).await?;
+
+        let reply = connection
+            .call_method(
+                Some(dbus_name.as_str()),
+                &unknown_item_path,
+                Some("org.freedesktop.Secret.Item"),
+                "GetSecret",
+                &(session_path.as_ref()),
+            )
+            .await?;
+        let secret: secret::Secret = reply.body().deserialize()?;
+        assert_eq!(secret.value, b"a-very-important-secret");
+        // Items of other applications still need a confirmation, which can't be given.
+        assert_eq!(
+            call_method_error(
+                &connection,
+                &dbus_name,
+                &other_item_path,
+                "org.freedesktop.Secret.Item",
+                "GetSecret",
+                &(session_path.as_ref()),
+            )
+            .await,
+            "org.freedesktop.DBus.Error.AccessDenied"
+        );
+
+        run_server_handle.abort();
+        assert!(run_server_handle.await.unwrap_err().is_cancelled());
+
+        Ok(())
+    }
 }
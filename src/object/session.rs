@@ -3,6 +3,9 @@
 //! The state tracked by the `Session` is used to encrypt and decrypt
 //! secrets. So, although not part of the `org.freedesktop.Secret.Session`
 //! D-Bus interface, we implement encryption and decryption methods here.
+use std::collections;
+use std::sync;
+
 use aes::cipher::{block_padding, BlockDecryptMut, BlockEncryptMut, KeyIvInit};
 
 use crate::error;
@@ -22,12 +25,20 @@ pub enum Algorithm {
     Dh { aes_key: [u8; 16] },
 }
 
+/// Length in bytes of the AES-128-CBC initialization vectors sent as secret parameters.
+const IV_LENGTH: usize = 16;
+
 impl Algorithm {
+    /// Encrypt `plaintext`, returning the ciphertext and the parameters needed to decrypt it.
+    ///
+    /// Every call uses a new random IV, as reusing one under the same key would reveal
+    /// whether two secrets start with the same blocks.
     pub fn encrypt(&self, plaintext: &[u8]) -> (Vec<u8>, Vec<u8>) {
-        let iv = [0x24; 16];
-
         match self {
             Algorithm::Dh { aes_key } => {
+                let mut iv = [0u8; IV_LENGTH];
+                getrandom::getrandom(&mut iv).expect("system random number generator unavailable");
+
                 let ciphertext = Aes128CbcEnc::new(aes_key.into(), &iv.into())
                     .encrypt_padded_vec_mut::<block_padding::Pkcs7>(plaintext);
                 (ciphertext, iv.into())
@@ -36,20 +47,199 @@ impl Algorithm {
         }
     }
 
-    pub fn decrypt(&self, ciphertext: &[u8], iv: &[u8]) -> Vec<u8> {
+    /// Decrypt `ciphertext` sent by a client with `iv` as its parameters.
+    ///
+    /// Fails with `InvalidSecret` if the IV is not 16 bytes long, or if the ciphertext
+    /// is not correctly padded, which is also what decrypting with the wrong key looks like.
+    pub fn decrypt(&self, ciphertext: &[u8], iv: &[u8]) -> Result<Vec<u8>, error::Error> {
         match self {
-            Algorithm::Dh { aes_key } => Aes128CbcDec::new(aes_key.into(), iv.into())
-                .decrypt_padded_vec_mut::<block_padding::Pkcs7>(ciphertext)
-                .unwrap(),
-            Algorithm::Plain => ciphertext.to_vec(),
+            Algorithm::Dh { aes_key } => {
+                if iv.len() != IV_LENGTH {
+                    return Err(error::Error::InvalidSecret(format!(
+                        "Expected a {IV_LENGTH} byte IV as parameters, got {} bytes",
+                        iv.len()
+                    )));
+                }
+
+                Aes128CbcDec::new(aes_key.into(), iv.into())
+                    .decrypt_padded_vec_mut::<block_padding::Pkcs7>(ciphertext)
+                    .map_err(|_| {
+                        error::Error::InvalidSecret(
+                            "Secret could not be decrypted with the session key".to_owned(),
+                        )
+                    })
+            }
+            Algorithm::Plain => Ok(ciphertext.to_vec()),
+        }
+    }
+}
+
+/// Prime of the Second Oakley Group defined in RFC 2409, section 6.2.
+///
+/// Together with `DH_GENERATOR`, these are the Diffie-Hellman parameters mandated
+/// for dh-ietf1024-sha256-aes128-cbc-pkcs7.
+const DH_PRIME: [u8; DH_KEY_LENGTH] = [
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xc9, 0x0f, 0xda, 0xa2, 0x21, 0x68, 0xc2, 0x34,
+    0xc4, 0xc6, 0x62, 0x8b, 0x80, 0xdc, 0x1c, 0xd1, 0x29, 0x02, 0x4e, 0x08, 0x8a, 0x67, 0xcc, 0x74,
+    0x02, 0x0b, 0xbe, 0xa6, 0x3b, 0x13, 0x9b, 0x22, 0x51, 0x4a, 0x08, 0x79, 0x8e, 0x34, 0x04, 0xdd,
+    0xef, 0x95, 0x19, 0xb3, 0xcd, 0x3a, 0x43, 0x1b, 0x30, 0x2b, 0x0a, 0x6d, 0xf2, 0x5f, 0x14, 0x37,
+    0x4f, 0xe1, 0x35, 0x6d, 0x6d, 0x51, 0xc2, 0x45, 0xe4, 0x85, 0xb5, 0x76, 0x62, 0x5e, 0x7e, 0xc6,
+    0xf4, 0x4c, 0x42, 0xe9, 0xa6, 0x37, 0xed, 0x6b, 0x0b, 0xff, 0x5c, 0xb6, 0xf4, 0x06, 0xb7, 0xed,
+    0xee, 0x38, 0x6b, 0xfb, 0x5a, 0x89, 0x9f, 0xa5, 0xae, 0x9f, 0x24, 0x11, 0x7c, 0x4b, 0x1f, 0xe6,
+    0x49, 0x28, 0x66, 0x51, 0xec, 0xe6, 0x53, 0x81, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+];
+const DH_GENERATOR: u32 = 2;
+/// Length in bytes of the group's prime, and so of public keys and shared secrets.
+const DH_KEY_LENGTH: usize = 128;
+
+/// A Diffie-Hellman key pair over the Second Oakley Group.
+///
+/// Public keys are exchanged as unsigned big-endian integers. Clients may send
+/// them without leading zeroes, but ours are always padded to `DH_KEY_LENGTH` bytes.
+pub struct DhKeyPair {
+    private_key: num_bigint::BigUint,
+    public_key: num_bigint::BigUint,
+}
+
+impl DhKeyPair {
+    /// Generate a new key pair with a random private key.
+    pub fn generate() -> Self {
+        let mut private_key_bytes = [0u8; DH_KEY_LENGTH];
+
+        loop {
+            getrandom::getrandom(&mut private_key_bytes)
+                .expect("system random number generator unavailable");
+
+            if let Some(key_pair) = Self::from_private_key(&private_key_bytes) {
+                return key_pair;
+            }
+        }
+    }
+
+    /// Build a key pair from a big-endian private key.
+    ///
+    /// Returns `None` if the private key is not in the range `[2, p - 2]`.
+    pub fn from_private_key(private_key_bytes: &[u8]) -> Option<Self> {
+        let prime = num_bigint::BigUint::from_bytes_be(&DH_PRIME);
+        let private_key = num_bigint::BigUint::from_bytes_be(private_key_bytes);
+
+        if private_key < num_bigint::BigUint::from(2u32) || private_key > &prime - 2u32 {
+            return None;
+        }
+
+        let public_key = num_bigint::BigUint::from(DH_GENERATOR).modpow(&private_key, &prime);
+
+        Some(Self {
+            private_key,
+            public_key,
+        })
+    }
+
+    pub fn public_key_bytes(&self) -> Vec<u8> {
+        pad_to_key_length(self.public_key.to_bytes_be())
+    }
+
+    /// Derive the AES-128 key shared with the owner of `peer_public_key`.
+    ///
+    /// The shared secret is padded to `DH_KEY_LENGTH` bytes and passed through
+    /// HKDF-SHA256 with no salt and no info, as the specification requires.
+    pub fn derive_aes_key(&self, peer_public_key: &[u8]) -> Result<[u8; 16], error::Error> {
+        let prime = num_bigint::BigUint::from_bytes_be(&DH_PRIME);
+        let peer_public_key = num_bigint::BigUint::from_bytes_be(peer_public_key);
+
+        // Values outside of (1, p - 1) would force the shared secret into a trivial subgroup.
+        if peer_public_key <= num_bigint::BigUint::from(1u32) || peer_public_key >= &prime - 1u32 {
+            return Err(error::Error::InvalidArgs {
+                method: "OpenSession",
+                argument: "input",
+                reason: "Invalid public key".to_owned(),
+            });
+        }
+
+        let shared_secret = peer_public_key.modpow(&self.private_key, &prime);
+        let shared_secret_padded = pad_to_key_length(shared_secret.to_bytes_be());
+
+        let info = [];
+        let salt = None;
+
+        let (_, hk) = hkdf::Hkdf::<sha2::Sha256>::extract(salt, &shared_secret_padded);
+        let mut output = [0; 16];
+        hk.expand(&info, &mut output)?;
+
+        Ok(output)
+    }
+}
+
+fn pad_to_key_length(bytes: Vec<u8>) -> Vec<u8> {
+    let mut padded = vec![0u8; DH_KEY_LENGTH.saturating_sub(bytes.len())];
+    padded.extend_from_slice(&bytes);
+    padded
+}
+
+/// Object paths of open sessions, by the unique name of the client that opened them.
+///
+/// Used to close every session of a client once it disconnects from the bus.
+#[derive(Debug, Default)]
+pub struct SessionOwners {
+    sessions: collections::HashMap<
+        zbus_names::OwnedUniqueName,
+        collections::HashSet<zvariant::OwnedObjectPath>,
+    >,
+}
+
+impl SessionOwners {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(
+        &mut self,
+        owner: zbus_names::OwnedUniqueName,
+        session_path: zvariant::OwnedObjectPath,
+    ) {
+        self.sessions.entry(owner).or_default().insert(session_path);
+    }
+
+    pub fn remove(
+        &mut self,
+        owner: &zbus_names::OwnedUniqueName,
+        session_path: &zvariant::OwnedObjectPath,
+    ) {
+        if let Some(sessions) = self.sessions.get_mut(owner) {
+            sessions.remove(session_path);
+            if sessions.is_empty() {
+                self.sessions.remove(owner);
+            }
         }
     }
+
+    pub fn contains(
+        &self,
+        owner: &zbus_names::OwnedUniqueName,
+        session_path: &zvariant::OwnedObjectPath,
+    ) -> bool {
+        self.sessions
+            .get(owner)
+            .is_some_and(|sessions| sessions.contains(session_path))
+    }
+
+    /// Stop tracking the sessions of `owner`, returning their object paths.
+    pub fn remove_owner(
+        &mut self,
+        owner: &zbus_names::UniqueName<'_>,
+    ) -> collections::HashSet<zvariant::OwnedObjectPath> {
+        self.sessions
+            .remove(&zbus_names::OwnedUniqueName::from(owner.to_owned()))
+            .unwrap_or_default()
+    }
 }
 
-#[derive(Debug, PartialEq, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct Session {
     pub algorithm: Algorithm,
     id: uuid::Uuid,
+    owner: zbus_names::OwnedUniqueName,
+    owners: sync::Arc<sync::Mutex<SessionOwners>>,
 }
 
 impl DbusObject for Session {
@@ -80,45 +270,70 @@ impl DbusObject for Session {
 }
 
 impl Session {
-    pub fn new_plain() -> Session {
+    pub fn new_plain(
+        owner: zbus_names::OwnedUniqueName,
+        owners: sync::Arc<sync::Mutex<SessionOwners>>,
+    ) -> Session {
         Session {
             algorithm: Algorithm::Plain,
             id: uuid::Uuid::new_v4(),
+            owner,
+            owners,
         }
     }
 
-    pub fn new_dh(client_public_key: [u8; 32]) -> Result<(Session, [u8; 32]), error::Error> {
-        let secret = x25519_dalek::EphemeralSecret::random();
-        let public_key = x25519_dalek::PublicKey::from(&secret);
-
-        let shared_secret =
-            secret.diffie_hellman(&x25519_dalek::PublicKey::from(client_public_key));
-        let shared_secret_bytes = shared_secret.to_bytes();
-
-        let mut shared_secret_padded = vec![0u8; 128 - shared_secret_bytes.len()];
-        shared_secret_padded.extend_from_slice(&shared_secret_bytes);
-
-        let info = [];
-        let salt = None;
-
-        let (_, hk) = hkdf::Hkdf::<sha2::Sha256>::extract(salt, &shared_secret_padded);
-        let mut output = [0; 16];
-        hk.expand(&info, &mut output)?;
+    /// Open a dh-ietf1024-sha256-aes128-cbc-pkcs7 session.
+    ///
+    /// Returns the new `Session` and the server's public key that must be sent
+    /// back to the client to complete the key exchange.
+    pub fn new_dh(
+        client_public_key: &[u8],
+        owner: zbus_names::OwnedUniqueName,
+        owners: sync::Arc<sync::Mutex<SessionOwners>>,
+    ) -> Result<(Session, Vec<u8>), error::Error> {
+        let key_pair = DhKeyPair::generate();
+        let aes_key = key_pair.derive_aes_key(client_public_key)?;
 
         Ok((
             Session {
-                algorithm: Algorithm::Dh { aes_key: output },
+                algorithm: Algorithm::Dh { aes_key },
                 id: uuid::Uuid::new_v4(),
+                owner,
+                owners,
             },
-            public_key.to_bytes(),
+            key_pair.public_key_bytes(),
         ))
     }
 
+    /// Get the session at `object_path` to use it on behalf of the client `sender`.
+    ///
+    /// Sessions can only be used by the client that opened them, so to any other
+    /// client they don't exist and this fails with `NoSession`.
+    pub async fn get_owned_by(
+        object_path: &zvariant::ObjectPath<'_>,
+        sender: Option<&zbus_names::UniqueName<'_>>,
+        object_server: &zbus::ObjectServer,
+    ) -> Result<Session, error::Error> {
+        let session_interface =
+            Self::get_interface_from_object_path(object_path, object_server).await?;
+        let session = session_interface.get().await;
+
+        if !session.is_owned_by(sender) {
+            return Err(error::Error::NoSession(object_path.as_str().to_owned()));
+        }
+
+        Ok(session.clone())
+    }
+
+    pub fn is_owned_by(&self, sender: Option<&zbus_names::UniqueName<'_>>) -> bool {
+        sender.is_some_and(|sender| sender.as_str() == self.owner.as_str())
+    }
+
     pub fn encrypt(&self, plaintext: &[u8]) -> (Vec<u8>, Vec<u8>) {
         self.algorithm.encrypt(plaintext)
     }
 
-    pub fn decrypt(&self, ciphertext: &[u8], iv: &[u8]) -> Vec<u8> {
+    pub fn decrypt(&self, ciphertext: &[u8], iv: &[u8]) -> Result<Vec<u8>, error::Error> {
         self.algorithm.decrypt(ciphertext, iv)
     }
 
@@ -134,10 +349,267 @@ impl Session {
 impl Session {
     /// Close method
     async fn close(
-        &mut self,
+        &self,
+        #[zbus(header)] header: zbus::message::Header<'_>,
         #[zbus(object_server)] object_server: &zbus::ObjectServer,
     ) -> Result<(), error::Error> {
+        if !self.is_owned_by(header.sender()) {
+            return Err(error::Error::NoSession(
+                self.get_object_path().as_str().to_owned(),
+            ));
+        }
+
+        self.owners
+            .lock()
+            .expect("lock is not poisoned")
+            .remove(&self.owner, &self.get_object_path());
         self.remove::<Session>(object_server).await?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Private keys used for known-answer tests: `0x01..=0x20` and `0x40..=0x5f`.
+    fn client_key_pair() -> DhKeyPair {
+        DhKeyPair::from_private_key(&(0x01..=0x20).collect::<Vec<u8>>()).unwrap()
+    }
+
+    fn server_key_pair() -> DhKeyPair {
+        DhKeyPair::from_private_key(&(0x40..=0x5f).collect::<Vec<u8>>()).unwrap()
+    }
+
+    #[test]
+    fn test_dh_private_key_out_of_range() {
+        let prime = num_bigint::BigUint::from_bytes_be(&DH_PRIME);
+
+        assert!(DhKeyPair::from_private_key(&[]).is_none());
+        assert!(DhKeyPair::from_private_key(&[1]).is_none());
+        assert!(DhKeyPair::from_private_key(&(&prime - 1u32).to_bytes_be()).is_none());
+        assert!(DhKeyPair::from_private_key(&[2]).is_some());
+        assert!(DhKeyPair::from_private_key(&(&prime - 2u32).to_bytes_be()).is_some());
+    }
+
+    #[test]
+    fn test_dh_public_key_known_answer() {
+        let expected_public_key: [u8; DH_KEY_LENGTH] = [
+            0x45, 0x71, 0x20, 0x76, 0x4f, 0x3a, 0x1e, 0x6f, 0xd5, 0x81, 0x03, 0xe4, 0x1a, 0x40,
+            0x93, 0xa6, 0xc8, 0xbc, 0x1d, 0x97, 0xcb, 0x87, 0x59, 0xde, 0x41, 0xc2, 0x1a, 0xfd,
+            0xd2, 0xd3, 0x04, 0x8a, 0x5e, 0xf3, 0xd8, 0x8c, 0xe2, 0x4a, 0xa6, 0xba, 0x4f, 0xe3,
+            0x0b, 0xcf, 0xb0, 0xb0, 0xf7, 0x5a, 0xbf, 0x1a, 0x8a, 0xea, 0xff, 0x37, 0x23, 0xf1,
+            0xbf, 0x53, 0x74, 0x0c, 0x90, 0x20, 0x05, 0xe1, 0x19, 0x9f, 0xab, 0xad, 0x7c, 0x53,
+            0x8e, 0x94, 0xa7, 0x03, 0x4f, 0xd5, 0x85, 0x33, 0x9a, 0x02, 0xf3, 0x63, 0x48, 0x93,
+            0xf7, 0x48, 0x92, 0x9d, 0x2a, 0x72, 0x57, 0x64, 0x3e, 0x39, 0x81, 0x30, 0x54, 0x1a,
+            0xe6, 0x41, 0x24, 0xc1, 0x7d, 0x45, 0x07, 0xa9, 0x7f, 0x1c, 0xbe, 0xbe, 0xb7, 0xb9,
+            0x33, 0x64, 0x2b, 0x8d, 0xf4, 0x79, 0xeb, 0x59, 0xe3, 0x6c, 0xfe, 0xff, 0xbf, 0x16,
+            0x71, 0xdd,
+        ];
+
+        assert_eq!(client_key_pair().public_key_bytes(), expected_public_key);
+    }
+
+    #[test]
+    fn test_dh_public_key_is_padded() {
+        // g^2 mod p = 4, which must still be sent as a full length key.
+        let key_pair = DhKeyPair::from_private_key(&[2]).unwrap();
+
+        let mut expected_public_key = vec![0u8; DH_KEY_LENGTH - 1];
+        expected_public_key.push(4);
+
+        assert_eq!(key_pair.public_key_bytes(), expected_public_key);
+    }
+
+    #[test]
+    fn test_dh_derive_aes_key_known_answer() {
+        let client = client_key_pair();
+        let server = server_key_pair();
+        let expected_aes_key: [u8; 16] = [
+            0x97, 0x0b, 0xf1, 0x7e, 0x38, 0x2d, 0xe8, 0x5d, 0x41, 0x10, 0x8d, 0x69, 0x45, 0xce,
+            0x8a, 0x1b,
+        ];
+
+        let client_aes_key = client.derive_aes_key(&server.public_key_bytes()).unwrap();
+        let server_aes_key = server.derive_aes_key(&client.public_key_bytes()).unwrap();
+
+        assert_eq!(client_aes_key, expected_aes_key);
+        assert_eq!(server_aes_key, expected_aes_key);
+    }
+
+    #[test]
+    fn test_dh_accepts_unpadded_public_key() {
+        let client = DhKeyPair::from_private_key(&[2]).unwrap();
+        let server = server_key_pair();
+
+        assert_eq!(
+            server.derive_aes_key(&[4]).unwrap(),
+            client.derive_aes_key(&server.public_key_bytes()).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_dh_rejects_trivial_public_keys() {
+        let prime = num_bigint::BigUint::from_bytes_be(&DH_PRIME);
+        let key_pair = DhKeyPair::generate();
+
+        for public_key in [
+            Vec::new(),
+            vec![1u8],
+            (&prime - 1u32).to_bytes_be(),
+            prime.to_bytes_be(),
+            vec![0xff; DH_KEY_LENGTH + 1],
+        ] {
+            assert!(matches!(
+                key_pair.derive_aes_key(&public_key),
+                Err(error::Error::InvalidArgs { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_new_dh_session_shares_key_with_client() {
+        let client = DhKeyPair::generate();
+        let (session, server_public_key) = Session::new_dh(
+            &client.public_key_bytes(),
+            zbus_names::UniqueName::from_static_str_unchecked(":1.1").into(),
+            sync::Arc::new(sync::Mutex::new(SessionOwners::new())),
+        )
+        .unwrap();
+        let client_aes_key = client.derive_aes_key(&server_public_key).unwrap();
+
+        assert_eq!(server_public_key.len(), DH_KEY_LENGTH);
+        assert_eq!(
+            session.algorithm,
+            Algorithm::Dh {
+                aes_key: client_aes_key
+            }
+        );
+    }
+
+    /// Fill a buffer of `length` random bytes.
+    fn random_bytes(length: usize) -> Vec<u8> {
+        let mut bytes = vec![0u8; length];
+        getrandom::getrandom(&mut bytes).unwrap();
+        bytes
+    }
+
+    /// A random length in `0..max`.
+    fn random_length(max: usize) -> usize {
+        usize::from(u16::from_be_bytes(random_bytes(2).try_into().unwrap())) % max
+    }
+
+    fn dh_algorithm() -> Algorithm {
+        Algorithm::Dh {
+            aes_key: random_bytes(16).try_into().unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_dh_encrypt_uses_random_iv() {
+        let algorithm = dh_algorithm();
+        let plaintext = b"a-very-important-secret";
+
+        let (first_ciphertext, first_iv) = algorithm.encrypt(plaintext);
+        let (second_ciphertext, second_iv) = algorithm.encrypt(plaintext);
+
+        assert_eq!(first_iv.len(), IV_LENGTH);
+        assert_ne!(first_iv, second_iv);
+        assert_ne!(first_ciphertext, second_ciphertext);
+        assert_eq!(
+            algorithm.decrypt(&first_ciphertext, &first_iv).unwrap(),
+            plaintext
+        );
+        assert_eq!(
+            algorithm.decrypt(&second_ciphertext, &second_iv).unwrap(),
+            plaintext
+        );
+    }
+
+    #[test]
+    fn test_dh_decrypt_rejects_invalid_iv_length() {
+        let algorithm = dh_algorithm();
+        let (ciphertext, _) = algorithm.encrypt(b"a-very-important-secret");
+
+        for iv_length in [0, 1, IV_LENGTH - 1, IV_LENGTH + 1, 4 * IV_LENGTH] {
+            assert!(matches!(
+                algorithm.decrypt(&ciphertext, &random_bytes(iv_length)),
+                Err(error::Error::InvalidSecret(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_dh_decrypt_rejects_invalid_padding() {
+        let aes_key: [u8; 16] = random_bytes(16).try_into().unwrap();
+        let algorithm = Algorithm::Dh { aes_key };
+        let (ciphertext, iv) = algorithm.encrypt(b"a-very-important-secret");
+
+        // A block of plaintext ending in a zero byte is never correctly padded.
+        let unpadded = Aes128CbcEnc::new(&aes_key.into(), iv.as_slice().into())
+            .encrypt_padded_vec_mut::<block_padding::NoPadding>(&[0u8; IV_LENGTH]);
+
+        // Neither are empty, truncated or overlong ciphertexts.
+        for ciphertext in [
+            &unpadded[..],
+            &[][..],
+            &ciphertext[..IV_LENGTH - 1],
+            &ciphertext[..ciphertext.len() - 1],
+            &[ciphertext.as_slice(), &[0u8]].concat(),
+        ] {
+            assert!(matches!(
+                algorithm.decrypt(ciphertext, &iv),
+                Err(error::Error::InvalidSecret(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_dh_decrypt_with_wrong_key() {
+        let (ciphertext, iv) = dh_algorithm().encrypt(b"a-very-important-secret");
+        let wrong_algorithm = dh_algorithm();
+
+        // A wrong key produces garbage that is only rarely padded correctly by chance,
+        // but it must never decrypt to the original secret.
+        match wrong_algorithm.decrypt(&ciphertext, &iv) {
+            Ok(plaintext) => assert_ne!(plaintext, b"a-very-important-secret"),
+            Err(err) => assert!(matches!(err, error::Error::InvalidSecret(_))),
+        }
+    }
+
+    #[test]
+    fn test_dh_decrypt_fuzz_never_panics() {
+        let algorithm = dh_algorithm();
+
+        for _ in 0..2000 {
+            let ciphertext = random_bytes(random_length(8 * IV_LENGTH));
+            let iv = random_bytes(if random_length(4) == 0 {
+                random_length(2 * IV_LENGTH)
+            } else {
+                IV_LENGTH
+            });
+
+            match algorithm.decrypt(&ciphertext, &iv) {
+                Ok(plaintext) => {
+                    // Only whole blocks with valid padding can be decrypted.
+                    assert_eq!(iv.len(), IV_LENGTH);
+                    assert_eq!(ciphertext.len() % IV_LENGTH, 0);
+                    assert!(plaintext.len() < ciphertext.len());
+                }
+                Err(err) => assert!(matches!(err, error::Error::InvalidSecret(_))),
+            }
+        }
+    }
+
+    #[test]
+    fn test_dh_round_trip_fuzz() {
+        let algorithm = dh_algorithm();
+
+        for _ in 0..500 {
+            let plaintext = random_bytes(random_length(8 * IV_LENGTH));
+            let (ciphertext, iv) = algorithm.encrypt(&plaintext);
+
+            assert_eq!(algorithm.decrypt(&ciphertext, &iv).unwrap(), plaintext);
+        }
+    }
+}
@@ -0,0 +1,143 @@
+//! Helpers shared by the tests of the Secret Service Dbus objects.
+
+use std::collections;
+use std::path;
+use std::time;
+
+use crate::access;
+use crate::error;
+use crate::kdbx;
+use crate::object::prompt;
+use crate::server;
+use crate::storage;
+
+/// How to run a `org.freedesktop.Secret.Service` server in tests.
+#[derive(Default)]
+pub struct ServerOptions {
+    /// Where to store collections, a temporary directory removed with the server if `None`.
+    pub data_dir: Option<path::PathBuf>,
+    /// KeePass databases to serve as collections too.
+    pub kdbx_databases: Vec<path::PathBuf>,
+    /// Whether to store collections in SQLite, instead of files.
+    pub sqlite: bool,
+    /// Who to ask passwords and confirmations to.
+    pub prompter: Option<prompt::Prompter>,
+    /// Which applications may access which items.
+    pub policy: access::Policy,
+}
+
+/// Run a `org.freedesktop.Secret.Service` server as configured by `options`.
+///
+/// This coroutine is meant to be awaited at the beginning of each test
+/// function that will be making calls to test the server.
+/// It returns a handle that **must** be aborted once the test is done,
+/// as otherwise the task **runs forever**.
+pub async fn run_service_server(options: ServerOptions) -> (String, tokio::task::JoinHandle<()>) {
+    let start_event = event_listener::Event::new();
+    let start_event_listener = start_event.listen();
+    let dbus_name = format!(
+        "org.freedesktop.secrets-test-{}",
+        uuid::Uuid::new_v4().as_simple()
+    );
+
+    let cloned_dbus_name = dbus_name.clone();
+    let run_server_handle = tokio::spawn(async move {
+        // A temporary `data_dir` is removed once the server is aborted.
+        let temporary_dir = tempfile::tempdir().unwrap();
+        let data_dir = options
+            .data_dir
+            .unwrap_or_else(|| temporary_dir.path().to_owned());
+        let inner: Box<dyn storage::Storage> = if options.sqlite {
+            Box::new(storage::sqlite::SqliteStorage::open(&data_dir).unwrap())
+        } else {
+            Box::new(storage::files::FileStorage::open(&data_dir).unwrap())
+        };
+        let mut storage = storage::keepass::KdbxStorage::new(inner);
+        for database in options.kdbx_databases {
+            storage
+                .open(&kdbx::DatabaseConfig {
+                    path: database,
+                    ..Default::default()
+                })
+                .unwrap();
+        }
+        let server = server::SecretServiceServer::new(
+            &cloned_dbus_name,
+            storage,
+            options.prompter,
+            options.policy,
+            start_event,
+        )
+        .await
+        .unwrap();
+        server.run().await.unwrap();
+    });
+
+    if tokio::time::timeout(time::Duration::from_secs(10), start_event_listener)
+        .await
+        .is_err()
+    {
+        if run_server_handle.is_finished() {
+            run_server_handle.await.unwrap();
+            panic!("Server exited early without an error");
+        } else {
+            panic!("Took to long to start test dbus server");
+        }
+    }
+
+    (dbus_name, run_server_handle)
+}
+
+/// Create a collection labelled `label` without a prompter, returning its object path.
+pub async fn create_collection(
+    dbus_name: &str,
+    label: &str,
+) -> Result<zvariant::OwnedObjectPath, error::Error> {
+    let connection = zbus::Connection::session().await?;
+    let collection_properties = collections::HashMap::from([(
+        "org.freedesktop.Secret.Collection.Label",
+        zvariant::Value::new(label),
+    )]);
+
+    let reply = connection
+        .call_method(
+            Some(dbus_name),
+            "/org/freedesktop/secrets",
+            Some("org.freedesktop.Secret.Service"),
+            "CreateCollection",
+            &(collection_properties, ""),
+        )
+        .await
+        .unwrap();
+
+    let body = reply.body();
+    let (collection_object_path, _): (zvariant::ObjectPath<'_>, zvariant::ObjectPath<'_>) =
+        body.deserialize().unwrap();
+
+    Ok(collection_object_path.into())
+}
+
+/// Open a session with the `plain` algorithm, returning its object path.
+pub async fn open_plain_session(
+    connection: &zbus::Connection,
+    dbus_name: &str,
+) -> Result<zvariant::OwnedObjectPath, error::Error> {
+    let plain_key: Vec<u8> = Vec::new();
+
+    let reply = connection
+        .call_method(
+            Some(dbus_name),
+            "/org/freedesktop/secrets",
+            Some("org.freedesktop.Secret.Service"),
+            "OpenSession",
+            &("plain", zvariant::Value::from(plain_key)),
+        )
+        .await
+        .unwrap();
+
+    let body = reply.body();
+    let (_, session_path): (zvariant::Value, zvariant::ObjectPath<'_>) =
+        body.deserialize().unwrap();
+
+    Ok(session_path.into())
+}
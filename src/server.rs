@@ -1,18 +1,32 @@
+use std::sync;
+
+use futures::StreamExt;
+
+use crate::access;
 use crate::error;
-use crate::object::collection;
+use crate::object::internal;
+use crate::object::prompt;
 use crate::object::service;
+use crate::object::session;
 use crate::object::DbusObject;
+use crate::storage;
 
 #[derive(Debug)]
 pub struct SecretServiceServer {
     connection: zbus::Connection,
     dbus_name: String,
+    policy: access::Policy,
+    prompter: Option<prompt::Prompter>,
     start_event: event_listener::Event,
+    storage: sync::Arc<dyn storage::Storage>,
 }
 
 impl SecretServiceServer {
     pub async fn new(
         dbus_name: &str,
+        storage: impl storage::Storage + 'static,
+        prompter: Option<prompt::Prompter>,
+        policy: access::Policy,
         start_event: event_listener::Event,
     ) -> Result<Self, error::Error> {
         let connection = zbus::Connection::session().await?;
@@ -20,13 +34,35 @@ impl SecretServiceServer {
         Ok(Self {
             connection,
             dbus_name: dbus_name.to_owned(),
+            policy,
+            prompter,
             start_event,
+            storage: sync::Arc::new(storage),
         })
     }
 
     pub async fn run(self) -> Result<(), error::Error> {
-        let service = service::Service::new();
+        let has_prompter = self.prompter.is_some();
+        let mut service = service::Service::new(
+            self.connection.clone(),
+            self.storage.clone(),
+            self.prompter,
+            self.policy,
+        );
+        // Reports every object below the service, and announces them as they come and go.
+        self.connection
+            .object_server()
+            .at(service.get_object_path(), zbus::fdo::ObjectManager)
+            .await?;
+        service
+            .load_collections(self.connection.object_server())
+            .await?;
+        let sessions = service.sessions.clone();
+        let access = service.access.clone();
         let (interface_path, _) = service.serve_at(self.connection.object_server()).await?;
+        internal::Internal::new(self.storage.clone())
+            .serve_at(self.connection.object_server())
+            .await?;
 
         log::info!("Serving Secret Service interface.");
 
@@ -37,33 +73,86 @@ impl SecretServiceServer {
             )
             .await?;
 
-            let properties = collection::CollectionReadWriteProperties {
-                label: "default".to_owned(),
-            };
+            if interface.get().await.read_alias("default").as_str() != "/" {
+                log::info!("Loaded default collection.");
+            } else if has_prompter {
+                // Clients create it through `CreateCollection`, which asks for its password.
+                log::info!("No default collection until a client creates one.");
+            } else {
+                interface
+                    .get()
+                    .await
+                    .add_passwordless_collection(
+                        "default",
+                        Some("default"),
+                        self.connection.object_server(),
+                        interface.signal_emitter(),
+                    )
+                    .await?;
+
+                log::warn!(
+                    "Created default collection without a password, as there is no prompter."
+                );
+            }
 
+            // The session collection is never stored, so it has to be created on every start.
             interface
-                .get_mut()
+                .get()
                 .await
-                .create_collection(
-                    properties,
-                    "default",
+                .add_ephemeral_collection(
+                    "session",
+                    Some("session"),
                     self.connection.object_server(),
-                    interface.signal_emitter().to_owned(),
+                    interface.signal_emitter(),
                 )
                 .await?;
+
+            log::info!("Created session collection.");
         }
-        log::info!("Created default collection.");
 
         let dbus_name = self.dbus_name;
         self.connection.request_name(dbus_name.as_str()).await?;
 
         log::info!("Dbus assigned name '{dbus_name}' to secret service server");
 
+        // Handling D-Bus messages is done in the background, here we only watch for
+        // clients disconnecting to close the sessions they left open.
+        let dbus_proxy = zbus::fdo::DBusProxy::new(&self.connection).await?;
+        let mut name_owner_changes = dbus_proxy.receive_name_owner_changed().await?;
+
         self.start_event.notify(usize::MAX);
 
-        loop {
-            // Handling D-Bus messages is done in the background
-            std::future::pending::<()>().await;
+        while let Some(signal) = name_owner_changes.next().await {
+            let args = signal.args()?;
+
+            if let (zbus_names::BusName::Unique(client), None) =
+                (args.name(), args.new_owner().as_ref())
+            {
+                access.forget(client);
+                let session_paths = sessions
+                    .lock()
+                    .expect("lock is not poisoned")
+                    .remove_owner(client);
+
+                for session_path in session_paths {
+                    // A session may have been closed while we were handling the signal.
+                    match self
+                        .connection
+                        .object_server()
+                        .remove::<session::Session, _>(&session_path)
+                        .await
+                    {
+                        Ok(_) => log::info!(
+                            "Closed session on '{session_path}' as '{client}' disconnected"
+                        ),
+                        Err(err) => {
+                            log::warn!("Failed to close session on '{session_path}': {err}")
+                        }
+                    }
+                }
+            }
         }
+
+        Ok(())
     }
 }
@@ -0,0 +1,353 @@
+//! Collections kept as JSON files.
+//!
+//! Each collection is kept, together with its items, in its own JSON file under
+//! `<path>/collections`, and aliases are kept in `<path>/aliases.json`. Every change is
+//! written back by atomically replacing the affected file, so a crash leaves either the
+//! old or the new version on disk, but never a partially written one.
+use std::collections;
+use std::fs;
+use std::io;
+use std::os::unix::fs::DirBuilderExt;
+use std::path;
+use std::sync;
+
+use super::{Backend, CollectionFile, Collections, WriteThrough};
+use crate::error;
+
+const COLLECTIONS_DIR: &str = "collections";
+const ALIASES_FILE: &str = "aliases.json";
+
+/// A `Storage` that writes collections and aliases to JSON files.
+#[derive(Debug)]
+pub struct FileStorage(sync::Arc<Collections>);
+
+impl FileStorage {
+    /// Open the `FileStorage` at `path`, loading any collections and aliases found.
+    ///
+    /// The directory is created, only accessible by the current user, if it
+    /// doesn't exist. All collections start locked.
+    pub fn open(path: &path::Path) -> Result<Self, error::Error> {
+        let collections_path = path.join(COLLECTIONS_DIR);
+        fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(&collections_path)?;
+
+        let mut files = Vec::new();
+        for entry in fs::read_dir(&collections_path)? {
+            let entry_path = entry?.path();
+
+            match entry_path
+                .extension()
+                .and_then(|extension| extension.to_str())
+            {
+                Some("json") => files.push(serde_json::from_slice(&fs::read(&entry_path)?)?),
+                Some("tmp") => {
+                    // Leftover from an interrupted write: the previous version is still intact.
+                    log::warn!("Removing incomplete write '{}'", entry_path.display());
+                    fs::remove_file(&entry_path)?;
+                }
+                _ => {}
+            }
+        }
+
+        let aliases = match fs::read(path.join(ALIASES_FILE)) {
+            Ok(contents) => serde_json::from_slice(&contents)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => collections::HashMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        log::info!(
+            "Loaded {} collections from '{}'",
+            files.len(),
+            path.display()
+        );
+
+        Ok(Self(sync::Arc::new(Collections::new(
+            files,
+            aliases,
+            Box::new(Directory(path.to_owned())),
+        ))))
+    }
+}
+
+impl WriteThrough for FileStorage {
+    fn cache(&self) -> &sync::Arc<Collections> {
+        &self.0
+    }
+}
+
+/// The directory of a `FileStorage`.
+#[derive(Debug)]
+struct Directory(path::PathBuf);
+
+impl Backend for Directory {
+    fn write_collection(
+        &self,
+        _original: Option<&CollectionFile>,
+        file: &CollectionFile,
+    ) -> Result<(), error::Error> {
+        let contents = serde_json::to_vec(file)?;
+        super::write_atomically(&collection_file_path(&self.0, &file.id), &contents)?;
+        Ok(())
+    }
+
+    fn delete_collection(&self, collection_id: &uuid::Uuid) -> Result<(), error::Error> {
+        match fs::remove_file(collection_file_path(&self.0, collection_id)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// All of the aliases are written at once, as they are kept in a single file.
+    fn write_alias(
+        &self,
+        aliases: &collections::HashMap<&String, &uuid::Uuid>,
+        _name: &str,
+        _collection_id: Option<&uuid::Uuid>,
+    ) -> Result<(), error::Error> {
+        let contents = serde_json::to_vec(aliases)?;
+        super::write_atomically(&self.0.join(ALIASES_FILE), &contents)?;
+        Ok(())
+    }
+}
+
+/// Path of the file of a collection, in a `FileStorage` at `path`.
+fn collection_file_path(path: &path::Path, collection_id: &uuid::Uuid) -> path::PathBuf {
+    let mut file_name = collection_id
+        .as_simple()
+        .encode_lower(&mut uuid::Uuid::encode_buffer())
+        .to_owned();
+    file_name.push_str(".json");
+
+    path.join(COLLECTIONS_DIR).join(file_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::tests::{check_changes_are_loaded_when_reopened, new_collection, new_item};
+    use crate::storage::Storage;
+
+    #[tokio::test]
+    async fn test_open_empty() -> Result<(), error::Error> {
+        let data_dir = tempfile::tempdir()?;
+        let storage = FileStorage::open(&data_dir.path().join("keyrings"))?;
+
+        assert!(storage.collections().await.is_empty());
+        assert!(storage.aliases().await.is_empty());
+        assert!(data_dir
+            .path()
+            .join("keyrings")
+            .join(COLLECTIONS_DIR)
+            .is_dir());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_changes_are_loaded_when_reopened() -> Result<(), error::Error> {
+        check_changes_are_loaded_when_reopened(FileStorage::open).await
+    }
+
+    #[tokio::test]
+    async fn test_secrets_are_encrypted_at_rest() -> Result<(), error::Error> {
+        let data_dir = tempfile::tempdir()?;
+        let storage = FileStorage::open(data_dir.path())?;
+
+        let collection = new_collection("collection");
+        let item = new_item("item");
+        storage
+            .create_collection(collection.clone(), b"master-password")
+            .await?;
+        storage
+            .create_item(&collection.id, item.clone(), b"a-very-important-secret")
+            .await?;
+
+        let contents = fs::read(collection_file_path(data_dir.path(), &collection.id))?;
+
+        assert!(!contents
+            .windows(b"a-very-important-secret".len())
+            .any(|window| window == b"a-very-important-secret"));
+        assert!(!contents
+            .windows(b"master-password".len())
+            .any(|window| window == b"master-password"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_change_password() -> Result<(), error::Error> {
+        let data_dir = tempfile::tempdir()?;
+        let storage = FileStorage::open(data_dir.path())?;
+
+        let collection = new_collection("collection");
+        let item = new_item("item");
+        storage
+            .create_collection(collection.clone(), b"password")
+            .await?;
+        storage
+            .create_item(&collection.id, item.clone(), b"secret")
+            .await?;
+        storage.lock_collection(&collection.id).await?;
+
+        assert!(
+            !storage
+                .change_password(&collection.id, b"wrong-password", b"new-password")
+                .await?
+        );
+        assert!(
+            storage
+                .change_password(&collection.id, b"password", b"new-password")
+                .await?
+        );
+        assert!(storage.is_locked(&collection.id).await);
+
+        // The new password is kept on disk.
+        let storage = FileStorage::open(data_dir.path())?;
+        assert!(
+            !storage
+                .unlock_collection(&collection.id, b"password")
+                .await?
+        );
+        assert!(
+            storage
+                .unlock_collection(&collection.id, b"new-password")
+                .await?
+        );
+        assert_eq!(
+            storage
+                .read_secret(&collection.id, &item.id)
+                .await?
+                .as_slice(),
+            b"secret"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_passwordless_collection() -> Result<(), error::Error> {
+        let data_dir = tempfile::tempdir()?;
+        let storage = FileStorage::open(data_dir.path())?;
+
+        let collection = new_collection("collection");
+        let protected_collection = new_collection("protected-collection");
+        storage
+            .create_passwordless_collection(collection.clone())
+            .await?;
+        storage
+            .create_collection(protected_collection.clone(), b"")
+            .await?;
+        assert!(storage.is_passwordless(&collection.id).await);
+        // An empty password is not the same as none.
+        assert!(!storage.is_passwordless(&protected_collection.id).await);
+
+        // It's kept on disk, and unlocked with an empty password.
+        let storage = FileStorage::open(data_dir.path())?;
+        assert!(storage.is_passwordless(&collection.id).await);
+        assert!(storage.is_locked(&collection.id).await);
+        assert!(storage.unlock_collection(&collection.id, b"").await?);
+
+        // Until it's given a password.
+        assert!(
+            storage
+                .change_password(&collection.id, b"", b"password")
+                .await?
+        );
+        assert!(!storage.is_passwordless(&collection.id).await);
+        let storage = FileStorage::open(data_dir.path())?;
+        assert!(!storage.is_passwordless(&collection.id).await);
+        assert!(!storage.unlock_collection(&collection.id, b"").await?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_delete_collection_removes_file_and_aliases() -> Result<(), error::Error> {
+        let data_dir = tempfile::tempdir()?;
+        let storage = FileStorage::open(data_dir.path())?;
+
+        let collection = new_collection("collection");
+        let other_collection = new_collection("other-collection");
+        storage.create_collection(collection.clone(), b"").await?;
+        storage
+            .create_collection(other_collection.clone(), b"")
+            .await?;
+        storage.set_alias("default", Some(&collection.id)).await?;
+        storage
+            .set_alias("other", Some(&other_collection.id))
+            .await?;
+
+        storage.delete_collection(&collection.id).await?;
+
+        assert!(!collection_file_path(data_dir.path(), &collection.id).exists());
+
+        let reopened_storage = FileStorage::open(data_dir.path())?;
+        assert_eq!(
+            reopened_storage.collections().await,
+            vec![other_collection.clone()]
+        );
+        assert_eq!(
+            reopened_storage.aliases().await,
+            collections::HashMap::from([("other".to_owned(), other_collection.id)])
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_ephemeral_collections_are_not_written_nor_locked() -> Result<(), error::Error> {
+        let data_dir = tempfile::tempdir()?;
+        let storage = FileStorage::open(data_dir.path())?;
+
+        let collection = new_collection("session");
+        let item = new_item("item");
+        storage
+            .create_ephemeral_collection(collection.clone())
+            .await?;
+        storage
+            .create_item(&collection.id, item.clone(), b"secret")
+            .await?;
+        storage.set_alias("session", Some(&collection.id)).await?;
+
+        assert!(storage.is_ephemeral(&collection.id).await);
+        assert!(!storage.lock_collection(&collection.id).await?);
+        assert!(!storage.is_locked(&collection.id).await);
+        assert_eq!(
+            storage
+                .read_secret(&collection.id, &item.id)
+                .await?
+                .as_slice(),
+            b"secret"
+        );
+        assert!(!collection_file_path(data_dir.path(), &collection.id).exists());
+
+        let reopened_storage = FileStorage::open(data_dir.path())?;
+        assert!(reopened_storage.collections().await.is_empty());
+        assert!(reopened_storage.aliases().await.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_interrupted_write_is_discarded() -> Result<(), error::Error> {
+        let data_dir = tempfile::tempdir()?;
+        let storage = FileStorage::open(data_dir.path())?;
+
+        let collection = new_collection("collection");
+        storage.create_collection(collection.clone(), b"").await?;
+
+        // Simulate a crash after writing the temporary file, but before renaming it.
+        let temporary_path =
+            collection_file_path(data_dir.path(), &collection.id).with_extension("tmp");
+        fs::write(&temporary_path, b"{\"partial\": ")?;
+
+        let reopened_storage = FileStorage::open(data_dir.path())?;
+
+        assert_eq!(reopened_storage.collections().await, vec![collection]);
+        assert!(!temporary_path.exists());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,766 @@
+//! KeePass databases served as collections, besides those of another `Storage`.
+//!
+//! Each group of a database is a collection, see `kdbx`. Until the database is unlocked
+//! with its password, only the collection of its root group is known, without any items.
+//! Unlocking or locking any collection of a database does so for all of them, as they share
+//! its password. Secrets are then kept in memory like those of ephemeral collections,
+//! encrypted with a random key, and every change is written back to the database file.
+//! Aliases, and all other collections, are left to the wrapped `Storage`.
+use std::collections;
+use std::fs;
+use std::sync;
+
+use super::{Ciphertext, CollectionKey, Storage, StoredCollection, StoredItem};
+use crate::error;
+use crate::kdbx;
+
+/// A `Storage` that serves KeePass databases as collections, and leaves everything else to
+/// the `Storage` it wraps.
+///
+/// Databases are only used by `super::blocking`, as they are locked while being unlocked
+/// or written to.
+#[derive(Debug)]
+pub struct KdbxStorage {
+    /// Databases by the ids of their collections, which are only all known once unlocked.
+    collections:
+        sync::Mutex<collections::HashMap<uuid::Uuid, sync::Arc<sync::Mutex<DatabaseRecord>>>>,
+    /// Databases in the order they were opened.
+    databases: Vec<sync::Arc<sync::Mutex<DatabaseRecord>>>,
+    inner: Box<dyn Storage>,
+}
+
+#[derive(Debug)]
+struct DatabaseRecord {
+    /// Collection of the root group, followed by those of the subgroups found when the
+    /// database was last unlocked.
+    collections: Vec<StoredCollection>,
+    database: kdbx::Database,
+    /// Whether the database is unlocked with an empty password, and its key file.
+    passwordless: bool,
+    /// Secrets of the items of all `collections`, encrypted with `key`.
+    secrets: collections::HashMap<uuid::Uuid, Ciphertext>,
+    /// Only set while the database is unlocked.
+    key: Option<CollectionKey>,
+}
+
+impl DatabaseRecord {
+    fn key(&self) -> Result<&CollectionKey, error::Error> {
+        self.key
+            .as_ref()
+            .ok_or_else(|| error::Error::IsLocked(self.database.id.to_string()))
+    }
+
+    fn collection_ids(&self) -> Vec<uuid::Uuid> {
+        self.collections
+            .iter()
+            .map(|collection| collection.id)
+            .collect()
+    }
+
+    fn collection(&self, collection_id: &uuid::Uuid) -> Result<&StoredCollection, error::Error> {
+        self.collections
+            .iter()
+            .find(|collection| collection.id == *collection_id)
+            .ok_or_else(|| error::Error::NoSuchObject(collection_id.to_string()))
+    }
+
+    fn collection_mut(
+        &mut self,
+        collection_id: &uuid::Uuid,
+    ) -> Result<&mut StoredCollection, error::Error> {
+        self.collections
+            .iter_mut()
+            .find(|collection| collection.id == *collection_id)
+            .ok_or_else(|| error::Error::NoSuchObject(collection_id.to_string()))
+    }
+
+    fn secret(&self, item_id: &uuid::Uuid) -> Result<zeroize::Zeroizing<Vec<u8>>, error::Error> {
+        let secret = self
+            .secrets
+            .get(item_id)
+            .ok_or_else(|| error::Error::NoSuchObject(item_id.to_string()))?;
+        self.key()?
+            .decrypt(secret, item_id.as_bytes())
+            .ok_or_else(|| {
+                error::Error::Storage(format!("Failed to decrypt secret of item '{item_id}'"))
+            })
+    }
+
+    /// Unlock the database, replacing its collections with those read from it.
+    fn unlock(&mut self, password: &[u8]) -> Result<bool, error::Error> {
+        let Some(contents) = self.database.unlock(password)? else {
+            return Ok(false);
+        };
+
+        let key = CollectionKey::generate();
+        self.secrets = contents
+            .secrets
+            .iter()
+            .map(|(item_id, secret)| (*item_id, key.encrypt(secret, item_id.as_bytes())))
+            .collect();
+        self.collections = contents.collections;
+        self.key = Some(key);
+
+        Ok(true)
+    }
+
+    /// Apply `modify` to the collection with `collection_id`, and only keep the changes if
+    /// they are written to the database. Only the timestamps of a locked database can
+    /// change, which are kept in memory.
+    fn modify<F>(&mut self, collection_id: &uuid::Uuid, modify: F) -> Result<(), error::Error>
+    where
+        F: FnOnce(&mut Self) -> Result<(), error::Error>,
+    {
+        let original_collections = self.collections.clone();
+        let original_secrets = self.secrets.clone();
+        let modified = modify(self).and_then(|_| {
+            if self.key.is_none() {
+                return Ok(());
+            }
+            self.write(collection_id)
+        });
+        if let Err(e) = modified {
+            self.collections = original_collections;
+            self.secrets = original_secrets;
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    /// Write a collection back to the database, which requires it to be unlocked.
+    fn write(&mut self, collection_id: &uuid::Uuid) -> Result<(), error::Error> {
+        let collection = self.collection(collection_id)?;
+        let secrets = collection
+            .items
+            .keys()
+            .map(|item_id| Ok((*item_id, self.secret(item_id)?)))
+            .collect::<Result<_, error::Error>>()?;
+        let collection = collection.clone();
+        self.database.write(&collection, &secrets)
+    }
+}
+
+impl KdbxStorage {
+    /// A `KdbxStorage` without any databases, wrapping `inner`.
+    pub fn new(inner: Box<dyn Storage>) -> Self {
+        Self {
+            collections: sync::Mutex::new(collections::HashMap::new()),
+            databases: Vec::new(),
+            inner,
+        }
+    }
+
+    /// Open the KeePass database of `config`, which is unlocked with its password and key
+    /// file, if any. Returns the id of the collection of its root group.
+    ///
+    /// The collection starts locked, and has no items until it's unlocked.
+    pub fn open(&mut self, config: &kdbx::DatabaseConfig) -> Result<uuid::Uuid, error::Error> {
+        let path = config.path.as_path();
+        let database = kdbx::Database::new(path, config.keyfile.as_deref());
+        let modified = fs::metadata(path)?
+            .modified()?
+            .duration_since(std::time::SystemTime::UNIX_EPOCH)
+            .map_or(0, |modified| modified.as_secs());
+
+        let collection = StoredCollection {
+            created: modified,
+            id: database.id,
+            items: collections::HashMap::new(),
+            label: database.label(),
+            modified,
+        };
+        log::info!(
+            "Opened KeePass database '{}' as collection '{}'",
+            path.display(),
+            collection.id
+        );
+
+        let id = collection.id;
+        let record = sync::Arc::new(sync::Mutex::new(DatabaseRecord {
+            collections: vec![collection],
+            database,
+            passwordless: config.passwordless,
+            secrets: collections::HashMap::new(),
+            key: None,
+        }));
+        self.collections
+            .get_mut()
+            .expect("lock is not poisoned")
+            .insert(id, record.clone());
+        self.databases.push(record);
+        Ok(id)
+    }
+
+    /// The database that a collection belongs to, if any.
+    fn database(
+        &self,
+        collection_id: &uuid::Uuid,
+    ) -> Option<sync::Arc<sync::Mutex<DatabaseRecord>>> {
+        self.collections
+            .lock()
+            .expect("lock is not poisoned")
+            .get(collection_id)
+            .cloned()
+    }
+
+    fn is_database(&self, collection_id: &uuid::Uuid) -> bool {
+        self.database(collection_id).is_some()
+    }
+
+    /// Run `f` with the database of a collection by `super::blocking`, or return `None` if
+    /// the collection is not in a database.
+    async fn with_database<T, F>(&self, collection_id: &uuid::Uuid, f: F) -> Option<T>
+    where
+        F: FnOnce(&mut DatabaseRecord) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let record = self.database(collection_id)?;
+        Some(super::blocking(move || f(&mut record.lock().expect("lock is not poisoned"))).await)
+    }
+
+    /// Apply `modify` to a collection in a database, see `DatabaseRecord::modify`.
+    async fn modify_database<F>(
+        &self,
+        collection_id: &uuid::Uuid,
+        modify: F,
+    ) -> Result<(), error::Error>
+    where
+        F: FnOnce(&mut DatabaseRecord) -> Result<(), error::Error> + Send + 'static,
+    {
+        let id = *collection_id;
+        self.with_database(collection_id, move |record| record.modify(&id, modify))
+            .await
+            .unwrap_or_else(|| Err(error::Error::NoSuchObject(collection_id.to_string())))
+    }
+}
+
+#[async_trait::async_trait]
+impl Storage for KdbxStorage {
+    async fn aliases(&self) -> collections::HashMap<String, uuid::Uuid> {
+        self.inner.aliases().await
+    }
+
+    async fn collection(&self, collection_id: &uuid::Uuid) -> Option<StoredCollection> {
+        let id = *collection_id;
+        match self
+            .with_database(collection_id, move |record| {
+                record.collection(&id).ok().cloned()
+            })
+            .await
+        {
+            Some(collection) => collection,
+            None => self.inner.collection(collection_id).await,
+        }
+    }
+
+    async fn collections(&self) -> Vec<StoredCollection> {
+        let mut collections = self.inner.collections().await;
+        for record in &self.databases {
+            let record = record.clone();
+            collections.extend(
+                super::blocking(move || {
+                    record
+                        .lock()
+                        .expect("lock is not poisoned")
+                        .collections
+                        .clone()
+                })
+                .await,
+            );
+        }
+        collections
+    }
+
+    async fn create_collection(
+        &self,
+        collection: StoredCollection,
+        password: &[u8],
+    ) -> Result<(), error::Error> {
+        self.inner.create_collection(collection, password).await
+    }
+
+    async fn create_passwordless_collection(
+        &self,
+        collection: StoredCollection,
+    ) -> Result<(), error::Error> {
+        self.inner.create_passwordless_collection(collection).await
+    }
+
+    async fn create_ephemeral_collection(
+        &self,
+        collection: StoredCollection,
+    ) -> Result<(), error::Error> {
+        self.inner.create_ephemeral_collection(collection).await
+    }
+
+    async fn is_ephemeral(&self, collection_id: &uuid::Uuid) -> bool {
+        !self.is_database(collection_id) && self.inner.is_ephemeral(collection_id).await
+    }
+
+    async fn is_passwordless(&self, collection_id: &uuid::Uuid) -> bool {
+        match self
+            .with_database(collection_id, |record| record.passwordless)
+            .await
+        {
+            Some(passwordless) => passwordless,
+            None => self.inner.is_passwordless(collection_id).await,
+        }
+    }
+
+    async fn update_collection(
+        &self,
+        collection_id: &uuid::Uuid,
+        label: &str,
+        modified: u64,
+    ) -> Result<(), error::Error> {
+        if !self.is_database(collection_id) {
+            return self
+                .inner
+                .update_collection(collection_id, label, modified)
+                .await;
+        }
+        let id = *collection_id;
+        let label = label.to_owned();
+        self.modify_database(collection_id, move |record| {
+            let collection = record.collection_mut(&id)?;
+            collection.label = label;
+            collection.modified = modified;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn touch_collection(
+        &self,
+        collection_id: &uuid::Uuid,
+        modified: u64,
+    ) -> Result<(), error::Error> {
+        if !self.is_database(collection_id) {
+            return self.inner.touch_collection(collection_id, modified).await;
+        }
+        let id = *collection_id;
+        self.modify_database(collection_id, move |record| {
+            record.collection_mut(&id)?.modified = modified;
+            Ok(())
+        })
+        .await
+    }
+
+    /// Collections of KeePass databases can't be deleted, as their files are not ours to
+    /// change that way.
+    async fn delete_collection(&self, collection_id: &uuid::Uuid) -> Result<(), error::Error> {
+        if self.is_database(collection_id) {
+            return Err(error::Error::Storage(format!(
+                "Collection '{collection_id}' is in a KeePass database, which can't be deleted"
+            )));
+        }
+        self.inner.delete_collection(collection_id).await
+    }
+
+    async fn is_locked(&self, collection_id: &uuid::Uuid) -> bool {
+        match self
+            .with_database(collection_id, |record| record.key.is_none())
+            .await
+        {
+            Some(locked) => locked,
+            None => self.inner.is_locked(collection_id).await,
+        }
+    }
+
+    /// Locking forgets the key of the database, and its decrypted document, for all of its
+    /// collections.
+    async fn lock_collection(&self, collection_id: &uuid::Uuid) -> Result<bool, error::Error> {
+        match self
+            .with_database(collection_id, |record| {
+                record.database.lock();
+                record.key = None;
+            })
+            .await
+        {
+            Some(()) => Ok(true),
+            None => self.inner.lock_collection(collection_id).await,
+        }
+    }
+
+    /// Unlocking reads the collections of all groups of the database anew.
+    async fn unlock_collection(
+        &self,
+        collection_id: &uuid::Uuid,
+        password: &[u8],
+    ) -> Result<bool, error::Error> {
+        let Some(record) = self.database(collection_id) else {
+            return self.inner.unlock_collection(collection_id, password).await;
+        };
+        let password = zeroize::Zeroizing::new(password.to_vec());
+        let unlocked_record = record.clone();
+        let (unlocked, collection_ids) = super::blocking(move || {
+            let mut record = unlocked_record.lock().expect("lock is not poisoned");
+            Ok::<_, error::Error>((record.unlock(&password)?, record.collection_ids()))
+        })
+        .await?;
+
+        let mut collections = self.collections.lock().expect("lock is not poisoned");
+        for collection_id in collection_ids {
+            collections.insert(collection_id, record.clone());
+        }
+        Ok(unlocked)
+    }
+
+    async fn linked_collections(&self, collection_id: &uuid::Uuid) -> Vec<uuid::Uuid> {
+        match self
+            .with_database(collection_id, |record| record.collection_ids())
+            .await
+        {
+            Some(collection_ids) => collection_ids,
+            None => self.inner.linked_collections(collection_id).await,
+        }
+    }
+
+    async fn change_password(
+        &self,
+        collection_id: &uuid::Uuid,
+        original: &[u8],
+        password: &[u8],
+    ) -> Result<bool, error::Error> {
+        if !self.is_database(collection_id) {
+            return self
+                .inner
+                .change_password(collection_id, original, password)
+                .await;
+        }
+        let original = zeroize::Zeroizing::new(original.to_vec());
+        let password = zeroize::Zeroizing::new(password.to_vec());
+        self.with_database(collection_id, move |record| {
+            record.database.change_password(&original, &password)
+        })
+        .await
+        .unwrap_or_else(|| Err(error::Error::NoSuchObject(collection_id.to_string())))
+    }
+
+    async fn create_item(
+        &self,
+        collection_id: &uuid::Uuid,
+        item: StoredItem,
+        secret: &[u8],
+    ) -> Result<(), error::Error> {
+        if !self.is_database(collection_id) {
+            return self.inner.create_item(collection_id, item, secret).await;
+        }
+        let id = *collection_id;
+        let secret = zeroize::Zeroizing::new(secret.to_vec());
+        self.modify_database(collection_id, move |record| {
+            let secret = record.key()?.encrypt(&secret, item.id.as_bytes());
+            record.secrets.insert(item.id, secret);
+            let collection = record.collection_mut(&id)?;
+            collection.modified = item.modified;
+            collection.items.insert(item.id, item);
+            Ok(())
+        })
+        .await
+    }
+
+    async fn write_item(
+        &self,
+        collection_id: &uuid::Uuid,
+        item: StoredItem,
+    ) -> Result<(), error::Error> {
+        if !self.is_database(collection_id) {
+            return self.inner.write_item(collection_id, item).await;
+        }
+        let id = *collection_id;
+        self.modify_database(collection_id, move |record| {
+            let collection = record.collection_mut(&id)?;
+            collection.modified = item.modified;
+            let stored_item = collection
+                .items
+                .get_mut(&item.id)
+                .ok_or_else(|| error::Error::NoSuchObject(item.id.to_string()))?;
+            *stored_item = item;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn delete_item(
+        &self,
+        collection_id: &uuid::Uuid,
+        item_id: &uuid::Uuid,
+        modified: u64,
+    ) -> Result<(), error::Error> {
+        if !self.is_database(collection_id) {
+            return self
+                .inner
+                .delete_item(collection_id, item_id, modified)
+                .await;
+        }
+        let id = *collection_id;
+        let item_id = *item_id;
+        self.modify_database(collection_id, move |record| {
+            let collection = record.collection_mut(&id)?;
+            collection.items.remove(&item_id);
+            collection.modified = modified;
+            record.secrets.remove(&item_id);
+            Ok(())
+        })
+        .await
+    }
+
+    async fn read_secret(
+        &self,
+        collection_id: &uuid::Uuid,
+        item_id: &uuid::Uuid,
+    ) -> Result<zeroize::Zeroizing<Vec<u8>>, error::Error> {
+        let id = *collection_id;
+        let database_item_id = *item_id;
+        match self
+            .with_database(collection_id, move |record| {
+                if !record
+                    .collection(&id)?
+                    .items
+                    .contains_key(&database_item_id)
+                {
+                    return Err(error::Error::NoSuchObject(database_item_id.to_string()));
+                }
+                record.secret(&database_item_id)
+            })
+            .await
+        {
+            Some(secret) => secret,
+            None => self.inner.read_secret(collection_id, item_id).await,
+        }
+    }
+
+    async fn write_secret(
+        &self,
+        collection_id: &uuid::Uuid,
+        item_id: &uuid::Uuid,
+        secret: &[u8],
+        content_type: &str,
+        modified: u64,
+    ) -> Result<(), error::Error> {
+        if !self.is_database(collection_id) {
+            return self
+                .inner
+                .write_secret(collection_id, item_id, secret, content_type, modified)
+                .await;
+        }
+        let id = *collection_id;
+        let item_id = *item_id;
+        let secret = zeroize::Zeroizing::new(secret.to_vec());
+        let content_type = content_type.to_owned();
+        self.modify_database(collection_id, move |record| {
+            let secret = record.key()?.encrypt(&secret, item_id.as_bytes());
+            let collection = record.collection_mut(&id)?;
+            let item = collection
+                .items
+                .get_mut(&item_id)
+                .ok_or_else(|| error::Error::NoSuchObject(item_id.to_string()))?;
+            item.content_type = content_type;
+            item.modified = modified;
+            collection.modified = modified;
+            record.secrets.insert(item_id, secret);
+            Ok(())
+        })
+        .await
+    }
+
+    async fn set_alias(
+        &self,
+        name: &str,
+        collection_id: Option<&uuid::Uuid>,
+    ) -> Result<(), error::Error> {
+        self.inner.set_alias(name, collection_id).await
+    }
+
+    /// Items of databases are searched in memory, when the wrapped `Storage` searches its own.
+    async fn search_items(
+        &self,
+        attributes: &collections::HashMap<String, String>,
+    ) -> Result<Option<Vec<(uuid::Uuid, uuid::Uuid)>>, error::Error> {
+        let Some(mut found) = self.inner.search_items(attributes).await? else {
+            return Ok(None);
+        };
+        for record in &self.databases {
+            let record = record.clone();
+            let attributes = attributes.clone();
+            found.extend(
+                super::blocking(move || {
+                    let record = record.lock().expect("lock is not poisoned");
+                    record
+                        .collections
+                        .iter()
+                        .flat_map(|collection| {
+                            collection
+                                .items
+                                .values()
+                                .filter(|item| super::has_attributes(&item.attributes, &attributes))
+                                .map(|item| (collection.id, item.id))
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .await,
+            );
+        }
+        Ok(Some(found))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::tests::new_item;
+    use crate::storage::{files, sqlite};
+
+    #[tokio::test]
+    async fn test_kdbx_collection() -> Result<(), error::Error> {
+        let data_dir = tempfile::tempdir()?;
+        let path = data_dir.path().join("Passwords.kdbx");
+        kdbx::tests::write_database(&path);
+        let mut storage = KdbxStorage::new(Box::new(files::FileStorage::open(
+            &data_dir.path().join("keyrings"),
+        )?));
+
+        let collection_id = storage.open(&kdbx::DatabaseConfig {
+            path: path.clone(),
+            ..Default::default()
+        })?;
+        assert!(storage.is_locked(&collection_id).await);
+        let collection = storage.collection(&collection_id).await.unwrap();
+        assert_eq!(collection.label, "Passwords");
+        assert!(collection.items.is_empty());
+
+        assert!(
+            !storage
+                .unlock_collection(&collection_id, b"wrong-password")
+                .await?
+        );
+        assert!(
+            storage
+                .unlock_collection(&collection_id, kdbx::tests::PASSWORD)
+                .await?
+        );
+        let collection = storage.collection(&collection_id).await.unwrap();
+        assert_eq!(collection.items.len(), 1);
+        assert_eq!(
+            storage
+                .read_secret(&collection_id, &kdbx::tests::MAIL)
+                .await?
+                .as_slice(),
+            b"hunter2"
+        );
+
+        // The subgroup is only found once unlocked, and is locked along with the root group.
+        let chat_id = kdbx::tests::CHAT_GROUP;
+        assert_eq!(
+            storage.linked_collections(&collection_id).await,
+            vec![collection_id, chat_id]
+        );
+        assert_eq!(storage.collections().await.len(), 2);
+        let chat = storage.collection(&chat_id).await.unwrap();
+        assert_eq!(chat.label, "Chat");
+        assert_eq!(chat.items.len(), 1);
+        assert!(!storage.is_locked(&chat_id).await);
+        assert!(matches!(
+            storage.read_secret(&chat_id, &kdbx::tests::MAIL).await,
+            Err(error::Error::NoSuchObject(_))
+        ));
+        let chat_item = new_item("chat-item");
+        storage
+            .create_item(&chat_id, chat_item.clone(), b"chat-secret")
+            .await?;
+        storage.update_collection(&chat_id, "Messages", 4).await?;
+
+        let item = new_item("item");
+        storage
+            .create_item(&collection_id, item.clone(), b"secret")
+            .await?;
+        storage
+            .delete_item(&collection_id, &kdbx::tests::MAIL, 4)
+            .await?;
+        // KeePass fields only hold text, and the failed write leaves no trace.
+        assert!(storage
+            .write_secret(&collection_id, &item.id, b"\xff", "text/plain", 4)
+            .await
+            .is_err());
+        assert!(matches!(
+            storage.delete_collection(&collection_id).await,
+            Err(error::Error::Storage(_))
+        ));
+
+        // Changes were written to the database, not to the data directory.
+        assert!(storage.lock_collection(&collection_id).await?);
+        assert!(storage.is_locked(&chat_id).await);
+        let mut storage = KdbxStorage::new(Box::new(files::FileStorage::open(
+            &data_dir.path().join("keyrings"),
+        )?));
+        assert!(storage.collections().await.is_empty());
+        let collection_id = storage.open(&kdbx::DatabaseConfig {
+            path: path.clone(),
+            ..Default::default()
+        })?;
+        assert!(
+            storage
+                .unlock_collection(&collection_id, kdbx::tests::PASSWORD)
+                .await?
+        );
+        let collection = storage.collection(&collection_id).await.unwrap();
+        assert_eq!(collection.items.len(), 1);
+        assert_eq!(collection.items[&item.id].label, "item");
+        assert_eq!(collection.items[&item.id].attributes, item.attributes);
+        assert!(!collection.items.contains_key(&kdbx::tests::MAIL));
+        assert_eq!(
+            storage
+                .read_secret(&collection_id, &item.id)
+                .await?
+                .as_slice(),
+            b"secret"
+        );
+        let chat = storage.collection(&chat_id).await.unwrap();
+        assert_eq!(chat.label, "Messages");
+        assert_eq!(chat.items.len(), 2);
+        assert_eq!(
+            storage
+                .read_secret(&chat_id, &chat_item.id)
+                .await?
+                .as_slice(),
+            b"chat-secret"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_search_items_with_sqlite() -> Result<(), error::Error> {
+        let data_dir = tempfile::tempdir()?;
+        let path = data_dir.path().join("Passwords.kdbx");
+        kdbx::tests::write_database(&path);
+        let attributes =
+            collections::HashMap::from([("service".to_owned(), "imap & smtp".to_owned())]);
+
+        // Without an index in the wrapped storage, the server searches on its own.
+        let storage = KdbxStorage::new(Box::new(files::FileStorage::open(
+            &data_dir.path().join("keyrings"),
+        )?));
+        assert!(storage.search_items(&attributes).await?.is_none());
+
+        let mut storage = KdbxStorage::new(Box::new(sqlite::SqliteStorage::open(
+            &data_dir.path().join("sqlite"),
+        )?));
+        let collection_id = storage.open(&kdbx::DatabaseConfig {
+            path,
+            ..Default::default()
+        })?;
+        assert_eq!(storage.search_items(&attributes).await?, Some(Vec::new()));
+
+        storage
+            .unlock_collection(&collection_id, kdbx::tests::PASSWORD)
+            .await?;
+        assert_eq!(
+            storage.search_items(&attributes).await?,
+            Some(vec![(collection_id, kdbx::tests::MAIL)])
+        );
+
+        Ok(())
+    }
+}
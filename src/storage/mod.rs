@@ -0,0 +1,1500 @@
+//! Persistent storage for collections, their items, and aliases.
+//!
+//! The D-Bus objects only go through the `Storage` trait, so backends can be swapped.
+//! `MemoryStorage` keeps everything in memory only, while `files::FileStorage` writes it
+//! to files, and `sqlite::SqliteStorage` to a SQLite database. All of them keep every
+//! collection in memory, loaded when opened, and only keep a change once it's written.
+//! As writing a change and deriving a key block for a while, their methods run on threads
+//! where blocking is fine, instead of those handling D-Bus calls.
+//!
+//! Item secrets are encrypted at rest with AES-256-GCM, using a key derived from
+//! the collection's master password with Argon2id. The key is only kept in memory
+//! while the collection is unlocked, and secrets are decrypted on demand, so locking
+//! a collection leaves no decrypted secrets behind.
+//!
+//! Collections explicitly created without a password are unlocked with an empty one. An
+//! empty password can't be made harder to guess, so their keys are derived with the
+//! cheapest Argon2id parameters: their secrets are only as safe as their file.
+//!
+//! Ephemeral collections, and aliases pointing to them, are never written, so they are
+//! gone once the process exits.
+//!
+//! `keepass::KdbxStorage` wraps any `Storage` to also serve KeePass databases as
+//! collections, which are written back to their own file instead, see `kdbx`.
+use std::collections;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::panic;
+use std::path;
+use std::sync;
+
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+
+use crate::error;
+
+pub mod files;
+pub mod keepass;
+pub mod sqlite;
+
+/// Known plaintext encrypted with a collection's key to verify master passwords.
+const VERIFIER_PLAINTEXT: &[u8] = b"secret-service-server";
+
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct StoredItem {
+    /// Executable of the application that created the item, if known.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub application: Option<String>,
+    pub attributes: collections::HashMap<String, String>,
+    /// Content type of the secret, as given by the client that stored it.
+    #[serde(default = "default_content_type")]
+    pub content_type: String,
+    pub created: u64,
+    pub id: uuid::Uuid,
+    pub label: String,
+    pub modified: u64,
+}
+
+/// Content type assumed for secrets stored before content types were kept.
+fn default_content_type() -> String {
+    "text/plain; charset=utf8".to_owned()
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct StoredCollection {
+    pub created: u64,
+    pub id: uuid::Uuid,
+    pub items: collections::HashMap<uuid::Uuid, StoredItem>,
+    pub label: String,
+    pub modified: u64,
+}
+
+/// Argon2id parameters used to derive a collection's key from its master password.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+struct KdfParameters {
+    memory_cost: u32,
+    parallelism: u32,
+    salt: Vec<u8>,
+    time_cost: u32,
+}
+
+impl KdfParameters {
+    fn generate() -> Self {
+        let mut salt = vec![0u8; argon2::RECOMMENDED_SALT_LEN];
+        getrandom::getrandom(&mut salt).expect("system random number generator unavailable");
+
+        Self {
+            memory_cost: argon2::Params::DEFAULT_M_COST,
+            parallelism: argon2::Params::DEFAULT_P_COST,
+            salt,
+            time_cost: argon2::Params::DEFAULT_T_COST,
+        }
+    }
+
+    /// Parameters for collections without a password, as cheap as Argon2id allows.
+    fn passwordless() -> Self {
+        Self {
+            memory_cost: argon2::Params::MIN_M_COST,
+            parallelism: argon2::Params::MIN_P_COST,
+            time_cost: argon2::Params::MIN_T_COST,
+            ..Self::generate()
+        }
+    }
+
+    fn derive_key(&self, password: &[u8]) -> Result<CollectionKey, error::Error> {
+        let params =
+            argon2::Params::new(self.memory_cost, self.time_cost, self.parallelism, Some(32))
+                .map_err(|e| {
+                    error::Error::Storage(format!("Invalid key derivation parameters: {e}"))
+                })?;
+
+        let mut key = zeroize::Zeroizing::new([0u8; 32]);
+        argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params)
+            .hash_password_into(password, &self.salt, key.as_mut())
+            .map_err(|e| error::Error::Storage(format!("Failed to derive key: {e}")))?;
+
+        Ok(CollectionKey(key))
+    }
+}
+
+/// Key used to encrypt the secrets of a collection.
+struct CollectionKey(zeroize::Zeroizing<[u8; 32]>);
+
+impl fmt::Debug for CollectionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CollectionKey(..)")
+    }
+}
+
+impl CollectionKey {
+    /// A random key, for collections that don't derive theirs from a password.
+    fn generate() -> Self {
+        let mut key = zeroize::Zeroizing::new([0u8; 32]);
+        getrandom::getrandom(key.as_mut()).expect("system random number generator unavailable");
+        Self(key)
+    }
+
+    /// Encrypt `plaintext`, authenticating `associated_data` along with it.
+    fn encrypt(&self, plaintext: &[u8], associated_data: &[u8]) -> Ciphertext {
+        let mut nonce = [0u8; 12];
+        getrandom::getrandom(&mut nonce).expect("system random number generator unavailable");
+
+        let data = aes_gcm::Aes256Gcm::new(self.0.as_ref().into())
+            .encrypt(
+                &nonce.into(),
+                Payload {
+                    msg: plaintext,
+                    aad: associated_data,
+                },
+            )
+            .expect("plaintext is not too long for AES-GCM");
+
+        Ciphertext {
+            data,
+            nonce: nonce.to_vec(),
+        }
+    }
+
+    /// Decrypt `ciphertext`, or return `None` if it fails to authenticate.
+    fn decrypt(
+        &self,
+        ciphertext: &Ciphertext,
+        associated_data: &[u8],
+    ) -> Option<zeroize::Zeroizing<Vec<u8>>> {
+        if ciphertext.nonce.len() != 12 {
+            return None;
+        }
+
+        aes_gcm::Aes256Gcm::new(self.0.as_ref().into())
+            .decrypt(
+                ciphertext.nonce.as_slice().into(),
+                Payload {
+                    msg: &ciphertext.data,
+                    aad: associated_data,
+                },
+            )
+            .ok()
+            .map(zeroize::Zeroizing::new)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+struct Ciphertext {
+    data: Vec<u8>,
+    nonce: Vec<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+struct ItemRecord {
+    #[serde(flatten)]
+    item: StoredItem,
+    secret: Ciphertext,
+}
+
+/// A collection as written to disk.
+#[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
+struct CollectionFile {
+    created: u64,
+    id: uuid::Uuid,
+    items: collections::HashMap<uuid::Uuid, ItemRecord>,
+    kdf: KdfParameters,
+    label: String,
+    modified: u64,
+    /// Whether the collection was created without a password, so that it's unlocked with
+    /// an empty one.
+    passwordless: bool,
+    verifier: Ciphertext,
+}
+
+impl CollectionFile {
+    fn to_stored(&self) -> StoredCollection {
+        StoredCollection {
+            created: self.created,
+            id: self.id,
+            items: self
+                .items
+                .iter()
+                .map(|(id, record)| (*id, record.item.clone()))
+                .collect(),
+            label: self.label.clone(),
+            modified: self.modified,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct CollectionRecord {
+    /// Ephemeral collections are only kept in memory, and never locked.
+    ephemeral: bool,
+    file: CollectionFile,
+    /// Only set while the collection is unlocked.
+    key: Option<CollectionKey>,
+}
+
+impl CollectionRecord {
+    fn key(&self) -> Result<&CollectionKey, error::Error> {
+        self.key
+            .as_ref()
+            .ok_or_else(|| error::Error::IsLocked(self.file.id.to_string()))
+    }
+}
+
+/// Collections, their items and secrets, and aliases, as the D-Bus objects in `object`
+/// load and save them.
+///
+/// Collections are identified by their id, and items by their id along with that of their
+/// collection. Secrets can only be read and written while their collection is unlocked.
+#[async_trait::async_trait]
+pub trait Storage: fmt::Debug + Send + Sync {
+    async fn aliases(&self) -> collections::HashMap<String, uuid::Uuid>;
+
+    async fn collection(&self, collection_id: &uuid::Uuid) -> Option<StoredCollection>;
+
+    async fn collections(&self) -> Vec<StoredCollection>;
+
+    /// Create a new collection protected by `password`, and leave it unlocked.
+    ///
+    /// Any items in `collection` are ignored, as they have no secret: add them with
+    /// `create_item` instead.
+    async fn create_collection(
+        &self,
+        collection: StoredCollection,
+        password: &[u8],
+    ) -> Result<(), error::Error>;
+
+    /// Create a new collection without a password, which is unlocked with an empty one,
+    /// and leave it unlocked.
+    async fn create_passwordless_collection(
+        &self,
+        collection: StoredCollection,
+    ) -> Result<(), error::Error>;
+
+    /// Create a new collection that is only kept in memory, and can't be locked.
+    async fn create_ephemeral_collection(
+        &self,
+        collection: StoredCollection,
+    ) -> Result<(), error::Error>;
+
+    async fn is_ephemeral(&self, collection_id: &uuid::Uuid) -> bool;
+
+    /// Whether a collection was explicitly made to have no password, so that it's unlocked
+    /// with an empty one without asking the user.
+    async fn is_passwordless(&self, collection_id: &uuid::Uuid) -> bool;
+
+    async fn update_collection(
+        &self,
+        collection_id: &uuid::Uuid,
+        label: &str,
+        modified: u64,
+    ) -> Result<(), error::Error>;
+
+    /// Bump the `modified` timestamp of a collection, which doesn't need to be unlocked.
+    async fn touch_collection(
+        &self,
+        collection_id: &uuid::Uuid,
+        modified: u64,
+    ) -> Result<(), error::Error>;
+
+    /// Delete a collection, with all of its items, and any aliases pointing to it.
+    async fn delete_collection(&self, collection_id: &uuid::Uuid) -> Result<(), error::Error>;
+
+    /// Whether a collection is locked. Collections not in storage are never locked.
+    async fn is_locked(&self, collection_id: &uuid::Uuid) -> bool;
+
+    /// Lock a collection.
+    ///
+    /// Returns whether the collection was locked, which ephemeral collections never are.
+    async fn lock_collection(&self, collection_id: &uuid::Uuid) -> Result<bool, error::Error>;
+
+    /// Unlock a collection with its master password.
+    ///
+    /// Returns whether `password` was correct, in which case the collection is unlocked.
+    async fn unlock_collection(
+        &self,
+        collection_id: &uuid::Uuid,
+        password: &[u8],
+    ) -> Result<bool, error::Error>;
+
+    /// Collections that are locked and unlocked along with a collection, itself included,
+    /// such as those of the groups of a KeePass database. Some of them may only be found
+    /// once the collection is unlocked.
+    async fn linked_collections(&self, collection_id: &uuid::Uuid) -> Vec<uuid::Uuid> {
+        vec![*collection_id]
+    }
+
+    /// Change the master password of a collection from `original` to `password`.
+    ///
+    /// Returns whether `original` was correct. The collection is left locked or
+    /// unlocked, as it was.
+    async fn change_password(
+        &self,
+        collection_id: &uuid::Uuid,
+        original: &[u8],
+        password: &[u8],
+    ) -> Result<bool, error::Error>;
+
+    /// Create an item in an unlocked collection, or replace the item with the same id.
+    ///
+    /// Like every change to an item, it bumps the `modified` timestamp of the collection to
+    /// that of the item.
+    async fn create_item(
+        &self,
+        collection_id: &uuid::Uuid,
+        item: StoredItem,
+        secret: &[u8],
+    ) -> Result<(), error::Error>;
+
+    /// Update the attributes, label, and timestamps of an existing item.
+    async fn write_item(
+        &self,
+        collection_id: &uuid::Uuid,
+        item: StoredItem,
+    ) -> Result<(), error::Error>;
+
+    /// Delete an item, bumping the `modified` timestamp of its collection.
+    async fn delete_item(
+        &self,
+        collection_id: &uuid::Uuid,
+        item_id: &uuid::Uuid,
+        modified: u64,
+    ) -> Result<(), error::Error>;
+
+    /// Read the secret of an item in an unlocked collection.
+    async fn read_secret(
+        &self,
+        collection_id: &uuid::Uuid,
+        item_id: &uuid::Uuid,
+    ) -> Result<zeroize::Zeroizing<Vec<u8>>, error::Error>;
+
+    /// Replace the secret of an item in an unlocked collection, along with its content type
+    /// and `modified` timestamp.
+    async fn write_secret(
+        &self,
+        collection_id: &uuid::Uuid,
+        item_id: &uuid::Uuid,
+        secret: &[u8],
+        content_type: &str,
+        modified: u64,
+    ) -> Result<(), error::Error>;
+
+    /// Point alias `name` to a collection, or remove the alias if `collection_id` is `None`.
+    async fn set_alias(
+        &self,
+        name: &str,
+        collection_id: Option<&uuid::Uuid>,
+    ) -> Result<(), error::Error>;
+
+    /// Find the items having all of `attributes`, as the ids of their collection and
+    /// themselves, if the storage indexes attributes itself. Otherwise, `None` leaves the
+    /// search to the attribute index of the server.
+    async fn search_items(
+        &self,
+        _attributes: &collections::HashMap<String, String>,
+    ) -> Result<Option<Vec<(uuid::Uuid, uuid::Uuid)>>, error::Error> {
+        Ok(None)
+    }
+}
+
+/// Where a storage of this module writes its collections and aliases to.
+trait Backend: fmt::Debug + Send + Sync {
+    /// Write a collection, given its `original` version as last written, or `None` if
+    /// it's new.
+    fn write_collection(
+        &self,
+        original: Option<&CollectionFile>,
+        file: &CollectionFile,
+    ) -> Result<(), error::Error>;
+
+    /// Delete a collection, with all of its items.
+    fn delete_collection(&self, collection_id: &uuid::Uuid) -> Result<(), error::Error>;
+
+    /// Point alias `name` to a collection, or remove the alias if `collection_id` is `None`.
+    ///
+    /// `aliases` are all of the aliases to write once changed, so they can be written at once.
+    fn write_alias(
+        &self,
+        aliases: &collections::HashMap<&String, &uuid::Uuid>,
+        name: &str,
+        collection_id: Option<&uuid::Uuid>,
+    ) -> Result<(), error::Error>;
+
+    /// Find the items having all of `attributes`, as the ids of their collection and
+    /// themselves, or return `None` if the backend doesn't index attributes.
+    fn search_items(
+        &self,
+        _attributes: &collections::HashMap<String, String>,
+    ) -> Option<Result<Vec<(uuid::Uuid, uuid::Uuid)>, error::Error>> {
+        None
+    }
+}
+
+/// Whether an item with `attributes` has all of those in `query`.
+fn has_attributes(
+    attributes: &collections::HashMap<String, String>,
+    query: &collections::HashMap<String, String>,
+) -> bool {
+    query
+        .iter()
+        .all(|(key, value)| attributes.get(key) == Some(value))
+}
+
+/// The backend of a `MemoryStorage`, which doesn't write anything.
+#[derive(Debug)]
+struct Nowhere;
+
+impl Backend for Nowhere {
+    fn write_collection(
+        &self,
+        _original: Option<&CollectionFile>,
+        _file: &CollectionFile,
+    ) -> Result<(), error::Error> {
+        Ok(())
+    }
+
+    fn delete_collection(&self, _collection_id: &uuid::Uuid) -> Result<(), error::Error> {
+        Ok(())
+    }
+
+    fn write_alias(
+        &self,
+        _aliases: &collections::HashMap<&String, &uuid::Uuid>,
+        _name: &str,
+        _collection_id: Option<&uuid::Uuid>,
+    ) -> Result<(), error::Error> {
+        Ok(())
+    }
+}
+
+/// Collections and aliases, as kept in memory by the storages of this module, which write
+/// every change through to their `Backend` before keeping it.
+#[derive(Debug)]
+struct Collections {
+    aliases: sync::Mutex<collections::HashMap<String, uuid::Uuid>>,
+    backend: Box<dyn Backend>,
+    records: sync::Mutex<collections::HashMap<uuid::Uuid, CollectionRecord>>,
+}
+
+/// A storage of this module, which implements `Storage` with its `Collections`.
+trait WriteThrough {
+    fn cache(&self) -> &sync::Arc<Collections>;
+}
+
+impl Collections {
+    /// Collections loaded from `backend`, which all start locked.
+    fn new(
+        files: Vec<CollectionFile>,
+        aliases: collections::HashMap<String, uuid::Uuid>,
+        backend: Box<dyn Backend>,
+    ) -> Self {
+        let records = files
+            .into_iter()
+            .map(|file| {
+                (
+                    file.id,
+                    CollectionRecord {
+                        ephemeral: false,
+                        file,
+                        key: None,
+                    },
+                )
+            })
+            .collect();
+
+        Self {
+            aliases: sync::Mutex::new(aliases),
+            backend,
+            records: sync::Mutex::new(records),
+        }
+    }
+
+    /// Derive the key of a collection from `password`, or return `None` if it's not the
+    /// collection's master password.
+    fn derive_verified_key(
+        &self,
+        collection_id: &uuid::Uuid,
+        password: &[u8],
+    ) -> Result<Option<CollectionKey>, error::Error> {
+        let (kdf, verifier) = {
+            let records = self.records.lock().expect("lock is not poisoned");
+            let record = records
+                .get(collection_id)
+                .ok_or_else(|| error::Error::NoSuchObject(collection_id.to_string()))?;
+            (record.file.kdf.clone(), record.file.verifier.clone())
+        };
+
+        // Key derivation is slow by design, so avoid holding the lock while it runs.
+        let key = kdf.derive_key(password)?;
+
+        Ok(key
+            .decrypt(&verifier, collection_id.as_bytes())
+            .is_some_and(|plaintext| plaintext.as_slice() == VERIFIER_PLAINTEXT)
+            .then_some(key))
+    }
+
+    /// Apply `modify` to a collection, and only keep the changes if they are written.
+    fn modify_collection<F>(
+        &self,
+        collection_id: &uuid::Uuid,
+        modify: F,
+    ) -> Result<(), error::Error>
+    where
+        F: FnOnce(&mut CollectionRecord) -> Result<(), error::Error>,
+    {
+        let mut records = self.records.lock().expect("lock is not poisoned");
+        let record = records
+            .get_mut(collection_id)
+            .ok_or_else(|| error::Error::NoSuchObject(collection_id.to_string()))?;
+
+        let original_file = record.file.clone();
+        let modified = modify(record).and_then(|_| {
+            // Nothing to write, like when the timestamp of a collection is bumped to the
+            // one it was given along with a change to one of its items.
+            if record.file == original_file {
+                return Ok(());
+            }
+            self.write_record(Some(&original_file), record)
+        });
+        if let Err(e) = modified {
+            record.file = original_file;
+            return Err(e);
+        }
+
+        Ok(())
+    }
+
+    /// Write a collection to the backend, unless it's ephemeral.
+    fn write_record(
+        &self,
+        original: Option<&CollectionFile>,
+        record: &CollectionRecord,
+    ) -> Result<(), error::Error> {
+        if record.ephemeral {
+            return Ok(());
+        }
+        self.backend.write_collection(original, &record.file)
+    }
+
+    /// Keep `collection` in memory, unlocked with `key`, and write it to the backend.
+    fn insert_collection(
+        &self,
+        collection: StoredCollection,
+        kdf: KdfParameters,
+        key: CollectionKey,
+        passwordless: bool,
+        ephemeral: bool,
+    ) -> Result<(), error::Error> {
+        let file = CollectionFile {
+            created: collection.created,
+            id: collection.id,
+            items: collections::HashMap::new(),
+            label: collection.label,
+            modified: collection.modified,
+            passwordless,
+            verifier: key.encrypt(VERIFIER_PLAINTEXT, collection.id.as_bytes()),
+            kdf,
+        };
+
+        let record = CollectionRecord {
+            ephemeral,
+            file,
+            key: Some(key),
+        };
+
+        let mut records = self.records.lock().expect("lock is not poisoned");
+        self.write_record(None, &record)?;
+        records.insert(record.file.id, record);
+
+        Ok(())
+    }
+
+    /// Write a change of alias `name` to the backend, given all of the `aliases` once
+    /// changed. Aliases pointing to ephemeral collections are not written.
+    fn write_alias(
+        &self,
+        aliases: &collections::HashMap<String, uuid::Uuid>,
+        name: &str,
+        collection_id: Option<&uuid::Uuid>,
+    ) -> Result<(), error::Error> {
+        let records = self.records.lock().expect("lock is not poisoned");
+        let is_persistent =
+            |id: &uuid::Uuid| !records.get(id).is_some_and(|record| record.ephemeral);
+
+        let persistent_aliases = aliases.iter().filter(|(_, id)| is_persistent(id)).collect();
+        self.backend.write_alias(
+            &persistent_aliases,
+            name,
+            collection_id.filter(|id| is_persistent(id)),
+        )
+    }
+
+    fn aliases(&self) -> collections::HashMap<String, uuid::Uuid> {
+        self.aliases.lock().expect("lock is not poisoned").clone()
+    }
+
+    fn collection(&self, collection_id: &uuid::Uuid) -> Option<StoredCollection> {
+        self.records
+            .lock()
+            .expect("lock is not poisoned")
+            .get(collection_id)
+            .map(|record| record.file.to_stored())
+    }
+
+    fn all(&self) -> Vec<StoredCollection> {
+        self.records
+            .lock()
+            .expect("lock is not poisoned")
+            .values()
+            .map(|record| record.file.to_stored())
+            .collect()
+    }
+
+    /// Find the items having all of `attributes` through the backend, if it indexes them,
+    /// along with those of ephemeral collections, which it never sees.
+    fn search_items(
+        &self,
+        attributes: &collections::HashMap<String, String>,
+    ) -> Result<Option<Vec<(uuid::Uuid, uuid::Uuid)>>, error::Error> {
+        let Some(found) = self.backend.search_items(attributes) else {
+            return Ok(None);
+        };
+        let mut found = found?;
+
+        let records = self.records.lock().expect("lock is not poisoned");
+        for record in records.values().filter(|record| record.ephemeral) {
+            found.extend(
+                record
+                    .file
+                    .items
+                    .values()
+                    .filter(|item| has_attributes(&item.item.attributes, attributes))
+                    .map(|item| (record.file.id, item.item.id)),
+            );
+        }
+        Ok(Some(found))
+    }
+
+    fn create_collection(
+        &self,
+        collection: StoredCollection,
+        password: &[u8],
+    ) -> Result<(), error::Error> {
+        let kdf = KdfParameters::generate();
+        let key = kdf.derive_key(password)?;
+
+        self.insert_collection(collection, kdf, key, false, false)
+    }
+
+    fn create_passwordless_collection(
+        &self,
+        collection: StoredCollection,
+    ) -> Result<(), error::Error> {
+        let kdf = KdfParameters::passwordless();
+        let key = kdf.derive_key(b"")?;
+
+        self.insert_collection(collection, kdf, key, true, false)
+    }
+
+    /// Its secrets are still encrypted, with a random key instead of one derived from a
+    /// password.
+    fn create_ephemeral_collection(
+        &self,
+        collection: StoredCollection,
+    ) -> Result<(), error::Error> {
+        self.insert_collection(
+            collection,
+            KdfParameters::generate(),
+            CollectionKey::generate(),
+            false,
+            true,
+        )
+    }
+
+    fn is_ephemeral(&self, collection_id: &uuid::Uuid) -> bool {
+        self.records
+            .lock()
+            .expect("lock is not poisoned")
+            .get(collection_id)
+            .is_some_and(|record| record.ephemeral)
+    }
+
+    fn is_passwordless(&self, collection_id: &uuid::Uuid) -> bool {
+        self.records
+            .lock()
+            .expect("lock is not poisoned")
+            .get(collection_id)
+            .is_some_and(|record| record.file.passwordless)
+    }
+
+    fn update_collection(
+        &self,
+        collection_id: &uuid::Uuid,
+        label: &str,
+        modified: u64,
+    ) -> Result<(), error::Error> {
+        self.modify_collection(collection_id, |record| {
+            record.file.label = label.to_owned();
+            record.file.modified = modified;
+            Ok(())
+        })
+    }
+
+    fn touch_collection(
+        &self,
+        collection_id: &uuid::Uuid,
+        modified: u64,
+    ) -> Result<(), error::Error> {
+        self.modify_collection(collection_id, |record| {
+            record.file.modified = modified;
+            Ok(())
+        })
+    }
+
+    fn delete_collection(&self, collection_id: &uuid::Uuid) -> Result<(), error::Error> {
+        let mut records = self.records.lock().expect("lock is not poisoned");
+        if records
+            .get(collection_id)
+            .is_some_and(|record| !record.ephemeral)
+        {
+            self.backend.delete_collection(collection_id)?;
+        }
+        records.remove(collection_id);
+        drop(records);
+
+        let mut aliases = self.aliases.lock().expect("lock is not poisoned");
+        let names: Vec<String> = aliases
+            .iter()
+            .filter(|(_, id)| *id == collection_id)
+            .map(|(name, _)| name.clone())
+            .collect();
+        for name in names {
+            let mut new_aliases = aliases.clone();
+            new_aliases.remove(&name);
+            self.write_alias(&new_aliases, &name, None)?;
+            *aliases = new_aliases;
+        }
+
+        Ok(())
+    }
+
+    fn is_locked(&self, collection_id: &uuid::Uuid) -> bool {
+        self.records
+            .lock()
+            .expect("lock is not poisoned")
+            .get(collection_id)
+            .is_some_and(|record| record.key.is_none())
+    }
+
+    /// Locking forgets the key of the collection.
+    fn lock_collection(&self, collection_id: &uuid::Uuid) -> Result<bool, error::Error> {
+        let mut records = self.records.lock().expect("lock is not poisoned");
+        let record = records
+            .get_mut(collection_id)
+            .ok_or_else(|| error::Error::NoSuchObject(collection_id.to_string()))?;
+        if record.ephemeral {
+            return Ok(false);
+        }
+        record.key = None;
+
+        Ok(true)
+    }
+
+    fn unlock_collection(
+        &self,
+        collection_id: &uuid::Uuid,
+        password: &[u8],
+    ) -> Result<bool, error::Error> {
+        let Some(key) = self.derive_verified_key(collection_id, password)? else {
+            return Ok(false);
+        };
+
+        let mut records = self.records.lock().expect("lock is not poisoned");
+        let record = records
+            .get_mut(collection_id)
+            .ok_or_else(|| error::Error::NoSuchObject(collection_id.to_string()))?;
+        record.key = Some(key);
+
+        Ok(true)
+    }
+
+    /// All of the secrets of the collection are encrypted again with a newly derived key.
+    fn change_password(
+        &self,
+        collection_id: &uuid::Uuid,
+        original: &[u8],
+        password: &[u8],
+    ) -> Result<bool, error::Error> {
+        let Some(original_key) = self.derive_verified_key(collection_id, original)? else {
+            return Ok(false);
+        };
+        let kdf = KdfParameters::generate();
+        let key = kdf.derive_key(password)?;
+
+        let mut records = self.records.lock().expect("lock is not poisoned");
+        let record = records
+            .get_mut(collection_id)
+            .ok_or_else(|| error::Error::NoSuchObject(collection_id.to_string()))?;
+
+        let mut file = record.file.clone();
+        for (item_id, item_record) in file.items.iter_mut() {
+            let secret = original_key
+                .decrypt(&item_record.secret, item_id.as_bytes())
+                .ok_or_else(|| {
+                    error::Error::Storage(format!("Failed to decrypt secret of item '{item_id}'"))
+                })?;
+            item_record.secret = key.encrypt(&secret, item_id.as_bytes());
+        }
+        file.verifier = key.encrypt(VERIFIER_PLAINTEXT, collection_id.as_bytes());
+        file.kdf = kdf;
+        file.passwordless = false;
+
+        let original_file = std::mem::replace(&mut record.file, file);
+        if let Err(e) = self.write_record(Some(&original_file), record) {
+            record.file = original_file;
+            return Err(e);
+        }
+        if record.key.is_some() {
+            record.key = Some(key);
+        }
+
+        Ok(true)
+    }
+
+    /// The collection and the item are written at once.
+    fn create_item(
+        &self,
+        collection_id: &uuid::Uuid,
+        item: StoredItem,
+        secret: &[u8],
+    ) -> Result<(), error::Error> {
+        self.modify_collection(collection_id, |record| {
+            let secret = record.key()?.encrypt(secret, item.id.as_bytes());
+            record.file.modified = item.modified;
+            record
+                .file
+                .items
+                .insert(item.id, ItemRecord { item, secret });
+            Ok(())
+        })
+    }
+
+    fn write_item(&self, collection_id: &uuid::Uuid, item: StoredItem) -> Result<(), error::Error> {
+        self.modify_collection(collection_id, |record| {
+            record.file.modified = item.modified;
+            let record = record
+                .file
+                .items
+                .get_mut(&item.id)
+                .ok_or_else(|| error::Error::NoSuchObject(item.id.to_string()))?;
+            record.item = item;
+            Ok(())
+        })
+    }
+
+    fn delete_item(
+        &self,
+        collection_id: &uuid::Uuid,
+        item_id: &uuid::Uuid,
+        modified: u64,
+    ) -> Result<(), error::Error> {
+        self.modify_collection(collection_id, |record| {
+            record.file.items.remove(item_id);
+            record.file.modified = modified;
+            Ok(())
+        })
+    }
+
+    /// Secrets are decrypted on demand, and never kept decrypted in memory.
+    fn read_secret(
+        &self,
+        collection_id: &uuid::Uuid,
+        item_id: &uuid::Uuid,
+    ) -> Result<zeroize::Zeroizing<Vec<u8>>, error::Error> {
+        let records = self.records.lock().expect("lock is not poisoned");
+        let record = records
+            .get(collection_id)
+            .ok_or_else(|| error::Error::NoSuchObject(collection_id.to_string()))?;
+        let item_record = record
+            .file
+            .items
+            .get(item_id)
+            .ok_or_else(|| error::Error::NoSuchObject(item_id.to_string()))?;
+
+        record
+            .key()?
+            .decrypt(&item_record.secret, item_id.as_bytes())
+            .ok_or_else(|| {
+                error::Error::Storage(format!("Failed to decrypt secret of item '{item_id}'"))
+            })
+    }
+
+    fn write_secret(
+        &self,
+        collection_id: &uuid::Uuid,
+        item_id: &uuid::Uuid,
+        secret: &[u8],
+        content_type: &str,
+        modified: u64,
+    ) -> Result<(), error::Error> {
+        self.modify_collection(collection_id, |record| {
+            let secret = record.key()?.encrypt(secret, item_id.as_bytes());
+            let item_record = record
+                .file
+                .items
+                .get_mut(item_id)
+                .ok_or_else(|| error::Error::NoSuchObject(item_id.to_string()))?;
+            item_record.secret = secret;
+            item_record.item.content_type = content_type.to_owned();
+            item_record.item.modified = modified;
+            record.file.modified = modified;
+            Ok(())
+        })
+    }
+
+    fn set_alias(
+        &self,
+        name: &str,
+        collection_id: Option<&uuid::Uuid>,
+    ) -> Result<(), error::Error> {
+        let mut aliases = self.aliases.lock().expect("lock is not poisoned");
+        let mut new_aliases = aliases.clone();
+        match collection_id {
+            Some(collection_id) => new_aliases.insert(name.to_owned(), *collection_id),
+            None => new_aliases.remove(name),
+        };
+
+        self.write_alias(&new_aliases, name, collection_id)?;
+        *aliases = new_aliases;
+
+        Ok(())
+    }
+}
+
+/// Run `f` on a thread where blocking is fine, as writing changes and deriving keys take a
+/// while, and would otherwise hold up the handling of every other D-Bus call.
+async fn blocking<T, F>(f: F) -> T
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .unwrap_or_else(|e| panic::resume_unwind(e.into_panic()))
+}
+
+/// Every method runs by `blocking`, including those only reading collections, as the
+/// collections are locked while changes to them are written.
+#[async_trait::async_trait]
+impl<T> Storage for T
+where
+    T: WriteThrough + fmt::Debug + Send + Sync,
+{
+    async fn aliases(&self) -> collections::HashMap<String, uuid::Uuid> {
+        let cache = self.cache().clone();
+        blocking(move || cache.aliases()).await
+    }
+
+    async fn collection(&self, collection_id: &uuid::Uuid) -> Option<StoredCollection> {
+        let cache = self.cache().clone();
+        let collection_id = *collection_id;
+        blocking(move || cache.collection(&collection_id)).await
+    }
+
+    async fn collections(&self) -> Vec<StoredCollection> {
+        let cache = self.cache().clone();
+        blocking(move || cache.all()).await
+    }
+
+    async fn create_collection(
+        &self,
+        collection: StoredCollection,
+        password: &[u8],
+    ) -> Result<(), error::Error> {
+        let cache = self.cache().clone();
+        let password = zeroize::Zeroizing::new(password.to_vec());
+        blocking(move || cache.create_collection(collection, &password)).await
+    }
+
+    async fn create_passwordless_collection(
+        &self,
+        collection: StoredCollection,
+    ) -> Result<(), error::Error> {
+        let cache = self.cache().clone();
+        blocking(move || cache.create_passwordless_collection(collection)).await
+    }
+
+    async fn create_ephemeral_collection(
+        &self,
+        collection: StoredCollection,
+    ) -> Result<(), error::Error> {
+        let cache = self.cache().clone();
+        blocking(move || cache.create_ephemeral_collection(collection)).await
+    }
+
+    async fn is_ephemeral(&self, collection_id: &uuid::Uuid) -> bool {
+        let cache = self.cache().clone();
+        let collection_id = *collection_id;
+        blocking(move || cache.is_ephemeral(&collection_id)).await
+    }
+
+    async fn is_passwordless(&self, collection_id: &uuid::Uuid) -> bool {
+        let cache = self.cache().clone();
+        let collection_id = *collection_id;
+        blocking(move || cache.is_passwordless(&collection_id)).await
+    }
+
+    async fn update_collection(
+        &self,
+        collection_id: &uuid::Uuid,
+        label: &str,
+        modified: u64,
+    ) -> Result<(), error::Error> {
+        let cache = self.cache().clone();
+        let (collection_id, label) = (*collection_id, label.to_owned());
+        blocking(move || cache.update_collection(&collection_id, &label, modified)).await
+    }
+
+    async fn touch_collection(
+        &self,
+        collection_id: &uuid::Uuid,
+        modified: u64,
+    ) -> Result<(), error::Error> {
+        let cache = self.cache().clone();
+        let collection_id = *collection_id;
+        blocking(move || cache.touch_collection(&collection_id, modified)).await
+    }
+
+    async fn delete_collection(&self, collection_id: &uuid::Uuid) -> Result<(), error::Error> {
+        let cache = self.cache().clone();
+        let collection_id = *collection_id;
+        blocking(move || cache.delete_collection(&collection_id)).await
+    }
+
+    async fn is_locked(&self, collection_id: &uuid::Uuid) -> bool {
+        let cache = self.cache().clone();
+        let collection_id = *collection_id;
+        blocking(move || cache.is_locked(&collection_id)).await
+    }
+
+    async fn lock_collection(&self, collection_id: &uuid::Uuid) -> Result<bool, error::Error> {
+        let cache = self.cache().clone();
+        let collection_id = *collection_id;
+        blocking(move || cache.lock_collection(&collection_id)).await
+    }
+
+    async fn unlock_collection(
+        &self,
+        collection_id: &uuid::Uuid,
+        password: &[u8],
+    ) -> Result<bool, error::Error> {
+        let cache = self.cache().clone();
+        let collection_id = *collection_id;
+        let password = zeroize::Zeroizing::new(password.to_vec());
+        blocking(move || cache.unlock_collection(&collection_id, &password)).await
+    }
+
+    async fn change_password(
+        &self,
+        collection_id: &uuid::Uuid,
+        original: &[u8],
+        password: &[u8],
+    ) -> Result<bool, error::Error> {
+        let cache = self.cache().clone();
+        let collection_id = *collection_id;
+        let original = zeroize::Zeroizing::new(original.to_vec());
+        let password = zeroize::Zeroizing::new(password.to_vec());
+        blocking(move || cache.change_password(&collection_id, &original, &password)).await
+    }
+
+    async fn create_item(
+        &self,
+        collection_id: &uuid::Uuid,
+        item: StoredItem,
+        secret: &[u8],
+    ) -> Result<(), error::Error> {
+        let cache = self.cache().clone();
+        let collection_id = *collection_id;
+        let secret = zeroize::Zeroizing::new(secret.to_vec());
+        blocking(move || cache.create_item(&collection_id, item, &secret)).await
+    }
+
+    async fn write_item(
+        &self,
+        collection_id: &uuid::Uuid,
+        item: StoredItem,
+    ) -> Result<(), error::Error> {
+        let cache = self.cache().clone();
+        let collection_id = *collection_id;
+        blocking(move || cache.write_item(&collection_id, item)).await
+    }
+
+    async fn delete_item(
+        &self,
+        collection_id: &uuid::Uuid,
+        item_id: &uuid::Uuid,
+        modified: u64,
+    ) -> Result<(), error::Error> {
+        let cache = self.cache().clone();
+        let (collection_id, item_id) = (*collection_id, *item_id);
+        blocking(move || cache.delete_item(&collection_id, &item_id, modified)).await
+    }
+
+    async fn read_secret(
+        &self,
+        collection_id: &uuid::Uuid,
+        item_id: &uuid::Uuid,
+    ) -> Result<zeroize::Zeroizing<Vec<u8>>, error::Error> {
+        let cache = self.cache().clone();
+        let (collection_id, item_id) = (*collection_id, *item_id);
+        blocking(move || cache.read_secret(&collection_id, &item_id)).await
+    }
+
+    async fn write_secret(
+        &self,
+        collection_id: &uuid::Uuid,
+        item_id: &uuid::Uuid,
+        secret: &[u8],
+        content_type: &str,
+        modified: u64,
+    ) -> Result<(), error::Error> {
+        let cache = self.cache().clone();
+        let (collection_id, item_id) = (*collection_id, *item_id);
+        let secret = zeroize::Zeroizing::new(secret.to_vec());
+        let content_type = content_type.to_owned();
+        blocking(move || {
+            cache.write_secret(&collection_id, &item_id, &secret, &content_type, modified)
+        })
+        .await
+    }
+
+    async fn set_alias(
+        &self,
+        name: &str,
+        collection_id: Option<&uuid::Uuid>,
+    ) -> Result<(), error::Error> {
+        let cache = self.cache().clone();
+        let (name, collection_id) = (name.to_owned(), collection_id.copied());
+        blocking(move || cache.set_alias(&name, collection_id.as_ref())).await
+    }
+
+    async fn search_items(
+        &self,
+        attributes: &collections::HashMap<String, String>,
+    ) -> Result<Option<Vec<(uuid::Uuid, uuid::Uuid)>>, error::Error> {
+        let cache = self.cache().clone();
+        let attributes = attributes.clone();
+        blocking(move || cache.search_items(&attributes)).await
+    }
+}
+
+/// A `Storage` that keeps everything in memory only, so it's gone once the process exits.
+/// Other than that, collections behave as usual, and can be locked.
+#[derive(Debug)]
+pub struct MemoryStorage(sync::Arc<Collections>);
+
+impl MemoryStorage {
+    /// A `MemoryStorage` without any collections or aliases.
+    pub fn new() -> Self {
+        Self(sync::Arc::new(Collections::new(
+            Vec::new(),
+            collections::HashMap::new(),
+            Box::new(Nowhere),
+        )))
+    }
+}
+
+impl Default for MemoryStorage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WriteThrough for MemoryStorage {
+    fn cache(&self) -> &sync::Arc<Collections> {
+        &self.0
+    }
+}
+
+/// Replace the file at `path` with `contents`.
+///
+/// The contents are first written and synced to a temporary file next to `path`,
+/// which is then renamed over `path`.
+pub fn write_atomically(path: &path::Path, contents: &[u8]) -> io::Result<()> {
+    let temporary_path = path.with_extension("tmp");
+
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&temporary_path)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+
+    fs::rename(&temporary_path, path)?;
+
+    if let Some(parent) = path.parent() {
+        fs::File::open(parent)?.sync_all()?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    pub fn new_collection(label: &str) -> StoredCollection {
+        StoredCollection {
+            created: 1,
+            id: uuid::Uuid::new_v4(),
+            items: collections::HashMap::new(),
+            label: label.to_owned(),
+            modified: 1,
+        }
+    }
+
+    pub fn new_item(label: &str) -> StoredItem {
+        StoredItem {
+            application: Some("/usr/bin/application".to_owned()),
+            attributes: collections::HashMap::from([("key".to_owned(), "value".to_owned())]),
+            content_type: "text/plain".to_owned(),
+            created: 2,
+            id: uuid::Uuid::new_v4(),
+            label: label.to_owned(),
+            modified: 3,
+        }
+    }
+
+    /// Check that changes made to the storage opened at a path with `open` are found in it
+    /// when reopened.
+    pub async fn check_changes_are_loaded_when_reopened<S: Storage>(
+        open: fn(&path::Path) -> Result<S, error::Error>,
+    ) -> Result<(), error::Error> {
+        let data_dir = tempfile::tempdir()?;
+        let storage = open(data_dir.path())?;
+
+        let mut collection = new_collection("collection");
+        let mut item = new_item("item");
+        let deleted_item = new_item("deleted-item");
+        storage
+            .create_collection(collection.clone(), b"password")
+            .await?;
+        storage
+            .create_item(&collection.id, item.clone(), b"secret")
+            .await?;
+        storage
+            .create_item(&collection.id, deleted_item.clone(), b"deleted-secret")
+            .await?;
+        storage
+            .delete_item(&collection.id, &deleted_item.id, 3)
+            .await?;
+        item.label = "new-item-label".to_owned();
+        storage.write_item(&collection.id, item.clone()).await?;
+        storage
+            .write_secret(
+                &collection.id,
+                &item.id,
+                &[0x00, 0xff, 0x80, 0x0a],
+                "application/octet-stream",
+                4,
+            )
+            .await?;
+        storage
+            .update_collection(&collection.id, "new-label", 4)
+            .await?;
+        storage.touch_collection(&collection.id, 5).await?;
+        storage.set_alias("default", Some(&collection.id)).await?;
+
+        let reopened_storage = open(data_dir.path())?;
+
+        collection.label = "new-label".to_owned();
+        collection.modified = 5;
+        item.content_type = "application/octet-stream".to_owned();
+        item.modified = 4;
+        collection.items.insert(item.id, item.clone());
+        assert_eq!(
+            reopened_storage.collections().await,
+            vec![collection.clone()]
+        );
+        assert_eq!(
+            reopened_storage.aliases().await,
+            collections::HashMap::from([("default".to_owned(), collection.id)])
+        );
+
+        assert!(reopened_storage.is_locked(&collection.id).await);
+        assert!(
+            reopened_storage
+                .unlock_collection(&collection.id, b"password")
+                .await?
+        );
+        assert_eq!(
+            reopened_storage
+                .read_secret(&collection.id, &item.id)
+                .await?
+                .as_slice(),
+            &[0x00, 0xff, 0x80, 0x0a]
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_memory_storage() -> Result<(), error::Error> {
+        let storage: sync::Arc<dyn Storage> = sync::Arc::new(MemoryStorage::new());
+
+        let collection = new_collection("collection");
+        let item = new_item("item");
+        storage
+            .create_collection(collection.clone(), b"password")
+            .await?;
+        storage
+            .create_item(&collection.id, item.clone(), b"secret")
+            .await?;
+        storage.set_alias("default", Some(&collection.id)).await?;
+
+        assert!(storage.lock_collection(&collection.id).await?);
+        assert!(storage.is_locked(&collection.id).await);
+        assert!(
+            !storage
+                .unlock_collection(&collection.id, b"wrong-password")
+                .await?
+        );
+        assert!(
+            storage
+                .unlock_collection(&collection.id, b"password")
+                .await?
+        );
+        assert_eq!(
+            storage
+                .read_secret(&collection.id, &item.id)
+                .await?
+                .as_slice(),
+            b"secret"
+        );
+
+        storage.delete_collection(&collection.id).await?;
+        assert!(storage.collections().await.is_empty());
+        assert!(storage.aliases().await.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_item_content_type_defaults_to_text() -> Result<(), error::Error> {
+        let item: StoredItem = serde_json::from_str(
+            r#"{"attributes": {}, "created": 1, "id": "67e55044-10b1-426f-9247-bb680e5fe0c8", "label": "item", "modified": 1}"#,
+        )?;
+
+        assert_eq!(item.content_type, "text/plain; charset=utf8");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_lock_and_unlock_collection() -> Result<(), error::Error> {
+        let storage = MemoryStorage::new();
+
+        let collection = new_collection("collection");
+        let item = new_item("item");
+        storage
+            .create_collection(collection.clone(), b"password")
+            .await?;
+        storage
+            .create_item(&collection.id, item.clone(), b"secret")
+            .await?;
+        assert!(!storage.is_locked(&collection.id).await);
+
+        assert!(storage.lock_collection(&collection.id).await?);
+
+        assert!(storage.is_locked(&collection.id).await);
+        assert!(matches!(
+            storage.read_secret(&collection.id, &item.id).await,
+            Err(error::Error::IsLocked(_))
+        ));
+        assert!(matches!(
+            storage
+                .write_secret(&collection.id, &item.id, b"new-secret", "text/plain", 4)
+                .await,
+            Err(error::Error::IsLocked(_))
+        ));
+        assert!(matches!(
+            storage
+                .create_item(&collection.id, new_item("other-item"), b"other-secret")
+                .await,
+            Err(error::Error::IsLocked(_))
+        ));
+
+        assert!(
+            !storage
+                .unlock_collection(&collection.id, b"wrong-password")
+                .await?
+        );
+        assert!(storage.is_locked(&collection.id).await);
+
+        assert!(
+            storage
+                .unlock_collection(&collection.id, b"password")
+                .await?
+        );
+        assert!(!storage.is_locked(&collection.id).await);
+        assert_eq!(
+            storage
+                .read_secret(&collection.id, &item.id)
+                .await?
+                .as_slice(),
+            b"secret"
+        );
+
+        // The failed writes while locked must have left no trace.
+        assert_eq!(storage.collections().await[0].items.len(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tampered_secret_fails_to_decrypt() -> Result<(), error::Error> {
+        let storage = MemoryStorage::new();
+
+        let collection = new_collection("collection");
+        let item = new_item("item");
+        let other_item = new_item("other-item");
+        storage.create_collection(collection.clone(), b"").await?;
+        storage
+            .create_item(&collection.id, item.clone(), b"secret")
+            .await?;
+        storage
+            .create_item(&collection.id, other_item.clone(), b"other-secret")
+            .await?;
+
+        // Swap the secrets of both items.
+        {
+            let mut collections = storage.0.records.lock().unwrap();
+            let items = &mut collections.get_mut(&collection.id).unwrap().file.items;
+            let secret = items[&item.id].secret.clone();
+            let other_secret = items[&other_item.id].secret.clone();
+            items.get_mut(&item.id).unwrap().secret = other_secret;
+            items.get_mut(&other_item.id).unwrap().secret = secret;
+        }
+
+        assert!(matches!(
+            storage.read_secret(&collection.id, &item.id).await,
+            Err(error::Error::Storage(_))
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_create_item_in_missing_collection() -> Result<(), error::Error> {
+        let storage = MemoryStorage::new();
+
+        let result = storage
+            .create_item(&uuid::Uuid::new_v4(), new_item("item"), b"secret")
+            .await;
+
+        assert!(matches!(result, Err(error::Error::NoSuchObject(_))));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,721 @@
+//! Collections and aliases kept in a SQLite database, instead of files.
+//!
+//! Collections, items, attributes and aliases each have their own table, and attributes
+//! are indexed by name and value, so that `SearchItems` looks items up in the database
+//! instead of in memory. Secrets and master password verifiers are kept encrypted with the
+//! key of their collection, exactly as in collection files.
+//!
+//! Instead of replacing a whole collection, every change to it only writes the rows that
+//! differ from its previous version, in a single transaction. So, changing one secret of
+//! a collection with thousands of items writes a single row.
+use std::collections;
+use std::fs;
+use std::os::unix::fs::DirBuilderExt;
+use std::path;
+use std::sync;
+
+use super::{
+    Backend, Ciphertext, CollectionFile, Collections, ItemRecord, KdfParameters, StoredItem,
+    WriteThrough,
+};
+use crate::error;
+
+const DATABASE_FILE: &str = "storage.sqlite3";
+
+const SCHEMA: &str = "
+PRAGMA foreign_keys = ON;
+PRAGMA journal_mode = WAL;
+
+CREATE TABLE IF NOT EXISTS collections (
+    id TEXT PRIMARY KEY NOT NULL,
+    label TEXT NOT NULL,
+    created INTEGER NOT NULL,
+    modified INTEGER NOT NULL,
+    kdf_memory_cost INTEGER NOT NULL,
+    kdf_parallelism INTEGER NOT NULL,
+    kdf_salt BLOB NOT NULL,
+    kdf_time_cost INTEGER NOT NULL,
+    passwordless INTEGER NOT NULL,
+    verifier BLOB NOT NULL,
+    verifier_nonce BLOB NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS items (
+    id TEXT PRIMARY KEY NOT NULL,
+    collection_id TEXT NOT NULL REFERENCES collections (id) ON DELETE CASCADE,
+    label TEXT NOT NULL,
+    application TEXT,
+    content_type TEXT NOT NULL,
+    created INTEGER NOT NULL,
+    modified INTEGER NOT NULL,
+    secret BLOB NOT NULL,
+    secret_nonce BLOB NOT NULL
+);
+CREATE INDEX IF NOT EXISTS items_by_collection ON items (collection_id);
+
+CREATE TABLE IF NOT EXISTS attributes (
+    item_id TEXT NOT NULL REFERENCES items (id) ON DELETE CASCADE,
+    name TEXT NOT NULL,
+    value TEXT NOT NULL,
+    PRIMARY KEY (item_id, name)
+);
+CREATE INDEX IF NOT EXISTS attributes_by_value ON attributes (name, value);
+
+-- Aliases may point to collections that are not in this database, like KeePass databases.
+CREATE TABLE IF NOT EXISTS aliases (
+    name TEXT PRIMARY KEY NOT NULL,
+    collection_id TEXT NOT NULL
+);
+";
+
+#[derive(Debug)]
+struct Database {
+    connection: rusqlite::Connection,
+}
+
+impl Database {
+    /// Open the database at `path`, creating it and its tables if they don't exist.
+    pub fn open(path: &path::Path) -> Result<Self, error::Error> {
+        let connection = rusqlite::Connection::open(path)?;
+        connection.execute_batch(SCHEMA)?;
+        Ok(Self { connection })
+    }
+
+    /// Read every collection, with its items.
+    pub fn collections(&self) -> Result<Vec<CollectionFile>, error::Error> {
+        let mut collections = collections::HashMap::new();
+        let mut statement = self.connection.prepare(
+            "SELECT id, label, created, modified, kdf_memory_cost, kdf_parallelism, kdf_salt,
+                    kdf_time_cost, passwordless, verifier, verifier_nonce
+             FROM collections",
+        )?;
+        let mut rows = statement.query([])?;
+        while let Some(row) = rows.next()? {
+            let collection = CollectionFile {
+                id: get_uuid(row, 0)?,
+                label: row.get(1)?,
+                created: row.get(2)?,
+                modified: row.get(3)?,
+                kdf: KdfParameters {
+                    memory_cost: row.get(4)?,
+                    parallelism: row.get(5)?,
+                    salt: row.get(6)?,
+                    time_cost: row.get(7)?,
+                },
+                passwordless: row.get(8)?,
+                verifier: Ciphertext {
+                    data: row.get(9)?,
+                    nonce: row.get(10)?,
+                },
+                items: collections::HashMap::new(),
+            };
+            collections.insert(collection.id, collection);
+        }
+
+        let mut statement = self.connection.prepare(
+            "SELECT id, collection_id, label, application, content_type, created, modified,
+                    secret, secret_nonce
+             FROM items",
+        )?;
+        let mut rows = statement.query([])?;
+        while let Some(row) = rows.next()? {
+            let item = ItemRecord {
+                item: StoredItem {
+                    id: get_uuid(row, 0)?,
+                    label: row.get(2)?,
+                    application: row.get(3)?,
+                    content_type: row.get(4)?,
+                    created: row.get(5)?,
+                    modified: row.get(6)?,
+                    attributes: collections::HashMap::new(),
+                },
+                secret: Ciphertext {
+                    data: row.get(7)?,
+                    nonce: row.get(8)?,
+                },
+            };
+            if let Some(collection) = collections.get_mut(&get_uuid(row, 1)?) {
+                collection.items.insert(item.item.id, item);
+            }
+        }
+
+        let mut statement = self.connection.prepare(
+            "SELECT items.collection_id, attributes.item_id, attributes.name, attributes.value
+             FROM attributes JOIN items ON items.id = attributes.item_id",
+        )?;
+        let mut rows = statement.query([])?;
+        while let Some(row) = rows.next()? {
+            let item_id = get_uuid(row, 1)?;
+            let item = collections
+                .get_mut(&get_uuid(row, 0)?)
+                .and_then(|collection| collection.items.get_mut(&item_id));
+            if let Some(item) = item {
+                item.item.attributes.insert(row.get(2)?, row.get(3)?);
+            }
+        }
+
+        Ok(collections.into_values().collect())
+    }
+
+    pub fn aliases(&self) -> Result<collections::HashMap<String, uuid::Uuid>, error::Error> {
+        let mut aliases = collections::HashMap::new();
+        let mut statement = self
+            .connection
+            .prepare("SELECT name, collection_id FROM aliases")?;
+        let mut rows = statement.query([])?;
+        while let Some(row) = rows.next()? {
+            aliases.insert(row.get(0)?, get_uuid(row, 1)?);
+        }
+        Ok(aliases)
+    }
+
+    /// Write `collection`, in a single transaction, given its `original` version as last
+    /// written, or `None` if it's new.
+    ///
+    /// Only rows that differ from `original` are written.
+    pub fn write_collection(
+        &mut self,
+        original: Option<&CollectionFile>,
+        collection: &CollectionFile,
+    ) -> Result<(), error::Error> {
+        let transaction = self.connection.transaction()?;
+        let collection_id = collection.id.to_string();
+
+        let changed = original.is_none_or(|original| {
+            (
+                original.created,
+                &original.kdf,
+                &original.label,
+                original.modified,
+                original.passwordless,
+                &original.verifier,
+            ) != (
+                collection.created,
+                &collection.kdf,
+                &collection.label,
+                collection.modified,
+                collection.passwordless,
+                &collection.verifier,
+            )
+        });
+        if changed {
+            // An upsert, as replacing the row would delete its items along with it.
+            transaction.execute(
+                "INSERT INTO collections (id, label, created, modified, kdf_memory_cost,
+                                          kdf_parallelism, kdf_salt, kdf_time_cost,
+                                          passwordless, verifier, verifier_nonce)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+                 ON CONFLICT (id) DO UPDATE SET
+                    label = excluded.label, created = excluded.created,
+                    modified = excluded.modified, kdf_memory_cost = excluded.kdf_memory_cost,
+                    kdf_parallelism = excluded.kdf_parallelism, kdf_salt = excluded.kdf_salt,
+                    kdf_time_cost = excluded.kdf_time_cost,
+                    passwordless = excluded.passwordless, verifier = excluded.verifier,
+                    verifier_nonce = excluded.verifier_nonce",
+                rusqlite::params![
+                    collection_id,
+                    collection.label,
+                    collection.created,
+                    collection.modified,
+                    collection.kdf.memory_cost,
+                    collection.kdf.parallelism,
+                    collection.kdf.salt,
+                    collection.kdf.time_cost,
+                    collection.passwordless,
+                    collection.verifier.data,
+                    collection.verifier.nonce,
+                ],
+            )?;
+        }
+
+        let no_items = collections::HashMap::new();
+        let original_items = original.map_or(&no_items, |original| &original.items);
+        for item_id in original_items.keys() {
+            if !collection.items.contains_key(item_id) {
+                // Its attributes are deleted along with it.
+                transaction
+                    .prepare_cached("DELETE FROM items WHERE id = ?1")?
+                    .execute([item_id.to_string()])?;
+            }
+        }
+
+        for (item_id, record) in &collection.items {
+            let original_record = original_items.get(item_id);
+            if original_record == Some(record) {
+                continue;
+            }
+
+            let item_id = item_id.to_string();
+            let item = &record.item;
+            transaction
+                .prepare_cached(
+                    "INSERT INTO items (id, collection_id, label, application, content_type,
+                                        created, modified, secret, secret_nonce)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+                     ON CONFLICT (id) DO UPDATE SET
+                        label = excluded.label, application = excluded.application,
+                        content_type = excluded.content_type, created = excluded.created,
+                        modified = excluded.modified, secret = excluded.secret,
+                        secret_nonce = excluded.secret_nonce",
+                )?
+                .execute(rusqlite::params![
+                    item_id,
+                    collection_id,
+                    item.label,
+                    item.application,
+                    item.content_type,
+                    item.created,
+                    item.modified,
+                    record.secret.data,
+                    record.secret.nonce,
+                ])?;
+
+            if original_record.is_none_or(|original| original.item.attributes != item.attributes) {
+                transaction
+                    .prepare_cached("DELETE FROM attributes WHERE item_id = ?1")?
+                    .execute([&item_id])?;
+                let mut insert = transaction.prepare_cached(
+                    "INSERT INTO attributes (item_id, name, value) VALUES (?1, ?2, ?3)",
+                )?;
+                for (name, value) in &item.attributes {
+                    insert.execute([&item_id, name, value])?;
+                }
+            }
+        }
+
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// Find the items having all of `attributes`, as the ids of their collection and
+    /// themselves, looking each attribute up in the index of the attributes table.
+    pub fn search_items(
+        &self,
+        attributes: &collections::HashMap<String, String>,
+    ) -> Result<Vec<(uuid::Uuid, uuid::Uuid)>, error::Error> {
+        let mut query = "SELECT collection_id, id FROM items".to_owned();
+        let mut parameters = Vec::new();
+        for (name, value) in attributes {
+            query.push_str(if parameters.is_empty() {
+                " WHERE "
+            } else {
+                " AND "
+            });
+            query.push_str(&format!(
+                "id IN (SELECT item_id FROM attributes WHERE name = ?{} AND value = ?{})",
+                parameters.len() + 1,
+                parameters.len() + 2
+            ));
+            parameters.extend([name, value]);
+        }
+
+        let mut statement = self.connection.prepare(&query)?;
+        let mut rows = statement.query(rusqlite::params_from_iter(parameters))?;
+        let mut found = Vec::new();
+        while let Some(row) = rows.next()? {
+            found.push((get_uuid(row, 0)?, get_uuid(row, 1)?));
+        }
+        Ok(found)
+    }
+
+    /// Delete a collection, with its items and any aliases pointing to it.
+    pub fn delete_collection(&mut self, collection_id: &uuid::Uuid) -> Result<(), error::Error> {
+        let transaction = self.connection.transaction()?;
+        let collection_id = collection_id.to_string();
+        transaction.execute(
+            "DELETE FROM aliases WHERE collection_id = ?1",
+            [&collection_id],
+        )?;
+        transaction.execute("DELETE FROM collections WHERE id = ?1", [&collection_id])?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    /// Point alias `name` to a collection, or remove the alias if `collection_id` is `None`.
+    pub fn set_alias(
+        &mut self,
+        name: &str,
+        collection_id: Option<&uuid::Uuid>,
+    ) -> Result<(), error::Error> {
+        match collection_id {
+            Some(collection_id) => self.connection.execute(
+                "INSERT INTO aliases (name, collection_id) VALUES (?1, ?2)
+                 ON CONFLICT (name) DO UPDATE SET collection_id = excluded.collection_id",
+                [name, &collection_id.to_string()],
+            )?,
+            None => self
+                .connection
+                .execute("DELETE FROM aliases WHERE name = ?1", [name])?,
+        };
+        Ok(())
+    }
+}
+
+/// A `Storage` that writes collections and aliases to a SQLite database at
+/// `<path>/storage.sqlite3`, where every change is written as the rows it affects, in a
+/// single transaction.
+#[derive(Debug)]
+pub struct SqliteStorage(sync::Arc<Collections>);
+
+impl SqliteStorage {
+    /// Open the `SqliteStorage` at `path`, loading any collections and aliases found.
+    ///
+    /// The directory is created, only accessible by the current user, if it doesn't
+    /// exist. All collections start locked.
+    pub fn open(path: &path::Path) -> Result<Self, error::Error> {
+        fs::DirBuilder::new()
+            .recursive(true)
+            .mode(0o700)
+            .create(path)?;
+
+        let database = Database::open(&path.join(DATABASE_FILE))?;
+        let aliases = database.aliases()?;
+        let files = database.collections()?;
+
+        log::info!(
+            "Loaded {} collections from '{}'",
+            files.len(),
+            path.join(DATABASE_FILE).display()
+        );
+
+        Ok(Self(sync::Arc::new(Collections::new(
+            files,
+            aliases,
+            Box::new(sync::Mutex::new(database)),
+        ))))
+    }
+}
+
+impl WriteThrough for SqliteStorage {
+    fn cache(&self) -> &sync::Arc<Collections> {
+        &self.0
+    }
+}
+
+impl Backend for sync::Mutex<Database> {
+    fn write_collection(
+        &self,
+        original: Option<&CollectionFile>,
+        file: &CollectionFile,
+    ) -> Result<(), error::Error> {
+        self.lock()
+            .expect("lock is not poisoned")
+            .write_collection(original, file)
+    }
+
+    /// The database deletes aliases pointing to the collection along with it.
+    fn delete_collection(&self, collection_id: &uuid::Uuid) -> Result<(), error::Error> {
+        self.lock()
+            .expect("lock is not poisoned")
+            .delete_collection(collection_id)
+    }
+
+    fn write_alias(
+        &self,
+        _aliases: &collections::HashMap<&String, &uuid::Uuid>,
+        name: &str,
+        collection_id: Option<&uuid::Uuid>,
+    ) -> Result<(), error::Error> {
+        self.lock()
+            .expect("lock is not poisoned")
+            .set_alias(name, collection_id)
+    }
+
+    fn search_items(
+        &self,
+        attributes: &collections::HashMap<String, String>,
+    ) -> Option<Result<Vec<(uuid::Uuid, uuid::Uuid)>, error::Error>> {
+        Some(
+            self.lock()
+                .expect("lock is not poisoned")
+                .search_items(attributes),
+        )
+    }
+}
+
+/// Read the UUID in column `index` of `row`, kept as text.
+fn get_uuid(row: &rusqlite::Row<'_>, index: usize) -> rusqlite::Result<uuid::Uuid> {
+    let text: String = row.get(index)?;
+    uuid::Uuid::parse_str(&text).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, Box::new(e))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::tests::{
+        self as storage_tests, check_changes_are_loaded_when_reopened, new_item,
+    };
+    use crate::storage::Storage;
+
+    fn new_collection(items: usize) -> CollectionFile {
+        let ciphertext = |data: &[u8]| Ciphertext {
+            data: data.to_vec(),
+            nonce: vec![0; 12],
+        };
+        let items = (0..items)
+            .map(|i| {
+                let id = uuid::Uuid::new_v4();
+                let item = StoredItem {
+                    application: None,
+                    attributes: collections::HashMap::from([
+                        ("index".to_owned(), i.to_string()),
+                        ("service".to_owned(), "ci".to_owned()),
+                    ]),
+                    content_type: "text/plain".to_owned(),
+                    created: 1,
+                    id,
+                    label: format!("token-{i}"),
+                    modified: 1,
+                };
+                (
+                    id,
+                    ItemRecord {
+                        item,
+                        secret: ciphertext(b"encrypted-secret"),
+                    },
+                )
+            })
+            .collect();
+
+        CollectionFile {
+            created: 1,
+            id: uuid::Uuid::new_v4(),
+            items,
+            kdf: KdfParameters::generate(),
+            label: "collection".to_owned(),
+            modified: 1,
+            passwordless: false,
+            verifier: ciphertext(b"verifier"),
+        }
+    }
+
+    #[test]
+    fn test_collections_are_read_back() -> Result<(), error::Error> {
+        let data_dir = tempfile::tempdir()?;
+        let path = data_dir.path().join("storage.sqlite3");
+        let mut database = Database::open(&path)?;
+
+        let mut collection = new_collection(3);
+        let empty_collection = new_collection(0);
+        database.write_collection(None, &collection)?;
+        database.write_collection(None, &empty_collection)?;
+        database.set_alias("default", Some(&collection.id))?;
+        database.set_alias("other", Some(&empty_collection.id))?;
+        database.set_alias("other", None)?;
+
+        let original = collection.clone();
+        let item_id = *collection.items.keys().next().unwrap();
+        let item = collection.items.get_mut(&item_id).unwrap();
+        item.item.attributes.remove("service");
+        item.item.application = Some("/usr/bin/application".to_owned());
+        collection.label = "new-label".to_owned();
+        database.write_collection(Some(&original), &collection)?;
+
+        let mut collections = Database::open(&path)?.collections()?;
+        collections.sort_by_key(|collection| collection.items.len());
+        assert_eq!(collections, vec![empty_collection, collection.clone()]);
+        assert_eq!(
+            database.aliases()?,
+            collections::HashMap::from([("default".to_owned(), collection.id)])
+        );
+
+        database.delete_collection(&collection.id)?;
+        assert_eq!(database.collections()?.len(), 1);
+        assert!(database.aliases()?.is_empty());
+        let orphans: u64 = database.connection.query_row(
+            "SELECT (SELECT COUNT(*) FROM items) + (SELECT COUNT(*) FROM attributes)",
+            [],
+            |row| row.get(0),
+        )?;
+        assert_eq!(orphans, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_only_changed_rows_are_written() -> Result<(), error::Error> {
+        let data_dir = tempfile::tempdir()?;
+        let mut database = Database::open(&data_dir.path().join("storage.sqlite3"))?;
+
+        let mut collection = new_collection(1000);
+        database.write_collection(None, &collection)?;
+
+        let original = collection.clone();
+        let item_id = *collection.items.keys().next().unwrap();
+        let item = collection.items.get_mut(&item_id).unwrap();
+        item.secret.data = b"new-encrypted-secret".to_vec();
+        item.item.modified = 2;
+        let deleted_id = *collection.items.keys().find(|id| **id != item_id).unwrap();
+        collection.items.remove(&deleted_id);
+
+        let changes = database.connection.total_changes();
+        database.write_collection(Some(&original), &collection)?;
+        // One updated item, and one deleted item along with its two attributes.
+        assert_eq!(database.connection.total_changes() - changes, 4);
+
+        let changes = database.connection.total_changes();
+        database.write_collection(Some(&collection), &collection)?;
+        assert_eq!(database.connection.total_changes(), changes);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_search_items() -> Result<(), error::Error> {
+        let data_dir = tempfile::tempdir()?;
+        let storage = SqliteStorage::open(data_dir.path())?;
+
+        let collection = storage_tests::new_collection("collection");
+        let session_collection = storage_tests::new_collection("session");
+        let item = new_item("item");
+        let mut other_item = new_item("other-item");
+        other_item
+            .attributes
+            .insert("user".to_owned(), "me".to_owned());
+        let session_item = new_item("session-item");
+        storage
+            .create_collection(collection.clone(), b"password")
+            .await?;
+        storage
+            .create_ephemeral_collection(session_collection.clone())
+            .await?;
+        storage
+            .create_item(&collection.id, item.clone(), b"secret")
+            .await?;
+        storage
+            .create_item(&collection.id, other_item.clone(), b"secret")
+            .await?;
+        storage
+            .create_item(&session_collection.id, session_item.clone(), b"secret")
+            .await?;
+
+        let search = |pairs: &[(&str, &str)]| {
+            let attributes = pairs
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect();
+            let storage = &storage;
+            async move {
+                let found = storage.search_items(&attributes).await?.unwrap();
+                Ok::<_, error::Error>(found.into_iter().collect::<collections::HashSet<_>>())
+            }
+        };
+        // Items of ephemeral collections are found too, although they are not in the
+        // database.
+        assert_eq!(
+            search(&[("key", "value")]).await?,
+            collections::HashSet::from([
+                (collection.id, item.id),
+                (collection.id, other_item.id),
+                (session_collection.id, session_item.id),
+            ])
+        );
+        assert_eq!(search(&[]).await?.len(), 3);
+        assert_eq!(
+            search(&[("key", "value"), ("user", "me")]).await?,
+            collections::HashSet::from([(collection.id, other_item.id)])
+        );
+        assert!(search(&[("key", "other")]).await?.is_empty());
+        assert!(search(&[("user", "me"), ("missing", "value")])
+            .await?
+            .is_empty());
+
+        // Locked collections are searched too, as their attributes are not encrypted.
+        storage.lock_collection(&collection.id).await?;
+        storage
+            .delete_item(&session_collection.id, &session_item.id, 4)
+            .await?;
+        assert_eq!(
+            search(&[("user", "me")]).await?,
+            collections::HashSet::from([(collection.id, other_item.id)])
+        );
+        assert_eq!(search(&[]).await?.len(), 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_changes_are_loaded_when_reopened() -> Result<(), error::Error> {
+        check_changes_are_loaded_when_reopened(SqliteStorage::open).await
+    }
+
+    #[tokio::test]
+    async fn test_sqlite_storage() -> Result<(), error::Error> {
+        let data_dir = tempfile::tempdir()?;
+        let storage = SqliteStorage::open(data_dir.path())?;
+
+        let collection = storage_tests::new_collection("collection");
+        let other_collection = storage_tests::new_collection("other-collection");
+        let session_collection = storage_tests::new_collection("session");
+        let item = new_item("item");
+        storage
+            .create_collection(collection.clone(), b"password")
+            .await?;
+        storage
+            .create_collection(other_collection.clone(), b"")
+            .await?;
+        storage
+            .create_ephemeral_collection(session_collection.clone())
+            .await?;
+        storage
+            .create_item(&collection.id, item.clone(), b"a-very-important-secret")
+            .await?;
+        storage.set_alias("default", Some(&collection.id)).await?;
+        storage
+            .set_alias("other", Some(&other_collection.id))
+            .await?;
+        storage
+            .set_alias("session", Some(&session_collection.id))
+            .await?;
+
+        // Nothing is written to collection files, and secrets are encrypted at rest.
+        assert!(fs::read_dir(data_dir.path().join("collections")).is_err());
+        for entry in fs::read_dir(data_dir.path())? {
+            let contents = fs::read(entry?.path())?;
+            assert!(!contents
+                .windows(b"a-very-important-secret".len())
+                .any(|window| window == b"a-very-important-secret"));
+        }
+
+        assert!(
+            storage
+                .change_password(&collection.id, b"password", b"new-password")
+                .await?
+        );
+        storage.delete_collection(&other_collection.id).await?;
+
+        let reopened_storage = SqliteStorage::open(data_dir.path())?;
+        let mut expected_collection = collection.clone();
+        expected_collection.modified = item.modified;
+        expected_collection.items.insert(item.id, item.clone());
+        assert_eq!(
+            reopened_storage.collections().await,
+            vec![expected_collection]
+        );
+        assert_eq!(
+            reopened_storage.aliases().await,
+            collections::HashMap::from([("default".to_owned(), collection.id)])
+        );
+        assert!(
+            !reopened_storage
+                .unlock_collection(&collection.id, b"password")
+                .await?
+        );
+        assert!(
+            reopened_storage
+                .unlock_collection(&collection.id, b"new-password")
+                .await?
+        );
+        assert_eq!(
+            reopened_storage
+                .read_secret(&collection.id, &item.id)
+                .await?
+                .as_slice(),
+            b"a-very-important-secret"
+        );
+
+        Ok(())
+    }
+}